@@ -0,0 +1,9 @@
+//! Converts, validates and renders Velo documents without opening a window.
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Err(err) = velo::cli::run(&args, &mut std::io::stdout().lock()) {
+        eprintln!("velo-cli: {}", err);
+        std::process::exit(err.exit_code());
+    }
+}
@@ -0,0 +1,450 @@
+//! `.velo` bundles, documents as zip archives.
+//!
+//! `manifest.json` holds the document fields and lists the other files:
+//! `tabs/<n>.json` for every tab, in order, and `images/<hash>.png` for every
+//! image. Images are the files themselves instead of base64 strings. JPEG
+//! images of bundles written by hand are converted to PNG when read.
+
+use base64::{engine::general_purpose, Engine};
+use image::ImageOutputFormat;
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{Cursor, Read, Write};
+use std::path::Path;
+use zip::result::ZipError;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::components::{Doc, Tab};
+use crate::utils::image_hash;
+
+const BUNDLE_EXTENSION: &str = "velo";
+const MANIFEST: &str = "manifest.json";
+const FORMAT: &str = "velo-bundle";
+/// Layout of the archive, the documents inside have their own schema version.
+const BUNDLE_VERSION: u64 = 1;
+
+#[derive(Debug)]
+pub enum BundleError {
+    Corrupt(String),
+    Unsupported(String),
+    Json(serde_json::Error),
+    MissingFile(String),
+    InvalidImage { file: String, reason: String },
+}
+
+impl fmt::Display for BundleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BundleError::Corrupt(message) => write!(f, "damaged bundle: {}", message),
+            BundleError::Unsupported(message) => write!(f, "unsupported bundle: {}", message),
+            BundleError::Json(err) => write!(f, "invalid bundle json: {}", err),
+            BundleError::MissingFile(file) => write!(f, "{} is missing from the bundle", file),
+            BundleError::InvalidImage { file, reason } => {
+                write!(f, "invalid image {}: {}", file, reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for BundleError {}
+
+impl From<serde_json::Error> for BundleError {
+    fn from(err: serde_json::Error) -> Self {
+        BundleError::Json(err)
+    }
+}
+
+impl From<ZipError> for BundleError {
+    fn from(err: ZipError) -> Self {
+        match err {
+            ZipError::UnsupportedArchive(message) => BundleError::Unsupported(message.to_string()),
+            err => BundleError::Corrupt(err.to_string()),
+        }
+    }
+}
+
+pub fn has_bundle_extension(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case(BUNDLE_EXTENSION))
+}
+
+/// Zip archives start with a local file header.
+pub fn is_bundle(data: &[u8]) -> bool {
+    data.starts_with(b"PK\x03\x04")
+}
+
+/// Writes a document returned by `export_doc`, which carries its images.
+pub fn doc_to_bundle(doc: &Doc) -> Result<Vec<u8>, BundleError> {
+    let mut files = vec![];
+    let mut tabs = vec![];
+    for (i, tab) in doc.tabs.iter().enumerate() {
+        let file = format!("tabs/{}.json", i);
+        files.push((file.clone(), serde_json::to_vec_pretty(tab)?, true));
+        tabs.push(Value::String(file));
+    }
+    let mut images = Map::new();
+    let mut hashes: Vec<&String> = doc.images.keys().collect();
+    hashes.sort();
+    for hash in hashes {
+        let bytes = general_purpose::STANDARD
+            .decode(&doc.images[hash])
+            .map_err(|err| BundleError::InvalidImage {
+                file: hash.clone(),
+                reason: err.to_string(),
+            })?;
+        let extension = if bytes.starts_with(&[0xff, 0xd8, 0xff]) {
+            "jpg"
+        } else {
+            "png"
+        };
+        let file = format!("images/{}.{}", hash, extension);
+        // images are compressed already
+        files.push((file.clone(), bytes, false));
+        images.insert(hash.clone(), Value::String(file));
+    }
+
+    let mut manifest = match serde_json::to_value(doc)? {
+        Value::Object(fields) => fields,
+        _ => Map::new(),
+    };
+    manifest.insert("format".to_string(), json!(FORMAT));
+    manifest.insert("bundle_version".to_string(), json!(BUNDLE_VERSION));
+    manifest.insert("tabs".to_string(), Value::Array(tabs));
+    manifest.insert("images".to_string(), Value::Object(images));
+
+    files.insert(
+        0,
+        (
+            MANIFEST.to_string(),
+            serde_json::to_vec_pretty(&manifest)?,
+            true,
+        ),
+    );
+    write_zip(&files)
+}
+
+/// An archive of names, contents and whether to deflate them.
+fn write_zip<N: AsRef<str>>(files: &[(N, Vec<u8>, bool)]) -> Result<Vec<u8>, BundleError> {
+    let mut zip = ZipWriter::new(Cursor::new(vec![]));
+    for (name, contents, compress) in files {
+        let method = match compress {
+            true => CompressionMethod::Deflated,
+            false => CompressionMethod::Stored,
+        };
+        zip.start_file(
+            name.as_ref(),
+            SimpleFileOptions::default().compression_method(method),
+        )?;
+        zip.write_all(contents).map_err(ZipError::from)?;
+    }
+    Ok(zip.finish()?.into_inner())
+}
+
+/// Entries of an archive as names and uncompressed contents.
+fn read_zip(data: &[u8]) -> Result<Vec<(String, Vec<u8>)>, BundleError> {
+    let mut zip = ZipArchive::new(Cursor::new(data))?;
+    let mut entries = Vec::with_capacity(zip.len());
+    for i in 0..zip.len() {
+        let mut file = zip.by_index(i)?;
+        let mut contents = vec![];
+        file.read_to_end(&mut contents)
+            .map_err(|err| BundleError::Corrupt(format!("{}: {}", file.name(), err)))?;
+        entries.push((file.name().to_string(), contents));
+    }
+    Ok(entries)
+}
+
+/// Reads a bundle back into the json of an exported document, which still
+/// has to go through the migrations like any other document json.
+pub fn bundle_to_json(data: &[u8]) -> Result<String, BundleError> {
+    let mut files: HashMap<String, Vec<u8>> = read_zip(data)?.into_iter().collect();
+    let mut take = |file: &str| {
+        files
+            .remove(file)
+            .ok_or_else(|| BundleError::MissingFile(file.to_string()))
+    };
+    let mut doc: Map<String, Value> = serde_json::from_slice(&take(MANIFEST)?)?;
+    if doc.remove("format") != Some(json!(FORMAT)) {
+        return Err(BundleError::Unsupported("not a Velo bundle".to_string()));
+    }
+    let version = doc.remove("bundle_version").and_then(|v| v.as_u64());
+    if version.unwrap_or(0) > BUNDLE_VERSION {
+        return Err(BundleError::Unsupported(format!(
+            "bundle version {} is newer than supported version {}",
+            version.unwrap_or(0),
+            BUNDLE_VERSION
+        )));
+    }
+    let invalid_manifest = || BundleError::Corrupt("invalid manifest".to_string());
+
+    let mut tabs = vec![];
+    let tab_files = doc.remove("tabs").ok_or_else(invalid_manifest)?;
+    for file in tab_files.as_array().ok_or_else(invalid_manifest)? {
+        let file = file.as_str().ok_or_else(invalid_manifest)?;
+        tabs.push(serde_json::from_slice::<Value>(&take(file)?)?);
+    }
+
+    let mut images = Map::new();
+    let mut renamed = HashMap::new();
+    let image_files = doc.remove("images").unwrap_or(json!({}));
+    for (hash, file) in image_files.as_object().ok_or_else(invalid_manifest)? {
+        let file = file.as_str().ok_or_else(invalid_manifest)?;
+        let mut bytes = take(file)?;
+        if !bytes.starts_with(b"\x89PNG") {
+            bytes = to_png(&bytes).map_err(|reason| BundleError::InvalidImage {
+                file: file.to_string(),
+                reason,
+            })?;
+            let png_hash = image_hash(&bytes);
+            renamed.insert(hash.clone(), png_hash.clone());
+            images.insert(png_hash, json!(general_purpose::STANDARD.encode(bytes)));
+            continue;
+        }
+        images.insert(hash.clone(), json!(general_purpose::STANDARD.encode(bytes)));
+    }
+
+    if !renamed.is_empty() {
+        // nodes refer to images by the hash of the png
+        for tab in tabs.iter_mut() {
+            let mut typed: Tab = serde_json::from_value(tab.take())?;
+            typed.history.edit_all(|_, _, images| {
+                for hash in images.values_mut() {
+                    if let Some(png_hash) = renamed.get(hash) {
+                        *hash = png_hash.clone();
+                    }
+                }
+            });
+            *tab = serde_json::to_value(typed)?;
+        }
+    }
+
+    doc.insert("tabs".to_string(), Value::Array(tabs));
+    doc.insert("images".to_string(), Value::Object(images));
+    Ok(serde_json::to_string(&doc)?)
+}
+
+/// Canvas textures are RGBA, so the png is too whatever the source format.
+fn to_png(bytes: &[u8]) -> Result<Vec<u8>, String> {
+    let image = image::load_from_memory(bytes).map_err(|err| err.to_string())?;
+    let mut png = Cursor::new(vec![]);
+    image::DynamicImage::ImageRgba8(image.to_rgba8())
+        .write_to(&mut png, ImageOutputFormat::Png)
+        .map_err(|err| err.to_string())?;
+    Ok(png.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::TabSnapshot;
+    use crate::history::TabHistory;
+    use crate::migrations::doc_from_json;
+    use crate::ui_plugin::{JsonNodeText, NodeType};
+    use crate::utils::decode_image;
+    use crate::{JsonNode, TextPos};
+    use image::{Rgb, RgbImage};
+    use uuid::Uuid;
+
+    fn encode(format: ImageOutputFormat) -> Vec<u8> {
+        let mut bytes = Cursor::new(vec![]);
+        image::DynamicImage::ImageRgb8(RgbImage::from_pixel(2, 2, Rgb([200, 10, 10])))
+            .write_to(&mut bytes, format)
+            .unwrap();
+        bytes.into_inner()
+    }
+
+    fn doc_with_image(hash: &str, image: &[u8]) -> Doc {
+        let snapshot = TabSnapshot {
+            images: HashMap::from([(Uuid::new_v4(), hash.to_string())]),
+            ..Default::default()
+        };
+        Doc {
+            name: "Bundle".to_string(),
+            tags: vec!["tag".to_string()],
+            schema_version: crate::migrations::CURRENT_SCHEMA_VERSION,
+            tabs: vec![
+                Tab {
+                    name: "First".to_string(),
+                    is_active: true,
+                    history: TabHistory::new(snapshot, 0.),
+                    ..Default::default()
+                },
+                Tab {
+                    name: "Second".to_string(),
+                    ..Default::default()
+                },
+            ],
+            images: HashMap::from([(hash.to_string(), general_purpose::STANDARD.encode(image))]),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_bundle_roundtrip() {
+        let png = encode(ImageOutputFormat::Png);
+        let doc = doc_with_image(&image_hash(&png), &png);
+        let bundle = doc_to_bundle(&doc).unwrap();
+        assert!(is_bundle(&bundle));
+
+        let names: Vec<String> = read_zip(&bundle)
+            .unwrap()
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        let image_file = format!("images/{}.png", image_hash(&png));
+        assert_eq!(
+            names,
+            vec!["manifest.json", "tabs/0.json", "tabs/1.json", &image_file]
+        );
+
+        let loaded = doc_from_json(&bundle_to_json(&bundle).unwrap()).unwrap();
+        assert_eq!(loaded.id, doc.id);
+        assert_eq!((&loaded.name, &loaded.tags), (&doc.name, &doc.tags));
+        assert_eq!(loaded.images, doc.images);
+        let tabs = |doc: &Doc| -> Vec<(String, bool, TabSnapshot)> {
+            doc.tabs
+                .iter()
+                .map(|tab| (tab.name.clone(), tab.is_active, tab.history.head().clone()))
+                .collect()
+        };
+        assert_eq!(tabs(&loaded), tabs(&doc));
+    }
+
+    #[test]
+    fn test_bundle_jpeg() {
+        let jpeg = encode(ImageOutputFormat::Jpeg(90));
+        let mut doc = doc_with_image("photo", &jpeg);
+        // text that happens to equal the hash is not an image reference
+        doc.tabs[0].name = "photo".to_string();
+        let node_id = *doc.tabs[0].history.head().images.keys().next().unwrap();
+        let mut snapshot = doc.tabs[0].history.head().clone();
+        snapshot.nodes.push(JsonNode {
+            id: node_id,
+            node_type: NodeType::Rect,
+            x: 0.,
+            y: 0.,
+            z: 1.,
+            width: 100.,
+            height: 100.,
+            text: JsonNodeText {
+                text: "photo".to_string(),
+                pos: TextPos::Center,
+            },
+            bg_color: "node_bg".to_string(),
+            visible: true,
+        });
+        doc.tabs[0].history = TabHistory::new(snapshot, 0.);
+        let manifest = json!({
+            "format": "velo-bundle",
+            "bundle_version": 1,
+            "id": doc.id,
+            "name": "photo",
+            "schema_version": doc.schema_version,
+            "tabs": ["tabs/0.json"],
+            "images": {"photo": "images/photo.jpg"},
+        });
+        let bundle = write_zip(&[
+            (MANIFEST, manifest.to_string().into_bytes(), true),
+            (
+                "tabs/0.json",
+                serde_json::to_vec(&doc.tabs[0]).unwrap(),
+                true,
+            ),
+            ("images/photo.jpg", jpeg, false),
+        ])
+        .unwrap();
+
+        let loaded = doc_from_json(&bundle_to_json(&bundle).unwrap()).unwrap();
+        let (hash, png) = loaded.images.iter().next().unwrap();
+        let png = general_purpose::STANDARD.decode(png).unwrap();
+        assert!(png.starts_with(b"\x89PNG"));
+        assert_eq!(
+            image::load_from_memory(&png).unwrap().color(),
+            image::ColorType::Rgba8
+        );
+        assert_eq!(*hash, image_hash(&png));
+        let head = loaded.tabs[0].history.head();
+        assert_eq!(head.images.values().collect::<Vec<_>>(), vec![hash]);
+        assert_eq!(head.nodes[0].text.text, "photo");
+        assert_eq!(
+            (loaded.name.as_str(), loaded.tabs[0].name.as_str()),
+            ("photo", "photo")
+        );
+        // the canvas loads it as a 2x2 RGBA texture
+        let texture = decode_image(&loaded.images[hash]).unwrap();
+        assert_eq!(texture.size(), bevy::math::Vec2::new(2., 2.));
+        assert_eq!(texture.data.len(), 2 * 2 * 4);
+    }
+
+    #[test]
+    fn test_zip64_bundle() {
+        let doc = doc_with_image("photo", &encode(ImageOutputFormat::Png));
+        let bundle = doc_to_bundle(&doc).unwrap();
+        // archivers switch to zip64 entries for large files
+        let mut zip = ZipWriter::new(Cursor::new(vec![]));
+        for (name, contents) in read_zip(&bundle).unwrap() {
+            let options = SimpleFileOptions::default().large_file(true);
+            zip.start_file(name, options).unwrap();
+            zip.write_all(&contents).unwrap();
+        }
+        let zip64 = zip.finish().unwrap().into_inner();
+        assert_eq!(
+            bundle_to_json(&zip64).unwrap(),
+            bundle_to_json(&bundle).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_damaged_bundle() {
+        let error = |data: &[u8]| bundle_to_json(data).unwrap_err().to_string();
+        assert!(error(b"{}").starts_with("damaged bundle: "));
+
+        let mut data = write_zip(&[("tab.json", b"[1, 2, 3]".to_vec(), false)]).unwrap();
+        // flip a byte of the stored contents
+        data[30 + "tab.json".len()] ^= 1;
+        assert!(error(&data).starts_with("damaged bundle: tab.json: "));
+        data.truncate(data.len() - 30);
+        assert!(error(&data).starts_with("damaged bundle: "));
+    }
+
+    #[test]
+    fn test_bundle_errors() {
+        let error = |files: &[(&str, &str)]| {
+            let files: Vec<(&str, Vec<u8>, bool)> = files
+                .iter()
+                .map(|(name, contents)| (*name, contents.as_bytes().to_vec(), true))
+                .collect();
+            bundle_to_json(&write_zip(&files).unwrap())
+                .unwrap_err()
+                .to_string()
+        };
+        assert_eq!(
+            error(&[("tabs/0.json", "{}")]),
+            "manifest.json is missing from the bundle"
+        );
+        assert_eq!(
+            error(&[(MANIFEST, r#"{"name": "doc"}"#)]),
+            "unsupported bundle: not a Velo bundle"
+        );
+        assert_eq!(
+            error(&[(
+                MANIFEST,
+                r#"{"format": "velo-bundle", "bundle_version": 2}"#
+            )]),
+            "unsupported bundle: bundle version 2 is newer than supported version 1"
+        );
+        assert_eq!(
+            error(&[(
+                MANIFEST,
+                r#"{"format": "velo-bundle", "tabs": ["tabs/0.json"]}"#
+            )]),
+            "tabs/0.json is missing from the bundle"
+        );
+        assert_eq!(
+            error(&[(MANIFEST, r#"{"format": "velo-bundle", "tabs": {}}"#)]),
+            "damaged bundle: invalid manifest"
+        );
+    }
+}
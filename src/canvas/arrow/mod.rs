@@ -1,10 +1,10 @@
 pub mod components;
 pub mod events;
 mod systems;
-mod utils;
+pub mod utils;
 use bevy::{
     app::{App, Plugin},
-    prelude::PreUpdate,
+    prelude::{IntoSystemConfigs, PreUpdate},
 };
 use bevy_prototype_lyon::prelude::ShapePlugin;
 use systems::*;
@@ -15,7 +15,11 @@ impl Plugin for ArrowPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugins(ShapePlugin).add_systems(
             PreUpdate, // due to CreateArrow event
-            (create_arrow_start, create_arrow_end, redraw_arrows),
+            // arrows are spawned before the checkpoint sent by create_arrow_start is saved
+            (
+                (create_arrow_start, create_arrow_end).chain(),
+                redraw_arrows,
+            ),
         );
     }
 }
@@ -5,7 +5,7 @@ use super::events::{CreateArrow, RedrawArrow};
 use super::utils::{build_arrow, create_arrow};
 use crate::themes::Theme;
 use crate::ui_plugin::ui_helpers::VeloNode;
-use crate::ui_plugin::{NodeInteraction, UiState};
+use crate::ui_plugin::{AddCheckpoint, NodeInteraction, UiState};
 use bevy_prototype_lyon::prelude::Path;
 
 pub fn create_arrow_start(
@@ -14,6 +14,7 @@ pub fn create_arrow_start(
     mut state: ResMut<UiState>,
     mut create_arrow: EventWriter<CreateArrow>,
     mut windows: Query<&mut Window, With<PrimaryWindow>>,
+    mut checkpoint_events: EventWriter<AddCheckpoint>,
 ) {
     let mut primary_window = windows.single_mut();
     for event in node_interaction_events.iter() {
@@ -35,6 +36,7 @@ pub fn create_arrow_start(
                                 end: *arrow_connect,
                                 arrow_type: state.arrow_type,
                             });
+                            checkpoint_events.send_default();
                         }
                         None => {
                             state.arrow_to_draw_start = Some(*arrow_connect);
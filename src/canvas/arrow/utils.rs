@@ -35,6 +35,16 @@ pub fn create_arrow(
         Stroke::new(theme.arrow, 1.5),
     ));
 }
+/// Position of an arrow connector relative to the center of its node.
+pub fn arrow_connector_offset(width: f32, height: f32, pos: ArrowConnectPos) -> Vec2 {
+    match pos {
+        ArrowConnectPos::Left => Vec2::new(-width / 2., 0.),
+        ArrowConnectPos::Bottom => Vec2::new(0., -height / 2.),
+        ArrowConnectPos::Top => Vec2::new(0., height / 2.),
+        ArrowConnectPos::Right => Vec2::new(width / 2., 0.),
+    }
+}
+
 fn parallel_arrow_mid(start: Vec2, end: Vec2, arrow_meta: ArrowMeta) -> (Vec2, Vec2) {
     let mid = (start + end) / 2.0;
     use ArrowConnectPos::*;
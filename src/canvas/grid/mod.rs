@@ -11,9 +11,6 @@ use systems::*;
 
 pub struct GridPlugin;
 
-#[derive(Resource, Default)]
-pub struct CanvasInserted(pub bool);
-
 impl Plugin for GridPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugins(Material2dPlugin::<CustomGridMaterial>::default())
@@ -0,0 +1,724 @@
+//! Commands of the `velo-cli` binary. They work on documents and tabs
+//! directly, without a window or any bevy plugin.
+//!
+//! Inputs are picked by extension: `.json` and `.velo` are documents, the
+//! formats tabs can be imported from become a document with a single tab.
+
+use bevy::prelude::*;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::bundle::{bundle_to_json, doc_to_bundle, is_bundle, BundleError};
+use crate::components::{Doc, Tab, TabSnapshot};
+use crate::formats::csv::csv_to_tab;
+use crate::formats::dot::{dot_to_tab, tab_to_dot};
+use crate::formats::excalidraw::excalidraw_to_tab;
+use crate::formats::html::doc_to_html;
+use crate::formats::json_canvas::{canvas_to_tab, tab_to_canvas};
+use crate::formats::mermaid::{mermaid_to_tab, tab_to_mermaid};
+use crate::formats::opml::{opml_to_tab, tab_to_opml};
+use crate::formats::outline::{outline_to_tab, tab_to_outline, OutlineStyle};
+use crate::formats::png::{export_font_system, tab_to_png, PngOptions};
+use crate::formats::svg::tab_to_svg;
+use crate::formats::{Bounds, FormatError, ThemeColors};
+use crate::history::TabHistory;
+use crate::migrations::{migrate_doc, MigrationError, CURRENT_SCHEMA_VERSION};
+use crate::notifications::VeloError;
+use crate::themes::{get_theme_by_name, Theme};
+use crate::utils::{decode_image, get_timestamp, ReflectableUuid};
+
+pub const USAGE: &str = "\
+usage: velo-cli <command> [options]
+
+commands:
+  validate <document>             check that a document loads and is consistent
+  convert <input> <output>        convert between documents (.json, .velo, .html)
+                                  and tab formats (.md, .mmd, .dot, .canvas, .opml)
+  export <input> <output>         render a tab as .svg, .png or .md
+  list-tabs <input>               print the tabs of a document
+  stats <input>                   print the number of tabs, notes, arrows and drawings
+
+inputs are documents (.json, .velo) or files a tab can be imported from
+(.md, .mmd, .dot, .canvas, .excalidraw, .opml, .csv, .tsv)
+
+options:
+  --tab <number|name>             tab to convert or export, the active tab by default
+  --theme <light|dark>            theme to render and lay out with, light by default
+  --outline <list|headings>       style of markdown outlines, list by default
+  --scale <pixels>                pixels per canvas unit of a png, 1 by default
+                                  (the app exports at the screen scale factor)
+  --background <color>            png background: transparent, #rrggbb or a theme key
+  --region <x0,y0,x1,y1>          part of the canvas to render into a png";
+
+#[derive(Debug)]
+pub enum CliError {
+    Usage(String),
+    Velo(VeloError),
+    /// `validate` found problems, they are printed already.
+    Invalid(usize),
+    Output(std::io::Error),
+}
+
+impl CliError {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            CliError::Usage(_) => 2,
+            CliError::Velo(_) | CliError::Invalid(_) | CliError::Output(_) => 1,
+        }
+    }
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CliError::Usage(message) => write!(f, "{}\n\n{}", message, USAGE),
+            CliError::Velo(err) => write!(f, "{}", err),
+            CliError::Invalid(count) => write!(f, "{} problem(s) found", count),
+            CliError::Output(err) => write!(f, "could not write output: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for CliError {}
+
+impl From<VeloError> for CliError {
+    fn from(err: VeloError) -> Self {
+        CliError::Velo(err)
+    }
+}
+
+impl From<FormatError> for CliError {
+    fn from(err: FormatError) -> Self {
+        CliError::Velo(err.into())
+    }
+}
+
+impl From<MigrationError> for CliError {
+    fn from(err: MigrationError) -> Self {
+        CliError::Velo(err.into())
+    }
+}
+
+impl From<BundleError> for CliError {
+    fn from(err: BundleError) -> Self {
+        CliError::Velo(err.into())
+    }
+}
+
+impl From<serde_json::Error> for CliError {
+    fn from(err: serde_json::Error) -> Self {
+        CliError::Velo(err.into())
+    }
+}
+
+impl From<std::io::Error> for CliError {
+    fn from(err: std::io::Error) -> Self {
+        CliError::Output(err)
+    }
+}
+
+fn usage(message: impl Into<String>) -> CliError {
+    CliError::Usage(message.into())
+}
+
+/// Options and positional arguments of a command.
+struct Args {
+    paths: Vec<PathBuf>,
+    options: HashMap<String, String>,
+}
+
+impl Args {
+    fn parse(args: &[String], allowed: &[&str]) -> Result<Args, CliError> {
+        let mut parsed = Args {
+            paths: vec![],
+            options: HashMap::new(),
+        };
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let Some(name) = arg.strip_prefix("--") else {
+                parsed.paths.push(PathBuf::from(arg));
+                continue;
+            };
+            if !allowed.contains(&name) {
+                return Err(usage(format!("unknown option --{}", name)));
+            }
+            let value = args
+                .next()
+                .ok_or_else(|| usage(format!("--{} needs a value", name)))?;
+            parsed.options.insert(name.to_string(), value.clone());
+        }
+        Ok(parsed)
+    }
+
+    fn paths<const N: usize>(&self) -> Result<[&Path; N], CliError> {
+        let paths: Vec<&Path> = self.paths.iter().map(PathBuf::as_path).collect();
+        paths
+            .try_into()
+            .map_err(|_| usage(format!("expected {} path(s)", N)))
+    }
+
+    fn theme(&self) -> Result<Theme, CliError> {
+        match self.options.get("theme").map(String::as_str) {
+            None => Ok(get_theme_by_name("light")),
+            Some(name @ ("light" | "dark")) => Ok(get_theme_by_name(name)),
+            Some(name) => Err(usage(format!("unknown theme {}", name))),
+        }
+    }
+
+    fn outline_style(&self) -> Result<OutlineStyle, CliError> {
+        match self.options.get("outline").map(String::as_str) {
+            None | Some("list") => Ok(OutlineStyle::List),
+            Some("headings") => Ok(OutlineStyle::Headings),
+            Some(style) => Err(usage(format!("unknown outline style {}", style))),
+        }
+    }
+
+    fn number(&self, name: &str) -> Result<Option<f32>, CliError> {
+        self.options
+            .get(name)
+            .map(|value| {
+                value
+                    .parse::<f32>()
+                    .ok()
+                    .filter(|number| number.is_finite() && *number > 0.)
+                    .ok_or_else(|| usage(format!("--{} must be a positive number", name)))
+            })
+            .transpose()
+    }
+}
+
+/// Runs the command in `args`, without the program name, writing its
+/// output to `out`.
+pub fn run(args: &[String], out: &mut impl Write) -> Result<(), CliError> {
+    let Some((command, args)) = args.split_first() else {
+        return Err(usage("missing command"));
+    };
+    match command.as_str() {
+        "validate" => validate(&Args::parse(args, &[])?, out),
+        "convert" => convert(&Args::parse(args, &["tab", "theme", "outline"])?, out),
+        "export" => export(
+            &Args::parse(
+                args,
+                &["tab", "theme", "outline", "scale", "background", "region"],
+            )?,
+            out,
+        ),
+        "list-tabs" => list_tabs(&Args::parse(args, &["theme"])?, out),
+        "stats" => stats(&Args::parse(args, &["theme"])?, out),
+        "help" | "--help" | "-h" => Ok(writeln!(out, "{}", USAGE)?),
+        command => Err(usage(format!("unknown command {}", command))),
+    }
+}
+
+fn extension(path: &Path) -> String {
+    path.extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
+
+fn read(path: &Path) -> Result<Vec<u8>, CliError> {
+    std::fs::read(path).map_err(|err| {
+        VeloError::ReadFile {
+            path: path.to_path_buf(),
+            err,
+        }
+        .into()
+    })
+}
+
+fn write(path: &Path, contents: &[u8]) -> Result<(), CliError> {
+    std::fs::write(path, contents).map_err(|err| {
+        VeloError::WriteFile {
+            path: path.to_path_buf(),
+            err,
+        }
+        .into()
+    })
+}
+
+fn read_text(path: &Path) -> Result<String, CliError> {
+    String::from_utf8(read(path)?).map_err(|err| {
+        VeloError::ReadFile {
+            path: path.to_path_buf(),
+            err: std::io::Error::new(std::io::ErrorKind::InvalidData, err),
+        }
+        .into()
+    })
+}
+
+type Import = fn(&str, &Theme) -> Result<TabSnapshot, FormatError>;
+
+/// Reads a document, or imports a file into a new document with one tab.
+fn load(path: &Path, theme: &Theme) -> Result<Doc, CliError> {
+    Ok(load_with_repairs(path, theme)?.0)
+}
+
+/// `load`, with the repairs the document needed to open in the app.
+fn load_with_repairs(path: &Path, theme: &Theme) -> Result<(Doc, Vec<String>), CliError> {
+    let import: Option<Import> = match extension(path).as_str() {
+        "json" | "velo" => None,
+        "md" => Some(|text, theme| Ok(outline_to_tab(text, theme))),
+        "mmd" => Some(mermaid_to_tab),
+        "dot" | "gv" => Some(dot_to_tab),
+        "canvas" => Some(canvas_to_tab),
+        "excalidraw" => Some(excalidraw_to_tab),
+        "opml" => Some(opml_to_tab),
+        "csv" | "tsv" => Some(csv_to_tab),
+        extension => return Err(usage(format!("cannot read .{} files", extension))),
+    };
+    let Some(import) = import else {
+        let contents = read(path)?;
+        let json = match is_bundle(&contents) {
+            true => bundle_to_json(&contents)?,
+            false => read_text(path)?,
+        };
+        let mut doc: Doc = serde_json::from_str(&json).map_err(MigrationError::from)?;
+        let repairs = migrate_doc(&mut doc)?;
+        return Ok((doc, repairs));
+    };
+    let snapshot = import(&read_text(path)?, theme)?;
+    let z_index = snapshot
+        .nodes
+        .iter()
+        .map(|node| node.z)
+        .chain(snapshot.drawings.iter().map(|drawing| drawing.z))
+        .fold(1., f32::max);
+    let name = path.file_stem().map_or("Untitled".to_string(), |stem| {
+        stem.to_string_lossy().into_owned()
+    });
+    let doc = Doc {
+        id: ReflectableUuid::generate(),
+        name,
+        tabs: vec![Tab {
+            id: ReflectableUuid::generate(),
+            name: "Tab 1".to_string(),
+            is_active: true,
+            history: TabHistory::new(snapshot, get_timestamp()),
+            z_index,
+            ..default()
+        }],
+        schema_version: CURRENT_SCHEMA_VERSION,
+        ..default()
+    };
+    Ok((doc, vec![]))
+}
+
+/// The tab named or numbered, counting from 1, by `--tab`, else the active tab.
+fn select_tab<'a>(doc: &'a Doc, args: &Args) -> Result<&'a Tab, CliError> {
+    let Some(selector) = args.options.get("tab") else {
+        return doc
+            .tabs
+            .iter()
+            .find(|tab| tab.is_active)
+            .or(doc.tabs.first())
+            .ok_or_else(|| usage("the document has no tabs"));
+    };
+    let by_number = selector
+        .parse::<usize>()
+        .ok()
+        .and_then(|number| doc.tabs.get(number.checked_sub(1)?));
+    by_number
+        .or_else(|| doc.tabs.iter().find(|tab| tab.name == *selector))
+        .ok_or_else(|| usage(format!("no tab {}", selector)))
+}
+
+fn validate(args: &Args, out: &mut impl Write) -> Result<(), CliError> {
+    let [path] = args.paths()?;
+    let theme = args.theme()?;
+    let (doc, mut problems) = load_with_repairs(path, &theme)?;
+    problems.extend(self::problems(&doc, &theme));
+    for problem in &problems {
+        writeln!(out, "{}", problem)?;
+    }
+    if !problems.is_empty() {
+        return Err(CliError::Invalid(problems.len()));
+    }
+    writeln!(out, "{}: ok, {} tab(s)", doc.name, doc.tabs.len())?;
+    Ok(())
+}
+
+/// Inconsistencies the app would silently drop, next to the ones `load`
+/// repairs.
+fn problems(doc: &Doc, theme: &Theme) -> Vec<String> {
+    let mut problems = vec![];
+    let mut hashes: Vec<&String> = doc.images.keys().collect();
+    hashes.sort();
+    for hash in hashes {
+        if let Err(err) = decode_image(&doc.images[hash]) {
+            problems.push(format!("image {}: {}", hash, err));
+        }
+    }
+    let colors = ThemeColors::new(theme);
+    for tab in &doc.tabs {
+        let snapshot = tab.history.head();
+        let mut ids = HashSet::new();
+        for node in &snapshot.nodes {
+            if !ids.insert(node.id) {
+                problems.push(format!("tab {}: duplicate node {}", tab.name, node.id));
+            }
+        }
+        let keys = snapshot.nodes.iter().map(|node| &node.bg_color).chain(
+            snapshot
+                .drawings
+                .iter()
+                .map(|drawing| &drawing.drawing_color),
+        );
+        for key in keys {
+            if colors.get(key).is_none() {
+                problems.push(format!("tab {}: unknown color {}", tab.name, key));
+            }
+        }
+        for arrow in &snapshot.arrows {
+            for end in [arrow.start.id, arrow.end.id] {
+                if !ids.contains(&end.0) {
+                    problems.push(format!("tab {}: arrow to missing node {}", tab.name, end.0));
+                }
+            }
+        }
+        for (node_id, hash) in &snapshot.images {
+            if !ids.contains(node_id) {
+                problems.push(format!(
+                    "tab {}: image of missing node {}",
+                    tab.name, node_id
+                ));
+            }
+            if !doc.images.contains_key(hash) {
+                problems.push(format!("tab {}: image {} is missing", tab.name, hash));
+            }
+        }
+    }
+    problems
+}
+
+fn convert(args: &Args, out: &mut impl Write) -> Result<(), CliError> {
+    let [input, output] = args.paths()?;
+    let theme = args.theme()?;
+    let doc = load(input, &theme)?;
+    let contents = match extension(output).as_str() {
+        "json" => serde_json::to_string_pretty(&doc)?.into_bytes(),
+        "velo" => doc_to_bundle(&doc)?,
+        "html" => doc_to_html(&doc, &theme)?.into_bytes(),
+        "svg" | "png" => {
+            return Err(usage(format!(
+                "use export to render .{} files",
+                extension(output)
+            )))
+        }
+        extension => {
+            let tab = select_tab(&doc, args)?;
+            let snapshot = tab.history.head();
+            match extension {
+                "md" => tab_to_outline(snapshot, args.outline_style()?),
+                "mmd" => tab_to_mermaid(snapshot),
+                "dot" | "gv" => tab_to_dot(snapshot, &theme),
+                "canvas" => tab_to_canvas(snapshot, &theme),
+                "opml" => tab_to_opml(snapshot, &tab.name),
+                extension => return Err(usage(format!("cannot write .{} files", extension))),
+            }
+            .into_bytes()
+        }
+    };
+    write(output, &contents)?;
+    writeln!(out, "converted {} to {}", input.display(), output.display())?;
+    Ok(())
+}
+
+fn export(args: &Args, out: &mut impl Write) -> Result<(), CliError> {
+    let [input, output] = args.paths()?;
+    let theme = args.theme()?;
+    let doc = load(input, &theme)?;
+    let tab = select_tab(&doc, args)?;
+    let snapshot = tab.history.head();
+    let contents = match extension(output).as_str() {
+        "svg" => tab_to_svg(snapshot, &doc.images, &theme).into_bytes(),
+        "md" => tab_to_outline(snapshot, args.outline_style()?).into_bytes(),
+        "png" => {
+            let options = PngOptions {
+                scale: args.number("scale")?.unwrap_or(1.),
+                background: args
+                    .options
+                    .get("background")
+                    .map(|background| parse_background(background, &theme))
+                    .transpose()?,
+                region: args
+                    .options
+                    .get("region")
+                    .map(|region| parse_region(region))
+                    .transpose()?,
+            };
+            let mut font_system = export_font_system();
+            tab_to_png(snapshot, &doc.images, &theme, &mut font_system, options)
+                .map_err(|err| VeloError::Export(err.to_string()))?
+        }
+        extension => return Err(usage(format!("cannot export .{} files", extension))),
+    };
+    write(output, &contents)?;
+    writeln!(
+        out,
+        "exported {} of {} to {}",
+        tab.name,
+        input.display(),
+        output.display()
+    )?;
+    Ok(())
+}
+
+fn parse_background(background: &str, theme: &Theme) -> Result<Color, CliError> {
+    if background == "transparent" {
+        return Ok(Color::NONE);
+    }
+    let color = match background.strip_prefix('#') {
+        Some(hex) => Color::hex(hex).ok(),
+        None => ThemeColors::new(theme).get(background),
+    };
+    color.ok_or_else(|| usage(format!("unknown color {}", background)))
+}
+
+fn parse_region(region: &str) -> Result<Bounds, CliError> {
+    let numbers: Vec<f32> = region
+        .split(',')
+        .filter_map(|number| number.trim().parse().ok())
+        .collect();
+    match numbers[..] {
+        [x0, y0, x1, y1] if x0 < x1 && y0 < y1 => Ok(Bounds {
+            min: Vec2::new(x0, y0),
+            max: Vec2::new(x1, y1),
+        }),
+        _ => Err(usage(
+            "--region must be x0,y0,x1,y1 with x0 < x1 and y0 < y1",
+        )),
+    }
+}
+
+fn list_tabs(args: &Args, out: &mut impl Write) -> Result<(), CliError> {
+    let [path] = args.paths()?;
+    let doc = load(path, &args.theme()?)?;
+    for (i, tab) in doc.tabs.iter().enumerate() {
+        let snapshot = tab.history.head();
+        writeln!(
+            out,
+            "{}\t{}\t{}\t{} notes\t{} arrows\t{} drawings",
+            i + 1,
+            tab.name,
+            if tab.is_active { "active" } else { "" },
+            snapshot.nodes.len(),
+            snapshot.arrows.len(),
+            snapshot.drawings.len()
+        )?;
+    }
+    Ok(())
+}
+
+fn stats(args: &Args, out: &mut impl Write) -> Result<(), CliError> {
+    let [path] = args.paths()?;
+    let doc = load(path, &args.theme()?)?;
+    let snapshots: Vec<&TabSnapshot> = doc.tabs.iter().map(|tab| tab.history.head()).collect();
+    let count = |f: fn(&TabSnapshot) -> usize| snapshots.iter().map(|s| f(s)).sum::<usize>();
+    writeln!(out, "name: {}", doc.name)?;
+    writeln!(out, "tabs: {}", doc.tabs.len())?;
+    writeln!(out, "notes: {}", count(|s| s.nodes.len()))?;
+    writeln!(
+        out,
+        "hidden notes: {}",
+        count(|s| s.nodes.iter().filter(|node| !node.visible).count())
+    )?;
+    writeln!(out, "arrows: {}", count(|s| s.arrows.len()))?;
+    writeln!(out, "drawings: {}", count(|s| s.drawings.len()))?;
+    writeln!(out, "images: {}", doc.images.len())?;
+    writeln!(
+        out,
+        "words: {}",
+        count(|s| {
+            s.nodes
+                .iter()
+                .map(|node| node.text.text.split_whitespace().count())
+                .sum()
+        })
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canvas::arrow::components::{ArrowConnect, ArrowConnectPos, ArrowMeta, ArrowType};
+
+    fn run_command(args: &[&str]) -> (Result<(), CliError>, String) {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        let mut out = vec![];
+        let result = run(&args, &mut out);
+        (result, String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn test_convert_and_export() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name).to_string_lossy().into_owned();
+        std::fs::write(path("flow.mmd"), "graph LR\n  A[Idea] --> B[Plan]\n").unwrap();
+
+        let (result, _) = run_command(&["convert", &path("flow.mmd"), &path("flow.json")]);
+        result.unwrap();
+        let (result, out) = run_command(&["validate", &path("flow.json")]);
+        result.unwrap();
+        assert_eq!(out, "flow: ok, 1 tab(s)\n");
+
+        run_command(&["convert", &path("flow.json"), &path("flow.velo")])
+            .0
+            .unwrap();
+        let (result, out) = run_command(&["list-tabs", &path("flow.velo")]);
+        result.unwrap();
+        assert_eq!(out, "1\tTab 1\tactive\t2 notes\t1 arrows\t0 drawings\n");
+        let (result, out) = run_command(&["stats", &path("flow.velo")]);
+        result.unwrap();
+        assert!(out.contains("notes: 2\n"));
+        assert!(out.contains("words: 2\n"));
+
+        run_command(&[
+            "convert",
+            &path("flow.velo"),
+            &path("flow.dot"),
+            "--tab",
+            "1",
+        ])
+        .0
+        .unwrap();
+        let dot = std::fs::read_to_string(path("flow.dot")).unwrap();
+        assert!(dot.starts_with("digraph {"));
+        assert!(dot.contains("label=\"Idea\""));
+
+        run_command(&["export", &path("flow.json"), &path("flow.svg")])
+            .0
+            .unwrap();
+        assert!(std::fs::read_to_string(path("flow.svg"))
+            .unwrap()
+            .starts_with("<svg"));
+        let (result, out) = run_command(&[
+            "export",
+            &path("flow.json"),
+            &path("flow.png"),
+            "--scale",
+            "0.5",
+            "--background",
+            "transparent",
+        ]);
+        result.unwrap();
+        assert!(out.starts_with("exported Tab 1 of "));
+        assert!(std::fs::read(path("flow.png"))
+            .unwrap()
+            .starts_with(b"\x89PNG"));
+    }
+
+    #[test]
+    fn test_validate_problems() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("broken.json");
+        let missing = ReflectableUuid::generate();
+        let snapshot = TabSnapshot {
+            arrows: vec![ArrowMeta {
+                visible: true,
+                arrow_type: ArrowType::Line,
+                start: ArrowConnect {
+                    id: missing,
+                    pos: ArrowConnectPos::Top,
+                },
+                end: ArrowConnect {
+                    id: missing,
+                    pos: ArrowConnectPos::Bottom,
+                },
+            }],
+            ..Default::default()
+        };
+        let doc = Doc {
+            name: "broken".to_string(),
+            schema_version: CURRENT_SCHEMA_VERSION,
+            tabs: vec![Tab {
+                name: "Tab 1".to_string(),
+                history: TabHistory::new(snapshot, 0.),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        std::fs::write(&path, serde_json::to_string(&doc).unwrap()).unwrap();
+        let (result, out) = run_command(&["validate", &path.to_string_lossy()]);
+        assert!(matches!(result, Err(CliError::Invalid(3))));
+        assert_eq!(
+            out,
+            format!(
+                "0 active tabs instead of one\n\
+                 tab Tab 1: arrow to missing node {0}\n\
+                 tab Tab 1: arrow to missing node {0}\n",
+                missing.0
+            )
+        );
+    }
+
+    #[test]
+    fn test_problems_images_and_colors() {
+        use crate::ui_plugin::{JsonNodeText, NodeType};
+        use crate::{JsonNode, TextPos};
+        use base64::{engine::general_purpose, Engine};
+
+        let snapshot = TabSnapshot {
+            nodes: vec![JsonNode {
+                id: uuid::Uuid::new_v4(),
+                node_type: NodeType::Rect,
+                x: 0.,
+                y: 0.,
+                z: 1.,
+                width: 100.,
+                height: 100.,
+                text: JsonNodeText {
+                    text: "neon".to_string(),
+                    pos: TextPos::Center,
+                },
+                bg_color: "neon".to_string(),
+                visible: true,
+            }],
+            ..Default::default()
+        };
+        let doc = Doc {
+            tabs: vec![Tab {
+                name: "Tab 1".to_string(),
+                is_active: true,
+                history: TabHistory::new(snapshot, 0.),
+                ..Default::default()
+            }],
+            // a png header is not enough, the image has to decode
+            images: HashMap::from([(
+                "truncated".to_string(),
+                general_purpose::STANDARD.encode(b"\x89PNG\r\n\x1a\n"),
+            )]),
+            ..Default::default()
+        };
+        let problems = problems(&doc, &crate::themes::velo_light());
+        assert_eq!(problems.len(), 2);
+        assert!(problems[0].starts_with("image truncated: could not decode image: "));
+        assert_eq!(problems[1], "tab Tab 1: unknown color neon");
+    }
+
+    #[test]
+    fn test_usage_errors() {
+        let message = |args: &[&str]| match run_command(args).0 {
+            Err(CliError::Usage(message)) => message,
+            result => panic!("expected a usage error, got {:?}", result),
+        };
+        assert_eq!(message(&[]), "missing command");
+        assert_eq!(message(&["draw"]), "unknown command draw");
+        assert_eq!(message(&["stats"]), "expected 1 path(s)");
+        assert_eq!(
+            message(&["stats", "a.json", "--scale", "2"]),
+            "unknown option --scale"
+        );
+        assert_eq!(
+            message(&["export", "a.json", "b.png", "--tab"]),
+            "--tab needs a value"
+        );
+        assert_eq!(message(&["list-tabs", "a.txt"]), "cannot read .txt files");
+        assert!(matches!(
+            run_command(&["validate", "missing.json"]).0,
+            Err(CliError::Velo(VeloError::ReadFile { .. }))
+        ));
+    }
+}
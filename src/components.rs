@@ -1,26 +1,56 @@
+use crate::canvas::arrow::components::ArrowMeta;
+use crate::history::TabHistory;
 use crate::utils::ReflectableUuid;
+use crate::{DrawingJsonNode, JsonNode};
 use bevy::prelude::*;
 use serde::{Deserialize, Serialize};
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
+use uuid::Uuid;
 #[derive(Component)]
 pub struct MainCamera;
 
 #[derive(Component)]
 pub struct EffectsCamera;
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
 pub struct Tab {
     pub is_active: bool,
     pub id: ReflectableUuid,
     pub name: String,
-    pub checkpoints: VecDeque<String>,
+    #[serde(default)]
+    pub history: TabHistory,
+    /// Checkpoints written before `TabSnapshot` existed, converted by migrations.
+    #[serde(
+        rename = "checkpoints",
+        default,
+        skip_serializing_if = "VecDeque::is_empty"
+    )]
+    pub legacy_checkpoints: VecDeque<String>,
+    #[serde(default)]
     pub z_index: f32,
 }
 
+/// State of a single tab at the moment it was saved.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct TabSnapshot {
+    /// Hashes of background images keyed by node id, see `AppState::images`.
+    pub images: HashMap<Uuid, String>,
+    pub nodes: Vec<JsonNode<String>>,
+    pub arrows: Vec<ArrowMeta>,
+    pub drawings: Vec<DrawingJsonNode<String>>,
+}
+
 #[derive(Default, Serialize, Deserialize, Clone, Debug)]
 pub struct Doc {
     pub tabs: Vec<Tab>,
     pub id: ReflectableUuid,
     pub name: String,
+    #[serde(default)]
     pub tags: Vec<String>,
+    #[serde(default)]
+    pub schema_version: u32,
+    /// Base64 encoded PNGs keyed by hash. Only filled in for exported documents,
+    /// stored documents keep their images in the shared image store.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub images: HashMap<String, String>,
 }
@@ -0,0 +1,219 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::components::Doc;
+use crate::ui_plugin::MAX_SAVED_DOCS_IN_MEMORY;
+use crate::utils::ReflectableUuid;
+
+/// Documents kept in memory, evicting the least recently used one when full.
+/// Evicted documents with unsaved changes wait in `evicted` until they are flushed to the store.
+pub struct DocCache {
+    docs: HashMap<ReflectableUuid, Doc>,
+    /// Least recently used first.
+    order: VecDeque<ReflectableUuid>,
+    dirty: HashSet<ReflectableUuid>,
+    evicted: Vec<Doc>,
+    capacity: usize,
+}
+
+impl Default for DocCache {
+    fn default() -> Self {
+        DocCache::new(MAX_SAVED_DOCS_IN_MEMORY)
+    }
+}
+
+impl DocCache {
+    pub fn new(capacity: usize) -> Self {
+        DocCache {
+            docs: HashMap::new(),
+            order: VecDeque::new(),
+            dirty: HashSet::new(),
+            evicted: Vec::new(),
+            capacity: capacity.max(1),
+        }
+    }
+
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity.max(1);
+        self.evict(None);
+    }
+
+    pub fn get(&self, id: &ReflectableUuid) -> Option<&Doc> {
+        self.docs.get(id)
+    }
+
+    /// Marks the document as used, changes have to be reported with `mark_dirty`.
+    pub fn get_mut(&mut self, id: &ReflectableUuid) -> Option<&mut Doc> {
+        if !self.docs.contains_key(id) {
+            return None;
+        }
+        self.touch(id);
+        self.docs.get_mut(id)
+    }
+
+    /// Adds a document that is not in the store yet, or differs from it.
+    pub fn insert(&mut self, id: ReflectableUuid, doc: Doc) {
+        self.dirty.insert(id);
+        self.insert_stored(id, doc);
+    }
+
+    /// Adds a document that was just read from the store.
+    pub fn insert_stored(&mut self, id: ReflectableUuid, doc: Doc) {
+        self.docs.insert(id, doc);
+        self.touch(&id);
+        self.evict(Some(id));
+    }
+
+    pub fn remove(&mut self, id: &ReflectableUuid) -> Option<Doc> {
+        self.order.retain(|x| x != id);
+        self.dirty.remove(id);
+        self.docs.remove(id)
+    }
+
+    pub fn contains_key(&self, id: &ReflectableUuid) -> bool {
+        self.docs.contains_key(id)
+    }
+
+    /// Ids ordered from the least to the most recently used.
+    pub fn keys(&self) -> impl DoubleEndedIterator<Item = &ReflectableUuid> {
+        self.order.iter()
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &Doc> {
+        self.docs.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.docs.len()
+    }
+
+    pub fn touch(&mut self, id: &ReflectableUuid) {
+        if self.order.back() != Some(id) {
+            self.order.retain(|x| x != id);
+            self.order.push_back(*id);
+        }
+    }
+
+    /// Records that the document differs from the store and has to be flushed on eviction.
+    pub fn mark_dirty(&mut self, id: &ReflectableUuid) {
+        if self.docs.contains_key(id) {
+            self.dirty.insert(*id);
+        }
+    }
+
+    pub fn mark_clean(&mut self, id: &ReflectableUuid) {
+        self.dirty.remove(id);
+    }
+
+    /// Evicted documents that still have to be written to the store.
+    pub fn evicted(&self) -> impl Iterator<Item = &Doc> {
+        self.evicted.iter()
+    }
+
+    pub fn take_evicted(&mut self) -> Vec<Doc> {
+        std::mem::take(&mut self.evicted)
+    }
+
+    /// Brings back an evicted document before it was flushed, the store has an older copy.
+    pub fn reclaim(&mut self, id: &ReflectableUuid) -> bool {
+        match self.evicted.iter().position(|doc| doc.id == *id) {
+            Some(index) => {
+                let doc = self.evicted.remove(index);
+                self.insert(*id, doc);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn evict(&mut self, keep: Option<ReflectableUuid>) {
+        while self.docs.len() > self.capacity {
+            let Some(index) = self.order.iter().position(|id| Some(*id) != keep) else {
+                break;
+            };
+            let id = self.order.remove(index).unwrap();
+            if let Some(doc) = self.docs.remove(&id) {
+                if self.dirty.remove(&id) {
+                    self.evicted.push(doc);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn doc() -> (ReflectableUuid, Doc) {
+        let id = ReflectableUuid::generate();
+        (
+            id,
+            Doc {
+                id,
+                ..Default::default()
+            },
+        )
+    }
+
+    #[test]
+    fn test_evicts_least_recently_used() {
+        let mut cache = DocCache::new(2);
+        let (a, doc_a) = doc();
+        let (b, doc_b) = doc();
+        let (c, doc_c) = doc();
+        cache.insert_stored(a, doc_a);
+        cache.insert_stored(b, doc_b);
+        cache.touch(&a);
+        cache.insert_stored(c, doc_c);
+
+        assert!(cache.contains_key(&a));
+        assert!(!cache.contains_key(&b));
+        assert!(cache.contains_key(&c));
+        assert_eq!(cache.keys().cloned().collect::<Vec<_>>(), vec![a, c]);
+        assert!(cache.take_evicted().is_empty());
+    }
+
+    #[test]
+    fn test_dirty_docs_are_kept_for_flushing() {
+        let mut cache = DocCache::new(1);
+        let (a, doc_a) = doc();
+        let (b, doc_b) = doc();
+        cache.insert_stored(a, doc_a);
+        cache.get_mut(&a).unwrap().name = "changed".to_string();
+        cache.mark_dirty(&a);
+        cache.insert_stored(b, doc_b);
+
+        assert_eq!(cache.evicted().count(), 1);
+        assert!(cache.reclaim(&a));
+        assert_eq!(cache.get(&a).unwrap().name, "changed");
+        assert!(!cache.contains_key(&b));
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn test_untouched_docs_are_not_flushed() {
+        let mut cache = DocCache::new(1);
+        let (a, doc_a) = doc();
+        let (b, doc_b) = doc();
+        cache.insert_stored(a, doc_a);
+        // read through a mutable borrow without changing anything
+        assert!(cache.get_mut(&a).unwrap().tabs.is_empty());
+        cache.insert_stored(b, doc_b);
+
+        assert!(!cache.contains_key(&a));
+        assert!(cache.take_evicted().is_empty());
+    }
+
+    #[test]
+    fn test_shrinking_capacity_evicts() {
+        let mut cache = DocCache::new(3);
+        let (a, doc_a) = doc();
+        let (b, doc_b) = doc();
+        cache.insert(a, doc_a);
+        cache.insert_stored(b, doc_b);
+        cache.set_capacity(1);
+
+        assert_eq!(cache.keys().cloned().collect::<Vec<_>>(), vec![b]);
+        assert_eq!(cache.take_evicted()[0].id, a);
+    }
+}
@@ -0,0 +1,390 @@
+//! Tables of notes: every CSV or TSV row is a note.
+//!
+//! A header row names the columns, in any order: `text`, `color`, `type`, `x`,
+//! `y` and `group`. Without a header the first column is the text.
+
+use bevy::prelude::*;
+use uuid::Uuid;
+
+use super::graph::text_node;
+use super::{FormatError, ThemeColors, NODE_COLOR_KEYS};
+use crate::components::TabSnapshot;
+use crate::themes::Theme;
+use crate::ui_plugin::NodeType;
+use crate::{JsonNode, JsonNodeText, TextPos};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Column {
+    Text,
+    Color,
+    Type,
+    X,
+    Y,
+    Group,
+}
+
+impl Column {
+    fn parse(name: &str) -> Option<Column> {
+        match name.trim().to_lowercase().as_str() {
+            "text" | "note" | "title" => Some(Column::Text),
+            "color" | "colour" => Some(Column::Color),
+            "type" | "shape" => Some(Column::Type),
+            "x" => Some(Column::X),
+            "y" => Some(Column::Y),
+            "group" | "cluster" => Some(Column::Group),
+            _ => None,
+        }
+    }
+}
+
+struct Row {
+    text: String,
+    bg_color: String,
+    node_type: NodeType,
+    position: Option<Vec2>,
+    group: String,
+}
+
+/// Creates a note for every row. Rows with `x` and `y` keep their position,
+/// the others are laid out in a grid, or in a grid per group with the group
+/// name above it, below the positioned notes.
+pub fn csv_to_tab(text: &str, theme: &Theme) -> Result<TabSnapshot, FormatError> {
+    let records = parse_records(text, delimiter(text))?;
+    let Some((_, first)) = records.first() else {
+        return Err(FormatError::Empty);
+    };
+    let header: Vec<Option<Column>> = first.iter().map(|name| Column::parse(name)).collect();
+    let (columns, records) = match header.iter().any(Option::is_some) {
+        true => {
+            if !header.contains(&Some(Column::Text)) {
+                return Err(FormatError::Syntax {
+                    line: records[0].0,
+                    message: "missing text column".to_string(),
+                });
+            }
+            (header, &records[1..])
+        }
+        false => (vec![Some(Column::Text)], &records[..]),
+    };
+
+    let colors = ThemeColors::new(theme);
+    let mut rows = vec![];
+    for (line, record) in records {
+        let error = |message: String| FormatError::Syntax {
+            line: *line,
+            message,
+        };
+        let mut row = Row {
+            text: String::new(),
+            bg_color: "node_bg".to_string(),
+            node_type: NodeType::Rect,
+            position: None,
+            group: String::new(),
+        };
+        let (mut x, mut y) = (None, None);
+        for (column, value) in columns.iter().zip(record) {
+            let value = value.trim();
+            if value.is_empty() {
+                continue;
+            }
+            match column {
+                Some(Column::Text) => row.text = value.to_string(),
+                Some(Column::Color) => {
+                    row.bg_color = parse_color(value, &colors)
+                        .ok_or_else(|| error(format!("unknown color `{}`", value)))?;
+                }
+                Some(Column::Type) => {
+                    row.node_type = parse_node_type(value)
+                        .ok_or_else(|| error(format!("unknown type `{}`", value)))?;
+                }
+                Some(Column::X) => {
+                    x = Some(
+                        parse_number(value)
+                            .ok_or_else(|| error(format!("invalid x `{}`", value)))?,
+                    )
+                }
+                Some(Column::Y) => {
+                    y = Some(
+                        parse_number(value)
+                            .ok_or_else(|| error(format!("invalid y `{}`", value)))?,
+                    )
+                }
+                Some(Column::Group) => row.group = value.to_string(),
+                None => {}
+            }
+        }
+        row.position = match (x, y) {
+            (Some(x), Some(y)) => Some(Vec2::new(x, y)),
+            (None, None) => None,
+            _ => return Err(error("x and y must be set together".to_string())),
+        };
+        rows.push(row);
+    }
+    if rows.is_empty() {
+        return Err(FormatError::Empty);
+    }
+    Ok(layout(rows, theme))
+}
+
+fn layout(rows: Vec<Row>, theme: &Theme) -> TabSnapshot {
+    let size = Vec2::new(theme.node_width, theme.node_height);
+    let cell = size * Vec2::new(1.25, 1.5);
+    // groups in the order they first appear, rows without a group last
+    let mut groups: Vec<(&str, Vec<usize>)> = vec![];
+    for (i, row) in rows.iter().enumerate() {
+        if row.position.is_some() {
+            continue;
+        }
+        match groups.iter_mut().find(|(group, _)| *group == row.group) {
+            Some((_, members)) => members.push(i),
+            None => groups.push((&row.group, vec![i])),
+        }
+    }
+    groups.sort_by_key(|(group, _)| group.is_empty());
+
+    // the grids start below the positioned notes
+    let fixed: Vec<Vec2> = rows.iter().filter_map(|row| row.position).collect();
+    let mut corner = match fixed.is_empty() {
+        true => Vec2::ZERO,
+        false => {
+            let left = fixed.iter().map(|p| p.x).fold(f32::MAX, f32::min);
+            let bottom = fixed.iter().map(|p| p.y).fold(f32::MAX, f32::min);
+            Vec2::new(left, bottom - cell.y)
+        }
+    };
+    let mut positions: Vec<Option<Vec2>> = rows.iter().map(|row| row.position).collect();
+    let mut labels = vec![];
+    for (group, members) in &groups {
+        let columns = (members.len() as f32).sqrt().ceil() as usize;
+        if !group.is_empty() {
+            let label = text_node(group.to_string(), Vec2::ZERO, theme);
+            let x = corner.x - size.x / 2. + label.width / 2.;
+            labels.push(JsonNode {
+                y: corner.y + size.y / 2. + label.height / 2.,
+                x,
+                ..label
+            });
+        }
+        for (i, member) in members.iter().enumerate() {
+            let offset = Vec2::new((i % columns) as f32, -((i / columns) as f32));
+            positions[*member] = Some(corner + offset * cell);
+        }
+        corner.x += columns as f32 * cell.x + size.x;
+    }
+
+    let mut nodes: Vec<JsonNode<String>> = rows
+        .into_iter()
+        .zip(positions)
+        .map(|(row, position)| {
+            let position = position.unwrap_or_default();
+            JsonNode {
+                id: Uuid::new_v4(),
+                node_type: row.node_type,
+                x: position.x,
+                y: position.y,
+                width: size.x,
+                height: size.y,
+                text: JsonNodeText {
+                    text: row.text,
+                    pos: TextPos::Center,
+                },
+                bg_color: row.bg_color,
+                visible: true,
+                ..default()
+            }
+        })
+        .collect();
+    nodes.extend(labels);
+    TabSnapshot { nodes, ..default() }
+}
+
+/// Tabs when the first line has one, else semicolons when it has those but no
+/// commas, else commas.
+fn delimiter(text: &str) -> char {
+    let first = text
+        .lines()
+        .find(|line| !line.trim().is_empty())
+        .unwrap_or_default();
+    if first.contains('\t') {
+        '\t'
+    } else if first.contains(';') && !first.contains(',') {
+        ';'
+    } else {
+        ','
+    }
+}
+
+/// Records with the line they start on. Quoted fields may contain the
+/// delimiter, line breaks and doubled quotes. Blank lines are skipped.
+fn parse_records(text: &str, delimiter: char) -> Result<Vec<(usize, Vec<String>)>, FormatError> {
+    let mut records = vec![];
+    let mut record = vec![];
+    let mut field = String::new();
+    let mut line = 1;
+    let mut start = 1;
+    let mut quoted = false;
+    let mut quote_line = 0;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (quoted, c) {
+            (true, '"') if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            (true, '"') => quoted = false,
+            (true, c) => {
+                if c == '\n' {
+                    line += 1;
+                }
+                field.push(c);
+            }
+            (false, '"') if field.trim().is_empty() => {
+                field.clear();
+                quoted = true;
+                quote_line = line;
+            }
+            (false, '\r') => {}
+            (false, '\n') => {
+                record.push(std::mem::take(&mut field));
+                if record.iter().any(|field| !field.trim().is_empty()) {
+                    records.push((start, std::mem::take(&mut record)));
+                }
+                record.clear();
+                line += 1;
+                start = line;
+            }
+            (false, c) if c == delimiter => record.push(std::mem::take(&mut field)),
+            (false, c) => field.push(c),
+        }
+    }
+    if quoted {
+        return Err(FormatError::Syntax {
+            line: quote_line,
+            message: "unterminated quoted field".to_string(),
+        });
+    }
+    record.push(field);
+    if record.iter().any(|field| !field.trim().is_empty()) {
+        records.push((start, record));
+    }
+    Ok(records)
+}
+
+/// A theme key like `color_change_2`, its number `2`, or a hex color that is
+/// matched to the closest node color.
+fn parse_color(value: &str, colors: &ThemeColors) -> Option<String> {
+    let value = value.to_lowercase();
+    if NODE_COLOR_KEYS.contains(&value.as_str()) {
+        return Some(value);
+    }
+    if let Ok(number @ 1..=5) = value.parse::<u8>() {
+        return Some(format!("color_change_{}", number));
+    }
+    let hex = value.strip_prefix('#')?;
+    Some(colors.closest_node_color(Color::hex(hex).ok()?))
+}
+
+fn parse_node_type(value: &str) -> Option<NodeType> {
+    match value.to_lowercase().as_str() {
+        "rect" | "rectangle" | "box" => Some(NodeType::Rect),
+        "paper" | "sticky" => Some(NodeType::Paper),
+        "circle" | "ellipse" => Some(NodeType::Circle),
+        _ => None,
+    }
+}
+
+fn parse_number(value: &str) -> Option<f32> {
+    value
+        .parse::<f32>()
+        .ok()
+        .filter(|number| number.is_finite())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::themes::velo_light;
+
+    #[test]
+    fn test_csv_to_tab() {
+        let csv = "Group,Text,Color,Shape\n\
+                   Ideas,\"Hello, world\",2,\n\
+                   ,Alone,,circle\n\
+                   Ideas,\"Say \"\"hi\"\"\nto all\",color_change_5,paper\n\
+                   \n\
+                   Risks,Rain,#000000,\n";
+        let theme = velo_light();
+        let snapshot = csv_to_tab(csv, &theme).unwrap();
+        let nodes: Vec<(&str, &str, NodeType)> = snapshot
+            .nodes
+            .iter()
+            .map(|node| {
+                let text = node.text.text.as_str();
+                (text, node.bg_color.as_str(), node.node_type.clone())
+            })
+            .collect();
+        assert_eq!(
+            nodes,
+            vec![
+                ("Hello, world", "color_change_2", NodeType::Rect),
+                ("Alone", "node_bg", NodeType::Circle),
+                ("Say \"hi\"\nto all", "color_change_5", NodeType::Paper),
+                ("Rain", "color_change_1", NodeType::Rect),
+                ("Ideas", "color_none", NodeType::Rect),
+                ("Risks", "color_none", NodeType::Rect),
+            ]
+        );
+        // a grid per group, from left to right, ungrouped rows last
+        let position = |i: usize| Vec2::new(snapshot.nodes[i].x, snapshot.nodes[i].y);
+        assert_eq!(position(0), Vec2::ZERO);
+        assert_eq!(position(2), Vec2::new(theme.node_width * 1.25, 0.));
+        assert!(position(3).x > position(2).x);
+        assert!(position(1).x > position(3).x);
+        assert!(snapshot.nodes[4].y > position(0).y);
+    }
+
+    #[test]
+    fn test_tsv_positions() {
+        let tsv = "one\t2\nx\ty\n";
+        let snapshot = csv_to_tab(tsv, &velo_light()).unwrap();
+        let texts: Vec<&str> = snapshot
+            .nodes
+            .iter()
+            .map(|node| node.text.text.as_str())
+            .collect();
+        assert_eq!(texts, vec!["one", "x"]);
+
+        let tsv = "text\tx\ty\nA\t100\t-50\nB\t\t\n";
+        let theme = velo_light();
+        let snapshot = csv_to_tab(tsv, &theme).unwrap();
+        assert_eq!((snapshot.nodes[0].x, snapshot.nodes[0].y), (100., -50.));
+        let b = &snapshot.nodes[1];
+        assert_eq!((b.x, b.y), (100., -50. - theme.node_height * 1.5));
+    }
+
+    #[test]
+    fn test_csv_errors() {
+        let theme = velo_light();
+        let error = |text: &str| csv_to_tab(text, &theme).unwrap_err().to_string();
+        assert!(matches!(
+            csv_to_tab("\n \n", &theme),
+            Err(FormatError::Empty)
+        ));
+        assert!(matches!(
+            csv_to_tab("text,color\n", &theme),
+            Err(FormatError::Empty)
+        ));
+        assert_eq!(error("color,x\nred,1"), "line 1: missing text column");
+        assert_eq!(
+            error("text,color\na,1\nb,red"),
+            "line 3: unknown color `red`"
+        );
+        assert_eq!(error("text,type\na,star"), "line 2: unknown type `star`");
+        assert_eq!(error("text,x,y\na,one,2"), "line 2: invalid x `one`");
+        assert_eq!(error("text,x\na,1"), "line 2: x and y must be set together");
+        assert_eq!(
+            error("text\na\n\"b\nc"),
+            "line 3: unterminated quoted field"
+        );
+    }
+}
@@ -0,0 +1,851 @@
+//! Graphviz DOT graphs.
+
+use bevy::prelude::*;
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use super::graph::{Direction, Graph, GraphEdge, GraphNode, TEXT_BG_COLOR};
+use super::svg::num;
+use super::{FormatError, ThemeColors};
+use crate::canvas::arrow::components::ArrowType;
+use crate::components::TabSnapshot;
+use crate::themes::Theme;
+use crate::ui_plugin::NodeType;
+
+/// Width of a default Graphviz node in points, matched to the theme node width.
+const DEFAULT_NODE_WIDTH: f32 = 54.;
+
+/// X11 colors that are common in hand written graphs.
+const NAMED_COLORS: [(&str, &str); 19] = [
+    ("white", "ffffff"),
+    ("black", "000000"),
+    ("gray", "bebebe"),
+    ("grey", "bebebe"),
+    ("lightgray", "d3d3d3"),
+    ("lightgrey", "d3d3d3"),
+    ("red", "ff0000"),
+    ("green", "00ff00"),
+    ("lightgreen", "90ee90"),
+    ("blue", "0000ff"),
+    ("lightblue", "add8e6"),
+    ("yellow", "ffff00"),
+    ("lightyellow", "ffffe0"),
+    ("orange", "ffa500"),
+    ("pink", "ffc0cb"),
+    ("purple", "a020f0"),
+    ("cyan", "00ffff"),
+    ("magenta", "ff00ff"),
+    ("transparent", "00000000"),
+];
+
+/// Writes the nodes and arrows of the tab as a directed graph. Positions are
+/// kept in points, so `neato -n` draws the graph like the canvas.
+pub fn tab_to_dot(snapshot: &TabSnapshot, theme: &Theme) -> String {
+    let graph = Graph::from_tab(snapshot);
+    let colors = ThemeColors::new(theme);
+    let scale = theme.node_width / DEFAULT_NODE_WIDTH;
+    let mut dot = String::from("digraph {\n");
+    let _ = writeln!(
+        dot,
+        "    rankdir={};",
+        match graph.direction {
+            Direction::Right => "LR",
+            Direction::Down => "TB",
+        }
+    );
+    dot.push_str("    node [style=filled];\n");
+    for (i, node) in graph.nodes.iter().enumerate() {
+        let mut attrs = vec![format!("label={}", quote(&node.text))];
+        let key = node.bg_color.as_deref().unwrap_or("node_bg");
+        if key == TEXT_BG_COLOR {
+            attrs.push("shape=plaintext".to_string());
+        } else {
+            let shape = match node.node_type {
+                NodeType::Rect => "box",
+                NodeType::Paper => "note",
+                NodeType::Circle => "circle",
+            };
+            attrs.push(format!("shape={}", shape));
+            if let Some(color) = colors.get(key) {
+                attrs.push(format!("fillcolor=\"{}\"", hex(color)));
+            }
+        }
+        if let Some(position) = node.position.filter(|position| position.is_finite()) {
+            attrs.push(format!(
+                "pos=\"{},{}!\"",
+                num(position.x / scale),
+                num(position.y / scale)
+            ));
+        }
+        let _ = writeln!(dot, "    n{} [{}];", i + 1, attrs.join(", "));
+    }
+    for edge in &graph.edges {
+        let mut attrs = vec![];
+        match edge.arrow_type {
+            ArrowType::Line | ArrowType::ParallelLine => attrs.push("dir=none".to_string()),
+            ArrowType::DoubleArrow | ArrowType::ParallelDoubleArrow => {
+                attrs.push("dir=both".to_string())
+            }
+            ArrowType::Arrow | ArrowType::ParallelArrow => {}
+        }
+        if let Some(label) = &edge.label {
+            attrs.push(format!("label={}", quote(label)));
+        }
+        let attrs = match attrs.is_empty() {
+            true => String::new(),
+            false => format!(" [{}]", attrs.join(", ")),
+        };
+        let _ = writeln!(dot, "    n{} -> n{}{};", edge.from + 1, edge.to + 1, attrs);
+    }
+    dot.push_str("}\n");
+    dot
+}
+
+fn quote(text: &str) -> String {
+    format!(
+        "\"{}\"",
+        text.replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('\n', "\\n")
+    )
+}
+
+fn hex(color: Color) -> String {
+    let [r, g, b, a] = color.as_rgba_u8();
+    match a {
+        255 => format!("#{:02x}{:02x}{:02x}", r, g, b),
+        _ => format!("#{:02x}{:02x}{:02x}{:02x}", r, g, b, a),
+    }
+}
+
+/// Reads a DOT graph. Nodes with a `pos` attribute keep their position, the
+/// others are laid out in the `rankdir` of the graph.
+pub fn dot_to_tab(text: &str, theme: &Theme) -> Result<TabSnapshot, FormatError> {
+    let graph = parse_dot(text, theme)?;
+    if graph.nodes.is_empty() {
+        return Err(FormatError::Empty);
+    }
+    Ok(graph.into_tab(theme))
+}
+
+fn parse_dot(text: &str, theme: &Theme) -> Result<Graph, FormatError> {
+    let mut parser = Parser {
+        tokens: tokenize(text)?,
+        scale: theme.node_width / DEFAULT_NODE_WIDTH,
+        ..Default::default()
+    };
+    parser.parse_graph()?;
+    Ok(parser.into_graph(theme))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    /// Identifier, number, quoted or HTML string.
+    Id {
+        text: String,
+        quoted: bool,
+    },
+    /// `->` if directed, `--` otherwise.
+    EdgeOp {
+        directed: bool,
+    },
+    Punct(char),
+}
+
+fn syntax_error(line: usize, message: &str) -> FormatError {
+    FormatError::Syntax {
+        line,
+        message: message.to_string(),
+    }
+}
+
+fn tokenize(text: &str) -> Result<Vec<(Token, usize)>, FormatError> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = vec![];
+    let mut line = 1;
+    let mut line_start = true;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        if c == '\n' {
+            line += 1;
+            line_start = true;
+            i += 1;
+            continue;
+        }
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+        let at_line_start = std::mem::replace(&mut line_start, false);
+        // `#` lines are preprocessor output
+        if (c == '#' && at_line_start) || (c == '/' && next == Some('/')) {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+            continue;
+        }
+        if c == '/' && next == Some('*') {
+            let start = line;
+            i += 2;
+            loop {
+                match chars.get(i) {
+                    None => return Err(syntax_error(start, "unterminated comment")),
+                    Some('*') if chars.get(i + 1) == Some(&'/') => {
+                        i += 2;
+                        break;
+                    }
+                    Some('\n') => line += 1,
+                    _ => {}
+                }
+                i += 1;
+            }
+            continue;
+        }
+        if c == '"' {
+            let start = line;
+            let mut text = String::new();
+            i += 1;
+            loop {
+                match chars.get(i) {
+                    None => return Err(syntax_error(start, "unterminated string")),
+                    Some('"') => break,
+                    Some('\\') if next_is(&chars, i, '"') => {
+                        text.push('"');
+                        i += 1;
+                    }
+                    // escaped backslashes stay for the label escapes
+                    Some('\\') if next_is(&chars, i, '\\') => {
+                        text.push_str("\\\\");
+                        i += 1;
+                    }
+                    Some('\\') if next_is(&chars, i, '\n') => {
+                        line += 1;
+                        i += 1;
+                    }
+                    Some(c) => {
+                        if *c == '\n' {
+                            line += 1;
+                        }
+                        text.push(*c);
+                    }
+                }
+                i += 1;
+            }
+            i += 1;
+            tokens.push((Token::Id { text, quoted: true }, start));
+            continue;
+        }
+        if c == '<' {
+            let start = line;
+            let mut html = String::new();
+            let mut depth = 0;
+            loop {
+                let Some(c) = chars.get(i) else {
+                    return Err(syntax_error(start, "unterminated HTML string"));
+                };
+                match c {
+                    '<' => depth += 1,
+                    '>' => depth -= 1,
+                    '\n' => line += 1,
+                    _ => {}
+                }
+                i += 1;
+                if depth == 0 {
+                    break;
+                }
+                html.push(*c);
+            }
+            let text = html_text(&html[1..]);
+            tokens.push((Token::Id { text, quoted: true }, start));
+            continue;
+        }
+        if c == '-' && matches!(next, Some('>') | Some('-')) {
+            let directed = next == Some('>');
+            tokens.push((Token::EdgeOp { directed }, line));
+            i += 2;
+            continue;
+        }
+        if c.is_alphanumeric() || matches!(c, '_' | '.' | '-') || !c.is_ascii() {
+            let start = i;
+            i += 1;
+            while i < chars.len()
+                && (chars[i].is_alphanumeric()
+                    || matches!(chars[i], '_' | '.')
+                    || !chars[i].is_ascii())
+            {
+                i += 1;
+            }
+            let text = chars[start..i].iter().collect();
+            tokens.push((
+                Token::Id {
+                    text,
+                    quoted: false,
+                },
+                line,
+            ));
+            continue;
+        }
+        if "{}[];,=:".contains(c) {
+            tokens.push((Token::Punct(c), line));
+            i += 1;
+            continue;
+        }
+        return Err(syntax_error(line, &format!("unexpected character '{}'", c)));
+    }
+    Ok(tokens)
+}
+
+fn next_is(chars: &[char], i: usize, c: char) -> bool {
+    chars.get(i + 1) == Some(&c)
+}
+
+/// Text of an HTML label, `<br/>` becomes a new line and other tags are dropped.
+fn html_text(html: &str) -> String {
+    let mut text = String::new();
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        text.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find('>')
+            .map_or(rest.len(), |end| start + end + 1);
+        if rest[start + 1..]
+            .trim_start()
+            .to_ascii_lowercase()
+            .starts_with("br")
+        {
+            text.push('\n');
+        }
+        rest = &rest[end..];
+    }
+    text.push_str(rest);
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&amp;", "&")
+}
+
+type Attrs = HashMap<String, String>;
+
+/// `node` and `edge` attribute statements, they apply until the end of the
+/// subgraph they are in.
+#[derive(Clone, Default)]
+struct Defaults {
+    node: Attrs,
+    edge: Attrs,
+}
+
+#[derive(Default)]
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    directed: bool,
+    /// At most one edge between two nodes.
+    strict: bool,
+    /// Canvas pixels per point.
+    scale: f32,
+    names: Vec<String>,
+    ids: HashMap<String, usize>,
+    node_attrs: Vec<Attrs>,
+    edges: Vec<(usize, usize, Attrs)>,
+    graph_attrs: Attrs,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(token, _)| token)
+    }
+
+    fn line(&self) -> usize {
+        self.tokens
+            .get(self.pos)
+            .or(self.tokens.last())
+            .map_or(1, |(_, line)| *line)
+    }
+
+    fn error(&self, message: &str) -> FormatError {
+        syntax_error(self.line(), message)
+    }
+
+    /// Keywords are case insensitive and never quoted.
+    fn keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Id { text, quoted: false }) if text.eq_ignore_ascii_case(keyword))
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        let found = self.peek() == Some(&Token::Punct(c));
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), FormatError> {
+        match self.eat(c) {
+            true => Ok(()),
+            false => Err(self.error(&format!("expected '{}'", c))),
+        }
+    }
+
+    fn id(&mut self) -> Result<String, FormatError> {
+        match self.peek() {
+            Some(Token::Id { text, .. }) => {
+                let text = text.clone();
+                self.pos += 1;
+                Ok(text)
+            }
+            _ => Err(self.error("expected an identifier")),
+        }
+    }
+
+    fn parse_graph(&mut self) -> Result<(), FormatError> {
+        if self.keyword("strict") {
+            self.strict = true;
+            self.pos += 1;
+        }
+        if self.keyword("digraph") {
+            self.directed = true;
+        } else if !self.keyword("graph") {
+            return Err(self.error("expected graph or digraph"));
+        }
+        self.pos += 1;
+        if matches!(self.peek(), Some(Token::Id { .. })) {
+            self.pos += 1;
+        }
+        self.expect('{')?;
+        self.parse_statements(Defaults::default())?;
+        self.expect('}')?;
+        if self.peek().is_some() {
+            return Err(self.error("unexpected text after the graph"));
+        }
+        Ok(())
+    }
+
+    /// Parses up to the closing brace, returns the nodes used in the statements.
+    fn parse_statements(&mut self, mut defaults: Defaults) -> Result<Vec<usize>, FormatError> {
+        let mut members = vec![];
+        loop {
+            match self.peek() {
+                None => return Err(self.error("missing '}'")),
+                Some(Token::Punct('}')) => return Ok(members),
+                Some(Token::Punct(';')) => self.pos += 1,
+                _ => self.parse_statement(&mut defaults, &mut members)?,
+            }
+        }
+    }
+
+    fn parse_statement(
+        &mut self,
+        defaults: &mut Defaults,
+        members: &mut Vec<usize>,
+    ) -> Result<(), FormatError> {
+        if self.keyword("graph") {
+            self.pos += 1;
+            let attrs = self.parse_attrs()?;
+            self.graph_attrs.extend(attrs);
+            return Ok(());
+        }
+        if self.keyword("node") {
+            self.pos += 1;
+            let attrs = self.parse_attrs()?;
+            defaults.node.extend(attrs);
+            return Ok(());
+        }
+        if self.keyword("edge") {
+            self.pos += 1;
+            let attrs = self.parse_attrs()?;
+            defaults.edge.extend(attrs);
+            return Ok(());
+        }
+        if matches!(self.peek(), Some(Token::Id { .. }))
+            && self.tokens.get(self.pos + 1).map(|(token, _)| token) == Some(&Token::Punct('='))
+        {
+            let key = self.id()?;
+            self.pos += 1;
+            let value = self.id()?;
+            self.graph_attrs.insert(key, value);
+            return Ok(());
+        }
+
+        let mut operands = vec![self.parse_operand(defaults, members)?];
+        while let Some(Token::EdgeOp { directed }) = self.peek() {
+            if *directed != self.directed {
+                return Err(self.error(match self.directed {
+                    true => "use -> in a digraph",
+                    false => "use -- in a graph",
+                }));
+            }
+            self.pos += 1;
+            operands.push(self.parse_operand(defaults, members)?);
+        }
+        let attrs = self.parse_attrs()?;
+        if let [(nodes, true)] = operands.as_slice() {
+            self.node_attrs[nodes[0]].extend(attrs);
+            return Ok(());
+        }
+        let mut edge_attrs = defaults.edge.clone();
+        edge_attrs.extend(attrs);
+        for pair in operands.windows(2) {
+            for from in &pair[0].0 {
+                for to in &pair[1].0 {
+                    self.add_edge(*from, *to, &edge_attrs);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds an edge, in a strict graph the attributes of a repeated edge go to
+    /// the first one instead.
+    fn add_edge(&mut self, from: usize, to: usize, attrs: &Attrs) {
+        let directed = self.directed;
+        let same = |(a, b, _): &&mut (usize, usize, Attrs)| {
+            (*a, *b) == (from, to) || (!directed && (*a, *b) == (to, from))
+        };
+        match self.edges.iter_mut().find(same).filter(|_| self.strict) {
+            Some((_, _, existing)) => existing.extend(attrs.clone()),
+            None => self.edges.push((from, to, attrs.clone())),
+        }
+    }
+
+    /// A node or a subgraph, with whether it is a node.
+    fn parse_operand(
+        &mut self,
+        defaults: &Defaults,
+        members: &mut Vec<usize>,
+    ) -> Result<(Vec<usize>, bool), FormatError> {
+        if self.keyword("subgraph") || self.peek() == Some(&Token::Punct('{')) {
+            if self.keyword("subgraph") {
+                self.pos += 1;
+                if matches!(self.peek(), Some(Token::Id { .. })) {
+                    self.pos += 1;
+                }
+            }
+            self.expect('{')?;
+            let nodes = self.parse_statements(defaults.clone())?;
+            self.expect('}')?;
+            members.extend(&nodes);
+            return Ok((nodes, false));
+        }
+        let name = self.id()?;
+        // ports only say where arrows attach
+        while self.eat(':') {
+            self.id()?;
+        }
+        let index = match self.ids.get(&name) {
+            Some(index) => *index,
+            None => {
+                self.names.push(name.clone());
+                self.node_attrs.push(defaults.node.clone());
+                self.ids.insert(name, self.names.len() - 1);
+                self.names.len() - 1
+            }
+        };
+        members.push(index);
+        Ok((vec![index], true))
+    }
+
+    fn parse_attrs(&mut self) -> Result<Attrs, FormatError> {
+        let mut attrs = Attrs::new();
+        while self.eat('[') {
+            while !self.eat(']') {
+                let key = self.id()?;
+                self.expect('=')?;
+                let line = self.line();
+                let value = self.id()?;
+                if key == "pos"
+                    && parse_point(&value).is_some_and(|pos| !(pos * self.scale).is_finite())
+                {
+                    return Err(syntax_error(line, "pos is out of range"));
+                }
+                attrs.insert(key, value);
+                if !self.eat(',') {
+                    self.eat(';');
+                }
+            }
+        }
+        Ok(attrs)
+    }
+
+    fn into_graph(self, theme: &Theme) -> Graph {
+        let colors = ThemeColors::new(theme);
+        let scale = self.scale;
+        let nodes = self
+            .names
+            .iter()
+            .zip(&self.node_attrs)
+            .map(|(name, attrs)| {
+                let get = |key: &str| attrs.get(key).map(String::as_str);
+                let shape = get("shape").unwrap_or_default().to_ascii_lowercase();
+                let filled = get("style")
+                    .is_some_and(|style| style.split(',').any(|style| style.trim() == "filled"));
+                let fill = get("fillcolor").or(get("color").filter(|_| filled));
+                let bg_color = match shape.as_str() {
+                    "plaintext" | "plain" | "none" => Some(TEXT_BG_COLOR.to_string()),
+                    _ => fill
+                        .and_then(parse_color)
+                        .map(|color| colors.closest_node_color(color)),
+                };
+                // the default ellipse would leave little room for text in a note
+                let node_type = match shape.as_str() {
+                    "circle" | "doublecircle" | "ellipse" | "oval" | "point" => NodeType::Circle,
+                    "note" | "tab" | "folder" => NodeType::Paper,
+                    _ => NodeType::Rect,
+                };
+                GraphNode {
+                    text: label(get("label").unwrap_or("\\N"), name),
+                    node_type,
+                    bg_color,
+                    position: get("pos").and_then(parse_point).map(|pos| pos * scale),
+                }
+            })
+            .collect();
+        let edges = self
+            .edges
+            .into_iter()
+            .map(|(from, to, attrs)| {
+                let dir = attrs.get("dir").map_or(
+                    if self.directed { "forward" } else { "none" },
+                    String::as_str,
+                );
+                let no_head = attrs.get("arrowhead").is_some_and(|head| head == "none");
+                let arrow_type = match dir {
+                    "both" => ArrowType::DoubleArrow,
+                    "none" => ArrowType::Line,
+                    _ if no_head => ArrowType::Line,
+                    _ => ArrowType::Arrow,
+                };
+                let (from, to) = if dir == "back" {
+                    (to, from)
+                } else {
+                    (from, to)
+                };
+                GraphEdge {
+                    from,
+                    to,
+                    arrow_type,
+                    label: attrs.get("label").map(|text| label(text, "")),
+                }
+            })
+            .collect();
+        let direction = match self.graph_attrs.get("rankdir").map(String::as_str) {
+            Some("LR" | "RL") => Direction::Right,
+            _ => Direction::Down,
+        };
+        Graph {
+            nodes,
+            edges,
+            direction,
+        }
+    }
+}
+
+/// Label text with the line break escapes and the node name `\N` replaced.
+fn label(text: &str, name: &str) -> String {
+    let mut label = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            label.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n' | 'l' | 'r') => label.push('\n'),
+            Some('N') => label.push_str(name),
+            Some(c) => label.push(c),
+            None => label.push('\\'),
+        }
+    }
+    label.trim_end().to_string()
+}
+
+/// `x,y` in points, `!` pins the node in Graphviz.
+fn parse_point(pos: &str) -> Option<Vec2> {
+    let mut coordinates = pos
+        .trim()
+        .trim_end_matches('!')
+        .split(',')
+        .map(|coordinate| coordinate.trim().parse::<f32>());
+    Some(Vec2::new(
+        coordinates.next()?.ok()?,
+        coordinates.next()?.ok()?,
+    ))
+}
+
+/// Hex and a few named colors, a color list like `red:blue` gives its first color.
+fn parse_color(color: &str) -> Option<Color> {
+    let color = color.split([':', ';']).next()?.trim().to_ascii_lowercase();
+    if let Some(hex) = color.strip_prefix('#') {
+        return Color::hex(hex).ok();
+    }
+    let (_, hex) = NAMED_COLORS.iter().find(|(name, _)| *name == color)?;
+    Color::hex(hex).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::themes::{velo_dark, velo_light};
+
+    #[test]
+    fn test_parse_dot() {
+        let dot = r##"
+# generated
+strict digraph deps {
+    rankdir = LR
+    node [shape=box, style=filled, fillcolor="#ffffff"]
+    /* the app */
+    app [label="velo\nv0.1\l"];
+    app:out -> { core; "ui" } [label=uses]
+    subgraph cluster_libs {
+        node [shape=note, fillcolor=black]
+        core -> lib -> <<b>html</b>>
+    }
+    lib -> ui [dir=both]; ui -> app [arrowhead=none]
+    legend [shape=plaintext, label="\N"]
+}
+"##;
+        let graph = parse_dot(dot, &velo_light()).unwrap();
+        let texts: Vec<&str> = graph.nodes.iter().map(|node| node.text.as_str()).collect();
+        assert_eq!(
+            texts,
+            vec!["velo\nv0.1", "core", "ui", "lib", "html", "legend"]
+        );
+        assert_eq!(graph.direction, Direction::Right);
+        assert_eq!(graph.nodes[0].node_type, NodeType::Rect);
+        assert_eq!(graph.nodes[0].bg_color.as_deref(), Some("node_bg"));
+        // nodes first used in the subgraph get its defaults
+        assert_eq!(graph.nodes[3].node_type, NodeType::Paper);
+        assert_eq!(graph.nodes[3].bg_color.as_deref(), Some("color_change_1"));
+        assert_eq!(graph.nodes[5].bg_color.as_deref(), Some(TEXT_BG_COLOR));
+        let edges: Vec<(usize, usize, ArrowType, Option<&str>)> = graph
+            .edges
+            .iter()
+            .map(|edge| (edge.from, edge.to, edge.arrow_type, edge.label.as_deref()))
+            .collect();
+        assert_eq!(
+            edges,
+            vec![
+                (0, 1, ArrowType::Arrow, Some("uses")),
+                (0, 2, ArrowType::Arrow, Some("uses")),
+                (1, 3, ArrowType::Arrow, None),
+                (3, 4, ArrowType::Arrow, None),
+                (3, 2, ArrowType::DoubleArrow, None),
+                (2, 0, ArrowType::Line, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_dot_errors() {
+        let error = |dot: &str| parse_dot(dot, &velo_light()).unwrap_err().to_string();
+        assert_eq!(error("flowchart {}"), "line 1: expected graph or digraph");
+        assert_eq!(error("graph {\n a -> b\n}"), "line 2: use -- in a graph");
+        assert_eq!(
+            error("digraph {\n a [label=\"x]\n}"),
+            "line 2: unterminated string"
+        );
+        assert_eq!(error("digraph {\n a [color]\n}"), "line 2: expected '='");
+        assert_eq!(error("digraph {\n a -> b\n"), "line 2: missing '}'");
+        assert!(matches!(
+            dot_to_tab("graph { }", &velo_light()),
+            Err(FormatError::Empty)
+        ));
+    }
+
+    #[test]
+    fn test_dot_colors_roundtrip() {
+        for theme in [velo_light(), velo_dark()] {
+            let snapshot = dot_to_tab("digraph { a [fillcolor=\"#ff0000\"] }", &theme).unwrap();
+            assert_eq!(snapshot.nodes[0].bg_color, "color_change_4");
+            let exported = tab_to_dot(&snapshot, &theme);
+            let reimported = dot_to_tab(&exported, &theme).unwrap();
+            assert_eq!(reimported.nodes[0].bg_color, "color_change_4");
+        }
+    }
+
+    #[test]
+    fn test_strict_dot_edges() {
+        let theme = velo_light();
+        let edges = |dot: &str| {
+            let graph = parse_dot(dot, &theme).unwrap();
+            let edges: Vec<(usize, usize, ArrowType, Option<String>)> = graph
+                .edges
+                .iter()
+                .map(|edge| (edge.from, edge.to, edge.arrow_type, edge.label.clone()))
+                .collect();
+            (edges, graph.self_loops())
+        };
+        assert_eq!(
+            edges("strict digraph { a -> b; a -> b [label=x]; b -> a; a -> a }"),
+            (
+                vec![
+                    (0, 1, ArrowType::Arrow, Some("x".to_string())),
+                    (1, 0, ArrowType::Arrow, None),
+                    (0, 0, ArrowType::Arrow, None),
+                ],
+                1
+            )
+        );
+        assert_eq!(
+            edges("strict graph { a -- b; b -- a }").0,
+            vec![(0, 1, ArrowType::Line, None)]
+        );
+        assert_eq!(edges("digraph { a -> b; a -> b }").0.len(), 2);
+
+        // the canvas cannot draw the loop
+        let snapshot = dot_to_tab("digraph { a -> a; a -> b }", &theme).unwrap();
+        assert_eq!(snapshot.arrows.len(), 1);
+    }
+
+    #[test]
+    fn test_dot_positions_out_of_range() {
+        let error = |dot: &str| parse_dot(dot, &velo_light()).unwrap_err().to_string();
+        assert_eq!(
+            error("graph {\n a [pos=\"1e40,1e40!\"]\n}"),
+            "line 2: pos is out of range"
+        );
+        assert_eq!(
+            error("graph {\n a [label=a,\n pos=\"0,NaN\"]\n}"),
+            "line 3: pos is out of range"
+        );
+    }
+
+    #[test]
+    fn test_dot_positions() {
+        let theme = velo_light();
+        let dot = "graph { a [pos=\"0,0!\"]; b [pos=\"-100,0\"]; a -- b -- c }";
+        let snapshot = dot_to_tab(dot, &theme).unwrap();
+        let scale = theme.node_width / DEFAULT_NODE_WIDTH;
+        assert_eq!(snapshot.nodes[1].x, -100. * scale);
+        assert_eq!(snapshot.nodes[1].y, 0.);
+        // c has no position and goes below the others
+        assert!(snapshot.nodes[2].y < 0.);
+        assert_eq!(snapshot.arrows[0].arrow_type, ArrowType::Line);
+    }
+
+    #[test]
+    fn test_dot_roundtrip() {
+        let theme = velo_light();
+        let dot = "digraph {\n    rankdir=TB;\n    node [style=filled];\n    n1 [label=\"say \\\"hi\\\"\\nback\\\\slash\", shape=box, fillcolor=\"#ffffff\"];\n    n2 [label=\"b\", shape=note, fillcolor=\"#000000\"];\n    n3 [label=\"c\", shape=circle, fillcolor=\"#ffffff\"];\n    n1 -> n2 [label=\"yes\"];\n    n1 -> n3 [dir=both];\n    n2 -> n3 [dir=none];\n}\n";
+        let snapshot = dot_to_tab(dot, &theme).unwrap();
+        assert_eq!(snapshot.nodes[0].text.text, "say \"hi\"\nback\\slash");
+        let exported = tab_to_dot(&snapshot, &theme);
+        // positions of the laid out nodes are written too
+        let without_positions: String = exported
+            .lines()
+            .map(|line| match line.find(", pos=") {
+                Some(start) => format!("{}];\n", &line[..start]),
+                None => format!("{}\n", line),
+            })
+            .collect();
+        assert_eq!(without_positions, dot);
+
+        let reimported = dot_to_tab(&exported, &theme).unwrap();
+        for (node, original) in reimported.nodes.iter().zip(&snapshot.nodes) {
+            assert!((node.x - original.x).abs() < 0.1 && (node.y - original.y).abs() < 0.1);
+            assert_eq!(node.bg_color, original.bg_color);
+        }
+    }
+}
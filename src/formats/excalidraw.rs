@@ -0,0 +1,387 @@
+//! Excalidraw scenes, the `.excalidraw` files and the clipboard format.
+//!
+//! Elements have their top left corner at `x, y` with y growing down, points
+//! of lines and free drawings are relative to it.
+
+use bevy::prelude::*;
+use serde::Deserialize;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use super::graph::text_node;
+use super::{FormatError, ThemeColors};
+use crate::canvas::arrow::components::{ArrowConnect, ArrowConnectPos, ArrowMeta, ArrowType};
+use crate::canvas::arrow::utils::arrow_connector_offset;
+use crate::components::TabSnapshot;
+use crate::themes::Theme;
+use crate::ui_plugin::NodeType;
+use crate::utils::ReflectableUuid;
+use crate::{DrawingJsonNode, JsonNode, JsonNodeText, TextPos};
+
+/// Stroke width of drawings made on the canvas.
+const DRAWING_WIDTH: f32 = 2.;
+
+#[derive(Deserialize)]
+struct Scene {
+    elements: Vec<Element>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Element {
+    id: String,
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    x: f32,
+    #[serde(default)]
+    y: f32,
+    #[serde(default)]
+    width: f32,
+    #[serde(default)]
+    height: f32,
+    #[serde(default)]
+    angle: f32,
+    #[serde(default)]
+    is_deleted: bool,
+    #[serde(default)]
+    stroke_color: Option<String>,
+    #[serde(default)]
+    background_color: Option<String>,
+    #[serde(default)]
+    text: Option<String>,
+    /// The text before wrapping.
+    #[serde(default)]
+    original_text: Option<String>,
+    #[serde(default)]
+    container_id: Option<String>,
+    #[serde(default)]
+    points: Vec<[f32; 2]>,
+    #[serde(default)]
+    start_binding: Option<Binding>,
+    #[serde(default)]
+    end_binding: Option<Binding>,
+    #[serde(default)]
+    start_arrowhead: Option<String>,
+    #[serde(default)]
+    end_arrowhead: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Binding {
+    element_id: String,
+}
+
+impl Element {
+    fn text(&self) -> String {
+        self.original_text
+            .clone()
+            .or(self.text.clone())
+            .unwrap_or_default()
+    }
+
+    fn center(&self) -> Vec2 {
+        Vec2::new(self.x + self.width / 2., -(self.y + self.height / 2.))
+    }
+
+    /// Point of a line in canvas coordinates.
+    fn point(&self, point: [f32; 2]) -> Vec2 {
+        Vec2::new(self.x + point[0], -(self.y + point[1]))
+    }
+}
+
+fn parse_color(color: Option<&str>) -> Option<Color> {
+    let color = color?.trim();
+    match color {
+        "transparent" => None,
+        _ => Color::hex(color.trim_start_matches('#')).ok(),
+    }
+}
+
+/// Side of the node that faces a point, arrows connect where they touch.
+fn closest_side(node: &JsonNode<String>, point: Vec2) -> ArrowConnectPos {
+    let size = Vec2::new(node.width, node.height).max(Vec2::ONE);
+    let offset = (point - Vec2::new(node.x, node.y)) / size;
+    if offset.x.abs() >= offset.y.abs() {
+        if offset.x >= 0. {
+            ArrowConnectPos::Right
+        } else {
+            ArrowConnectPos::Left
+        }
+    } else if offset.y >= 0. {
+        ArrowConnectPos::Top
+    } else {
+        ArrowConnectPos::Bottom
+    }
+}
+
+/// Line through `points` relative to the element origin, rotated like the element.
+fn drawing(
+    element: &Element,
+    points: &[[f32; 2]],
+    z: f32,
+    colors: &ThemeColors,
+) -> DrawingJsonNode<String> {
+    let (min, max) = points.iter().fold(
+        (Vec2::splat(f32::MAX), Vec2::splat(f32::MIN)),
+        |(min, max), point| (min.min(Vec2::from(*point)), max.max(Vec2::from(*point))),
+    );
+    let center = (min + max) / 2.;
+    let (sin, cos) = element.angle.sin_cos();
+    let points = points
+        .iter()
+        .map(|point| {
+            let p = Vec2::from(*point) - center;
+            let rotated = center + Vec2::new(p.x * cos - p.y * sin, p.x * sin + p.y * cos);
+            Vec2::new(rotated.x, -rotated.y)
+        })
+        .collect();
+    let stroke = parse_color(element.stroke_color.as_deref());
+    DrawingJsonNode {
+        x: element.x,
+        y: -element.y,
+        z,
+        id: ReflectableUuid::generate(),
+        points,
+        drawing_color: stroke.map_or("drawing_pencil_btn".to_string(), |color| {
+            colors.closest_drawing_color(color)
+        }),
+        width: DRAWING_WIDTH,
+    }
+}
+
+/// Reads an Excalidraw scene. Rectangles, diamonds and ellipses become notes
+/// with the text bound to them, arrows bound to two of them become arrows.
+/// Free drawings become drawings, unbound lines and arrows straight drawings
+/// from their first to their last point.
+pub fn excalidraw_to_tab(text: &str, theme: &Theme) -> Result<TabSnapshot, FormatError> {
+    let scene: Scene = serde_json::from_str(text)?;
+    let elements: Vec<&Element> = scene
+        .elements
+        .iter()
+        .filter(|element| !element.is_deleted)
+        .collect();
+    let bound_texts: HashMap<&str, &Element> = elements
+        .iter()
+        .filter(|element| element.kind == "text")
+        .filter_map(|element| Some((element.container_id.as_deref()?, *element)))
+        .filter(|(container, _)| elements.iter().any(|element| element.id == *container))
+        .collect();
+    let colors = ThemeColors::new(theme);
+
+    let mut snapshot = TabSnapshot::default();
+    let mut ids: HashMap<&str, Uuid> = HashMap::new();
+    let mut lines = vec![];
+    for (z, element) in elements.iter().enumerate() {
+        let z = z as f32;
+        match element.kind.as_str() {
+            "rectangle" | "diamond" | "ellipse" => {
+                let id = Uuid::new_v4();
+                ids.insert(&element.id, id);
+                let center = element.center();
+                snapshot.nodes.push(JsonNode {
+                    id,
+                    node_type: match element.kind.as_str() {
+                        "ellipse" => NodeType::Circle,
+                        _ => NodeType::Rect,
+                    },
+                    x: center.x,
+                    y: center.y,
+                    z,
+                    width: element.width.abs(),
+                    height: element.height.abs(),
+                    text: JsonNodeText {
+                        text: bound_texts
+                            .get(element.id.as_str())
+                            .map(|text| text.text())
+                            .unwrap_or_default(),
+                        pos: TextPos::Center,
+                    },
+                    bg_color: parse_color(element.background_color.as_deref())
+                        .map_or("node_bg".to_string(), |color| {
+                            colors.closest_node_color(color)
+                        }),
+                    visible: true,
+                });
+            }
+            "text" if !bound_texts.values().any(|text| text.id == element.id) => {
+                let mut node = text_node(element.text(), element.center(), theme);
+                node.z = z;
+                snapshot.nodes.push(node);
+            }
+            "arrow" | "line" => lines.push((z, *element)),
+            "freedraw" => {
+                let drawing = drawing(element, &element.points, z, &colors);
+                snapshot.drawings.push(drawing);
+            }
+            _ => {}
+        }
+    }
+
+    for (z, line) in lines {
+        let (Some(first), Some(last)) = (line.points.first(), line.points.last()) else {
+            continue;
+        };
+        let (start, end) = (line.point(*first), line.point(*last));
+        let node = |binding: &Option<Binding>| {
+            let id = ids.get(binding.as_ref()?.element_id.as_str())?;
+            snapshot.nodes.iter().find(|node| node.id == *id)
+        };
+        let label = bound_texts.get(line.id.as_str()).map(|text| text.text());
+        let middle = match (node(&line.start_binding), node(&line.end_binding)) {
+            (Some(from), Some(to)) if from.id != to.id => {
+                let mut start = ArrowConnect {
+                    id: ReflectableUuid(from.id),
+                    pos: closest_side(from, start),
+                };
+                let mut end = ArrowConnect {
+                    id: ReflectableUuid(to.id),
+                    pos: closest_side(to, end),
+                };
+                let middle = (Vec2::new(from.x, from.y)
+                    + arrow_connector_offset(from.width, from.height, start.pos)
+                    + Vec2::new(to.x, to.y)
+                    + arrow_connector_offset(to.width, to.height, end.pos))
+                    / 2.;
+                let arrow_type = match (&line.start_arrowhead, &line.end_arrowhead) {
+                    (Some(_), Some(_)) => ArrowType::DoubleArrow,
+                    (None, None) => ArrowType::Line,
+                    (Some(_), None) => {
+                        // Velo arrows point at their end
+                        std::mem::swap(&mut start, &mut end);
+                        ArrowType::Arrow
+                    }
+                    (None, Some(_)) => ArrowType::Arrow,
+                };
+                snapshot.arrows.push(ArrowMeta {
+                    visible: true,
+                    arrow_type,
+                    start,
+                    end,
+                });
+                middle
+            }
+            _ => {
+                let points = [*first, *last];
+                let drawing = drawing(line, &points, z, &colors);
+                snapshot.drawings.push(drawing);
+                (start + end) / 2.
+            }
+        };
+        if let Some(label) = label.filter(|label| !label.trim().is_empty()) {
+            let mut node = text_node(label, middle, theme);
+            node.z = z;
+            snapshot.nodes.push(node);
+        }
+    }
+
+    if snapshot.nodes.is_empty() && snapshot.drawings.is_empty() {
+        return Err(FormatError::Empty);
+    }
+    Ok(snapshot)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::graph::TEXT_BG_COLOR;
+    use crate::themes::velo_light;
+
+    const SCENE: &str = r##"{
+        "type": "excalidraw",
+        "version": 2,
+        "elements": [
+            {"id": "r", "type": "rectangle", "x": 0, "y": 0, "width": 200, "height": 100,
+             "backgroundColor": "transparent", "boundElements": [{"id": "t", "type": "text"}]},
+            {"id": "t", "type": "text", "x": 10, "y": 40, "width": 100, "height": 20,
+             "text": "Wrapped\ntext", "originalText": "Wrapped text", "containerId": "r"},
+            {"id": "e", "type": "ellipse", "x": 400, "y": 0, "width": 100, "height": 100,
+             "backgroundColor": "#000000"},
+            {"id": "d", "type": "diamond", "x": 0, "y": 300, "width": 100, "height": 100,
+             "isDeleted": true},
+            {"id": "a", "type": "arrow", "x": 200, "y": 50, "width": 200, "height": 0,
+             "points": [[0, 0], [100, 10], [200, 0]], "startArrowhead": null, "endArrowhead": "arrow",
+             "startBinding": {"elementId": "r", "focus": 0, "gap": 1},
+             "endBinding": {"elementId": "e", "focus": 0, "gap": 1}},
+            {"id": "l", "type": "text", "x": 280, "y": 40, "width": 40, "height": 20,
+             "text": "yes", "containerId": "a"},
+            {"id": "f", "type": "line", "x": 0, "y": 200, "width": 50, "height": 50,
+             "points": [[0, 0], [20, 40], [50, 50]], "strokeColor": "#1e1e1e"},
+            {"id": "p", "type": "freedraw", "x": 100, "y": 200, "width": 10, "height": 10,
+             "points": [[0, 0], [5, 5], [10, 10]], "strokeColor": "#e03131"},
+            {"id": "n", "type": "text", "x": 0, "y": 500, "width": 100, "height": 20,
+             "text": "Note", "containerId": null}
+        ],
+        "appState": {"viewBackgroundColor": "#ffffff"},
+        "files": {}
+    }"##;
+
+    #[test]
+    fn test_excalidraw_to_tab() {
+        let snapshot = excalidraw_to_tab(SCENE, &velo_light()).unwrap();
+        let texts: Vec<&str> = snapshot
+            .nodes
+            .iter()
+            .map(|node| node.text.text.as_str())
+            .collect();
+        assert_eq!(texts, vec!["Wrapped text", "", "Note", "yes"]);
+
+        let rect = &snapshot.nodes[0];
+        assert_eq!((rect.x, rect.y, rect.width), (100., -50., 200.));
+        assert_eq!(rect.bg_color, "node_bg");
+        let ellipse = &snapshot.nodes[1];
+        assert_eq!(ellipse.node_type, NodeType::Circle);
+        assert_eq!(ellipse.bg_color, "color_change_1");
+        assert_eq!(snapshot.nodes[2].bg_color, TEXT_BG_COLOR);
+
+        assert_eq!(snapshot.arrows.len(), 1);
+        let arrow = &snapshot.arrows[0];
+        assert_eq!(arrow.arrow_type, ArrowType::Arrow);
+        assert_eq!(
+            (arrow.start.id.0, arrow.start.pos),
+            (rect.id, ArrowConnectPos::Right)
+        );
+        assert_eq!(
+            (arrow.end.id.0, arrow.end.pos),
+            (ellipse.id, ArrowConnectPos::Left)
+        );
+        // the arrow label sits between the two nodes
+        assert_eq!((snapshot.nodes[3].x, snapshot.nodes[3].y), (300., -50.));
+
+        // the free line keeps its ends, the free drawing all of its points
+        assert_eq!(snapshot.drawings.len(), 2);
+        let line = &snapshot.drawings[1];
+        assert_eq!((line.x, line.y), (0., -200.));
+        assert_eq!(line.points, vec![Vec2::new(0., 0.), Vec2::new(50., -50.)]);
+        assert_eq!(line.drawing_color, "color_change_1");
+        let freedraw = &snapshot.drawings[0];
+        assert_eq!(freedraw.points.len(), 3);
+        assert_eq!(freedraw.drawing_color, "color_change_4");
+    }
+
+    #[test]
+    fn test_rotated_drawing() {
+        let scene = r#"{"elements": [{"id": "p", "type": "freedraw", "x": 0, "y": 0,
+            "angle": 3.14159265, "points": [[0, 0], [10, 0]]}]}"#;
+        let snapshot = excalidraw_to_tab(scene, &velo_light()).unwrap();
+        let points = &snapshot.drawings[0].points;
+        assert!(points[0].distance(Vec2::new(10., 0.)) < 0.001);
+        assert!(points[1].distance(Vec2::new(0., 0.)) < 0.001);
+    }
+
+    #[test]
+    fn test_excalidraw_errors() {
+        let theme = velo_light();
+        assert_eq!(
+            excalidraw_to_tab("{\"elements\": 1}", &theme)
+                .unwrap_err()
+                .to_string(),
+            "line 1: invalid type: integer `1`, expected a sequence"
+        );
+        assert!(matches!(
+            excalidraw_to_tab("{\"elements\": []}", &theme),
+            Err(FormatError::Empty)
+        ));
+    }
+}
@@ -0,0 +1,377 @@
+//! Format independent diagrams: nodes with a shape and text, edges with an
+//! arrow type and an optional label.
+
+use bevy::prelude::*;
+use uuid::Uuid;
+
+use super::arrow_endpoints;
+use super::layout::tree_layout;
+use crate::canvas::arrow::components::{ArrowConnect, ArrowConnectPos, ArrowMeta, ArrowType};
+use crate::canvas::arrow::utils::arrow_connector_offset;
+use crate::components::TabSnapshot;
+use crate::themes::Theme;
+use crate::ui_plugin::NodeType;
+use crate::utils::ReflectableUuid;
+use crate::{JsonNode, JsonNodeText, TextPos};
+
+/// Theme key of text elements, the notes without background.
+pub const TEXT_BG_COLOR: &str = "color_none";
+
+/// How far a text element may be from the middle of an arrow to be its label.
+const LABEL_DISTANCE: f32 = 40.;
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct GraphNode {
+    pub text: String,
+    pub node_type: NodeType,
+    /// Theme key of the background, `node_bg` if `None`.
+    pub bg_color: Option<String>,
+    /// Center on the canvas, laid out automatically if `None`.
+    pub position: Option<Vec2>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GraphEdge {
+    pub from: usize,
+    pub to: usize,
+    pub arrow_type: ArrowType,
+    pub label: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Direction {
+    #[default]
+    Right,
+    Down,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Graph {
+    pub nodes: Vec<GraphNode>,
+    pub edges: Vec<GraphEdge>,
+    pub direction: Direction,
+}
+
+impl Graph {
+    /// Reads the visible nodes and arrows of a tab. Text elements close to the
+    /// middle of an arrow become its label.
+    pub fn from_tab(snapshot: &TabSnapshot) -> Graph {
+        let visible: Vec<&JsonNode<String>> =
+            snapshot.nodes.iter().filter(|node| node.visible).collect();
+        let labels = arrow_labels(snapshot);
+        let label_ids: Vec<Uuid> = labels.iter().flatten().map(|node| node.id).collect();
+        let arrows: Vec<(&ArrowMeta, Option<String>)> = snapshot
+            .arrows
+            .iter()
+            .zip(labels)
+            .filter(|(arrow, _)| arrow.visible && arrow_endpoints(snapshot, arrow).is_some())
+            .map(|(arrow, label)| (arrow, label.map(|node| node.text.text.trim().to_string())))
+            .collect();
+
+        let mut graph = Graph::default();
+        let mut ids = vec![];
+        for node in visible.iter().filter(|node| !label_ids.contains(&node.id)) {
+            graph.nodes.push(GraphNode {
+                text: node.text.text.trim().to_string(),
+                node_type: node.node_type.clone(),
+                bg_color: Some(node.bg_color.clone()),
+                position: Some(Vec2::new(node.x, node.y)),
+            });
+            ids.push(node.id);
+        }
+        let mut vertical = 0;
+        for (arrow, label) in arrows {
+            let from = ids.iter().position(|id| *id == arrow.start.id.0);
+            let to = ids.iter().position(|id| *id == arrow.end.id.0);
+            let (Some(from), Some(to)) = (from, to) else {
+                continue;
+            };
+            if matches!(
+                arrow.start.pos,
+                ArrowConnectPos::Top | ArrowConnectPos::Bottom
+            ) {
+                vertical += 1;
+            }
+            graph.edges.push(GraphEdge {
+                from,
+                to,
+                arrow_type: arrow.arrow_type,
+                label,
+            });
+        }
+        if vertical * 2 > graph.edges.len() {
+            graph.direction = Direction::Down;
+        }
+        graph
+    }
+
+    /// Edges from a node to itself, the canvas has no arrows for them.
+    pub fn self_loops(&self) -> usize {
+        self.edges
+            .iter()
+            .filter(|edge| edge.from == edge.to)
+            .count()
+    }
+
+    /// Creates a tab with the nodes at their positions. Nodes without one are
+    /// laid out as trees in the graph direction, below the positioned nodes.
+    pub fn into_tab(self, theme: &Theme) -> TabSnapshot {
+        let size = Vec2::new(theme.node_width, theme.node_height);
+        let edges: Vec<(usize, usize)> = self.edges.iter().map(|e| (e.from, e.to)).collect();
+        let layout: Vec<Vec2> = match self.direction {
+            Direction::Right => tree_layout(&vec![size; self.nodes.len()], &edges),
+            // grow down instead of right by laying out with swapped axes
+            Direction::Down => {
+                tree_layout(&vec![Vec2::new(size.y, size.x); self.nodes.len()], &edges)
+                    .into_iter()
+                    .map(|position| Vec2::new(-position.y, -position.x))
+                    .collect()
+            }
+        };
+        let fixed: Vec<Vec2> = self.nodes.iter().filter_map(|node| node.position).collect();
+        let offset = match fixed.is_empty() {
+            true => Vec2::ZERO,
+            false => {
+                let free = self
+                    .nodes
+                    .iter()
+                    .zip(&layout)
+                    .filter(|(node, _)| node.position.is_none());
+                let layout_top_left = free.fold(Vec2::new(f32::MAX, f32::MIN), |corner, (_, p)| {
+                    Vec2::new(corner.x.min(p.x), corner.y.max(p.y))
+                });
+                let fixed_bottom_left = fixed
+                    .iter()
+                    .fold(Vec2::new(f32::MAX, f32::MAX), |corner, p| corner.min(*p));
+                fixed_bottom_left - Vec2::new(0., size.y * 1.5) - layout_top_left
+            }
+        };
+        let positions: Vec<Vec2> = self
+            .nodes
+            .iter()
+            .zip(&layout)
+            .map(|(node, layout)| node.position.unwrap_or(*layout + offset))
+            .collect();
+        let connectors = |from: Vec2, to: Vec2| {
+            if !fixed.is_empty() {
+                return facing_connectors(to - from);
+            }
+            match self.direction {
+                Direction::Right => (ArrowConnectPos::Right, ArrowConnectPos::Left),
+                Direction::Down => (ArrowConnectPos::Bottom, ArrowConnectPos::Top),
+            }
+        };
+
+        let mut nodes: Vec<JsonNode<String>> = self
+            .nodes
+            .iter()
+            .zip(&positions)
+            .map(|(node, position)| JsonNode {
+                id: Uuid::new_v4(),
+                node_type: node.node_type.clone(),
+                x: position.x,
+                y: position.y,
+                width: size.x,
+                height: size.y,
+                text: JsonNodeText {
+                    text: node.text.clone(),
+                    pos: TextPos::Center,
+                },
+                bg_color: node.bg_color.clone().unwrap_or("node_bg".to_string()),
+                visible: true,
+                ..default()
+            })
+            .collect();
+        let loops = self.self_loops();
+        if loops > 0 {
+            warn!("Skipped {} edges from a node to itself", loops);
+        }
+        let mut arrows = vec![];
+        let mut labels = vec![];
+        for edge in &self.edges {
+            if edge.from == edge.to || edge.from >= nodes.len() || edge.to >= nodes.len() {
+                continue;
+            }
+            let (start_pos, end_pos) = connectors(positions[edge.from], positions[edge.to]);
+            if let Some(label) = edge.label.clone().filter(|label| !label.is_empty()) {
+                let middle = (positions[edge.from]
+                    + arrow_connector_offset(size.x, size.y, start_pos)
+                    + positions[edge.to]
+                    + arrow_connector_offset(size.x, size.y, end_pos))
+                    / 2.;
+                labels.push(text_node(label, middle, theme));
+            }
+            arrows.push(ArrowMeta {
+                visible: true,
+                arrow_type: edge.arrow_type,
+                start: ArrowConnect {
+                    id: ReflectableUuid(nodes[edge.from].id),
+                    pos: start_pos,
+                },
+                end: ArrowConnect {
+                    id: ReflectableUuid(nodes[edge.to].id),
+                    pos: end_pos,
+                },
+            });
+        }
+        nodes.extend(labels);
+        TabSnapshot {
+            nodes,
+            arrows,
+            ..default()
+        }
+    }
+}
+
+/// Text elements close to the middle of a visible arrow, they are its label.
+/// Every text element labels at most one arrow. Parallel to `snapshot.arrows`.
+pub fn arrow_labels(snapshot: &TabSnapshot) -> Vec<Option<&JsonNode<String>>> {
+    let middles: Vec<Option<Vec2>> = snapshot
+        .arrows
+        .iter()
+        .map(|arrow| {
+            let (start, end) = arrow_endpoints(snapshot, arrow)?;
+            arrow.visible.then_some((start + end) / 2.)
+        })
+        .collect();
+    let mut labels = vec![None; snapshot.arrows.len()];
+    let texts = snapshot
+        .nodes
+        .iter()
+        .filter(|node| node.visible && node.bg_color == TEXT_BG_COLOR)
+        .filter(|node| !node.text.text.trim().is_empty());
+    for node in texts {
+        let center = Vec2::new(node.x, node.y);
+        let closest = middles
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| labels[*i].is_none())
+            .filter_map(|(i, middle)| Some((i, middle.as_ref()?.distance(center))))
+            .filter(|(_, distance)| *distance <= LABEL_DISTANCE)
+            .min_by(|a, b| a.1.total_cmp(&b.1));
+        if let Some((i, _)) = closest {
+            labels[i] = Some(node);
+        }
+    }
+    labels
+}
+
+/// Connectors of the sides of two nodes that face each other.
+pub fn facing_connectors(offset: Vec2) -> (ArrowConnectPos, ArrowConnectPos) {
+    if offset.x.abs() >= offset.y.abs() {
+        if offset.x >= 0. {
+            (ArrowConnectPos::Right, ArrowConnectPos::Left)
+        } else {
+            (ArrowConnectPos::Left, ArrowConnectPos::Right)
+        }
+    } else if offset.y < 0. {
+        (ArrowConnectPos::Bottom, ArrowConnectPos::Top)
+    } else {
+        (ArrowConnectPos::Top, ArrowConnectPos::Bottom)
+    }
+}
+
+/// Text element sized to its text, transparent nodes use a three times larger font.
+pub fn text_node(text: String, center: Vec2, theme: &Theme) -> JsonNode<String> {
+    let columns = text
+        .lines()
+        .map(|line| line.chars().count())
+        .max()
+        .unwrap_or(0);
+    let rows = text.lines().count().max(1);
+    JsonNode {
+        id: Uuid::new_v4(),
+        node_type: NodeType::Rect,
+        x: center.x,
+        y: center.y,
+        width: columns as f32 * super::GLYPH_ADVANCE * 3. * theme.font_size + 10.,
+        height: rows as f32 * 3. * theme.line_height + 10.,
+        text: JsonNodeText {
+            text,
+            pos: TextPos::Center,
+        },
+        bg_color: TEXT_BG_COLOR.to_string(),
+        visible: true,
+        ..default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::themes::velo_light;
+
+    #[test]
+    fn test_graph_roundtrip() {
+        let node = |text: &str, node_type| GraphNode {
+            text: text.to_string(),
+            node_type,
+            ..default()
+        };
+        let graph = Graph {
+            nodes: vec![
+                node("a", NodeType::Rect),
+                node("b", NodeType::Paper),
+                node("c", NodeType::Circle),
+            ],
+            edges: vec![
+                GraphEdge {
+                    from: 0,
+                    to: 1,
+                    arrow_type: ArrowType::Arrow,
+                    label: Some("yes".to_string()),
+                },
+                GraphEdge {
+                    from: 0,
+                    to: 2,
+                    arrow_type: ArrowType::DoubleArrow,
+                    label: None,
+                },
+            ],
+            direction: Direction::Down,
+        };
+        let snapshot = graph.clone().into_tab(&velo_light());
+        // the label is a text element in the middle of its arrow
+        assert_eq!(snapshot.nodes.len(), 4);
+        assert_eq!(snapshot.nodes[3].bg_color, TEXT_BG_COLOR);
+        assert!(snapshot.nodes[1].y < snapshot.nodes[0].y);
+
+        let mut read = Graph::from_tab(&snapshot);
+        assert_eq!(read.nodes[0].bg_color.as_deref(), Some("node_bg"));
+        for node in &mut read.nodes {
+            node.bg_color = None;
+            node.position = None;
+        }
+        assert_eq!(read, graph);
+    }
+
+    #[test]
+    fn test_graph_with_positions() {
+        let node = |text: &str, position: Option<Vec2>| GraphNode {
+            text: text.to_string(),
+            position,
+            ..default()
+        };
+        let graph = Graph {
+            nodes: vec![
+                node("a", Some(Vec2::new(0., 0.))),
+                node("b", Some(Vec2::new(-500., 0.))),
+                node("c", None),
+            ],
+            edges: vec![GraphEdge {
+                from: 0,
+                to: 1,
+                arrow_type: ArrowType::Arrow,
+                label: None,
+            }],
+            direction: Direction::Right,
+        };
+        let snapshot = graph.into_tab(&velo_light());
+        assert_eq!(snapshot.nodes[1].x, -500.);
+        // the unpositioned node goes below the others
+        assert!(snapshot.nodes[2].y < -velo_light().node_height);
+        // the arrow leaves the side facing the end node
+        assert_eq!(snapshot.arrows[0].start.pos, ArrowConnectPos::Left);
+        assert_eq!(snapshot.arrows[0].end.pos, ArrowConnectPos::Right);
+    }
+}
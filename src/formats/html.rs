@@ -0,0 +1,217 @@
+//! A single HTML page to view a document without Velo.
+//!
+//! Every tab is an inline SVG that can be panned by dragging and zoomed with
+//! the mouse wheel. The document JSON is embedded as well so that the page can
+//! give it back to be loaded in Velo.
+
+use std::fmt::Write;
+
+use super::canvas_background;
+use super::svg::{color, escape, tab_to_svg};
+use super::FormatError;
+use crate::components::Doc;
+use crate::themes::Theme;
+
+const STYLE: &str = r#"
+* { box-sizing: border-box; }
+html, body { margin: 0; height: 100%; overflow: hidden; font-family: sans-serif; }
+body { display: flex; flex-direction: column; }
+nav { display: flex; flex-wrap: wrap; gap: 4px; padding: 4px; align-items: center; }
+nav h1 { font-size: 16px; margin: 0 12px 0 4px; }
+nav button { border: 1px solid var(--border); border-radius: 4px; padding: 4px 10px; cursor: pointer; background: var(--tab); color: inherit; }
+nav button.active { font-weight: bold; border-width: 2px; }
+nav .download { margin-left: auto; }
+main { flex: 1; position: relative; }
+.tab { position: absolute; inset: 0; }
+.tab[hidden] { display: none; }
+.tab svg { width: 100%; height: 100%; cursor: grab; touch-action: none; user-select: none; }
+.tab svg.dragging { cursor: grabbing; }
+"#;
+
+const SCRIPT: &str = r#"
+const tabs = [...document.querySelectorAll(".tab")];
+const buttons = [...document.querySelectorAll("nav button[data-tab]")];
+function show(index) {
+  tabs.forEach((tab, i) => (tab.hidden = i !== index));
+  buttons.forEach((button, i) => button.classList.toggle("active", i === index));
+}
+buttons.forEach((button, i) => button.addEventListener("click", () => show(i)));
+show(Math.max(0, buttons.findIndex((button) => button.classList.contains("active"))));
+
+for (const svg of document.querySelectorAll(".tab svg")) {
+  const initial = svg.getAttribute("viewBox");
+  let box = initial.split(" ").map(Number);
+  const apply = () => svg.setAttribute("viewBox", box.join(" "));
+  const toSvg = (event) => {
+    const point = svg.createSVGPoint();
+    point.x = event.clientX;
+    point.y = event.clientY;
+    return point.matrixTransform(svg.getScreenCTM().inverse());
+  };
+  svg.addEventListener("wheel", (event) => {
+    event.preventDefault();
+    const point = toSvg(event);
+    const scale = Math.exp(event.deltaY * 0.002);
+    box = [point.x - (point.x - box[0]) * scale, point.y - (point.y - box[1]) * scale, box[2] * scale, box[3] * scale];
+    apply();
+  }, { passive: false });
+  let drag = null;
+  svg.addEventListener("pointerdown", (event) => {
+    drag = { x: event.clientX, y: event.clientY, moved: false };
+  });
+  svg.addEventListener("pointermove", (event) => {
+    if (!drag) return;
+    const dx = event.clientX - drag.x;
+    const dy = event.clientY - drag.y;
+    if (!drag.moved && Math.hypot(dx, dy) < 3) return;
+    if (!drag.moved) svg.setPointerCapture(event.pointerId);
+    drag.moved = true;
+    svg.classList.add("dragging");
+    const pixels = svg.getScreenCTM().a;
+    box[0] -= dx / pixels;
+    box[1] -= dy / pixels;
+    drag.x = event.clientX;
+    drag.y = event.clientY;
+    apply();
+  });
+  const stop = () => {
+    svg.classList.remove("dragging");
+    setTimeout(() => (drag = null));
+  };
+  svg.addEventListener("pointerup", stop);
+  svg.addEventListener("pointercancel", stop);
+  // a drag over a link does not open it
+  svg.addEventListener("click", (event) => {
+    if (drag && drag.moved) event.preventDefault();
+  }, true);
+  svg.addEventListener("dblclick", () => {
+    box = initial.split(" ").map(Number);
+    apply();
+  });
+  for (const link of svg.querySelectorAll("a")) {
+    link.setAttribute("target", "_blank");
+    link.setAttribute("rel", "noopener");
+  }
+}
+
+document.querySelector(".download").addEventListener("click", () => {
+  const json = document.getElementById("velo-document").textContent;
+  const link = document.createElement("a");
+  link.href = URL.createObjectURL(new Blob([json], { type: "application/json" }));
+  link.download = document.title + ".json";
+  link.click();
+  URL.revokeObjectURL(link.href);
+});
+"#;
+
+/// Renders every tab of the document, which should come from `export_doc` so
+/// that it carries its images, into a page that works offline.
+pub fn doc_to_html(doc: &Doc, theme: &Theme) -> Result<String, FormatError> {
+    // `<` only appears in JSON strings, escaping it keeps `</script>` out of the page
+    let json = serde_json::to_string(doc)?.replace('<', "\\u003c");
+    let mut html = String::new();
+    let _ = write!(
+        html,
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n\
+         <meta name=\"generator\" content=\"Velo\">\n<title>{}</title>\n<style>",
+        escape(&doc.name)
+    );
+    html.push_str(STYLE);
+    let _ = writeln!(
+        html,
+        "body {{ background: {}; color: {}; }}\nnav {{ background: {}; }}\n:root {{ --tab: {}; --border: {}; }}\n</style>\n</head>\n<body>",
+        color(canvas_background(theme)),
+        color(theme.font),
+        color(theme.bottom_panel_bg),
+        color(theme.tab_bg),
+        color(theme.btn_border),
+    );
+
+    let _ = writeln!(html, "<nav>\n<h1>{}</h1>", escape(&doc.name));
+    for (i, tab) in doc.tabs.iter().enumerate() {
+        let class = if tab.is_active {
+            " class=\"active\""
+        } else {
+            ""
+        };
+        let _ = writeln!(
+            html,
+            "<button data-tab=\"{}\"{}>{}</button>",
+            i,
+            class,
+            escape(&tab.name)
+        );
+    }
+    html.push_str("<button class=\"download\">Download .json</button>\n</nav>\n<main>\n");
+    for tab in &doc.tabs {
+        html.push_str("<div class=\"tab\" hidden>\n");
+        html.push_str(&tab_to_svg(tab.history.head(), &doc.images, theme));
+        html.push_str("</div>\n");
+    }
+    html.push_str("</main>\n");
+    let _ = writeln!(
+        html,
+        "<script type=\"application/json\" id=\"velo-document\">{}</script>",
+        json
+    );
+    let _ = writeln!(html, "<script>{}</script>\n</body>\n</html>", SCRIPT);
+    Ok(html)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::{Tab, TabSnapshot};
+    use crate::history::TabHistory;
+    use crate::themes::velo_light;
+    use crate::{JsonNode, JsonNodeText};
+
+    #[test]
+    fn test_doc_to_html() {
+        let snapshot = TabSnapshot {
+            nodes: vec![JsonNode {
+                text: JsonNodeText {
+                    text: "[link](https://velo.test)".to_string(),
+                    ..Default::default()
+                },
+                width: 100.,
+                height: 50.,
+                visible: true,
+                bg_color: "node_bg".to_string(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let doc = Doc {
+            name: "Plans & ideas".to_string(),
+            tabs: vec![
+                Tab {
+                    name: "First".to_string(),
+                    history: TabHistory::new(snapshot, 0.),
+                    ..Default::default()
+                },
+                Tab {
+                    name: "</script>".to_string(),
+                    is_active: true,
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        let html = doc_to_html(&doc, &velo_light()).unwrap();
+        assert!(html.contains("<title>Plans &amp; ideas</title>"));
+        assert!(html.contains(r#"<button data-tab="0">First</button>"#));
+        assert!(html.contains(r#"<button data-tab="1" class="active">&lt;/script&gt;</button>"#));
+        assert_eq!(html.matches("<svg ").count(), 2);
+        assert!(html.contains(r#"<a href="https://velo.test">"#));
+        // only the two script elements are closed
+        assert_eq!(html.matches("</script>").count(), 2);
+
+        let start = html.find(r#"id="velo-document">"#).unwrap() + 19;
+        let end = start + html[start..].find("</script>").unwrap();
+        let embedded: Doc = serde_json::from_str(&html[start..end]).unwrap();
+        assert_eq!(embedded.name, doc.name);
+        assert_eq!(embedded.tabs[0].history.head(), doc.tabs[0].history.head());
+    }
+}
@@ -0,0 +1,482 @@
+//! JSON Canvas, the `.canvas` files of Obsidian, see <https://jsoncanvas.org>.
+//!
+//! Canvas nodes have their top left corner at `x, y` with y growing down.
+//! What the format has no field for, like note shapes and parallel arrows, is
+//! kept in `velo` objects that other tools ignore.
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize, Serializer};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use super::graph::{arrow_labels, facing_connectors, text_node};
+use super::{FormatError, ThemeColors};
+use crate::canvas::arrow::components::{ArrowConnect, ArrowConnectPos, ArrowMeta, ArrowType};
+use crate::canvas::arrow::utils::arrow_connector_offset;
+use crate::components::TabSnapshot;
+use crate::themes::Theme;
+use crate::ui_plugin::NodeType;
+use crate::utils::ReflectableUuid;
+use crate::{JsonNode, JsonNodeText, TextPos};
+
+/// Colors of the canvas presets "1" to "6" in Obsidian.
+const PRESET_COLORS: [&str; 6] = ["fb464c", "e9973f", "e0de71", "44cf6e", "53dfdd", "a882ff"];
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct Canvas {
+    #[serde(default)]
+    nodes: Vec<CanvasNode>,
+    #[serde(default)]
+    edges: Vec<CanvasEdge>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct CanvasNode {
+    id: String,
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(serialize_with = "integer")]
+    x: f32,
+    #[serde(serialize_with = "integer")]
+    y: f32,
+    #[serde(serialize_with = "integer")]
+    width: f32,
+    #[serde(serialize_with = "integer")]
+    height: f32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    color: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    text: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    label: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    file: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    subpath: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    velo: Option<VeloNode>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct VeloNode {
+    node_type: NodeType,
+    text_pos: TextPos,
+    bg_color: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct CanvasEdge {
+    id: String,
+    from_node: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    from_side: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    from_end: Option<String>,
+    to_node: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    to_side: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    to_end: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    label: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    velo: Option<VeloEdge>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct VeloEdge {
+    arrow_type: ArrowType,
+}
+
+/// The format stores whole pixels.
+fn integer<S: Serializer>(value: &f32, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_i64(value.round() as i64)
+}
+
+fn side_name(pos: ArrowConnectPos) -> String {
+    match pos {
+        ArrowConnectPos::Top => "top",
+        ArrowConnectPos::Bottom => "bottom",
+        ArrowConnectPos::Left => "left",
+        ArrowConnectPos::Right => "right",
+    }
+    .to_string()
+}
+
+fn parse_side(side: &str) -> Option<ArrowConnectPos> {
+    match side {
+        "top" => Some(ArrowConnectPos::Top),
+        "bottom" => Some(ArrowConnectPos::Bottom),
+        "left" => Some(ArrowConnectPos::Left),
+        "right" => Some(ArrowConnectPos::Right),
+        _ => None,
+    }
+}
+
+/// A note that encloses other notes and is drawn under them works as a group.
+fn is_group(node: &JsonNode<String>, nodes: &[&JsonNode<String>]) -> bool {
+    let contains = |other: &JsonNode<String>| {
+        (other.x - node.x).abs() + other.width / 2. <= node.width / 2.
+            && (other.y - node.y).abs() + other.height / 2. <= node.height / 2.
+    };
+    nodes
+        .iter()
+        .any(|other| other.id != node.id && other.z > node.z && contains(other))
+}
+
+/// Writes the visible notes and arrows of the tab as a JSON Canvas. Text
+/// elements labelling an arrow become the edge label.
+pub fn tab_to_canvas(snapshot: &TabSnapshot, theme: &Theme) -> String {
+    let colors = ThemeColors::new(theme);
+    let labels = arrow_labels(snapshot);
+    let label_ids: Vec<Uuid> = labels.iter().flatten().map(|node| node.id).collect();
+    let mut nodes: Vec<&JsonNode<String>> = snapshot
+        .nodes
+        .iter()
+        .filter(|node| node.visible && !label_ids.contains(&node.id))
+        .collect();
+    // the canvas draws nodes in order
+    nodes.sort_by(|a, b| a.z.total_cmp(&b.z));
+
+    let mut canvas = Canvas::default();
+    for node in &nodes {
+        let text = node.text.text.clone();
+        let group = is_group(node, &nodes);
+        let color = match node.bg_color.as_str() {
+            "node_bg" => None,
+            key => colors.get(key).filter(|color| color.a() > 0.).map(|color| {
+                let [r, g, b, _] = color.as_rgba_u8();
+                format!("#{:02x}{:02x}{:02x}", r, g, b)
+            }),
+        };
+        canvas.nodes.push(CanvasNode {
+            id: node.id.to_string(),
+            kind: if group { "group" } else { "text" }.to_string(),
+            x: node.x - node.width / 2.,
+            y: -(node.y + node.height / 2.),
+            width: node.width,
+            height: node.height,
+            color,
+            text: (!group).then(|| text.clone()),
+            label: (group && !text.is_empty()).then_some(text),
+            file: None,
+            subpath: None,
+            url: None,
+            velo: Some(VeloNode {
+                node_type: node.node_type.clone(),
+                text_pos: node.text.pos.clone(),
+                bg_color: node.bg_color.clone(),
+            }),
+        });
+    }
+    for (arrow, label) in snapshot.arrows.iter().zip(labels) {
+        let exported = |id: Uuid| nodes.iter().any(|node| node.id == id);
+        if !arrow.visible || !exported(arrow.start.id.0) || !exported(arrow.end.id.0) {
+            continue;
+        }
+        let end = |arrow: bool| Some(if arrow { "arrow" } else { "none" }.to_string());
+        let (from_end, to_end) = match arrow.arrow_type {
+            ArrowType::Line | ArrowType::ParallelLine => (false, false),
+            ArrowType::Arrow | ArrowType::ParallelArrow => (false, true),
+            ArrowType::DoubleArrow | ArrowType::ParallelDoubleArrow => (true, true),
+        };
+        canvas.edges.push(CanvasEdge {
+            id: Uuid::new_v4().to_string(),
+            from_node: arrow.start.id.0.to_string(),
+            from_side: Some(side_name(arrow.start.pos)),
+            from_end: end(from_end),
+            to_node: arrow.end.id.0.to_string(),
+            to_side: Some(side_name(arrow.end.pos)),
+            to_end: end(to_end),
+            label: label.map(|node| node.text.text.trim().to_string()),
+            velo: Some(VeloEdge {
+                arrow_type: arrow.arrow_type,
+            }),
+        });
+    }
+    serde_json::to_string_pretty(&canvas).unwrap_or_default()
+}
+
+/// Reads a JSON Canvas. Groups become notes under their members, file and
+/// link nodes become notes with the path or URL.
+pub fn canvas_to_tab(text: &str, theme: &Theme) -> Result<TabSnapshot, FormatError> {
+    let mut canvas: Canvas = serde_json::from_str(text)?;
+    if canvas.nodes.is_empty() {
+        return Err(FormatError::Empty);
+    }
+    // groups are drawn under the other nodes, outer groups first
+    canvas.nodes.sort_by(|a, b| {
+        let group = |node: &CanvasNode| node.kind == "group";
+        let area = |node: &CanvasNode| node.width * node.height;
+        group(b).cmp(&group(a)).then(match group(a) && group(b) {
+            true => area(b).total_cmp(&area(a)),
+            false => std::cmp::Ordering::Equal,
+        })
+    });
+
+    let colors = ThemeColors::new(theme);
+    let mut ids = HashMap::new();
+    let mut snapshot = TabSnapshot::default();
+    for (i, node) in canvas.nodes.into_iter().enumerate() {
+        let group = node.kind == "group";
+        let text = match node.kind.as_str() {
+            "group" => node.label,
+            "file" => node
+                .file
+                .map(|file| file + node.subpath.as_deref().unwrap_or_default()),
+            "link" => node.url,
+            _ => node.text,
+        };
+        let bg_color = match &node.velo {
+            Some(velo) => velo.bg_color.clone(),
+            None => node
+                .color
+                .as_deref()
+                .and_then(parse_color)
+                .map_or("node_bg".to_string(), |color| {
+                    colors.closest_node_color(color)
+                }),
+        };
+        let id = Uuid::new_v4();
+        ids.insert(node.id, id);
+        snapshot.nodes.push(JsonNode {
+            id,
+            node_type: node
+                .velo
+                .as_ref()
+                .map_or(NodeType::Rect, |velo| velo.node_type.clone()),
+            x: node.x + node.width / 2.,
+            y: -(node.y + node.height / 2.),
+            z: i as f32,
+            width: node.width,
+            height: node.height,
+            text: JsonNodeText {
+                text: text.unwrap_or_default(),
+                pos: match &node.velo {
+                    Some(velo) => velo.text_pos.clone(),
+                    None if group => TextPos::TopLeft,
+                    None => TextPos::Center,
+                },
+            },
+            bg_color,
+            visible: true,
+        });
+    }
+
+    let mut labels = vec![];
+    for edge in canvas.edges {
+        let find = |id: &String| {
+            let id = ids.get(id)?;
+            snapshot.nodes.iter().find(|node| node.id == *id)
+        };
+        let (Some(from), Some(to)) = (find(&edge.from_node), find(&edge.to_node)) else {
+            continue;
+        };
+        let (mut from, mut to) = (from, to);
+        let mut from_side = edge.from_side.as_deref().and_then(parse_side);
+        let mut to_side = edge.to_side.as_deref().and_then(parse_side);
+        let from_arrow = edge.from_end.as_deref() == Some("arrow");
+        let to_arrow = edge.to_end.as_deref() != Some("none");
+        let arrow_type = match (&edge.velo, from_arrow, to_arrow) {
+            (Some(velo), _, _) => velo.arrow_type,
+            (None, true, true) => ArrowType::DoubleArrow,
+            (None, false, false) => ArrowType::Line,
+            (None, _, _) => ArrowType::Arrow,
+        };
+        // Velo arrows point at their end
+        if edge.velo.is_none() && from_arrow && !to_arrow {
+            std::mem::swap(&mut from, &mut to);
+            std::mem::swap(&mut from_side, &mut to_side);
+        }
+        let facing = facing_connectors(Vec2::new(to.x - from.x, to.y - from.y));
+        let start = ArrowConnect {
+            id: ReflectableUuid(from.id),
+            pos: from_side.unwrap_or(facing.0),
+        };
+        let end = ArrowConnect {
+            id: ReflectableUuid(to.id),
+            pos: to_side.unwrap_or(facing.1),
+        };
+        if let Some(label) = edge.label.filter(|label| !label.trim().is_empty()) {
+            let point = |node: &JsonNode<String>, pos| {
+                Vec2::new(node.x, node.y) + arrow_connector_offset(node.width, node.height, pos)
+            };
+            let middle = (point(from, start.pos) + point(to, end.pos)) / 2.;
+            let mut label = text_node(label, middle, theme);
+            label.z = (snapshot.nodes.len() + labels.len()) as f32;
+            labels.push(label);
+        }
+        snapshot.arrows.push(ArrowMeta {
+            visible: true,
+            arrow_type,
+            start,
+            end,
+        });
+    }
+    snapshot.nodes.extend(labels);
+    Ok(snapshot)
+}
+
+/// A preset number or a hex color.
+fn parse_color(color: &str) -> Option<Color> {
+    let hex = match color.parse::<usize>() {
+        Ok(preset) => PRESET_COLORS.get(preset.checked_sub(1)?)?,
+        Err(_) => color.trim_start_matches('#'),
+    };
+    Color::hex(hex).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::themes::velo_light;
+
+    #[test]
+    fn test_canvas_to_tab() {
+        let canvas = r##"{
+            "nodes": [
+                {"id": "a", "type": "text", "text": "Hello", "x": 0, "y": 0, "width": 200, "height": 100, "color": "4"},
+                {"id": "b", "type": "link", "url": "https://velo.app", "x": 400, "y": 0, "width": 200, "height": 100},
+                {"id": "g", "type": "group", "label": "Ideas", "x": -50, "y": -50, "width": 800, "height": 300, "color": "#000000"},
+                {"id": "f", "type": "file", "file": "notes/todo.md", "subpath": "#today", "x": 0, "y": 400, "width": 200, "height": 100}
+            ],
+            "edges": [
+                {"id": "e1", "fromNode": "a", "fromSide": "right", "toNode": "b", "toSide": "left", "label": "see"},
+                {"id": "e2", "fromNode": "a", "toNode": "f", "toEnd": "none"},
+                {"id": "e3", "fromNode": "a", "fromEnd": "arrow", "toNode": "b", "toEnd": "none"},
+                {"id": "e4", "fromNode": "a", "toNode": "missing"}
+            ]
+        }"##;
+        let snapshot = canvas_to_tab(canvas, &velo_light()).unwrap();
+        let texts: Vec<&str> = snapshot
+            .nodes
+            .iter()
+            .map(|node| node.text.text.as_str())
+            .collect();
+        assert_eq!(
+            texts,
+            vec![
+                "Ideas",
+                "Hello",
+                "https://velo.app",
+                "notes/todo.md#today",
+                "see"
+            ]
+        );
+        // the group is under its members, with its label at the top
+        let group = &snapshot.nodes[0];
+        assert_eq!((group.x, group.y, group.z), (350., -100., 0.));
+        assert_eq!(group.text.pos, TextPos::TopLeft);
+        assert_eq!(group.bg_color, "color_change_1");
+        assert_eq!(snapshot.nodes[1].bg_color, "color_change_5");
+        assert_eq!(snapshot.nodes[2].bg_color, "node_bg");
+
+        let arrows: Vec<(&str, ArrowConnectPos, &str, ArrowConnectPos, ArrowType)> = snapshot
+            .arrows
+            .iter()
+            .map(|arrow| {
+                let text = |id: ReflectableUuid| {
+                    let node = snapshot.nodes.iter().find(|node| node.id == id.0).unwrap();
+                    node.text.text.as_str()
+                };
+                let (start, end) = (text(arrow.start.id), text(arrow.end.id));
+                (start, arrow.start.pos, end, arrow.end.pos, arrow.arrow_type)
+            })
+            .collect();
+        assert_eq!(
+            arrows,
+            vec![
+                (
+                    "Hello",
+                    ArrowConnectPos::Right,
+                    "https://velo.app",
+                    ArrowConnectPos::Left,
+                    ArrowType::Arrow
+                ),
+                (
+                    "Hello",
+                    ArrowConnectPos::Bottom,
+                    "notes/todo.md#today",
+                    ArrowConnectPos::Top,
+                    ArrowType::Line
+                ),
+                (
+                    "https://velo.app",
+                    ArrowConnectPos::Left,
+                    "Hello",
+                    ArrowConnectPos::Right,
+                    ArrowType::Arrow
+                ),
+            ]
+        );
+        // the label is in the middle of its arrow
+        let label = &snapshot.nodes[4];
+        assert_eq!((label.x, label.y), (300., -50.));
+    }
+
+    #[test]
+    fn test_canvas_errors() {
+        let theme = velo_light();
+        assert_eq!(
+            canvas_to_tab("{\n\"nodes\": [}", &theme)
+                .unwrap_err()
+                .to_string(),
+            "line 2: expected value"
+        );
+        assert!(matches!(
+            canvas_to_tab("{}", &theme),
+            Err(FormatError::Empty)
+        ));
+    }
+
+    #[test]
+    fn test_canvas_roundtrip() {
+        let theme = velo_light();
+        let canvas = r##"{
+            "nodes": [
+                {"id": "g", "type": "group", "label": "Group", "x": -100, "y": -100, "width": 700, "height": 400},
+                {"id": "a", "type": "text", "text": "A", "x": 0, "y": 0, "width": 100, "height": 50, "color": "#000000"},
+                {"id": "b", "type": "text", "text": "B", "x": 300, "y": 100, "width": 100, "height": 50}
+            ],
+            "edges": [
+                {"id": "e", "fromNode": "a", "fromSide": "bottom", "fromEnd": "arrow", "toNode": "b", "toSide": "left", "label": "both"}
+            ]
+        }"##;
+        let mut snapshot = canvas_to_tab(canvas, &theme).unwrap();
+        snapshot.nodes[2].node_type = NodeType::Paper;
+        snapshot.arrows[0].arrow_type = ArrowType::ParallelDoubleArrow;
+
+        let exported = tab_to_canvas(&snapshot, &theme);
+        let parsed: Canvas = serde_json::from_str(&exported).unwrap();
+        let kinds: Vec<&str> = parsed.nodes.iter().map(|node| node.kind.as_str()).collect();
+        assert_eq!(kinds, vec!["group", "text", "text"]);
+        assert_eq!(parsed.nodes[0].label.as_deref(), Some("Group"));
+        assert_eq!(parsed.nodes[1].color.as_deref(), Some("#000000"));
+        assert_eq!(
+            (parsed.nodes[2].x, parsed.nodes[2].y, parsed.nodes[2].width),
+            (300., 100., 100.)
+        );
+        assert_eq!(parsed.edges.len(), 1);
+        assert_eq!(parsed.edges[0].label.as_deref(), Some("both"));
+        assert_eq!(parsed.edges[0].from_end.as_deref(), Some("arrow"));
+        assert_eq!(parsed.edges[0].to_side.as_deref(), Some("left"));
+
+        let reimported = canvas_to_tab(&exported, &theme).unwrap();
+        for (node, original) in reimported.nodes.iter().zip(&snapshot.nodes) {
+            assert_eq!(node.text, original.text);
+            assert_eq!(node.node_type, original.node_type);
+            assert_eq!(node.bg_color, original.bg_color);
+            assert_eq!((node.x, node.y), (original.x, original.y));
+        }
+        assert_eq!(
+            reimported.arrows[0].arrow_type,
+            ArrowType::ParallelDoubleArrow
+        );
+        assert_eq!(reimported.arrows[0].start.pos, ArrowConnectPos::Bottom);
+    }
+}
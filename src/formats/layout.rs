@@ -0,0 +1,122 @@
+//! Automatic placement of imported nodes.
+
+use bevy::prelude::*;
+use std::collections::HashSet;
+
+/// Space between columns and between stacked nodes.
+const LAYOUT_GAP: Vec2 = Vec2::new(60., 20.);
+
+/// Places a graph as trees growing to the right. Every node goes in the column
+/// of its depth, under the first parent that reaches it. Leaves are stacked top
+/// to bottom and parents are centered on their children. Returns node centers.
+pub fn tree_layout(sizes: &[Vec2], edges: &[(usize, usize)]) -> Vec<Vec2> {
+    let count = sizes.len();
+    let mut children = vec![vec![]; count];
+    let mut has_parent = vec![false; count];
+    for (parent, child) in edges {
+        if *parent >= count || *child >= count || parent == child {
+            continue;
+        }
+        if !children[*parent].contains(child) {
+            children[*parent].push(*child);
+        }
+        has_parent[*child] = true;
+    }
+
+    // spanning forest, nodes only reachable through a cycle become roots too
+    let mut visited = HashSet::new();
+    let mut tree = vec![vec![]; count];
+    let mut depth = vec![0; count];
+    let mut roots = vec![];
+    let candidates = (0..count)
+        .filter(|i| !has_parent[*i])
+        .chain(0..count)
+        .collect::<Vec<_>>();
+    for root in candidates {
+        if !visited.insert(root) {
+            continue;
+        }
+        roots.push(root);
+        let mut stack = vec![root];
+        while let Some(node) = stack.pop() {
+            for child in children[node].iter().rev() {
+                if visited.insert(*child) {
+                    tree[node].insert(0, *child);
+                    depth[*child] = depth[node] + 1;
+                    stack.push(*child);
+                }
+            }
+        }
+    }
+
+    let columns = depth.iter().max().map_or(0, |max| max + 1);
+    let mut column_width = vec![0f32; columns];
+    for (i, size) in sizes.iter().enumerate() {
+        column_width[depth[i]] = column_width[depth[i]].max(size.x);
+    }
+    let mut column_x = vec![0.; columns];
+    for column in 1..columns {
+        column_x[column] = column_x[column - 1]
+            + (column_width[column - 1] + column_width[column]) / 2.
+            + LAYOUT_GAP.x;
+    }
+
+    let mut positions = vec![Vec2::ZERO; count];
+    let mut top = 0.;
+    for root in roots {
+        top = place(root, top, &tree, sizes, &depth, &column_x, &mut positions);
+    }
+    positions
+}
+
+/// Places the subtree below `top`, returns the top of the next subtree.
+fn place(
+    node: usize,
+    top: f32,
+    tree: &[Vec<usize>],
+    sizes: &[Vec2],
+    depth: &[usize],
+    column_x: &[f32],
+    positions: &mut [Vec2],
+) -> f32 {
+    let x = column_x[depth[node]];
+    let height = sizes[node].y;
+    if tree[node].is_empty() {
+        positions[node] = Vec2::new(x, top - height / 2.);
+        return top - height - LAYOUT_GAP.y;
+    }
+    let mut bottom = top;
+    for child in &tree[node] {
+        bottom = place(*child, bottom, tree, sizes, depth, column_x, positions);
+    }
+    let first = positions[tree[node][0]].y;
+    let last = positions[tree[node][tree[node].len() - 1]].y;
+    let y = ((first + last) / 2.).min(top - height / 2.);
+    positions[node] = Vec2::new(x, y);
+    bottom.min(y - height / 2. - LAYOUT_GAP.y)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tree_layout() {
+        let sizes = vec![Vec2::new(100., 50.); 5];
+        // 0 -> 1 -> 3, 0 -> 2, 4 is alone
+        let positions = tree_layout(&sizes, &[(0, 1), (0, 2), (1, 3)]);
+        assert_eq!(positions[3], Vec2::new(320., -25.));
+        assert_eq!(positions[1], Vec2::new(160., -25.));
+        assert_eq!(positions[2], Vec2::new(160., -95.));
+        // the root is centered on its children
+        assert_eq!(positions[0], Vec2::new(0., -60.));
+        assert_eq!(positions[4], Vec2::new(0., -165.));
+    }
+
+    #[test]
+    fn test_tree_layout_with_cycle() {
+        let sizes = vec![Vec2::new(100., 50.); 2];
+        let positions = tree_layout(&sizes, &[(0, 1), (1, 0)]);
+        assert_eq!(positions, vec![Vec2::new(0., -25.), Vec2::new(160., -25.)]);
+    }
+}
@@ -0,0 +1,434 @@
+//! Mermaid flowcharts, https://mermaid.js.org/syntax/flowchart.html
+
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use super::graph::{Direction, Graph, GraphEdge, GraphNode};
+use super::FormatError;
+use crate::canvas::arrow::components::ArrowType;
+use crate::components::TabSnapshot;
+use crate::themes::Theme;
+use crate::ui_plugin::NodeType;
+
+/// Node shape delimiters, longer ones first so that `((` is not read as `(`.
+const SHAPES: [(&str, &str); 12] = [
+    ("(((", ")))"),
+    ("((", "))"),
+    ("([", "])"),
+    ("[[", "]]"),
+    ("[(", ")]"),
+    ("[/", "/]"),
+    ("[\\", "\\]"),
+    ("{{", "}}"),
+    ("[", "]"),
+    ("(", ")"),
+    ("{", "}"),
+    (">", "]"),
+];
+
+/// Statements that only style or group nodes.
+const IGNORED_KEYWORDS: [&str; 8] = [
+    "subgraph",
+    "end",
+    "style",
+    "classDef",
+    "class",
+    "click",
+    "linkStyle",
+    "direction",
+];
+
+/// Writes the nodes and arrows of the tab as a Mermaid flowchart. Text elements
+/// next to an arrow become its label.
+pub fn tab_to_mermaid(snapshot: &TabSnapshot) -> String {
+    let graph = Graph::from_tab(snapshot);
+    let mut mermaid = String::new();
+    let direction = match graph.direction {
+        Direction::Right => "LR",
+        Direction::Down => "TD",
+    };
+    let _ = writeln!(mermaid, "flowchart {}", direction);
+    for (i, node) in graph.nodes.iter().enumerate() {
+        let (open, close) = match node.node_type {
+            NodeType::Rect => ("[", "]"),
+            NodeType::Paper => ("(", ")"),
+            NodeType::Circle => ("((", "))"),
+        };
+        let _ = writeln!(
+            mermaid,
+            "    n{}{}\"{}\"{}",
+            i + 1,
+            open,
+            escape(&node.text),
+            close
+        );
+    }
+    for edge in &graph.edges {
+        let link = match edge.arrow_type {
+            ArrowType::Line | ArrowType::ParallelLine => "---",
+            ArrowType::Arrow | ArrowType::ParallelArrow => "-->",
+            ArrowType::DoubleArrow | ArrowType::ParallelDoubleArrow => "<-->",
+        };
+        let label = edge
+            .label
+            .as_ref()
+            .map(|label| format!("|\"{}\"|", escape(label)))
+            .unwrap_or_default();
+        let _ = writeln!(
+            mermaid,
+            "    n{} {}{} n{}",
+            edge.from + 1,
+            link,
+            label,
+            edge.to + 1
+        );
+    }
+    mermaid
+}
+
+/// Reads a `flowchart` or `graph` definition. Round shapes become paper notes,
+/// circles become circles and every other shape becomes a rectangle.
+pub fn mermaid_to_tab(mermaid: &str, theme: &Theme) -> Result<TabSnapshot, FormatError> {
+    let graph = parse_mermaid(mermaid)?;
+    if graph.nodes.is_empty() {
+        return Err(FormatError::Empty);
+    }
+    Ok(graph.into_tab(theme))
+}
+
+fn parse_mermaid(mermaid: &str) -> Result<Graph, FormatError> {
+    let mut graph = Graph::default();
+    let mut ids = HashMap::new();
+    let mut has_header = false;
+    for (line_number, line) in mermaid.lines().enumerate() {
+        let syntax_error = |message: String| FormatError::Syntax {
+            line: line_number + 1,
+            message,
+        };
+        let line = line.find("%%").map_or(line, |comment| &line[..comment]);
+        for statement in split_statements(line) {
+            let mut words = statement.split_whitespace();
+            let Some(keyword) = words.next() else {
+                continue;
+            };
+            if !has_header {
+                if keyword != "flowchart" && keyword != "graph" {
+                    return Err(syntax_error("expected `flowchart` or `graph`".to_string()));
+                }
+                if let Some("LR" | "RL") = words.next() {
+                    graph.direction = Direction::Right;
+                } else {
+                    graph.direction = Direction::Down;
+                }
+                has_header = true;
+            } else if !IGNORED_KEYWORDS.contains(&keyword) {
+                parse_statement(statement, &mut graph, &mut ids).map_err(syntax_error)?;
+            }
+        }
+    }
+    Ok(graph)
+}
+
+/// Splits a line at `;` outside of quotes and entity codes like `#quot;`.
+fn split_statements(line: &str) -> Vec<&str> {
+    let mut statements = vec![];
+    let mut start = 0;
+    let mut in_quotes = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            ';' if !in_quotes => {
+                let is_entity = line[start..i].rfind('#').is_some_and(|hash| {
+                    let code = &line[start + hash + 1..i];
+                    !code.is_empty() && code.chars().all(|c| c.is_ascii_alphanumeric())
+                });
+                if !is_entity {
+                    statements.push(line[start..i].trim());
+                    start = i + 1;
+                }
+            }
+            _ => {}
+        }
+    }
+    statements.push(line[start..].trim());
+    statements
+}
+
+/// A chain like `a & b --> c -- label --- d`.
+fn parse_statement(
+    statement: &str,
+    graph: &mut Graph,
+    ids: &mut HashMap<String, usize>,
+) -> Result<(), String> {
+    let (mut previous, mut rest) = parse_group(statement, graph, ids)?;
+    while !rest.is_empty() {
+        let (arrow_type, label, after_edge) =
+            parse_edge(rest).ok_or_else(|| format!("unexpected `{}`", rest))?;
+        let (group, after_group) = parse_group(after_edge, graph, ids)?;
+        for from in &previous {
+            for to in &group {
+                graph.edges.push(GraphEdge {
+                    from: *from,
+                    to: *to,
+                    arrow_type,
+                    label: label.clone(),
+                });
+            }
+        }
+        previous = group;
+        rest = after_group;
+    }
+    Ok(())
+}
+
+fn parse_group<'a>(
+    text: &'a str,
+    graph: &mut Graph,
+    ids: &mut HashMap<String, usize>,
+) -> Result<(Vec<usize>, &'a str), String> {
+    let mut group = vec![];
+    let mut rest = text;
+    loop {
+        let (node, after) = parse_node(rest.trim_start(), graph, ids)?;
+        group.push(node);
+        rest = after.trim_start();
+        match rest.strip_prefix('&') {
+            Some(after) => rest = after,
+            None => return Ok((group, rest)),
+        }
+    }
+}
+
+fn parse_node<'a>(
+    text: &'a str,
+    graph: &mut Graph,
+    ids: &mut HashMap<String, usize>,
+) -> Result<(usize, &'a str), String> {
+    let id_len = text
+        .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .unwrap_or(text.len());
+    if id_len == 0 {
+        return Err(format!("expected a node at `{}`", text));
+    }
+    let id = &text[..id_len];
+    let mut rest = &text[id_len..];
+    let mut shape = None;
+    for (open, close) in SHAPES {
+        if let Some(body) = rest.strip_prefix(open) {
+            let (label, after) = read_text(body, close)?;
+            let node_type = match open {
+                "(((" | "((" => NodeType::Circle,
+                "(" => NodeType::Paper,
+                _ => NodeType::Rect,
+            };
+            shape = Some((label, node_type));
+            rest = after;
+            break;
+        }
+    }
+    if let Some(class) = rest.strip_prefix(":::") {
+        let class_len = class
+            .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '-'))
+            .unwrap_or(class.len());
+        rest = &class[class_len..];
+    }
+
+    let index = *ids.entry(id.to_string()).or_insert_with(|| {
+        graph.nodes.push(GraphNode {
+            text: id.to_string(),
+            node_type: NodeType::Rect,
+            ..Default::default()
+        });
+        graph.nodes.len() - 1
+    });
+    if let Some((label, node_type)) = shape {
+        graph.nodes[index] = GraphNode {
+            text: label,
+            node_type,
+            ..Default::default()
+        };
+    }
+    Ok((index, rest))
+}
+
+/// Text up to the closing delimiter, optionally in quotes.
+fn read_text<'a>(text: &'a str, close: &str) -> Result<(String, &'a str), String> {
+    let (label, rest) = match text.trim_start().strip_prefix('"') {
+        Some(quoted) => {
+            let end = quoted
+                .find('"')
+                .ok_or_else(|| "unterminated string".to_string())?;
+            let rest = quoted[end + 1..].trim_start();
+            let rest = rest
+                .strip_prefix(close)
+                .ok_or_else(|| format!("expected `{}`", close))?;
+            (&quoted[..end], rest)
+        }
+        None => {
+            let end = text
+                .find(close)
+                .ok_or_else(|| format!("expected `{}`", close))?;
+            (&text[..end], &text[end + close.len()..])
+        }
+    };
+    Ok((unescape(label.trim()), rest))
+}
+
+/// Parses a link like `-->`, `<-->`, `---`, `-.->`, `==>`, `-- label -->` or
+/// `-->|label|`, returns what follows it.
+fn parse_edge(text: &str) -> Option<(ArrowType, Option<String>, &str)> {
+    let is_link = |c: char| matches!(c, '-' | '=' | '.');
+    let (start_head, text) = match text.strip_prefix('<') {
+        Some(text) => (true, text),
+        None => (false, text),
+    };
+    let link_len = text.find(|c| !is_link(c)).unwrap_or(text.len());
+    if link_len < 2 {
+        return None;
+    }
+    let mut rest = &text[link_len..];
+    let mut label = None;
+    let mut end_head = end_of_link(&mut rest);
+    if !end_head && rest.starts_with(char::is_whitespace) {
+        // `-- label -->`, the label runs up to the next link
+        let close = rest
+            .find(|c: char| is_link(c))
+            .filter(|close| rest[*close..].chars().take_while(|c| is_link(*c)).count() >= 2);
+        if let Some(close) = close {
+            label = Some(unescape(rest[..close].trim()));
+            rest = rest[close..].trim_start_matches(is_link);
+            end_head = end_of_link(&mut rest);
+        }
+    }
+    let rest = rest.trim_start();
+    let rest = match rest.strip_prefix('|') {
+        Some(piped) => {
+            let end = piped.find('|')?;
+            let text = piped[..end].trim();
+            let text = text
+                .strip_prefix('"')
+                .and_then(|text| text.strip_suffix('"'))
+                .unwrap_or(text);
+            label = Some(unescape(text));
+            &piped[end + 1..]
+        }
+        None => rest,
+    };
+    let arrow_type = match (start_head, end_head) {
+        (true, true) => ArrowType::DoubleArrow,
+        (false, true) => ArrowType::Arrow,
+        _ => ArrowType::Line,
+    };
+    Some((arrow_type, label, rest))
+}
+
+/// Consumes an arrow head, `>`, or the circle and cross ends `o` and `x`.
+fn end_of_link(rest: &mut &str) -> bool {
+    if let Some(after) = rest.strip_prefix('>') {
+        *rest = after;
+        return true;
+    }
+    let mut chars = rest.chars();
+    if matches!(chars.next(), Some('o' | 'x'))
+        && !chars
+            .next()
+            .is_some_and(|c| c.is_alphanumeric() || c == '_')
+    {
+        *rest = &rest[1..];
+        return true;
+    }
+    false
+}
+
+fn escape(text: &str) -> String {
+    text.replace('"', "#quot;").replace('\n', "<br/>")
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("<br/>", "\n")
+        .replace("<br />", "\n")
+        .replace("<br>", "\n")
+        .replace("#quot;", "\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::themes::velo_light;
+
+    fn node(text: &str, node_type: NodeType) -> GraphNode {
+        GraphNode {
+            text: text.to_string(),
+            node_type,
+            ..Default::default()
+        }
+    }
+
+    fn edge(from: usize, to: usize, arrow_type: ArrowType, label: Option<&str>) -> GraphEdge {
+        GraphEdge {
+            from,
+            to,
+            arrow_type,
+            label: label.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_parse_mermaid() {
+        let mermaid = r#"
+%% deploy flow
+flowchart LR
+    start((Start)) --> check{Tests pass?}
+    check -->|yes| deploy("Deploy<br/>to prod")
+    check -- no --- fix[Fix it] & notify
+    deploy <--> fix; notify -.-> start
+    style start fill:#f9f
+"#;
+        let graph = parse_mermaid(mermaid).unwrap();
+        assert_eq!(graph.direction, Direction::Right);
+        assert_eq!(
+            graph.nodes,
+            vec![
+                node("Start", NodeType::Circle),
+                node("Tests pass?", NodeType::Rect),
+                node("Deploy\nto prod", NodeType::Paper),
+                node("Fix it", NodeType::Rect),
+                node("notify", NodeType::Rect),
+            ]
+        );
+        assert_eq!(
+            graph.edges,
+            vec![
+                edge(0, 1, ArrowType::Arrow, None),
+                edge(1, 2, ArrowType::Arrow, Some("yes")),
+                edge(1, 3, ArrowType::Line, Some("no")),
+                edge(1, 4, ArrowType::Line, Some("no")),
+                edge(2, 3, ArrowType::DoubleArrow, None),
+                edge(4, 0, ArrowType::Arrow, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_mermaid_errors() {
+        assert!(matches!(
+            parse_mermaid("sequenceDiagram\n"),
+            Err(FormatError::Syntax { line: 1, .. })
+        ));
+        assert!(matches!(
+            parse_mermaid("graph TD\na --> b[oops\n"),
+            Err(FormatError::Syntax { line: 2, .. })
+        ));
+        assert!(matches!(
+            mermaid_to_tab("graph TD\n", &velo_light()),
+            Err(FormatError::Empty)
+        ));
+    }
+
+    #[test]
+    fn test_mermaid_roundtrip() {
+        let mermaid = "flowchart TD\n    n1[\"say #quot;hi#quot;\"]\n    n2((\"b\"))\n    n3(\"c\")\n    n1 -->|\"yes\"| n2\n    n1 <--> n3\n    n2 --- n3\n";
+        let snapshot = mermaid_to_tab(mermaid, &velo_light()).unwrap();
+        assert_eq!(tab_to_mermaid(&snapshot), mermaid);
+    }
+}
@@ -0,0 +1,123 @@
+//! Reads a tab as a mind map: arrows point from a parent node to a child node.
+
+use std::collections::{HashMap, HashSet};
+use uuid::Uuid;
+
+use crate::components::TabSnapshot;
+use crate::JsonNode;
+
+pub struct TreeNode<'a> {
+    pub node: &'a JsonNode<String>,
+    pub children: Vec<TreeNode<'a>>,
+    /// The node was already reached through another parent or a cycle, its
+    /// children are listed there.
+    pub repeated: bool,
+}
+
+pub struct MindMap<'a> {
+    pub roots: Vec<TreeNode<'a>>,
+    /// Nodes without any arrow.
+    pub unconnected: Vec<&'a JsonNode<String>>,
+}
+
+/// Builds the trees of the tab starting from nodes without incoming arrows.
+/// Siblings are ordered top to bottom, then left to right.
+pub fn mind_map(snapshot: &TabSnapshot) -> MindMap<'_> {
+    build(snapshot, false)
+}
+
+/// Like `mind_map`, including hidden nodes and arrows.
+pub fn mind_map_with_hidden(snapshot: &TabSnapshot) -> MindMap<'_> {
+    build(snapshot, true)
+}
+
+fn build(snapshot: &TabSnapshot, hidden: bool) -> MindMap<'_> {
+    let mut nodes: Vec<&JsonNode<String>> = snapshot
+        .nodes
+        .iter()
+        .filter(|node| hidden || node.visible)
+        .collect();
+    nodes.sort_by(|a, b| b.y.total_cmp(&a.y).then(a.x.total_cmp(&b.x)));
+    let order: HashMap<Uuid, usize> = nodes
+        .iter()
+        .enumerate()
+        .map(|(i, node)| (node.id, i))
+        .collect();
+
+    let mut children: HashMap<Uuid, Vec<usize>> = HashMap::new();
+    let mut has_parent = HashSet::new();
+    let mut connected = HashSet::new();
+    for arrow in snapshot
+        .arrows
+        .iter()
+        .filter(|arrow| hidden || arrow.visible)
+    {
+        let (parent, child) = (arrow.start.id.0, arrow.end.id.0);
+        let (Some(_), Some(child_index)) = (order.get(&parent), order.get(&child)) else {
+            continue;
+        };
+        if parent == child {
+            continue;
+        }
+        let siblings = children.entry(parent).or_default();
+        if !siblings.contains(child_index) {
+            siblings.push(*child_index);
+        }
+        has_parent.insert(child);
+        connected.insert(parent);
+        connected.insert(child);
+    }
+    for siblings in children.values_mut() {
+        siblings.sort();
+    }
+
+    let mut visited = HashSet::new();
+    let mut roots = vec![];
+    for node in &nodes {
+        if connected.contains(&node.id) && !has_parent.contains(&node.id) {
+            roots.push(walk(node, &nodes, &children, &mut visited));
+        }
+    }
+    // nodes that are only reachable through a cycle
+    for node in &nodes {
+        if connected.contains(&node.id) && !visited.contains(&node.id) {
+            roots.push(walk(node, &nodes, &children, &mut visited));
+        }
+    }
+    MindMap {
+        roots,
+        unconnected: nodes
+            .into_iter()
+            .filter(|node| !connected.contains(&node.id))
+            .collect(),
+    }
+}
+
+fn walk<'a>(
+    node: &'a JsonNode<String>,
+    nodes: &[&'a JsonNode<String>],
+    children: &HashMap<Uuid, Vec<usize>>,
+    visited: &mut HashSet<Uuid>,
+) -> TreeNode<'a> {
+    if !visited.insert(node.id) {
+        return TreeNode {
+            node,
+            children: vec![],
+            repeated: true,
+        };
+    }
+    let children = children
+        .get(&node.id)
+        .map(|indices| {
+            indices
+                .iter()
+                .map(|i| walk(nodes[*i], nodes, children, visited))
+                .collect()
+        })
+        .unwrap_or_default();
+    TreeNode {
+        node,
+        children,
+        repeated: false,
+    }
+}
@@ -0,0 +1,528 @@
+//! Conversions of tabs to and from other formats. Everything here works on
+//! `TabSnapshot`s, the same data `save_tab` collects, and never touches the ECS.
+
+use bevy::prelude::*;
+use bevy_markdown::{generate_markdown_lines, BevyMarkdown};
+use bevy_prototype_lyon::prelude::Path;
+use cosmic_text::{AttrsOwned, Style, Weight};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::fmt;
+use uuid::Uuid;
+
+use crate::canvas::arrow::components::{ArrowConnect, ArrowMeta};
+use crate::canvas::arrow::utils::{arrow_connector_offset, build_arrow};
+use crate::components::TabSnapshot;
+use crate::themes::Theme;
+use crate::ui_plugin::ui_helpers::{markdown_theme, node_shape_path};
+use crate::JsonNode;
+
+pub mod csv;
+pub mod dot;
+pub mod excalidraw;
+mod graph;
+pub mod html;
+pub mod json_canvas;
+mod layout;
+pub mod mermaid;
+mod mind_map;
+pub mod opml;
+pub mod outline;
+pub mod png;
+mod raster;
+pub mod svg;
+
+/// Blank space around an exported tab.
+const EXPORT_MARGIN: f32 = 20.;
+
+/// Width of a glyph of the monospace node font relative to the font size.
+const GLYPH_ADVANCE: f32 = 0.6;
+
+/// Theme keys a node background can be set to.
+const NODE_COLOR_KEYS: [&str; 8] = [
+    "node_bg",
+    "paper_node_bg",
+    "color_change_1",
+    "color_change_2",
+    "color_change_3",
+    "color_change_4",
+    "color_change_5",
+    "color_none",
+];
+
+/// Theme keys a drawing can be drawn with.
+const DRAWING_COLOR_KEYS: [&str; 6] = [
+    "drawing_pencil_btn",
+    "color_change_1",
+    "color_change_2",
+    "color_change_3",
+    "color_change_4",
+    "color_change_5",
+];
+
+/// Errors of reading a tab from another format.
+#[derive(Debug)]
+pub enum FormatError {
+    Syntax { line: usize, message: String },
+    Empty,
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FormatError::Syntax { line, message } => write!(f, "line {}: {}", line, message),
+            FormatError::Empty => write!(f, "nothing to import"),
+        }
+    }
+}
+
+impl std::error::Error for FormatError {}
+
+impl From<serde_json::Error> for FormatError {
+    fn from(err: serde_json::Error) -> Self {
+        let message = err.to_string();
+        // the line is shown separately
+        let message = message.split(" at line").next().unwrap_or_default();
+        FormatError::Syntax {
+            line: err.line(),
+            message: message.to_string(),
+        }
+    }
+}
+
+/// Resolves the theme keys stored in snapshots, like `node_bg`, to colors.
+pub struct ThemeColors(Map<String, Value>);
+
+impl ThemeColors {
+    pub fn new(theme: &Theme) -> Self {
+        match serde_json::to_value(theme) {
+            Ok(Value::Object(map)) => ThemeColors(map),
+            _ => ThemeColors(Map::new()),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<Color> {
+        serde_json::from_value(self.0.get(key)?.clone()).ok()
+    }
+
+    /// Theme key of the node background closest to `color`, for imported colors.
+    /// Text elements are never picked, they have no background to match.
+    pub fn closest_node_color(&self, color: Color) -> String {
+        let keys: Vec<&str> = NODE_COLOR_KEYS
+            .into_iter()
+            .filter(|key| *key != graph::TEXT_BG_COLOR)
+            .collect();
+        self.closest(color, &keys)
+    }
+
+    /// Theme key of the drawing color closest to `color`.
+    pub fn closest_drawing_color(&self, color: Color) -> String {
+        self.closest(color, &DRAWING_COLOR_KEYS)
+    }
+
+    /// Compares RGB only, a transparent color gives the first key.
+    fn closest(&self, color: Color, keys: &[&str]) -> String {
+        if color.a() == 0. {
+            return keys[0].to_string();
+        }
+        // theme colors are not always within 0 to 1
+        let rgb =
+            |color: Color| Vec3::new(color.r(), color.g(), color.b()).clamp(Vec3::ZERO, Vec3::ONE);
+        let distance = |other: Color| rgb(color).distance_squared(rgb(other));
+        keys.iter()
+            .filter_map(|key| Some((*key, distance(self.get(key)?))))
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map_or(keys[0], |(key, _)| key)
+            .to_string()
+    }
+}
+
+pub fn canvas_background(theme: &Theme) -> Color {
+    theme
+        .canvas_bg_color
+        .unwrap_or(Color::rgb(253. / 255., 251. / 255., 247. / 255.))
+}
+
+/// Both ends of an arrow in canvas coordinates, `None` if a node it connects is missing.
+pub fn arrow_endpoints(snapshot: &TabSnapshot, arrow: &ArrowMeta) -> Option<(Vec2, Vec2)> {
+    let point = |connect: &ArrowConnect| {
+        let node = snapshot.nodes.iter().find(|node| node.id == connect.id.0)?;
+        Some(
+            Vec2::new(node.x, node.y)
+                + arrow_connector_offset(node.width, node.height, connect.pos),
+        )
+    };
+    Some((point(&arrow.start)?, point(&arrow.end)?))
+}
+
+pub fn arrow_path(snapshot: &TabSnapshot, arrow: &ArrowMeta) -> Option<Path> {
+    let (start, end) = arrow_endpoints(snapshot, arrow)?;
+    Some(build_arrow(start, end, *arrow))
+}
+
+/// Arrows are drawn on top of the higher of the two nodes they connect.
+pub fn arrow_z(snapshot: &TabSnapshot, arrow: &ArrowMeta) -> f32 {
+    [arrow.start.id.0, arrow.end.id.0]
+        .iter()
+        .filter_map(|id| snapshot.nodes.iter().find(|node| node.id == *id))
+        .map(|node| node.z)
+        .fold(0., f32::max)
+}
+
+/// Node outline in canvas coordinates.
+pub fn node_path(node: &JsonNode<String>) -> Path {
+    let path = node_shape_path(&node.node_type, node.width, node.height);
+    Path(
+        path.0
+            .transformed(&bevy_prototype_lyon::prelude::tess::geom::Translation::new(
+                node.x, node.y,
+            )),
+    )
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bounds {
+    pub min: Vec2,
+    pub max: Vec2,
+}
+
+impl Bounds {
+    pub fn size(&self) -> Vec2 {
+        self.max - self.min
+    }
+
+    fn extend(bounds: Option<Bounds>, point: Vec2) -> Option<Bounds> {
+        Some(match bounds {
+            Some(bounds) => Bounds {
+                min: bounds.min.min(point),
+                max: bounds.max.max(point),
+            },
+            None => Bounds {
+                min: point,
+                max: point,
+            },
+        })
+    }
+}
+
+/// Area covered by the visible items of the tab, `None` for an empty tab.
+pub fn snapshot_bounds(snapshot: &TabSnapshot) -> Option<Bounds> {
+    let mut bounds = None;
+    for node in snapshot.nodes.iter().filter(|node| node.visible) {
+        let half = Vec2::new(node.width, node.height) / 2.;
+        bounds = Bounds::extend(bounds, Vec2::new(node.x, node.y) - half);
+        bounds = Bounds::extend(bounds, Vec2::new(node.x, node.y) + half);
+    }
+    for drawing in &snapshot.drawings {
+        for point in &drawing.points {
+            bounds = Bounds::extend(bounds, Vec2::new(drawing.x, drawing.y) + *point);
+        }
+    }
+    bounds
+}
+
+/// Area of the canvas exported for the whole tab, the visible items plus a margin.
+pub fn export_bounds(snapshot: &TabSnapshot) -> Bounds {
+    let bounds = snapshot_bounds(snapshot).unwrap_or(Bounds {
+        min: Vec2::ZERO,
+        max: Vec2::ZERO,
+    });
+    Bounds {
+        min: bounds.min - EXPORT_MARGIN,
+        max: bounds.max + EXPORT_MARGIN,
+    }
+}
+
+/// Area of the canvas exported for one note, `None` if the tab has no such note.
+pub fn node_export_bounds(snapshot: &TabSnapshot, id: Uuid) -> Option<Bounds> {
+    let node = snapshot.nodes.iter().find(|node| node.id == id)?;
+    let half = Vec2::new(node.width, node.height) / 2.;
+    Some(Bounds {
+        min: Vec2::new(node.x, node.y) - half - EXPORT_MARGIN,
+        max: Vec2::new(node.x, node.y) + half + EXPORT_MARGIN,
+    })
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextSpan {
+    pub text: String,
+    pub color: Color,
+    pub bold: bool,
+    pub italic: bool,
+    pub link: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextLine {
+    pub spans: Vec<TextSpan>,
+    pub width: f32,
+}
+
+/// Text of a node laid out like the canvas does, in node local coordinates.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NodeText {
+    pub lines: Vec<TextLine>,
+    pub font_size: f32,
+    pub line_height: f32,
+    /// Offset of the first line's top left corner from the node's top left corner.
+    pub offset: Vec2,
+}
+
+impl NodeText {
+    pub fn glyph_width(&self) -> f32 {
+        GLYPH_ADVANCE * self.font_size
+    }
+}
+
+/// Renders the markdown of a node and wraps it to the node width. The font is
+/// monospace, so glyph advances are estimated instead of measured.
+pub fn layout_node_text(node: &JsonNode<String>, theme: &Theme, bg_color: Color) -> NodeText {
+    let scale = if bg_color == Color::NONE { 3. } else { 1. };
+    let font_size = scale * theme.font_size;
+    let line_height = scale * theme.line_height;
+    let glyph_width = GLYPH_ADVANCE * font_size;
+    let max_chars = ((node.width / glyph_width).floor() as usize).max(1);
+
+    let mut attrs = cosmic_text::Attrs::new();
+    attrs = attrs.family(cosmic_text::Family::Name(theme.font_name.as_str()));
+    attrs = attrs.color(crate::utils::bevy_color_to_cosmic(theme.font));
+    let markdown_lines = generate_markdown_lines(BevyMarkdown {
+        text: node.text.text.clone(),
+        attrs: AttrsOwned::new(attrs),
+        markdown_theme: markdown_theme(theme),
+    });
+    let lines: Vec<Vec<TextSpan>> = match markdown_lines {
+        Ok(markdown_lines) => markdown_lines
+            .lines
+            .into_iter()
+            .map(|line| {
+                line.into_iter()
+                    .map(|(text, attrs)| TextSpan {
+                        text,
+                        color: attrs
+                            .color_opt
+                            .map(|color| Color::rgba_u8(color.r(), color.g(), color.b(), color.a()))
+                            .unwrap_or(theme.font),
+                        bold: attrs.weight >= Weight::BOLD,
+                        italic: attrs.style == Style::Italic,
+                        link: markdown_lines
+                            .span_metadata
+                            .get(attrs.metadata)
+                            .and_then(|metadata| metadata.link.clone()),
+                    })
+                    .collect()
+            })
+            .collect(),
+        Err(_) => node
+            .text
+            .text
+            .lines()
+            .map(|line| {
+                vec![TextSpan {
+                    text: line.to_string(),
+                    color: theme.font,
+                    bold: false,
+                    italic: false,
+                    link: None,
+                }]
+            })
+            .collect(),
+    };
+
+    let lines: Vec<TextLine> = lines
+        .into_iter()
+        .flat_map(|spans| wrap_line(spans, max_chars))
+        .map(|spans| TextLine {
+            width: spans
+                .iter()
+                .map(|span| span.text.chars().count())
+                .sum::<usize>() as f32
+                * glyph_width,
+            spans,
+        })
+        .collect();
+
+    let offset = match node.text.pos {
+        crate::TextPos::TopLeft => Vec2::ZERO,
+        crate::TextPos::Center => {
+            let text_width = lines.iter().map(|line| line.width).fold(0., f32::max);
+            let text_height = lines.len() as f32 * line_height;
+            Vec2::new(
+                ((node.width - text_width) / 2.).floor(),
+                ((node.height - text_height) / 2.).floor(),
+            )
+        }
+    };
+    NodeText {
+        lines,
+        font_size,
+        line_height,
+        offset,
+    }
+}
+
+/// Greedy word wrap. Words longer than a line are broken at the line width.
+fn wrap_line(spans: Vec<TextSpan>, max_chars: usize) -> Vec<Vec<TextSpan>> {
+    let mut lines = vec![];
+    let mut line: Vec<TextSpan> = vec![];
+    let mut line_chars = 0;
+    for span in spans {
+        for word in span.text.split_inclusive(' ') {
+            let mut word: Vec<char> = word.chars().collect();
+            let visible_chars = word.iter().filter(|c| **c != ' ').count();
+            if line_chars > 0 && line_chars + visible_chars > max_chars {
+                lines.push(std::mem::take(&mut line));
+                line_chars = 0;
+            }
+            while line_chars + word.len() > max_chars && word.len() > max_chars {
+                let rest = word.split_off(max_chars - line_chars);
+                push_text(&mut line, &span, word.into_iter().collect());
+                lines.push(std::mem::take(&mut line));
+                line_chars = 0;
+                word = rest;
+            }
+            line_chars += word.len();
+            push_text(&mut line, &span, word.into_iter().collect());
+        }
+    }
+    lines.push(line);
+    lines
+}
+
+fn push_text(line: &mut Vec<TextSpan>, style: &TextSpan, text: String) {
+    if text.is_empty() {
+        return;
+    }
+    match line.last_mut() {
+        Some(last)
+            if last.color == style.color
+                && last.bold == style.bold
+                && last.italic == style.italic
+                && last.link == style.link =>
+        {
+            last.text.push_str(&text)
+        }
+        _ => line.push(TextSpan {
+            text,
+            ..style.clone()
+        }),
+    }
+}
+
+/// Painting order of the items of a tab, bottom to top.
+pub enum TabItem<'a> {
+    Node(&'a JsonNode<String>),
+    Arrow(&'a ArrowMeta),
+    Drawing(&'a crate::DrawingJsonNode<String>),
+}
+
+pub fn paint_order(snapshot: &TabSnapshot) -> Vec<TabItem<'_>> {
+    let mut items: Vec<(f32, usize, TabItem)> = vec![];
+    for node in snapshot.nodes.iter().filter(|node| node.visible) {
+        items.push((node.z, 0, TabItem::Node(node)));
+    }
+    for arrow in snapshot.arrows.iter().filter(|arrow| arrow.visible) {
+        items.push((arrow_z(snapshot, arrow), 1, TabItem::Arrow(arrow)));
+    }
+    for drawing in &snapshot.drawings {
+        items.push((drawing.z, 2, TabItem::Drawing(drawing)));
+    }
+    items.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+    items.into_iter().map(|(_, _, item)| item).collect()
+}
+
+/// Base64 PNGs of the node images of the tab keyed by node id.
+pub fn node_images<'a>(
+    snapshot: &TabSnapshot,
+    images: &'a HashMap<String, String>,
+) -> HashMap<Uuid, &'a String> {
+    snapshot
+        .images
+        .iter()
+        .filter_map(|(node_id, hash)| Some((*node_id, images.get(hash)?)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::themes::{velo_dark, velo_light};
+
+    fn span(text: &str) -> TextSpan {
+        TextSpan {
+            text: text.to_string(),
+            color: Color::BLACK,
+            bold: false,
+            italic: false,
+            link: None,
+        }
+    }
+
+    fn texts(lines: Vec<Vec<TextSpan>>) -> Vec<String> {
+        lines
+            .into_iter()
+            .map(|line| line.into_iter().map(|span| span.text).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_node_export_bounds() {
+        let id = Uuid::new_v4();
+        let snapshot = TabSnapshot {
+            nodes: vec![JsonNode {
+                visible: true,
+                node_type: crate::ui_plugin::NodeType::Rect,
+                id,
+                x: 100.,
+                y: -50.,
+                z: 1.,
+                width: 60.,
+                height: 40.,
+                bg_color: "node_bg".to_string(),
+                text: crate::JsonNodeText {
+                    text: String::new(),
+                    pos: crate::TextPos::Center,
+                },
+            }],
+            ..Default::default()
+        };
+        assert_eq!(
+            node_export_bounds(&snapshot, id),
+            Some(Bounds {
+                min: Vec2::new(50., -90.),
+                max: Vec2::new(150., -10.),
+            })
+        );
+        assert_eq!(node_export_bounds(&snapshot, Uuid::new_v4()), None);
+    }
+
+    #[test]
+    fn test_closest_color() {
+        for theme in [velo_light(), velo_dark()] {
+            let colors = ThemeColors::new(&theme);
+            assert_eq!(colors.closest_node_color(Color::RED), "color_change_4");
+            assert_eq!(
+                colors.closest_node_color(Color::rgba(1., 0., 0., 0.5)),
+                "color_change_4"
+            );
+            assert_eq!(colors.closest_node_color(Color::NONE), "node_bg");
+            assert_eq!(
+                colors.closest_drawing_color(Color::RED),
+                "drawing_pencil_btn"
+            );
+        }
+    }
+
+    #[test]
+    fn test_wrap_line() {
+        assert_eq!(
+            texts(wrap_line(vec![span("hello big "), span("world")], 9)),
+            vec!["hello big ", "world"]
+        );
+        assert_eq!(
+            texts(wrap_line(vec![span("abcdefghij")], 4)),
+            vec!["abcd", "efgh", "ij"]
+        );
+        assert_eq!(texts(wrap_line(vec![], 4)), vec![""]);
+    }
+}
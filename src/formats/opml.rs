@@ -0,0 +1,481 @@
+//! OPML outlines, the exchange format of outliners.
+//!
+//! Collapsed entries are listed in `expansionState` of the head: the 1-based
+//! positions, in document order, of the entries that are expanded. Children
+//! of a collapsed entry are hidden nodes in Velo.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use super::graph::{Graph, GraphEdge, GraphNode};
+use super::mind_map::{mind_map_with_hidden, TreeNode};
+use super::outline::label;
+use super::svg::escape;
+use super::FormatError;
+use crate::canvas::arrow::components::ArrowType;
+use crate::components::TabSnapshot;
+use crate::themes::Theme;
+use crate::ui_plugin::NodeType;
+
+/// Writes the mind map of the tab as OPML. The first line of a note is the
+/// entry text, the other lines its `_note`. Nodes without arrows are entries
+/// at the end.
+pub fn tab_to_opml(snapshot: &TabSnapshot, title: &str) -> String {
+    let mind_map = mind_map_with_hidden(snapshot);
+    let mut body = String::new();
+    let mut count = 0;
+    let mut expanded = vec![];
+    let mut has_children = false;
+    for root in &mind_map.roots {
+        write_entry(&mut body, root, 1, &mut count, &mut expanded);
+        has_children = true;
+    }
+    for node in mind_map.unconnected {
+        let (text, note) = split_label(&label(node, false));
+        let _ = writeln!(body, "    <outline {}/>", attributes(&text, &note));
+    }
+
+    let mut opml = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<opml version=\"2.0\">\n  <head>\n",
+    );
+    let _ = writeln!(opml, "    <title>{}</title>", escape(title));
+    if has_children {
+        let expanded: Vec<String> = expanded.iter().map(usize::to_string).collect();
+        let _ = writeln!(
+            opml,
+            "    <expansionState>{}</expansionState>",
+            expanded.join(",")
+        );
+    }
+    opml.push_str("  </head>\n  <body>\n");
+    opml.push_str(&body);
+    opml.push_str("  </body>\n</opml>\n");
+    opml
+}
+
+fn write_entry(
+    opml: &mut String,
+    tree: &TreeNode,
+    depth: usize,
+    count: &mut usize,
+    expanded: &mut Vec<usize>,
+) {
+    *count += 1;
+    let indent = "  ".repeat(depth + 1);
+    let (text, note) = split_label(&label(tree.node, tree.repeated));
+    if tree.children.is_empty() {
+        let _ = writeln!(opml, "{}<outline {}/>", indent, attributes(&text, &note));
+        return;
+    }
+    // an entry is collapsed when its children are hidden
+    if tree.children.iter().any(|child| child.node.visible) {
+        expanded.push(*count);
+    }
+    let _ = writeln!(opml, "{}<outline {}>", indent, attributes(&text, &note));
+    for child in &tree.children {
+        write_entry(opml, child, depth + 1, count, expanded);
+    }
+    let _ = writeln!(opml, "{}</outline>", indent);
+}
+
+fn split_label(label: &str) -> (String, String) {
+    match label.split_once('\n') {
+        Some((text, note)) => (text.to_string(), note.trim().to_string()),
+        None => (label.to_string(), String::new()),
+    }
+}
+
+fn attributes(text: &str, note: &str) -> String {
+    let attribute = |value: &str| escape(value).replace('\n', "&#10;");
+    match note.is_empty() {
+        true => format!("text=\"{}\"", attribute(text)),
+        false => format!("text=\"{}\" _note=\"{}\"", attribute(text), attribute(note)),
+    }
+}
+
+/// Reads the entries of an OPML outline as nodes with an arrow from every
+/// entry to its children. The `_note` of an entry is added to its text.
+pub fn opml_to_tab(text: &str, theme: &Theme) -> Result<TabSnapshot, FormatError> {
+    let mut texts: Vec<String> = vec![];
+    let mut parents: Vec<Option<usize>> = vec![];
+    let mut open: Vec<usize> = vec![];
+    let mut expansion_state: Option<String> = None;
+    let mut in_expansion_state = false;
+    let events = parse_xml(text)?;
+    if let Some((event, line)) = events.first() {
+        if !matches!(event, Event::Open { name, .. } if name == "opml") {
+            return Err(FormatError::Syntax {
+                line: *line,
+                message: "not an OPML document".to_string(),
+            });
+        }
+    }
+    for (event, _) in events {
+        match event {
+            Event::Open { name, attrs, empty } => match name.as_str() {
+                "outline" => {
+                    let title = attrs.get("text").or(attrs.get("title"));
+                    let mut text = title.map_or("", |title| title.trim()).to_string();
+                    if let Some(note) = attrs.get("_note").filter(|note| !note.trim().is_empty()) {
+                        text.push('\n');
+                        text.push_str(note.trim());
+                    }
+                    parents.push(open.last().copied());
+                    texts.push(text);
+                    if !empty {
+                        open.push(texts.len() - 1);
+                    }
+                }
+                "expansionState" => {
+                    in_expansion_state = !empty;
+                    expansion_state.get_or_insert_with(String::new);
+                }
+                _ => {}
+            },
+            Event::Close { name } => match name.as_str() {
+                "outline" => {
+                    open.pop();
+                }
+                "expansionState" => in_expansion_state = false,
+                _ => {}
+            },
+            Event::Text(text) => {
+                if let (true, Some(state)) = (in_expansion_state, expansion_state.as_mut()) {
+                    state.push_str(&text);
+                }
+            }
+        }
+    }
+    if texts.is_empty() {
+        return Err(FormatError::Empty);
+    }
+
+    // without an expansion state every entry is expanded
+    let expanded: Option<Vec<usize>> = expansion_state.map(|state| {
+        state
+            .split(',')
+            .filter_map(|number| number.trim().parse::<usize>().ok())
+            .collect()
+    });
+    let mut visible = vec![true; texts.len()];
+    for (i, parent) in parents.iter().enumerate() {
+        if let Some(parent) = parent {
+            let collapsed = expanded
+                .as_ref()
+                .is_some_and(|expanded| !expanded.contains(&(parent + 1)));
+            visible[i] = visible[*parent] && !collapsed;
+        }
+    }
+
+    let graph = Graph {
+        nodes: texts
+            .into_iter()
+            .map(|text| GraphNode {
+                text,
+                node_type: NodeType::Rect,
+                ..Default::default()
+            })
+            .collect(),
+        edges: parents
+            .iter()
+            .enumerate()
+            .filter_map(|(child, parent)| {
+                Some(GraphEdge {
+                    from: (*parent)?,
+                    to: child,
+                    arrow_type: ArrowType::ParallelArrow,
+                    label: None,
+                })
+            })
+            .collect(),
+        ..Default::default()
+    };
+    let mut snapshot = graph.into_tab(theme);
+    for (node, visible) in snapshot.nodes.iter_mut().zip(&visible) {
+        node.visible = *visible;
+    }
+    let hidden: HashMap<_, _> = snapshot
+        .nodes
+        .iter()
+        .map(|node| (node.id, !node.visible))
+        .collect();
+    for arrow in &mut snapshot.arrows {
+        arrow.visible = !hidden.get(&arrow.end.id.0).copied().unwrap_or_default();
+    }
+    Ok(snapshot)
+}
+
+#[derive(Debug, PartialEq)]
+enum Event {
+    Open {
+        name: String,
+        attrs: HashMap<String, String>,
+        empty: bool,
+    },
+    Close {
+        name: String,
+    },
+    Text(String),
+}
+
+fn syntax_error(line: usize, message: &str) -> FormatError {
+    FormatError::Syntax {
+        line,
+        message: message.to_string(),
+    }
+}
+
+/// Elements and text of an XML document, with the line each starts on.
+/// Checks that elements are closed in order.
+fn parse_xml(text: &str) -> Result<Vec<(Event, usize)>, FormatError> {
+    let mut events = vec![];
+    let mut stack: Vec<String> = vec![];
+    let mut rest = text;
+    let mut line = 1;
+    while !rest.is_empty() {
+        let start = rest.find('<').unwrap_or(rest.len());
+        if !rest[..start].trim().is_empty() {
+            events.push((Event::Text(decode(&rest[..start])), line));
+        }
+        line += rest[..start].matches('\n').count();
+        rest = &rest[start..];
+        if rest.is_empty() {
+            break;
+        }
+        let tag_line = line;
+        let (end, skip) = if rest.starts_with("<!--") {
+            (rest.find("-->").map(|end| end + 3), true)
+        } else if rest.starts_with("<![CDATA[") {
+            let end = rest.find("]]>");
+            if let Some(end) = end {
+                events.push((Event::Text(rest[9..end].to_string()), line));
+            }
+            (end.map(|end| end + 3), true)
+        } else if rest.starts_with("<?") || rest.starts_with("<!") {
+            (rest.find('>').map(|end| end + 1), true)
+        } else {
+            (tag_end(rest).map(|end| end + 1), false)
+        };
+        let Some(end) = end else {
+            return Err(syntax_error(tag_line, "unterminated tag"));
+        };
+        line += rest[..end].matches('\n').count();
+        if !skip {
+            let event = parse_tag(&rest[1..end - 1], tag_line)?;
+            match &event {
+                Event::Open {
+                    name, empty: false, ..
+                } => stack.push(name.clone()),
+                Event::Close { name } => match stack.pop() {
+                    Some(open) if open == *name => {}
+                    Some(open) => {
+                        return Err(syntax_error(tag_line, &format!("expected </{}>", open)))
+                    }
+                    None => return Err(syntax_error(tag_line, &format!("unexpected </{}>", name))),
+                },
+                _ => {}
+            }
+            events.push((event, tag_line));
+        }
+        rest = &rest[end..];
+    }
+    if let Some(open) = stack.pop() {
+        return Err(syntax_error(line, &format!("missing </{}>", open)));
+    }
+    Ok(events)
+}
+
+/// Position of the `>` closing the tag, `>` may appear in attribute values.
+fn tag_end(tag: &str) -> Option<usize> {
+    let mut quote = None;
+    for (i, c) in tag.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(open), c) if c == open => quote = None,
+            (None, '>') => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// The text between `<` and `>`.
+fn parse_tag(tag: &str, line: usize) -> Result<Event, FormatError> {
+    if let Some(name) = tag.strip_prefix('/') {
+        return Ok(Event::Close {
+            name: name.trim().to_string(),
+        });
+    }
+    let empty = tag.ends_with('/');
+    let tag = tag.trim_end_matches('/');
+    let name_end = tag.find(char::is_whitespace).unwrap_or(tag.len());
+    let name = tag[..name_end].to_string();
+    if name.is_empty() {
+        return Err(syntax_error(line, "missing tag name"));
+    }
+    let mut attrs = HashMap::new();
+    let mut rest = tag[name_end..].trim_start();
+    while !rest.is_empty() {
+        let malformed = || syntax_error(line, &format!("malformed attribute in <{}>", name));
+        let (key, value) = rest.split_once('=').ok_or_else(malformed)?;
+        let value = value.trim_start();
+        let quote = value.chars().next().filter(|c| *c == '"' || *c == '\'');
+        let quote = quote.ok_or_else(malformed)?;
+        let end = value[1..].find(quote).ok_or_else(malformed)? + 1;
+        attrs.insert(key.trim().to_string(), decode(&value[1..end]));
+        rest = value[end + 1..].trim_start();
+    }
+    Ok(Event::Open { name, attrs, empty })
+}
+
+/// Replaces character and entity references.
+fn decode(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let entity = rest[1..].find(';').map(|end| &rest[1..end + 1]);
+        let c = entity.and_then(|entity| match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => {
+                let code = match entity.strip_prefix("#x").or(entity.strip_prefix("#X")) {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                    None => entity.strip_prefix('#')?.parse().ok(),
+                };
+                char::from_u32(code?)
+            }
+        });
+        match (c, entity) {
+            (Some(c), Some(entity)) => {
+                decoded.push(c);
+                rest = &rest[entity.len() + 2..];
+            }
+            _ => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::themes::velo_light;
+    use crate::utils::ReflectableUuid;
+
+    const OPML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!-- exported by an outliner -->
+<opml version="2.0">
+  <head>
+    <title>Plans</title>
+    <expansionState>1,5</expansionState>
+  </head>
+  <body>
+    <outline text="Trip &amp; holidays" _note="book &lt;soon&gt;&#10;pack">
+      <outline text="Flights">
+        <outline text="Seats"/>
+      </outline>
+      <outline title="Hotel"/>
+    </outline>
+    <outline text='Books'>
+      <outline text="Dune"/>
+    </outline>
+  </body>
+</opml>"#;
+
+    #[test]
+    fn test_opml_to_tab() {
+        let snapshot = opml_to_tab(OPML, &velo_light()).unwrap();
+        let nodes: Vec<(&str, bool)> = snapshot
+            .nodes
+            .iter()
+            .map(|node| (node.text.text.as_str(), node.visible))
+            .collect();
+        assert_eq!(
+            nodes,
+            vec![
+                ("Trip & holidays\nbook <soon>\npack", true),
+                ("Flights", true),
+                ("Seats", false),
+                ("Hotel", true),
+                ("Books", true),
+                ("Dune", true),
+            ]
+        );
+        let arrows: Vec<(&str, &str, bool)> = snapshot
+            .arrows
+            .iter()
+            .map(|arrow| {
+                let text = |id: ReflectableUuid| {
+                    let node = snapshot.nodes.iter().find(|node| node.id == id.0).unwrap();
+                    node.text.text.lines().next().unwrap()
+                };
+                (text(arrow.start.id), text(arrow.end.id), arrow.visible)
+            })
+            .collect();
+        assert_eq!(
+            arrows,
+            vec![
+                ("Trip & holidays", "Flights", true),
+                ("Flights", "Seats", false),
+                ("Trip & holidays", "Hotel", true),
+                ("Books", "Dune", true),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_opml_errors() {
+        let theme = velo_light();
+        let error = |text: &str| opml_to_tab(text, &theme).unwrap_err().to_string();
+        assert!(matches!(
+            opml_to_tab("<opml><body></body></opml>", &theme),
+            Err(FormatError::Empty)
+        ));
+        assert_eq!(
+            error("<rss>\n<outline text=\"a\"/></rss>"),
+            "line 1: not an OPML document"
+        );
+        assert_eq!(
+            error("<opml><body>\n<outline text=\"a\"></body></opml>"),
+            "line 2: expected </outline>"
+        );
+        assert_eq!(
+            error("<opml><body><outline text=\"a\"/>\n</body>"),
+            "line 2: missing </opml>"
+        );
+        assert_eq!(
+            error("<opml>\n<outline text=a/></opml>"),
+            "line 2: malformed attribute in <outline>"
+        );
+    }
+
+    #[test]
+    fn test_opml_roundtrip() {
+        let snapshot = opml_to_tab(OPML, &velo_light()).unwrap();
+        let opml = tab_to_opml(&snapshot, "Plans <2>");
+        assert!(opml.contains("<title>Plans &lt;2&gt;</title>"));
+        assert!(opml.contains("<expansionState>1,5</expansionState>"));
+        assert!(opml.contains(
+            "<outline text=\"Trip &amp; holidays\" _note=\"book &lt;soon&gt;&#10;pack\">"
+        ));
+        let again = opml_to_tab(&opml, &velo_light()).unwrap();
+        let texts = |snapshot: &TabSnapshot| -> Vec<(String, bool)> {
+            snapshot
+                .nodes
+                .iter()
+                .map(|node| (node.text.text.clone(), node.visible))
+                .collect()
+        };
+        assert_eq!(texts(&again), texts(&snapshot));
+        assert_eq!(again.arrows.len(), snapshot.arrows.len());
+    }
+}
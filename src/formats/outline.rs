@@ -0,0 +1,359 @@
+use std::fmt::Write;
+
+use super::graph::{Graph, GraphEdge, GraphNode};
+use super::mind_map::{mind_map, TreeNode};
+use crate::canvas::arrow::components::ArrowType;
+use crate::components::TabSnapshot;
+use crate::themes::Theme;
+use crate::ui_plugin::NodeType;
+use crate::JsonNode;
+
+/// Deepest level that gets a heading, deeper nodes become list items.
+const MAX_HEADING_LEVEL: usize = 6;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutlineStyle {
+    /// Nested bullet list.
+    #[default]
+    List,
+    /// Roots are `#` headings, their children `##` headings and so on.
+    Headings,
+}
+
+/// Writes the mind map of the tab as a Markdown outline. Nodes without arrows
+/// are listed in a separate section at the end.
+pub fn tab_to_outline(snapshot: &TabSnapshot, style: OutlineStyle) -> String {
+    let mind_map = mind_map(snapshot);
+    let mut outline = String::new();
+    for root in &mind_map.roots {
+        match style {
+            OutlineStyle::List => write_list_item(&mut outline, root, 0),
+            OutlineStyle::Headings => write_heading(&mut outline, root, 0),
+        }
+    }
+    if !mind_map.unconnected.is_empty() {
+        if !mind_map.roots.is_empty() {
+            let level = match style {
+                OutlineStyle::List => 2,
+                OutlineStyle::Headings => 1,
+            };
+            start_block(&mut outline);
+            let _ = write!(outline, "{} Unconnected\n\n", "#".repeat(level));
+        }
+        for node in mind_map.unconnected {
+            write_item(&mut outline, &label(node, false), 0);
+        }
+    }
+    outline
+}
+
+fn write_list_item(outline: &mut String, tree: &TreeNode, depth: usize) {
+    write_item(outline, &label(tree.node, tree.repeated), depth);
+    for child in &tree.children {
+        write_list_item(outline, child, depth + 1);
+    }
+}
+
+fn write_heading(outline: &mut String, tree: &TreeNode, depth: usize) {
+    if depth >= MAX_HEADING_LEVEL {
+        return write_list_item(outline, tree, depth - MAX_HEADING_LEVEL);
+    }
+    let label = label(tree.node, tree.repeated);
+    let mut lines = label.lines();
+    start_block(outline);
+    let _ = write!(
+        outline,
+        "{} {}\n\n",
+        "#".repeat(depth + 1),
+        lines.next().unwrap_or_default()
+    );
+    let body: Vec<&str> = lines.collect();
+    if !body.is_empty() {
+        let _ = write!(outline, "{}\n\n", body.join("\n"));
+    }
+    for child in &tree.children {
+        write_heading(outline, child, depth + 1);
+    }
+}
+
+/// Separates a heading from the list before it by a blank line.
+fn start_block(outline: &mut String) {
+    if !outline.is_empty() && !outline.ends_with("\n\n") {
+        outline.push('\n');
+    }
+}
+
+/// Bullet with the continuation lines of a multi-line note indented under it.
+fn write_item(outline: &mut String, label: &str, depth: usize) {
+    let indent = "  ".repeat(depth);
+    for (i, line) in label.lines().enumerate() {
+        if i == 0 {
+            let _ = writeln!(outline, "{}- {}", indent, line);
+        } else if line.trim().is_empty() {
+            outline.push('\n');
+        } else {
+            let _ = writeln!(outline, "{}  {}", indent, line);
+        }
+    }
+}
+
+pub(super) fn label(node: &JsonNode<String>, repeated: bool) -> String {
+    let text = node.text.text.trim();
+    let text = if text.is_empty() { "(empty)" } else { text };
+    if repeated {
+        format!("{} (see above)", text.lines().next().unwrap_or_default())
+    } else {
+        text.to_string()
+    }
+}
+
+/// Reads the headings and list items of a Markdown outline as nodes with an
+/// arrow from every item to its children. Any other text is added to the note
+/// of the heading or item above it.
+pub fn outline_to_tab(markdown: &str, theme: &Theme) -> TabSnapshot {
+    let mut texts: Vec<String> = vec![];
+    let mut edges = vec![];
+    let mut headings: Vec<(usize, usize)> = vec![];
+    let mut items: Vec<(usize, usize)> = vec![];
+    let mut in_code_block = false;
+    for line in markdown.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with("```") || in_code_block {
+            if trimmed.starts_with("```") {
+                in_code_block = !in_code_block;
+            }
+            if let Some(text) = texts.last_mut() {
+                text.push('\n');
+                text.push_str(line);
+                continue;
+            }
+        }
+        if trimmed.is_empty() {
+            continue;
+        }
+        let (parent, text) = if let Some((level, text)) = parse_heading(trimmed) {
+            items.clear();
+            while headings.last().is_some_and(|(last, _)| *last >= level) {
+                headings.pop();
+            }
+            let parent = headings.last().map(|(_, index)| *index);
+            headings.push((level, texts.len()));
+            (parent, text)
+        } else if let Some((indent, text)) = parse_list_item(line) {
+            while items.last().is_some_and(|(last, _)| *last >= indent) {
+                items.pop();
+            }
+            let parent = items.last().or(headings.last()).map(|(_, index)| *index);
+            items.push((indent, texts.len()));
+            (parent, text)
+        } else if let Some(text) = texts.last_mut() {
+            text.push('\n');
+            text.push_str(trimmed);
+            continue;
+        } else {
+            (None, trimmed)
+        };
+        if let Some(parent) = parent {
+            edges.push((parent, texts.len()));
+        }
+        texts.push(text.to_string());
+    }
+    Graph {
+        nodes: texts
+            .into_iter()
+            .map(|text| GraphNode {
+                text,
+                node_type: NodeType::Rect,
+                ..Default::default()
+            })
+            .collect(),
+        edges: edges
+            .into_iter()
+            .map(|(from, to)| GraphEdge {
+                from,
+                to,
+                arrow_type: ArrowType::ParallelArrow,
+                label: None,
+            })
+            .collect(),
+        ..Default::default()
+    }
+    .into_tab(theme)
+}
+
+fn parse_heading(line: &str) -> Option<(usize, &str)> {
+    let level = line.chars().take_while(|c| *c == '#').count();
+    let text = &line[level..];
+    if !(1..=MAX_HEADING_LEVEL).contains(&level) || !(text.is_empty() || text.starts_with(' ')) {
+        return None;
+    }
+    Some((level, text.trim()))
+}
+
+/// Indentation and text of `- item`, `* item`, `+ item` and `1. item` lines.
+fn parse_list_item(line: &str) -> Option<(usize, &str)> {
+    let text = line.trim_start();
+    let indent = line[..line.len() - text.len()]
+        .chars()
+        .map(|c| if c == '\t' { 4 } else { 1 })
+        .sum();
+    let digits = text.chars().take_while(|c| c.is_ascii_digit()).count();
+    let marker = if digits > 0 {
+        text[digits..]
+            .starts_with(['.', ')'])
+            .then_some(digits + 1)?
+    } else {
+        text.starts_with(['-', '*', '+']).then_some(1)?
+    };
+    let rest = &text[marker..];
+    if rest.is_empty() {
+        return Some((indent, rest));
+    }
+    rest.starts_with([' ', '\t'])
+        .then_some((indent, rest.trim()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canvas::arrow::components::{ArrowConnect, ArrowConnectPos, ArrowMeta};
+    use crate::themes::velo_light;
+    use crate::utils::ReflectableUuid;
+    use crate::{JsonNodeText, TextPos};
+    use uuid::Uuid;
+
+    fn node(id: Uuid, x: f32, y: f32, text: &str) -> JsonNode<String> {
+        JsonNode {
+            id,
+            node_type: NodeType::Rect,
+            x,
+            y,
+            z: 1.,
+            width: 100.,
+            height: 50.,
+            text: JsonNodeText {
+                text: text.to_string(),
+                pos: TextPos::Center,
+            },
+            bg_color: "node_bg".to_string(),
+            visible: true,
+        }
+    }
+
+    fn arrow(start: Uuid, end: Uuid) -> ArrowMeta {
+        ArrowMeta {
+            visible: true,
+            arrow_type: ArrowType::Arrow,
+            start: ArrowConnect {
+                id: ReflectableUuid(start),
+                pos: ArrowConnectPos::Right,
+            },
+            end: ArrowConnect {
+                id: ReflectableUuid(end),
+                pos: ArrowConnectPos::Left,
+            },
+        }
+    }
+
+    fn snapshot() -> TabSnapshot {
+        let ids: Vec<Uuid> = (0..6).map(|_| Uuid::new_v4()).collect();
+        TabSnapshot {
+            nodes: vec![
+                node(ids[0], 0., 0., "Velo"),
+                node(ids[1], 200., -100., "Export"),
+                node(ids[2], 200., 100., "Import\nfrom files"),
+                node(ids[3], 400., 100., "Markdown"),
+                node(ids[4], 0., 300., "Todo"),
+                node(ids[5], 400., -100., ""),
+            ],
+            arrows: vec![
+                arrow(ids[0], ids[1]),
+                arrow(ids[0], ids[2]),
+                arrow(ids[2], ids[3]),
+                arrow(ids[1], ids[3]),
+                // a cycle below the root
+                arrow(ids[3], ids[2]),
+                arrow(ids[1], ids[5]),
+            ],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_tab_to_list_outline() {
+        let outline = tab_to_outline(&snapshot(), OutlineStyle::List);
+        assert_eq!(
+            outline,
+            "- Velo\n  - Import\n    from files\n    - Markdown\n      - Import (see above)\n  - Export\n    - Markdown (see above)\n    - (empty)\n\n## Unconnected\n\n- Todo\n"
+        );
+    }
+
+    #[test]
+    fn test_tab_to_heading_outline() {
+        let outline = tab_to_outline(&snapshot(), OutlineStyle::Headings);
+        assert!(outline.starts_with("# Velo\n\n## Import\n\nfrom files\n\n### Markdown\n"));
+        assert!(outline.ends_with("# Unconnected\n\n- Todo\n"));
+    }
+
+    #[test]
+    fn test_cycle_without_root() {
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        let snapshot = TabSnapshot {
+            nodes: vec![node(a, 0., 0., "A"), node(b, 0., -100., "B")],
+            arrows: vec![arrow(a, b), arrow(b, a)],
+            ..Default::default()
+        };
+        assert_eq!(
+            tab_to_outline(&snapshot, OutlineStyle::List),
+            "- A\n  - B\n    - A (see above)\n"
+        );
+    }
+
+    #[test]
+    fn test_outline_to_tab() {
+        let markdown = "# Meeting\nnotes from monday\n\n## Decisions\n\n- ship it\n  - on friday\n* write docs\n\n## Next\n1. demo\n\n```\n# not a heading\n```\n";
+        let snapshot = outline_to_tab(markdown, &velo_light());
+        let texts: Vec<&str> = snapshot
+            .nodes
+            .iter()
+            .map(|node| node.text.text.as_str())
+            .collect();
+        assert_eq!(
+            texts,
+            vec![
+                "Meeting\nnotes from monday",
+                "Decisions",
+                "ship it",
+                "on friday",
+                "write docs",
+                "Next",
+                "demo\n```\n# not a heading\n```",
+            ]
+        );
+        let text = |id: ReflectableUuid| {
+            let node = snapshot.nodes.iter().find(|node| node.id == id.0).unwrap();
+            node.text.text.lines().next().unwrap()
+        };
+        let edges: Vec<(&str, &str)> = snapshot
+            .arrows
+            .iter()
+            .map(|arrow| (text(arrow.start.id), text(arrow.end.id)))
+            .collect();
+        assert_eq!(
+            edges,
+            vec![
+                ("Meeting", "Decisions"),
+                ("Decisions", "ship it"),
+                ("ship it", "on friday"),
+                ("Decisions", "write docs"),
+                ("Meeting", "Next"),
+                ("Next", "demo"),
+            ]
+        );
+        // the exported outline reads back as the same tree
+        let outline = tab_to_outline(&snapshot, OutlineStyle::List);
+        assert!(outline.starts_with(
+            "- Meeting\n  notes from monday\n  - Decisions\n    - ship it\n      - on friday\n"
+        ));
+    }
+}
@@ -0,0 +1,349 @@
+use base64::{engine::general_purpose, Engine};
+use bevy::prelude::*;
+use bevy_cosmic_edit::{
+    cosmic_edit_set_text, create_cosmic_font_system, get_x_offset, get_y_offset, CosmicFontConfig,
+    CosmicText,
+};
+use bevy_markdown::{generate_markdown_lines, BevyMarkdown};
+use cosmic_text::{AttrsOwned, Buffer, Edit, Editor, FontSystem, Metrics, SwashCache};
+use image::imageops::FilterType;
+use image::{ImageError, ImageOutputFormat};
+use std::collections::HashMap;
+use std::io::Cursor;
+
+use super::raster::Pixmap;
+use super::{
+    arrow_path, canvas_background, export_bounds, node_images, node_path, paint_order, Bounds,
+    TabItem, ThemeColors,
+};
+use crate::components::TabSnapshot;
+use crate::themes::Theme;
+use crate::ui_plugin::ui_helpers::{drawing_path, markdown_theme};
+use crate::ui_plugin::NodeType;
+use crate::utils::{bevy_color_to_cosmic, velo_font_bytes};
+use crate::{JsonNode, TextPos};
+
+/// Larger images are refused instead of allocating gigabytes.
+const MAX_PIXELS: u64 = 100_000_000;
+
+/// The app exports at the screen scale factor, on the canvas background or a
+/// transparent one, and the region of the selected note; `velo-cli export` can set every option.
+#[derive(Debug, Clone, Copy)]
+pub struct PngOptions {
+    /// Pixels per canvas unit.
+    pub scale: f32,
+    /// `None` keeps the canvas background, `Color::NONE` gives a transparent image.
+    pub background: Option<Color>,
+    /// Part of the canvas to render, the whole tab if `None`.
+    pub region: Option<Bounds>,
+}
+
+impl Default for PngOptions {
+    fn default() -> Self {
+        PngOptions {
+            scale: 1.,
+            background: None,
+            region: None,
+        }
+    }
+}
+
+/// Font system with only the bundled fonts, for rendering without the app.
+pub fn export_font_system() -> FontSystem {
+    create_cosmic_font_system(CosmicFontConfig {
+        fonts_dir_path: None,
+        font_bytes: Some(velo_font_bytes()),
+        load_system_fonts: false,
+    })
+}
+
+/// Renders the tab on the CPU and encodes it as PNG. `images` are the base64
+/// PNGs of `AppState::images`.
+pub fn tab_to_png(
+    snapshot: &TabSnapshot,
+    images: &HashMap<String, String>,
+    theme: &Theme,
+    font_system: &mut FontSystem,
+    options: PngOptions,
+) -> Result<Vec<u8>, ImageError> {
+    let pixmap = render_tab(snapshot, images, theme, font_system, options)?;
+    let mut png = Vec::new();
+    pixmap
+        .image
+        .write_to(&mut Cursor::new(&mut png), ImageOutputFormat::Png)?;
+    Ok(png)
+}
+
+pub fn render_tab(
+    snapshot: &TabSnapshot,
+    images: &HashMap<String, String>,
+    theme: &Theme,
+    font_system: &mut FontSystem,
+    options: PngOptions,
+) -> Result<Pixmap, ImageError> {
+    let bounds = options.region.unwrap_or_else(|| export_bounds(snapshot));
+    let size = (bounds.size() * options.scale).ceil();
+    if size.x < 1.
+        || size.y < 1.
+        || !options.scale.is_finite()
+        || size.x as u64 * size.y as u64 > MAX_PIXELS
+    {
+        return Err(ImageError::Limits(image::error::LimitError::from_kind(
+            image::error::LimitErrorKind::DimensionError,
+        )));
+    }
+    let background = options
+        .background
+        .unwrap_or_else(|| canvas_background(theme));
+    let mut pixmap = Pixmap::new(
+        size.x as u32,
+        size.y as u32,
+        Vec2::new(bounds.min.x, bounds.max.y),
+        options.scale,
+        background,
+    );
+
+    let colors = ThemeColors::new(theme);
+    let node_images = node_images(snapshot, images);
+    let mut swash_cache = SwashCache::new();
+    for item in paint_order(snapshot) {
+        match item {
+            TabItem::Node(node) => {
+                let bg_color = colors.get(&node.bg_color).unwrap_or(theme.node_bg);
+                render_node(&mut pixmap, node, bg_color, theme);
+                if let Some(image) = node_images.get(&node.id) {
+                    render_image(&mut pixmap, node, image);
+                }
+                render_text(
+                    &mut pixmap,
+                    node,
+                    bg_color,
+                    theme,
+                    font_system,
+                    &mut swash_cache,
+                );
+            }
+            TabItem::Arrow(arrow) => {
+                if let Some(path) = arrow_path(snapshot, arrow) {
+                    pixmap.stroke_path(&path, 1.5, theme.arrow);
+                }
+            }
+            TabItem::Drawing(drawing) => {
+                let points: Vec<Vec2> = drawing
+                    .points
+                    .iter()
+                    .map(|point| Vec2::new(drawing.x, drawing.y) + *point)
+                    .collect();
+                let color = colors.get(&drawing.drawing_color).unwrap_or(theme.font);
+                pixmap.stroke_path(&drawing_path(&points), drawing.width, color);
+            }
+        }
+    }
+    Ok(pixmap)
+}
+
+fn render_node(pixmap: &mut Pixmap, node: &JsonNode<String>, bg_color: Color, theme: &Theme) {
+    if node.node_type == NodeType::Paper {
+        let shadow = JsonNode {
+            x: node.x + 3.,
+            y: node.y - 3.,
+            ..node.clone()
+        };
+        pixmap.fill_path(&node_path(&shadow), theme.node_shadow);
+    }
+    let path = node_path(node);
+    pixmap.fill_path(&path, bg_color);
+    if node.node_type != NodeType::Paper && bg_color != Color::NONE {
+        pixmap.stroke_path(&path, 1., theme.node_border);
+    }
+}
+
+/// Images cover the node like on the canvas, cropping what doesn't fit.
+fn render_image(pixmap: &mut Pixmap, node: &JsonNode<String>, image: &str) {
+    let Ok(bytes) = general_purpose::STANDARD.decode(image.as_bytes()) else {
+        return;
+    };
+    let Ok(image) = image::load_from_memory_with_format(&bytes, image::ImageFormat::Png) else {
+        return;
+    };
+    let top_left = pixmap.to_pixel(Vec2::new(
+        node.x - node.width / 2.,
+        node.y + node.height / 2.,
+    ));
+    let width = (node.width * pixmap.scale()).round() as u32;
+    let height = (node.height * pixmap.scale()).round() as u32;
+    if width == 0 || height == 0 {
+        return;
+    }
+    let image = image
+        .resize_to_fill(width, height, FilterType::Triangle)
+        .to_rgba8();
+    let (left, top) = (top_left.x.round() as i64, top_left.y.round() as i64);
+    let (pixmap_width, pixmap_height) = pixmap.image.dimensions();
+    for (x, y, pixel) in image.enumerate_pixels() {
+        let (px, py) = (left + x as i64, top + y as i64);
+        if px < 0 || py < 0 || px >= pixmap_width as i64 || py >= pixmap_height as i64 {
+            continue;
+        }
+        let [r, g, b, a] = pixel.0;
+        pixmap.blend(px as u32, py as u32, Color::rgba_u8(r, g, b, a), 1.);
+    }
+}
+
+/// Lays the text out with cosmic-text exactly like the node editor does.
+fn render_text(
+    pixmap: &mut Pixmap,
+    node: &JsonNode<String>,
+    bg_color: Color,
+    theme: &Theme,
+    font_system: &mut FontSystem,
+    swash_cache: &mut SwashCache,
+) {
+    if node.text.text.is_empty() {
+        return;
+    }
+    let is_transparent = bg_color == Color::NONE;
+    let (font_size, line_height) = if is_transparent {
+        (3. * theme.font_size, 3. * theme.line_height)
+    } else {
+        (theme.font_size, theme.line_height)
+    };
+    let scale = pixmap.scale();
+    let mut editor = Editor::new(Buffer::new(
+        font_system,
+        Metrics::new(font_size, line_height).scale(scale),
+    ));
+    let mut attrs = cosmic_text::Attrs::new();
+    attrs = attrs.family(cosmic_text::Family::Name(theme.font_name.as_str()));
+    attrs = attrs.color(bevy_color_to_cosmic(theme.font));
+    let text = match generate_markdown_lines(BevyMarkdown {
+        text: node.text.text.clone(),
+        attrs: AttrsOwned::new(attrs),
+        markdown_theme: markdown_theme(theme),
+    }) {
+        Ok(markdown_lines) => CosmicText::MultiStyle(markdown_lines.lines),
+        Err(_) => CosmicText::OneStyle(node.text.text.clone()),
+    };
+    cosmic_edit_set_text(text, AttrsOwned::new(attrs), &mut editor, font_system);
+    let (width, height) = (node.width * scale, node.height * scale);
+    editor.buffer_mut().set_size(font_system, width, height);
+    editor.buffer_mut().shape_until_scroll(font_system);
+
+    let (offset_y, offset_x) = match node.text.pos {
+        TextPos::Center => (get_y_offset(editor.buffer()), get_x_offset(editor.buffer())),
+        TextPos::TopLeft => (0, 0),
+    };
+    let top_left = pixmap.to_pixel(Vec2::new(
+        node.x - node.width / 2.,
+        node.y + node.height / 2.,
+    ));
+    let (left, top) = (top_left.x.round() as i32, top_left.y.round() as i32);
+    let (pixmap_width, pixmap_height) = pixmap.image.dimensions();
+    let font_color = bevy_color_to_cosmic(theme.font);
+    editor
+        .buffer()
+        .draw(font_system, swash_cache, font_color, |x, y, w, h, color| {
+            for row in 0..h as i32 {
+                for col in 0..w as i32 {
+                    // like the editor texture, text is clipped to the node
+                    let (node_x, node_y) = (x + col + offset_x, y + row + offset_y);
+                    if node_x < 0 || node_y < 0 || node_x >= width as i32 || node_y >= height as i32
+                    {
+                        continue;
+                    }
+                    let (px, py) = (left + node_x, top + node_y);
+                    if px < 0 || py < 0 || px >= pixmap_width as i32 || py >= pixmap_height as i32 {
+                        continue;
+                    }
+                    let rgba = Color::rgba_u8(color.r(), color.g(), color.b(), color.a());
+                    pixmap.blend(px as u32, py as u32, rgba, 1.);
+                }
+            }
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::themes::velo_light;
+    use crate::JsonNodeText;
+    use uuid::Uuid;
+
+    fn snapshot() -> TabSnapshot {
+        TabSnapshot {
+            nodes: vec![JsonNode {
+                id: Uuid::new_v4(),
+                node_type: NodeType::Rect,
+                x: 0.,
+                y: 0.,
+                z: 1.,
+                width: 100.,
+                height: 50.,
+                text: JsonNodeText {
+                    text: "**Velo**".to_string(),
+                    pos: TextPos::Center,
+                },
+                bg_color: "node_bg".to_string(),
+                visible: true,
+            }],
+            ..default()
+        }
+    }
+
+    #[test]
+    fn test_render_tab() {
+        let theme = velo_light();
+        let mut font_system = export_font_system();
+        let options = PngOptions {
+            scale: 2.,
+            ..default()
+        };
+        let pixmap = render_tab(
+            &snapshot(),
+            &HashMap::new(),
+            &theme,
+            &mut font_system,
+            options,
+        )
+        .unwrap();
+        // node plus a margin of 20 on each side, at twice the size
+        assert_eq!(pixmap.image.dimensions(), (280, 180));
+        let background = canvas_background(&theme).as_rgba_u8();
+        assert_eq!(pixmap.image.get_pixel(5, 5).0, background);
+        let node_color = ThemeColors::new(&theme)
+            .get("node_bg")
+            .unwrap()
+            .as_rgba_u8();
+        assert_eq!(pixmap.image.get_pixel(60, 50).0, node_color);
+        // the text is drawn in the middle of the node
+        let text_pixels = (120..160)
+            .flat_map(|x| (80..100).map(move |y| (x, y)))
+            .filter(|(x, y)| pixmap.image.get_pixel(*x, *y).0 != node_color)
+            .count();
+        assert!(text_pixels > 0);
+    }
+
+    #[test]
+    fn test_render_region_with_transparent_background() {
+        let options = PngOptions {
+            scale: 1.,
+            background: Some(Color::NONE),
+            region: Some(Bounds {
+                min: Vec2::new(-100., -10.),
+                max: Vec2::new(-40., 10.),
+            }),
+        };
+        let png = tab_to_png(
+            &snapshot(),
+            &HashMap::new(),
+            &velo_light(),
+            &mut export_font_system(),
+            options,
+        )
+        .unwrap();
+        let image = image::load_from_memory(&png).unwrap().to_rgba8();
+        assert_eq!(image.dimensions(), (60, 20));
+        assert_eq!(image.get_pixel(0, 0).0[3], 0);
+        assert_eq!(image.get_pixel(59, 10).0[3], 255);
+    }
+}
@@ -0,0 +1,234 @@
+//! A small software rasterizer. Shapes are tessellated with lyon, like the
+//! canvas does on the GPU, and the triangles are filled with anti-aliased scanlines.
+
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::{
+    tess::{
+        math::Point, BuffersBuilder, FillTessellator, FillVertex, StrokeTessellator, StrokeVertex,
+        VertexBuffers,
+    },
+    FillOptions, Path, StrokeOptions,
+};
+use image::RgbaImage;
+
+/// Sub-scanlines per pixel row used for vertical anti-aliasing.
+const SUBSAMPLES: usize = 4;
+
+pub struct Pixmap {
+    pub image: RgbaImage,
+    /// Canvas point at the top left corner of the image.
+    origin: Vec2,
+    scale: f32,
+}
+
+impl Pixmap {
+    pub fn new(width: u32, height: u32, origin: Vec2, scale: f32, background: Color) -> Self {
+        let image = RgbaImage::from_pixel(width, height, image::Rgba(background.as_rgba_u8()));
+        Pixmap {
+            image,
+            origin,
+            scale,
+        }
+    }
+
+    /// Pixel position of a canvas point, y grows downwards in the image.
+    pub fn to_pixel(&self, point: Vec2) -> Vec2 {
+        Vec2::new(point.x - self.origin.x, self.origin.y - point.y) * self.scale
+    }
+
+    pub fn scale(&self) -> f32 {
+        self.scale
+    }
+
+    pub fn fill_path(&mut self, path: &Path, color: Color) {
+        let mut buffers: VertexBuffers<Point, u32> = VertexBuffers::new();
+        let options = FillOptions::default().with_tolerance(0.1 / self.scale);
+        let result = FillTessellator::new().tessellate_path(
+            &path.0,
+            &options,
+            &mut BuffersBuilder::new(&mut buffers, |vertex: FillVertex| vertex.position()),
+        );
+        if result.is_ok() {
+            self.fill_buffers(&buffers, color);
+        }
+    }
+
+    pub fn stroke_path(&mut self, path: &Path, width: f32, color: Color) {
+        let mut buffers: VertexBuffers<Point, u32> = VertexBuffers::new();
+        let options = StrokeOptions::default()
+            .with_line_width(width)
+            .with_tolerance(0.1 / self.scale);
+        let result = StrokeTessellator::new().tessellate_path(
+            &path.0,
+            &options,
+            &mut BuffersBuilder::new(&mut buffers, |vertex: StrokeVertex| vertex.position()),
+        );
+        if result.is_ok() {
+            self.fill_buffers(&buffers, color);
+        }
+    }
+
+    fn fill_buffers(&mut self, buffers: &VertexBuffers<Point, u32>, color: Color) {
+        let vertices: Vec<Vec2> = buffers
+            .vertices
+            .iter()
+            .map(|vertex| self.to_pixel(Vec2::new(vertex.x, vertex.y)))
+            .collect();
+        let triangles: Vec<[Vec2; 3]> = buffers
+            .indices
+            .chunks_exact(3)
+            .map(|indices| {
+                [
+                    vertices[indices[0] as usize],
+                    vertices[indices[1] as usize],
+                    vertices[indices[2] as usize],
+                ]
+            })
+            .collect();
+        self.fill_triangles(&triangles, color);
+    }
+
+    /// Fills the union of the triangles, so overlapping triangles of a stroke
+    /// don't blend twice.
+    fn fill_triangles(&mut self, triangles: &[[Vec2; 3]], color: Color) {
+        let (width, height) = self.image.dimensions();
+        if triangles.is_empty() || width == 0 || height == 0 {
+            return;
+        }
+        let mut edges = vec![];
+        let (mut min_y, mut max_y) = (f32::MAX, f32::MIN);
+        for [a, b, c] in triangles {
+            // wind every triangle the same way so that winding numbers never cancel
+            let (b, c) = if (*b - *a).perp_dot(*c - *a) < 0. {
+                (c, b)
+            } else {
+                (b, c)
+            };
+            for (from, to) in [(*a, *b), (*b, *c), (*c, *a)] {
+                if from.y != to.y {
+                    edges.push((from, to));
+                }
+                min_y = min_y.min(from.y);
+                max_y = max_y.max(from.y);
+            }
+        }
+        let first_row = min_y.floor().max(0.) as u32;
+        let last_row = (max_y.ceil().max(0.) as u32).min(height);
+        let mut coverage = vec![0f32; width as usize];
+        let mut crossings: Vec<(f32, i32)> = vec![];
+        for row in first_row..last_row {
+            let (mut dirty_start, mut dirty_end) = (width as usize, 0);
+            for sample in 0..SUBSAMPLES {
+                let y = row as f32 + (sample as f32 + 0.5) / SUBSAMPLES as f32;
+                crossings.clear();
+                for (from, to) in &edges {
+                    if (from.y <= y) != (to.y <= y) {
+                        let x = from.x + (y - from.y) * (to.x - from.x) / (to.y - from.y);
+                        crossings.push((x, if to.y > from.y { 1 } else { -1 }));
+                    }
+                }
+                crossings.sort_by(|a, b| a.0.total_cmp(&b.0));
+                let mut winding = 0;
+                let mut span_start = 0.;
+                for (x, direction) in &crossings {
+                    if winding == 0 {
+                        span_start = *x;
+                    }
+                    winding += direction;
+                    if winding == 0 {
+                        let span = add_span(&mut coverage, span_start, *x);
+                        dirty_start = dirty_start.min(span.0);
+                        dirty_end = dirty_end.max(span.1);
+                    }
+                }
+            }
+            for (x, pixel_coverage) in coverage
+                .iter_mut()
+                .enumerate()
+                .take(dirty_end)
+                .skip(dirty_start)
+            {
+                let alpha = (std::mem::take(pixel_coverage) / SUBSAMPLES as f32).min(1.);
+                if alpha > 0. {
+                    self.blend(x as u32, row, color, alpha);
+                }
+            }
+        }
+    }
+
+    /// Blends `color` over the pixel, `alpha` is the coverage of the pixel.
+    pub fn blend(&mut self, x: u32, y: u32, color: Color, alpha: f32) {
+        let [r, g, b, a] = color.as_rgba_f32();
+        let source_alpha = a * alpha;
+        if source_alpha <= 0. {
+            return;
+        }
+        let pixel = self.image.get_pixel_mut(x, y);
+        let [dr, dg, db, da] = pixel.0.map(|channel| channel as f32 / 255.);
+        let out_alpha = source_alpha + da * (1. - source_alpha);
+        let mix = |source: f32, destination: f32| {
+            (source * source_alpha + destination * da * (1. - source_alpha)) / out_alpha
+        };
+        pixel.0 = [mix(r, dr), mix(g, dg), mix(b, db), out_alpha]
+            .map(|channel| (channel * 255.).round().clamp(0., 255.) as u8);
+    }
+}
+
+/// Adds the horizontal coverage of `[start, end)` to the row, returns the touched pixels.
+fn add_span(coverage: &mut [f32], start: f32, end: f32) -> (usize, usize) {
+    let width = coverage.len() as f32;
+    let (start, end) = (start.clamp(0., width), end.clamp(0., width));
+    if end <= start {
+        return (coverage.len(), 0);
+    }
+    let (first, last) = (start.floor() as usize, end.floor() as usize);
+    if first == last {
+        coverage[first] += end - start;
+        return (first, first + 1);
+    }
+    coverage[first] += first as f32 + 1. - start;
+    for pixel in &mut coverage[first + 1..last] {
+        *pixel += 1.;
+    }
+    if last < coverage.len() {
+        coverage[last] += end - last as f32;
+        (first, last + 1)
+    } else {
+        (first, last)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy_prototype_lyon::prelude::{shapes, GeometryBuilder};
+
+    #[test]
+    fn test_fill_path() {
+        let mut pixmap = Pixmap::new(10, 10, Vec2::new(0., 10.), 1., Color::WHITE);
+        let square = GeometryBuilder::build_as(&shapes::Rectangle {
+            extents: Vec2::new(4., 4.),
+            origin: shapes::RectangleOrigin::BottomLeft,
+        });
+        pixmap.fill_path(&square, Color::BLACK);
+        // the square covers x 0..4 and y 6..10 in pixels
+        assert_eq!(pixmap.image.get_pixel(1, 8).0, [0, 0, 0, 255]);
+        assert_eq!(pixmap.image.get_pixel(5, 8).0, [255, 255, 255, 255]);
+        assert_eq!(pixmap.image.get_pixel(1, 4).0, [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn test_overlapping_stroke_is_blended_once() {
+        let mut pixmap = Pixmap::new(10, 10, Vec2::new(0., 10.), 1., Color::WHITE);
+        let path = crate::ui_plugin::ui_helpers::drawing_path(&[
+            Vec2::new(1., 5.),
+            Vec2::new(9., 5.),
+            Vec2::new(5., 9.),
+            Vec2::new(5., 1.),
+        ]);
+        pixmap.stroke_path(&path, 2., Color::rgba(0., 0., 0., 0.5));
+        // the last segment crosses the first one at (5, 5)
+        assert_eq!(pixmap.image.get_pixel(5, 5).0, [128, 128, 128, 255]);
+        assert_eq!(pixmap.image.get_pixel(2, 5).0, [128, 128, 128, 255]);
+    }
+}
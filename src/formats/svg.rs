@@ -0,0 +1,363 @@
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::{tess::path::Event, Path};
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use super::{
+    arrow_path, canvas_background, export_bounds, layout_node_text, node_images, node_path,
+    paint_order, Bounds, TabItem, ThemeColors,
+};
+use crate::components::TabSnapshot;
+use crate::themes::Theme;
+use crate::ui_plugin::NodeType;
+use crate::JsonNode;
+
+/// Maps canvas coordinates, y up, to SVG coordinates, y down.
+struct Viewport {
+    bounds: Bounds,
+}
+
+impl Viewport {
+    fn point(&self, point: Vec2) -> Vec2 {
+        Vec2::new(point.x - self.bounds.min.x, self.bounds.max.y - point.y)
+    }
+}
+
+/// Renders the tab as a standalone SVG document. `images` are the base64 PNGs
+/// of `AppState::images`.
+pub fn tab_to_svg(
+    snapshot: &TabSnapshot,
+    images: &HashMap<String, String>,
+    theme: &Theme,
+) -> String {
+    let colors = ThemeColors::new(theme);
+    let viewport = Viewport {
+        bounds: export_bounds(snapshot),
+    };
+    let size = viewport.bounds.size();
+    let node_images = node_images(snapshot, images);
+
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" width="{w}" height="{h}" viewBox="0 0 {w} {h}">"#,
+        w = num(size.x),
+        h = num(size.y),
+    );
+    let _ = writeln!(
+        svg,
+        r#"<rect width="100%" height="100%" fill="{}"/>"#,
+        color(canvas_background(theme))
+    );
+    for item in paint_order(snapshot) {
+        match item {
+            TabItem::Node(node) => {
+                let bg_color = colors.get(&node.bg_color).unwrap_or(theme.node_bg);
+                write_node(
+                    &mut svg,
+                    &viewport,
+                    node,
+                    bg_color,
+                    node_images.get(&node.id).copied(),
+                    theme,
+                );
+            }
+            TabItem::Arrow(arrow) => {
+                if let Some(path) = arrow_path(snapshot, arrow) {
+                    let _ = writeln!(
+                        svg,
+                        r#"<path d="{}" fill="none" stroke="{}" stroke-width="1.5"/>"#,
+                        path_data(&viewport, &path),
+                        color(theme.arrow)
+                    );
+                }
+            }
+            TabItem::Drawing(drawing) => {
+                let points: Vec<String> = drawing
+                    .points
+                    .iter()
+                    .map(|point| {
+                        let point = viewport.point(Vec2::new(drawing.x, drawing.y) + *point);
+                        format!("{},{}", num(point.x), num(point.y))
+                    })
+                    .collect();
+                let _ = writeln!(
+                    svg,
+                    r#"<polyline points="{}" fill="none" stroke="{}" stroke-width="{}" stroke-linecap="round" stroke-linejoin="round"/>"#,
+                    points.join(" "),
+                    color(colors.get(&drawing.drawing_color).unwrap_or(theme.font)),
+                    num(drawing.width)
+                );
+            }
+        }
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+fn write_node(
+    svg: &mut String,
+    viewport: &Viewport,
+    node: &JsonNode<String>,
+    bg_color: Color,
+    image: Option<&String>,
+    theme: &Theme,
+) {
+    let is_transparent = bg_color == Color::NONE;
+    let top_left = viewport.point(Vec2::new(
+        node.x - node.width / 2.,
+        node.y + node.height / 2.,
+    ));
+    let path = path_data(viewport, &node_path(node));
+    let _ = writeln!(svg, "<g>");
+    if node.node_type == NodeType::Paper {
+        let _ = writeln!(
+            svg,
+            r#"<path d="{}" fill="{}" transform="translate(3 3)"/>"#,
+            path,
+            color(theme.node_shadow)
+        );
+    }
+    let stroke = if node.node_type != NodeType::Paper && !is_transparent {
+        format!(r#" stroke="{}" stroke-width="1""#, color(theme.node_border))
+    } else {
+        String::new()
+    };
+    let _ = writeln!(
+        svg,
+        r#"<path d="{}" fill="{}"{}/>"#,
+        path,
+        color(bg_color),
+        stroke
+    );
+    if let Some(image) = image {
+        let _ = writeln!(
+            svg,
+            r#"<image x="{}" y="{}" width="{}" height="{}" preserveAspectRatio="xMidYMid slice" href="data:image/png;base64,{}"/>"#,
+            num(top_left.x),
+            num(top_left.y),
+            num(node.width),
+            num(node.height),
+            image
+        );
+    }
+
+    let text = layout_node_text(node, theme, bg_color);
+    if text.lines.iter().any(|line| !line.spans.is_empty()) {
+        let _ = writeln!(
+            svg,
+            r#"<text font-family="{}, monospace" font-size="{}" xml:space="preserve" dominant-baseline="central">"#,
+            escape(&theme.font_name),
+            num(text.font_size)
+        );
+        for (i, line) in text.lines.iter().enumerate() {
+            let mut x = top_left.x + text.offset.x;
+            let y = top_left.y + text.offset.y + (i as f32 + 0.5) * text.line_height;
+            for span in &line.spans {
+                let mut attrs = format!(
+                    r#"x="{}" y="{}" fill="{}""#,
+                    num(x),
+                    num(y),
+                    color(span.color)
+                );
+                if span.bold {
+                    attrs.push_str(r#" font-weight="bold""#);
+                }
+                if span.italic {
+                    attrs.push_str(r#" font-style="italic""#);
+                }
+                let tspan = format!("<tspan {}>{}</tspan>", attrs, escape(&span.text));
+                match span.link.as_ref().filter(|link| safe_link(link)) {
+                    Some(link) => {
+                        let _ = writeln!(svg, r#"<a href="{}">{}</a>"#, escape(link), tspan);
+                    }
+                    None => {
+                        let _ = writeln!(svg, "{}", tspan);
+                    }
+                }
+                x += span.text.chars().count() as f32 * text.glyph_width();
+            }
+        }
+        let _ = writeln!(svg, "</text>");
+    }
+    let _ = writeln!(svg, "</g>");
+}
+
+/// Links that are safe to follow from an exported file: web and mail links or
+/// relative ones. Other schemes, like `javascript:`, are rendered as plain text.
+fn safe_link(link: &str) -> bool {
+    // browsers ignore whitespace and control characters inside the scheme
+    let link: String = link
+        .chars()
+        .filter(|c| !c.is_ascii_whitespace() && !c.is_control())
+        .collect();
+    let scheme_end = link.find([':', '/', '?', '#']);
+    match scheme_end {
+        Some(end) if link[end..].starts_with(':') => {
+            matches!(
+                link[..end].to_ascii_lowercase().as_str(),
+                "http" | "https" | "mailto"
+            )
+        }
+        _ => true,
+    }
+}
+
+fn path_data(viewport: &Viewport, path: &Path) -> String {
+    let mut data = vec![];
+    let point = |point: bevy_prototype_lyon::prelude::tess::math::Point| {
+        let point = viewport.point(Vec2::new(point.x, point.y));
+        format!("{} {}", num(point.x), num(point.y))
+    };
+    for event in path.0.iter() {
+        match event {
+            Event::Begin { at } => data.push(format!("M{}", point(at))),
+            Event::Line { to, .. } => data.push(format!("L{}", point(to))),
+            Event::Quadratic { ctrl, to, .. } => {
+                data.push(format!("Q{} {}", point(ctrl), point(to)))
+            }
+            Event::Cubic {
+                ctrl1, ctrl2, to, ..
+            } => data.push(format!("C{} {} {}", point(ctrl1), point(ctrl2), point(to))),
+            Event::End { close, .. } => {
+                if close {
+                    data.push("Z".to_string());
+                }
+            }
+        }
+    }
+    data.join(" ")
+}
+
+pub(crate) fn color(color: Color) -> String {
+    let [r, g, b, a] = color.as_rgba_u8();
+    if a == 255 {
+        format!("#{:02x}{:02x}{:02x}", r, g, b)
+    } else if a == 0 {
+        "none".to_string()
+    } else {
+        format!("rgba({},{},{},{})", r, g, b, num(a as f32 / 255.))
+    }
+}
+
+pub(crate) fn num(value: f32) -> String {
+    let rounded = (value * 100.).round() / 100.;
+    if rounded == 0. {
+        "0".to_string()
+    } else {
+        rounded.to_string()
+    }
+}
+
+pub(crate) fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canvas::arrow::components::{ArrowConnect, ArrowConnectPos, ArrowMeta, ArrowType};
+    use crate::themes::velo_light;
+    use crate::utils::ReflectableUuid;
+    use crate::{DrawingJsonNode, JsonNodeText, TextPos};
+    use uuid::Uuid;
+
+    fn node(id: Uuid, node_type: NodeType, x: f32, text: &str) -> JsonNode<String> {
+        JsonNode {
+            id,
+            node_type,
+            x,
+            y: 0.,
+            z: 1.,
+            width: 100.,
+            height: 50.,
+            text: JsonNodeText {
+                text: text.to_string(),
+                pos: TextPos::Center,
+            },
+            bg_color: "node_bg".to_string(),
+            visible: true,
+        }
+    }
+
+    #[test]
+    fn test_tab_to_svg() {
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        let snapshot = TabSnapshot {
+            images: HashMap::from([(b, "hash".to_string())]),
+            nodes: vec![
+                node(
+                    a,
+                    NodeType::Rect,
+                    0.,
+                    "**a < b** & [link](https://velo.test)",
+                ),
+                node(b, NodeType::Circle, 200., ""),
+            ],
+            arrows: vec![ArrowMeta {
+                visible: true,
+                arrow_type: ArrowType::Arrow,
+                start: ArrowConnect {
+                    id: ReflectableUuid(a),
+                    pos: ArrowConnectPos::Right,
+                },
+                end: ArrowConnect {
+                    id: ReflectableUuid(b),
+                    pos: ArrowConnectPos::Left,
+                },
+            }],
+            drawings: vec![DrawingJsonNode {
+                x: 0.,
+                y: 100.,
+                z: 0.,
+                id: ReflectableUuid::generate(),
+                points: vec![Vec2::ZERO, Vec2::new(10., -10.)],
+                drawing_color: "color_change_1".to_string(),
+                width: 2.,
+            }],
+        };
+        let images = HashMap::from([("hash".to_string(), "iVBORw0KGgo".to_string())]);
+        let svg = tab_to_svg(&snapshot, &images, &velo_light());
+
+        assert!(svg.starts_with("<svg "));
+        assert!(svg.contains(r#"width="340" height="165""#));
+        // the arrow starts at the right connector of the first node
+        assert!(svg.contains(r#"<path d="M120 120 L220 120"#));
+        assert!(svg.contains(r#"<polyline points="70,20 80,30""#));
+        assert!(svg.contains(r#"href="data:image/png;base64,iVBORw0KGgo""#));
+        assert!(svg.contains("a &lt; b"));
+        assert!(svg.contains(r#"font-weight="bold""#));
+        assert!(svg.contains(r#"<a href="https://velo.test">"#));
+        assert_eq!(svg.matches("<g>").count(), 2);
+    }
+
+    #[test]
+    fn test_unsafe_links_are_plain_text() {
+        let text = "[a](JavaScript:alert(1)) [b](java\tscript:x) [c](data:text/html,x) \
+                    [d](mailto:a@velo.test) [e](notes/page.html#top)";
+        let snapshot = TabSnapshot {
+            nodes: vec![node(Uuid::new_v4(), NodeType::Rect, 0., text)],
+            ..Default::default()
+        };
+        let svg = tab_to_svg(&snapshot, &HashMap::new(), &velo_light());
+
+        assert_eq!(svg.matches("<a href=").count(), 2);
+        assert!(svg.contains(r#"<a href="mailto:a@velo.test">"#));
+        assert!(svg.contains(r#"<a href="notes/page.html#top">"#));
+        assert!(!svg.to_lowercase().contains("script:"));
+        assert!(!safe_link(" JavaScript:alert(1)"));
+        assert!(!safe_link("java\tscript:alert(1)"));
+        assert!(safe_link("HTTPS://velo.test/a:b"));
+    }
+}
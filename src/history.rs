@@ -0,0 +1,414 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use uuid::Uuid;
+
+use crate::canvas::arrow::components::ArrowMeta;
+use crate::components::TabSnapshot;
+use crate::utils::ReflectableUuid;
+use crate::{DrawingJsonNode, JsonNode};
+
+/// Changes between two consecutive snapshots of a tab.
+/// Nodes, drawings and images are keyed by id, arrows by their value.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct SnapshotDelta {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub nodes: Vec<JsonNode<String>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub removed_nodes: Vec<Uuid>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub arrows: Vec<ArrowMeta>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub removed_arrows: Vec<ArrowMeta>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub drawings: Vec<DrawingJsonNode<String>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub removed_drawings: Vec<ReflectableUuid>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub images: HashMap<Uuid, String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub removed_images: Vec<Uuid>,
+}
+
+impl SnapshotDelta {
+    pub fn between(old: &TabSnapshot, new: &TabSnapshot) -> Self {
+        let mut delta = SnapshotDelta::default();
+
+        let old_nodes: HashMap<Uuid, &JsonNode<String>> =
+            old.nodes.iter().map(|node| (node.id, node)).collect();
+        for node in new.nodes.iter() {
+            if old_nodes.get(&node.id) != Some(&node) {
+                delta.nodes.push(node.clone());
+            }
+        }
+        delta.removed_nodes = old_nodes
+            .keys()
+            .filter(|id| !new.nodes.iter().any(|node| node.id == **id))
+            .cloned()
+            .collect();
+        delta.removed_nodes.sort();
+
+        let mut removed_arrows = old.arrows.clone();
+        for arrow in new.arrows.iter() {
+            match removed_arrows.iter().position(|old| old == arrow) {
+                Some(index) => {
+                    removed_arrows.remove(index);
+                }
+                None => delta.arrows.push(*arrow),
+            }
+        }
+        delta.removed_arrows = removed_arrows;
+
+        let old_drawings: HashMap<ReflectableUuid, &DrawingJsonNode<String>> = old
+            .drawings
+            .iter()
+            .map(|drawing| (drawing.id, drawing))
+            .collect();
+        for drawing in new.drawings.iter() {
+            if old_drawings.get(&drawing.id) != Some(&drawing) {
+                delta.drawings.push(drawing.clone());
+            }
+        }
+        delta.removed_drawings = old_drawings
+            .keys()
+            .filter(|id| !new.drawings.iter().any(|drawing| drawing.id == **id))
+            .cloned()
+            .collect();
+        delta.removed_drawings.sort_by_key(|id| id.0);
+
+        for (node_id, hash) in new.images.iter() {
+            if old.images.get(node_id) != Some(hash) {
+                delta.images.insert(*node_id, hash.clone());
+            }
+        }
+        delta.removed_images = old
+            .images
+            .keys()
+            .filter(|node_id| !new.images.contains_key(node_id))
+            .cloned()
+            .collect();
+        delta.removed_images.sort();
+
+        delta
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == SnapshotDelta::default()
+    }
+
+    pub fn apply(&self, snapshot: &mut TabSnapshot) {
+        snapshot
+            .nodes
+            .retain(|node| !self.removed_nodes.contains(&node.id));
+        for node in self.nodes.iter() {
+            match snapshot.nodes.iter_mut().find(|old| old.id == node.id) {
+                Some(old) => *old = node.clone(),
+                None => snapshot.nodes.push(node.clone()),
+            }
+        }
+        snapshot.nodes.sort_by_key(|node| node.id);
+
+        for arrow in self.removed_arrows.iter() {
+            if let Some(index) = snapshot.arrows.iter().position(|old| old == arrow) {
+                snapshot.arrows.remove(index);
+            }
+        }
+        snapshot.arrows.extend(self.arrows.iter().cloned());
+        snapshot
+            .arrows
+            .sort_by_key(|arrow| (arrow.start.id.0, arrow.end.id.0));
+
+        snapshot
+            .drawings
+            .retain(|drawing| !self.removed_drawings.contains(&drawing.id));
+        for drawing in self.drawings.iter() {
+            match snapshot
+                .drawings
+                .iter_mut()
+                .find(|old| old.id == drawing.id)
+            {
+                Some(old) => *old = drawing.clone(),
+                None => snapshot.drawings.push(drawing.clone()),
+            }
+        }
+        snapshot.drawings.sort_by_key(|drawing| drawing.id.0);
+
+        for node_id in self.removed_images.iter() {
+            snapshot.images.remove(node_id);
+        }
+        snapshot
+            .images
+            .extend(self.images.iter().map(|(id, hash)| (*id, hash.clone())));
+    }
+
+    pub fn image_hashes(&self) -> impl Iterator<Item = &String> {
+        self.images.values()
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Checkpoint {
+    /// Milliseconds since the unix epoch, 0 for checkpoints made before history was timestamped.
+    pub timestamp: f64,
+    pub delta: SnapshotDelta,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredTabHistory {
+    base: TabSnapshot,
+    #[serde(default)]
+    base_timestamp: f64,
+    #[serde(default)]
+    checkpoints: VecDeque<Checkpoint>,
+}
+
+/// Undo history of a tab: the oldest kept snapshot followed by the changes made since.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(from = "StoredTabHistory", into = "StoredTabHistory")]
+pub struct TabHistory {
+    base: TabSnapshot,
+    base_timestamp: f64,
+    checkpoints: VecDeque<Checkpoint>,
+    /// Latest snapshot, kept in memory so that saving does not replay the whole history.
+    head: TabSnapshot,
+    /// Undone checkpoints, dropped as soon as a new checkpoint is added.
+    redo: Vec<Checkpoint>,
+}
+
+impl From<StoredTabHistory> for TabHistory {
+    fn from(stored: StoredTabHistory) -> Self {
+        let mut history = TabHistory {
+            base: stored.base,
+            base_timestamp: stored.base_timestamp,
+            checkpoints: stored.checkpoints,
+            ..Default::default()
+        };
+        history.head = history.replay();
+        history
+    }
+}
+
+impl From<TabHistory> for StoredTabHistory {
+    fn from(history: TabHistory) -> Self {
+        StoredTabHistory {
+            base: history.base,
+            base_timestamp: history.base_timestamp,
+            checkpoints: history.checkpoints,
+        }
+    }
+}
+
+impl TabHistory {
+    pub fn new(snapshot: TabSnapshot, timestamp: f64) -> Self {
+        TabHistory {
+            head: snapshot.clone(),
+            base: snapshot,
+            base_timestamp: timestamp,
+            ..Default::default()
+        }
+    }
+
+    /// Builds a history out of full snapshots, oldest first.
+    pub fn from_snapshots(snapshots: impl IntoIterator<Item = TabSnapshot>) -> Self {
+        let mut snapshots = snapshots.into_iter();
+        let Some(base) = snapshots.next() else {
+            return TabHistory::default();
+        };
+        let mut history = TabHistory::new(base, 0.);
+        for snapshot in snapshots {
+            history.push(snapshot, 0., usize::MAX);
+        }
+        history
+    }
+
+    pub fn head(&self) -> &TabSnapshot {
+        &self.head
+    }
+
+    /// Records `snapshot` as the latest state, keeping at most `depth` undo steps.
+    /// Returns false when nothing changed since the previous checkpoint.
+    pub fn push(&mut self, snapshot: TabSnapshot, timestamp: f64, depth: usize) -> bool {
+        let delta = SnapshotDelta::between(&self.head, &snapshot);
+        if delta.is_empty() {
+            return false;
+        }
+        self.head = snapshot;
+        self.checkpoints.push_back(Checkpoint { timestamp, delta });
+        self.redo.clear();
+        while self.checkpoints.len() > depth {
+            let oldest = self.checkpoints.pop_front().unwrap();
+            oldest.delta.apply(&mut self.base);
+            self.base_timestamp = oldest.timestamp;
+        }
+        true
+    }
+
+    pub fn undo(&mut self) -> bool {
+        match self.checkpoints.pop_back() {
+            Some(checkpoint) => {
+                self.redo.push(checkpoint);
+                self.head = self.replay();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn redo(&mut self) -> bool {
+        match self.redo.pop() {
+            Some(checkpoint) => {
+                checkpoint.delta.apply(&mut self.head);
+                self.checkpoints.push_back(checkpoint);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Drops the redo stack, it is not part of exported documents.
+    pub fn clear_redo(&mut self) {
+        self.redo.clear();
+    }
+
+    /// Applies `edit` to the nodes, drawings and images of the base snapshot
+    /// and of every change since, for fixes that have to reach all undo steps.
+    pub fn edit_all(
+        &mut self,
+        mut edit: impl FnMut(
+            &mut [JsonNode<String>],
+            &mut [DrawingJsonNode<String>],
+            &mut HashMap<Uuid, String>,
+        ),
+    ) {
+        edit(
+            &mut self.base.nodes,
+            &mut self.base.drawings,
+            &mut self.base.images,
+        );
+        for checkpoint in self.checkpoints.iter_mut().chain(self.redo.iter_mut()) {
+            let delta = &mut checkpoint.delta;
+            edit(&mut delta.nodes, &mut delta.drawings, &mut delta.images);
+        }
+        self.head = self.replay();
+    }
+
+    /// Hashes of all images any undo or redo step may restore.
+    pub fn image_hashes(&self) -> impl Iterator<Item = &String> {
+        self.base.images.values().chain(
+            self.checkpoints
+                .iter()
+                .chain(self.redo.iter())
+                .flat_map(|checkpoint| checkpoint.delta.image_hashes()),
+        )
+    }
+
+    fn replay(&self) -> TabSnapshot {
+        let mut snapshot = self.base.clone();
+        for checkpoint in self.checkpoints.iter() {
+            checkpoint.delta.apply(&mut snapshot);
+        }
+        snapshot
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canvas::arrow::components::{ArrowConnect, ArrowConnectPos};
+
+    fn node(id: Uuid, x: f32) -> JsonNode<String> {
+        JsonNode {
+            id,
+            x,
+            ..Default::default()
+        }
+    }
+
+    fn snapshot(nodes: Vec<JsonNode<String>>) -> TabSnapshot {
+        let mut snapshot = TabSnapshot {
+            nodes,
+            ..Default::default()
+        };
+        snapshot.nodes.sort_by_key(|node| node.id);
+        snapshot
+    }
+
+    #[test]
+    fn test_delta_roundtrip() {
+        let (a, b, c) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let arrow = ArrowMeta {
+            start: ArrowConnect {
+                id: ReflectableUuid(a),
+                pos: ArrowConnectPos::Right,
+            },
+            end: ArrowConnect {
+                id: ReflectableUuid(b),
+                pos: ArrowConnectPos::Left,
+            },
+            ..Default::default()
+        };
+        let mut old = snapshot(vec![node(a, 0.), node(b, 0.)]);
+        old.arrows.push(arrow);
+        old.images.insert(b, "hash".to_string());
+        let new = snapshot(vec![node(a, 10.), node(c, 0.)]);
+
+        let delta = SnapshotDelta::between(&old, &new);
+        assert_eq!(delta.nodes.len(), 2);
+        assert_eq!(delta.removed_nodes, vec![b]);
+        assert_eq!(delta.removed_arrows, vec![arrow]);
+        assert_eq!(delta.removed_images, vec![b]);
+
+        let mut applied = old.clone();
+        delta.apply(&mut applied);
+        assert_eq!(applied, new);
+        assert!(SnapshotDelta::between(&new, &new).is_empty());
+    }
+
+    #[test]
+    fn test_undo_redo() {
+        let id = Uuid::new_v4();
+        let mut history = TabHistory::new(TabSnapshot::default(), 0.);
+        assert!(history.push(snapshot(vec![node(id, 0.)]), 1., 10));
+        assert!(history.push(snapshot(vec![node(id, 5.)]), 2., 10));
+        assert!(!history.push(snapshot(vec![node(id, 5.)]), 3., 10));
+        assert_eq!(history.checkpoints.len(), 2);
+
+        assert!(history.undo());
+        assert_eq!(history.head(), &snapshot(vec![node(id, 0.)]));
+        assert!(history.undo());
+        assert_eq!(history.head(), &TabSnapshot::default());
+        assert!(!history.undo());
+
+        assert!(history.redo());
+        assert_eq!(history.head(), &snapshot(vec![node(id, 0.)]));
+        assert!(history.push(snapshot(vec![node(id, 7.)]), 4., 10));
+        assert!(!history.redo());
+    }
+
+    #[test]
+    fn test_depth_folds_oldest_checkpoints_into_base() {
+        let id = Uuid::new_v4();
+        let mut history = TabHistory::new(TabSnapshot::default(), 0.);
+        for x in 0..5 {
+            history.push(snapshot(vec![node(id, x as f32)]), x as f64, 2);
+        }
+        assert_eq!(history.checkpoints.len(), 2);
+        assert_eq!(history.base, snapshot(vec![node(id, 2.)]));
+        assert_eq!(history.base_timestamp, 2.);
+        assert_eq!(history.head(), &snapshot(vec![node(id, 4.)]));
+    }
+
+    #[test]
+    fn test_serde_restores_head() {
+        let id = Uuid::new_v4();
+        let mut history = TabHistory::new(TabSnapshot::default(), 0.);
+        history.push(snapshot(vec![node(id, 1.)]), 1., 10);
+        history.push(snapshot(vec![node(id, 2.)]), 2., 10);
+        history.undo();
+
+        let json = serde_json::to_string(&history).unwrap();
+        let restored: TabHistory = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.head(), history.head());
+        assert_eq!(restored.checkpoints.len(), 1);
+        assert!(restored.redo.is_empty());
+    }
+}
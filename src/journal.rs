@@ -0,0 +1,480 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+use crate::components::{Doc, Tab};
+use crate::history::SnapshotDelta;
+use crate::utils::ReflectableUuid;
+
+/// One line of the journal.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct JournalEntry {
+    pub doc_id: ReflectableUuid,
+    #[serde(flatten)]
+    pub op: JournalOp,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum JournalOp {
+    Checkpoint {
+        tab_id: ReflectableUuid,
+        timestamp: f64,
+        delta: Box<SnapshotDelta>,
+        /// Base64 PNGs added by the delta, they are not in the store yet.
+        #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+        images: HashMap<String, String>,
+    },
+    Undo {
+        tab_id: ReflectableUuid,
+    },
+    Redo {
+        tab_id: ReflectableUuid,
+    },
+    /// A new empty tab, it becomes the active one.
+    AddTab {
+        tab_id: ReflectableUuid,
+        name: String,
+    },
+    RenameTab {
+        tab_id: ReflectableUuid,
+        name: String,
+    },
+    /// The last tab becomes active if the deleted one was.
+    DeleteTab {
+        tab_id: ReflectableUuid,
+    },
+    RenameDoc {
+        name: String,
+    },
+    SetTags {
+        tags: Vec<String>,
+    },
+    /// The document was written to the store, earlier entries are not needed anymore.
+    Saved,
+}
+
+/// Append-only log of edits that are not in the store yet, one file per running instance.
+/// The file is locked while Velo runs and removed on a clean exit, so finding an unlocked one
+/// on start means an instance did not shut down properly.
+#[derive(Resource)]
+pub struct Journal {
+    path: PathBuf,
+    file: File,
+}
+
+impl Journal {
+    /// Opens a new journal in `dir`, returning the entries left by instances that did not shut down properly.
+    /// Those entries move into the new journal, so they survive another crash until they are replayed or discarded.
+    /// Journals of running instances are locked and left alone.
+    pub fn open(dir: &Path) -> io::Result<(Journal, Vec<JournalEntry>)> {
+        fs::create_dir_all(dir)?;
+        let mut paths = fs::read_dir(dir)?
+            .filter_map(|entry| Some(entry.ok()?.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "journal"))
+            .collect::<Vec<_>>();
+        paths.sort();
+        let mut pending = vec![];
+        let mut orphans = vec![];
+        for path in paths {
+            let Some(file) = lock_orphan(&path)? else {
+                continue;
+            };
+            pending.extend(pending_entries(read_entries(&io::read_to_string(&file)?)));
+            orphans.push((path, file));
+        }
+
+        let path = dir.join(format!("{}.journal", Uuid::new_v4()));
+        let mut file = OpenOptions::new()
+            .create_new(true)
+            .append(true)
+            .open(&path)?;
+        file.try_lock().map_err(io::Error::from)?;
+        for entry in pending.iter() {
+            writeln!(file, "{}", serde_json::to_string(entry)?)?;
+        }
+        file.sync_data()?;
+        // emptied first, an instance that opened the file before it is removed finds nothing to replay
+        for (orphan, orphan_file) in orphans {
+            orphan_file.set_len(0)?;
+            fs::remove_file(&orphan)?;
+        }
+        Ok((Journal { path, file }, pending))
+    }
+
+    pub fn append(&mut self, doc_id: ReflectableUuid, op: JournalOp) {
+        let entry = JournalEntry { doc_id, op };
+        let line = serde_json::to_string(&entry).unwrap();
+        if let Err(err) = writeln!(self.file, "{}", line) {
+            error!("Failed to write journal entry: {}", err);
+        }
+    }
+
+    /// Called on a clean exit.
+    pub fn remove(self) {
+        if let Err(err) = fs::remove_file(&self.path) {
+            error!("Failed to remove journal: {}", err);
+        }
+    }
+}
+
+/// Unsaved changes found on start, waiting for the user to replay or discard them.
+#[derive(Resource)]
+pub struct JournalRecovery {
+    pub entries: Vec<JournalEntry>,
+    pub offered: bool,
+}
+
+impl JournalRecovery {
+    pub fn doc_ids(&self) -> Vec<ReflectableUuid> {
+        let mut doc_ids = vec![];
+        for entry in self.entries.iter() {
+            if !doc_ids.contains(&entry.doc_id) {
+                doc_ids.push(entry.doc_id);
+            }
+        }
+        doc_ids
+    }
+}
+
+/// Opens the journal of another instance unless that instance still holds its lock.
+fn lock_orphan(path: &Path) -> io::Result<Option<File>> {
+    let file = match OpenOptions::new().read(true).write(true).open(path) {
+        Ok(file) => file,
+        // removed by an instance that recovered it first
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err),
+    };
+    match file.try_lock() {
+        Ok(()) => Ok(Some(file)),
+        Err(TryLockError::WouldBlock) => Ok(None),
+        Err(TryLockError::Error(err)) => Err(err),
+    }
+}
+
+/// Skips lines that cannot be parsed, the last one may have been cut by the crash.
+fn read_entries(content: &str) -> Vec<JournalEntry> {
+    content
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect()
+}
+
+/// Entries written after the last save of their document.
+pub fn pending_entries(entries: Vec<JournalEntry>) -> Vec<JournalEntry> {
+    let mut saved = HashSet::new();
+    let mut pending: Vec<JournalEntry> = entries
+        .into_iter()
+        .rev()
+        .filter(|entry| {
+            if entry.op == JournalOp::Saved {
+                saved.insert(entry.doc_id);
+            }
+            !saved.contains(&entry.doc_id)
+        })
+        .collect();
+    pending.reverse();
+    pending
+}
+
+/// Replays the entries of `doc` on top of the stored document, returns how many were applied.
+pub fn replay(
+    doc: &mut Doc,
+    entries: &[JournalEntry],
+    images: &mut HashMap<String, String>,
+    history_depth: usize,
+) -> usize {
+    let mut applied = 0;
+    for entry in entries.iter().filter(|entry| entry.doc_id == doc.id) {
+        let tab_id = match &entry.op {
+            JournalOp::Saved => continue,
+            JournalOp::RenameDoc { name } => {
+                doc.name = name.clone();
+                applied += 1;
+                continue;
+            }
+            JournalOp::SetTags { tags } => {
+                doc.tags = tags.clone();
+                applied += 1;
+                continue;
+            }
+            JournalOp::AddTab { tab_id, name } => {
+                if !doc.tabs.iter().any(|tab| tab.id == *tab_id) {
+                    doc.tabs.iter_mut().for_each(|tab| tab.is_active = false);
+                    doc.tabs.push(Tab {
+                        id: *tab_id,
+                        name: name.clone(),
+                        is_active: true,
+                        z_index: 1.,
+                        ..Default::default()
+                    });
+                }
+                applied += 1;
+                continue;
+            }
+            JournalOp::Checkpoint { tab_id, .. }
+            | JournalOp::Undo { tab_id }
+            | JournalOp::Redo { tab_id }
+            | JournalOp::RenameTab { tab_id, .. }
+            | JournalOp::DeleteTab { tab_id } => *tab_id,
+        };
+        let Some(index) = doc.tabs.iter().position(|tab| tab.id == tab_id) else {
+            warn!("Journal refers to missing tab {}", tab_id.0);
+            continue;
+        };
+        let tab = &mut doc.tabs[index];
+        match &entry.op {
+            JournalOp::Checkpoint {
+                timestamp,
+                delta,
+                images: new_images,
+                ..
+            } => {
+                let mut snapshot = tab.history.head().clone();
+                delta.apply(&mut snapshot);
+                images.extend(new_images.clone());
+                tab.history.push(snapshot, *timestamp, history_depth);
+            }
+            JournalOp::Undo { .. } => {
+                tab.history.undo();
+            }
+            JournalOp::Redo { .. } => {
+                tab.history.redo();
+            }
+            JournalOp::RenameTab { name, .. } => {
+                tab.name = name.clone();
+            }
+            JournalOp::DeleteTab { .. } => {
+                // the app keeps at least one tab
+                if doc.tabs.len() < 2 {
+                    continue;
+                }
+                let removed = doc.tabs.remove(index);
+                if removed.is_active {
+                    doc.tabs.last_mut().unwrap().is_active = true;
+                }
+            }
+            _ => {}
+        }
+        applied += 1;
+    }
+    applied
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::{Tab, TabSnapshot};
+    use crate::history::TabHistory;
+    use crate::{JsonNode, JsonNodeText, TextPos};
+    use tempfile::tempdir;
+    use uuid::Uuid;
+
+    fn node(id: Uuid, x: f32) -> JsonNode<String> {
+        JsonNode {
+            id,
+            node_type: crate::NodeType::Rect,
+            x,
+            y: 0.,
+            width: 10.,
+            height: 10.,
+            text: JsonNodeText {
+                text: "".to_string(),
+                pos: TextPos::Center,
+            },
+            bg_color: "white".to_string(),
+            z: 1.,
+            visible: true,
+        }
+    }
+
+    fn checkpoint(tab_id: ReflectableUuid, old: &TabSnapshot, new: &TabSnapshot) -> JournalOp {
+        JournalOp::Checkpoint {
+            tab_id,
+            timestamp: 1.,
+            delta: Box::new(SnapshotDelta::between(old, new)),
+            images: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_pending_entries_skip_saved_docs() {
+        let saved_doc = ReflectableUuid::generate();
+        let crashed_doc = ReflectableUuid::generate();
+        let tab_id = ReflectableUuid::generate();
+        let entries = vec![
+            JournalEntry {
+                doc_id: saved_doc,
+                op: JournalOp::Undo { tab_id },
+            },
+            JournalEntry {
+                doc_id: crashed_doc,
+                op: JournalOp::Undo { tab_id },
+            },
+            JournalEntry {
+                doc_id: saved_doc,
+                op: JournalOp::Saved,
+            },
+            JournalEntry {
+                doc_id: crashed_doc,
+                op: JournalOp::Redo { tab_id },
+            },
+        ];
+        let pending = pending_entries(entries.clone());
+        assert_eq!(pending, vec![entries[1].clone(), entries[3].clone()]);
+    }
+
+    fn journal_files(dir: &Path) -> Vec<PathBuf> {
+        fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect()
+    }
+
+    #[test]
+    fn test_reopen_after_crash() {
+        let dir = tempdir().unwrap();
+        let doc_id = ReflectableUuid::generate();
+        let tab_id = ReflectableUuid::generate();
+        let (mut journal, pending) = Journal::open(dir.path()).unwrap();
+        assert!(pending.is_empty());
+        journal.append(doc_id, JournalOp::Undo { tab_id });
+        let path = journal.path.clone();
+        // a crash releases the lock but leaves the file behind
+        drop(journal);
+        // and can leave half of the last line behind
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        write!(file, "{{\"doc_id\":").unwrap();
+
+        let (journal, pending) = Journal::open(dir.path()).unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].op, JournalOp::Undo { tab_id });
+        assert_eq!(journal_files(dir.path()), vec![journal.path.clone()]);
+
+        // the recovered entries survive another crash
+        drop(journal);
+        let (journal, pending) = Journal::open(dir.path()).unwrap();
+        assert_eq!(pending.len(), 1);
+        journal.remove();
+        assert!(journal_files(dir.path()).is_empty());
+    }
+
+    #[test]
+    fn test_running_instance_keeps_its_journal() {
+        let dir = tempdir().unwrap();
+        let doc_id = ReflectableUuid::generate();
+        let tab_id = ReflectableUuid::generate();
+        let (mut first, _) = Journal::open(dir.path()).unwrap();
+        first.append(doc_id, JournalOp::Redo { tab_id });
+
+        let (second, pending) = Journal::open(dir.path()).unwrap();
+        assert!(pending.is_empty());
+        assert_eq!(journal_files(dir.path()).len(), 2);
+        second.remove();
+
+        drop(first);
+        let (_, pending) = Journal::open(dir.path()).unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].op, JournalOp::Redo { tab_id });
+    }
+
+    #[test]
+    fn test_replay() {
+        let tab_id = ReflectableUuid::generate();
+        let node_id = Uuid::new_v4();
+        let empty = TabSnapshot::default();
+        let mut moved = TabSnapshot::default();
+        moved.nodes.push(node(node_id, 0.));
+        let mut doc = Doc {
+            id: ReflectableUuid::generate(),
+            tabs: vec![Tab {
+                id: tab_id,
+                history: TabHistory::new(empty.clone(), 0.),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let mut again = moved.clone();
+        again.nodes[0].x = 100.;
+        let entries = vec![
+            JournalEntry {
+                doc_id: doc.id,
+                op: checkpoint(tab_id, &empty, &moved),
+            },
+            JournalEntry {
+                doc_id: doc.id,
+                op: checkpoint(tab_id, &moved, &again),
+            },
+            JournalEntry {
+                doc_id: doc.id,
+                op: JournalOp::Undo { tab_id },
+            },
+            JournalEntry {
+                doc_id: ReflectableUuid::generate(),
+                op: JournalOp::Redo { tab_id },
+            },
+        ];
+
+        let applied = replay(&mut doc, &entries, &mut HashMap::new(), 10);
+
+        assert_eq!(applied, 3);
+        assert_eq!(doc.tabs[0].history.head(), &moved);
+        assert!(doc.tabs[0].history.redo());
+        assert_eq!(doc.tabs[0].history.head(), &again);
+    }
+
+    #[test]
+    fn test_replay_tabs_names_and_tags() {
+        let first = ReflectableUuid::generate();
+        let second = ReflectableUuid::generate();
+        let mut doc = Doc {
+            id: ReflectableUuid::generate(),
+            name: "doc".to_string(),
+            tabs: vec![Tab {
+                id: first,
+                name: "Tab 1".to_string(),
+                is_active: true,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let ops = vec![
+            JournalOp::AddTab {
+                tab_id: second,
+                name: "Tab 2".to_string(),
+            },
+            JournalOp::RenameTab {
+                tab_id: second,
+                name: "ideas".to_string(),
+            },
+            JournalOp::DeleteTab { tab_id: first },
+            // the last tab stays
+            JournalOp::DeleteTab { tab_id: second },
+            JournalOp::RenameDoc {
+                name: "plans".to_string(),
+            },
+            JournalOp::SetTags {
+                tags: vec!["work".to_string()],
+            },
+        ];
+        let entries: Vec<JournalEntry> = ops
+            .into_iter()
+            .map(|op| JournalEntry { doc_id: doc.id, op })
+            .collect();
+
+        replay(&mut doc, &entries, &mut HashMap::new(), 10);
+
+        let tabs: Vec<(ReflectableUuid, &str, bool)> = doc
+            .tabs
+            .iter()
+            .map(|tab| (tab.id, tab.name.as_str(), tab.is_active))
+            .collect();
+        assert_eq!(tabs, vec![(second, "ideas", true)]);
+        assert_eq!(doc.name, "plans");
+        assert_eq!(doc.tags, vec!["work"]);
+    }
+}
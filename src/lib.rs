@@ -1,10 +1,21 @@
+mod bundle;
 mod canvas;
+pub mod cli;
 mod components;
+mod doc_cache;
+mod formats;
+mod history;
+mod journal;
+mod migrations;
+mod notifications;
 mod resources;
+mod store;
 mod systems;
+mod tags;
 mod themes;
 mod ui_plugin;
 mod utils;
+mod workspace;
 
 use bevy::{prelude::*, window::PresentMode};
 use bevy_cosmic_edit::CosmicEditPlugin;
@@ -12,7 +23,6 @@ use bevy_embedded_assets::EmbeddedAssetPlugin;
 #[cfg(not(target_arch = "wasm32"))]
 use bevy_hanabi::HanabiPlugin;
 use bevy_pancam::PanCamPlugin;
-use bevy_pkv::PkvStore;
 use canvas::CanvasPlugin;
 use resources::FontSystemState;
 use systems::*;
@@ -47,7 +57,7 @@ impl Plugin for VeloPlugin {
             .add_plugins(CanvasPlugin)
             .add_plugins(UiPlugin)
             .add_plugins(PanCamPlugin)
-            .insert_resource(PkvStore::new(ORG_NAME, APP_NAME))
+            .insert_resource(store::DocStore::from_config(ORG_NAME, APP_NAME))
             .init_resource::<FontSystemState>();
 
         #[cfg(not(target_arch = "wasm32"))]
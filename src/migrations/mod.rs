@@ -0,0 +1,393 @@
+use base64::{engine::general_purpose, Engine};
+use serde_json::{json, Map, Value};
+use std::collections::{BTreeSet, VecDeque};
+use std::fmt;
+
+use crate::components::{Doc, TabSnapshot};
+use crate::formats::ThemeColors;
+use crate::history::TabHistory;
+use crate::themes::velo_light;
+use crate::utils::image_hash;
+
+/// Version written into every document saved by this build.
+/// Bump it together with a new entry in `MIGRATIONS`.
+pub const CURRENT_SCHEMA_VERSION: u32 = 1;
+
+type Migration = fn(&mut Doc) -> Result<(), MigrationError>;
+
+/// `MIGRATIONS[n]` upgrades a document from version `n` to `n + 1`.
+const MIGRATIONS: [Migration; CURRENT_SCHEMA_VERSION as usize] = [v0_to_v1];
+
+#[derive(Debug)]
+pub enum MigrationError {
+    Json(serde_json::Error),
+    UnsupportedVersion(u32),
+    InvalidCheckpoint { tab_id: String, reason: String },
+    InvalidImage { node_id: String, reason: String },
+}
+
+impl fmt::Display for MigrationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MigrationError::Json(err) => write!(f, "invalid document json: {}", err),
+            MigrationError::UnsupportedVersion(version) => write!(
+                f,
+                "document schema version {} is newer than supported version {}",
+                version, CURRENT_SCHEMA_VERSION
+            ),
+            MigrationError::InvalidCheckpoint { tab_id, reason } => {
+                write!(f, "invalid checkpoint in tab {}: {}", tab_id, reason)
+            }
+            MigrationError::InvalidImage { node_id, reason } => {
+                write!(f, "invalid image of node {}: {}", node_id, reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for MigrationError {}
+
+impl From<serde_json::Error> for MigrationError {
+    fn from(err: serde_json::Error) -> Self {
+        MigrationError::Json(err)
+    }
+}
+
+/// Upgrades `doc` step by step until it reaches `CURRENT_SCHEMA_VERSION`,
+/// then repairs it with `repair_doc`. Returns the repairs made.
+pub fn migrate_doc(doc: &mut Doc) -> Result<Vec<String>, MigrationError> {
+    if doc.schema_version > CURRENT_SCHEMA_VERSION {
+        return Err(MigrationError::UnsupportedVersion(doc.schema_version));
+    }
+    while doc.schema_version < CURRENT_SCHEMA_VERSION {
+        MIGRATIONS[doc.schema_version as usize](doc)?;
+        doc.schema_version += 1;
+    }
+    Ok(repair_doc(doc))
+}
+
+/// Fixes what the app would trip over in a document of any version: images
+/// that are not PNGs, color keys the themes do not have, a `z_index` below the
+/// notes of its tab and anything but one active tab. Returns what was fixed.
+pub fn repair_doc(doc: &mut Doc) -> Vec<String> {
+    let mut repairs = vec![];
+    let mut broken: Vec<String> = doc
+        .images
+        .iter()
+        .filter(|(_, image)| {
+            !general_purpose::STANDARD
+                .decode(image.as_bytes())
+                .is_ok_and(|png| png.starts_with(b"\x89PNG"))
+        })
+        .map(|(hash, _)| hash.clone())
+        .collect();
+    broken.sort();
+    for hash in broken {
+        // nodes without their image load with a plain background
+        doc.images.remove(&hash);
+        repairs.push(format!("image {} is not a png", hash));
+    }
+
+    let colors = ThemeColors::new(&velo_light());
+    for tab in doc.tabs.iter_mut() {
+        let mut unknown = BTreeSet::new();
+        tab.history.edit_all(|nodes, drawings, _| {
+            for node in nodes.iter_mut() {
+                if colors.get(&node.bg_color).is_none() {
+                    unknown.insert(std::mem::replace(&mut node.bg_color, "node_bg".to_string()));
+                }
+            }
+            for drawing in drawings.iter_mut() {
+                if colors.get(&drawing.drawing_color).is_none() {
+                    unknown.insert(std::mem::replace(
+                        &mut drawing.drawing_color,
+                        "drawing_pencil_btn".to_string(),
+                    ));
+                }
+            }
+        });
+        for key in unknown {
+            repairs.push(format!("tab {}: unknown color {}", tab.name, key));
+        }
+        let head = tab.history.head();
+        let max_z = head
+            .nodes
+            .iter()
+            .map(|node| node.z)
+            .chain(head.drawings.iter().map(|drawing| drawing.z))
+            .fold(1., f32::max);
+        tab.z_index = tab.z_index.max(max_z);
+    }
+
+    let active = doc.tabs.iter().filter(|tab| tab.is_active).count();
+    if active != 1 && !doc.tabs.is_empty() {
+        repairs.push(format!("{} active tabs instead of one", active));
+        let first_active = doc.tabs.iter().position(|tab| tab.is_active).unwrap_or(0);
+        for (i, tab) in doc.tabs.iter_mut().enumerate() {
+            tab.is_active = i == first_active;
+        }
+    }
+    repairs
+}
+
+/// Parses a document exported to json (file, url, gist) and migrates it.
+pub fn doc_from_json(json: &str) -> Result<Doc, MigrationError> {
+    let mut doc: Doc = serde_json::from_str(json)?;
+    migrate_doc(&mut doc)?;
+    Ok(doc)
+}
+
+/// v0: documents saved before schema versioning, with checkpoints as json
+/// strings that embed base64 PNGs and may miss keys added over time. Tabs may
+/// miss `z_index`.
+///
+/// v1 keeps a tab history of typed snapshots and a single copy of every image
+/// in `Doc::images`, referenced by hash.
+fn v0_to_v1(doc: &mut Doc) -> Result<(), MigrationError> {
+    for tab in doc.tabs.iter_mut() {
+        let mut max_z: f32 = 1.;
+        let mut snapshots = VecDeque::new();
+        for checkpoint in std::mem::take(&mut tab.legacy_checkpoints) {
+            let mut snapshot = legacy_checkpoint_to_snapshot(&checkpoint).map_err(|reason| {
+                MigrationError::InvalidCheckpoint {
+                    tab_id: tab.id.0.to_string(),
+                    reason,
+                }
+            })?;
+            for node in snapshot.nodes.iter() {
+                max_z = max_z.max(node.z);
+            }
+            for (node_id, image) in snapshot.images.iter_mut() {
+                let png = general_purpose::STANDARD
+                    .decode(image.as_bytes())
+                    .map_err(|err| MigrationError::InvalidImage {
+                        node_id: node_id.to_string(),
+                        reason: err.to_string(),
+                    })?;
+                let hash = image_hash(&png);
+                let base64 = std::mem::replace(image, hash.clone());
+                doc.images.entry(hash).or_insert(base64);
+            }
+            if snapshots.back() != Some(&snapshot) {
+                snapshots.push_back(snapshot);
+            }
+        }
+        if tab.z_index < 1. {
+            tab.z_index = max_z;
+        }
+        tab.history = TabHistory::from_snapshots(snapshots);
+    }
+    Ok(())
+}
+
+/// Compatibility path for checkpoints saved as json strings.
+fn legacy_checkpoint_to_snapshot(checkpoint: &str) -> Result<TabSnapshot, String> {
+    let mut value: Value = serde_json::from_str(checkpoint).map_err(|err| err.to_string())?;
+    normalize_legacy_checkpoint(&mut value)?;
+    serde_json::from_value(value).map_err(|err| err.to_string())
+}
+
+/// Fills in checkpoint fields that older versions of Velo did not write.
+fn normalize_legacy_checkpoint(value: &mut Value) -> Result<(), String> {
+    let checkpoint = value
+        .as_object_mut()
+        .ok_or_else(|| "checkpoint is not an object".to_string())?;
+    checkpoint.entry("images").or_insert_with(|| json!({}));
+    for key in ["nodes", "arrows", "drawings"] {
+        checkpoint.entry(key).or_insert_with(|| json!([]));
+        if !checkpoint[key].is_array() {
+            return Err(format!("`{}` is not an array", key));
+        }
+    }
+    for node in checkpoint["nodes"].as_array_mut().unwrap() {
+        let node = as_object(node, "node")?;
+        node.entry("node_type").or_insert_with(|| json!("Rect"));
+        node.entry("visible").or_insert_with(|| json!(true));
+        node.entry("z").or_insert_with(|| json!(1.));
+        let text = node
+            .entry("text")
+            .or_insert_with(|| json!({ "text": "", "pos": "Center" }));
+        if let Some(text_str) = text.as_str() {
+            *text = json!({ "text": text_str, "pos": "Center" });
+        }
+        as_object(text, "node text")?
+            .entry("pos")
+            .or_insert_with(|| json!("Center"));
+    }
+    for arrow in checkpoint["arrows"].as_array_mut().unwrap() {
+        as_object(arrow, "arrow")?
+            .entry("visible")
+            .or_insert_with(|| json!(true));
+    }
+    for drawing in checkpoint["drawings"].as_array_mut().unwrap() {
+        as_object(drawing, "drawing")?
+            .entry("width")
+            .or_insert_with(|| json!(2.));
+    }
+    Ok(())
+}
+
+fn as_object<'a>(value: &'a mut Value, what: &str) -> Result<&'a mut Map<String, Value>, String> {
+    value
+        .as_object_mut()
+        .ok_or_else(|| format!("{} is not an object", what))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::Tab;
+    use crate::utils::ReflectableUuid;
+    use crate::{DrawingJsonNode, JsonNode};
+    use std::collections::HashMap;
+
+    /// Goes through `Value` so that image maps are serialized in a stable order.
+    fn readable(doc: &Doc) -> Value {
+        serde_json::to_value(doc).unwrap()
+    }
+
+    #[test]
+    fn test_migrate_v0_board() {
+        let doc = doc_from_json(include_str!("fixtures/v0_board.json")).unwrap();
+        assert_eq!(doc.schema_version, CURRENT_SCHEMA_VERSION);
+        insta::assert_json_snapshot!(readable(&doc));
+    }
+
+    #[test]
+    fn test_migrate_v0_images() {
+        let doc = doc_from_json(include_str!("fixtures/v0_board.json")).unwrap();
+        assert_eq!(doc.images.len(), 1);
+        let (hash, image) = doc.images.iter().next().unwrap();
+        assert_eq!(
+            hash,
+            &image_hash(&general_purpose::STANDARD.decode(image).unwrap())
+        );
+        let tab = &doc.tabs[2];
+        assert!(tab.legacy_checkpoints.is_empty());
+        // the last two checkpoints are equal and kept once
+        let mut history = tab.history.clone();
+        assert!(history.undo());
+        assert!(!history.undo());
+        let snapshot = tab.history.head();
+        assert_eq!(snapshot.images.values().collect::<Vec<_>>(), vec![hash]);
+    }
+
+    #[test]
+    fn test_migrate_v0_without_active_tab() {
+        let json = r#"{
+            "id": "e2b8fa03-6c5a-4d4f-9b3e-8a7f6e5d4c3b",
+            "name": "No active tab",
+            "tags": [],
+            "tabs": [
+                {"is_active": false, "id": "6b5c4d3e-2f1a-4b0c-9d8e-7f6a5b4c3d2e", "name": "Tab 1", "z_index": 1.0, "checkpoints": []},
+                {"is_active": false, "id": "7c6d5e4f-3a2b-4c1d-8e9f-0a1b2c3d4e5f", "name": "Tab 2", "z_index": 4.5, "checkpoints": []}
+            ]
+        }"#;
+        let doc = doc_from_json(json).unwrap();
+        let active = doc.tabs.iter().map(|tab| tab.is_active).collect::<Vec<_>>();
+        assert_eq!(active, vec![true, false]);
+        assert_eq!(doc.tabs[1].z_index, 4.5);
+    }
+
+    #[test]
+    fn test_migrate_current_version_is_noop() {
+        let doc = doc_from_json(include_str!("fixtures/v0_board.json")).unwrap();
+        let mut migrated_again = doc.clone();
+        assert!(migrate_doc(&mut migrated_again).unwrap().is_empty());
+        assert_eq!(readable(&doc), readable(&migrated_again));
+    }
+
+    #[test]
+    fn test_repair_current_version() {
+        let node_id = uuid::Uuid::new_v4();
+        let snapshot = |bg_color: &str| TabSnapshot {
+            images: HashMap::from([(node_id, "broken".to_string())]),
+            nodes: vec![JsonNode {
+                id: node_id,
+                bg_color: bg_color.to_string(),
+                z: 3.,
+                ..Default::default()
+            }],
+            drawings: vec![DrawingJsonNode {
+                x: 0.,
+                y: 0.,
+                z: 1.,
+                id: ReflectableUuid(node_id),
+                points: vec![],
+                drawing_color: "font_size".to_string(),
+                width: 2.,
+            }],
+            ..Default::default()
+        };
+        let tab = |name: &str| Tab {
+            name: name.to_string(),
+            is_active: true,
+            history: TabHistory::from_snapshots([snapshot("node_bg"), snapshot("purple")]),
+            ..Default::default()
+        };
+        let mut doc = Doc {
+            schema_version: CURRENT_SCHEMA_VERSION,
+            tabs: vec![tab("a"), tab("b")],
+            images: HashMap::from([("broken".to_string(), "not base64!".to_string())]),
+            ..Default::default()
+        };
+        assert_eq!(
+            migrate_doc(&mut doc).unwrap(),
+            vec![
+                "image broken is not a png",
+                "tab a: unknown color font_size",
+                "tab a: unknown color purple",
+                "tab b: unknown color font_size",
+                "tab b: unknown color purple",
+                "2 active tabs instead of one",
+            ]
+        );
+        assert!(doc.images.is_empty());
+        let tab = &doc.tabs[0];
+        assert_eq!(tab.z_index, 3.);
+        assert_eq!(tab.history.head().nodes[0].bg_color, "node_bg");
+        assert_eq!(
+            tab.history.head().drawings[0].drawing_color,
+            "drawing_pencil_btn"
+        );
+        // undo steps are repaired too
+        let mut history = tab.history.clone();
+        assert!(history.undo());
+        assert_eq!(
+            history.head().drawings[0].drawing_color,
+            "drawing_pencil_btn"
+        );
+        let active: Vec<bool> = doc.tabs.iter().map(|tab| tab.is_active).collect();
+        assert_eq!(active, vec![true, false]);
+    }
+
+    #[test]
+    fn test_migrate_rejects_future_version() {
+        let mut doc = Doc {
+            schema_version: CURRENT_SCHEMA_VERSION + 1,
+            ..Default::default()
+        };
+        assert!(matches!(
+            migrate_doc(&mut doc),
+            Err(MigrationError::UnsupportedVersion(_))
+        ));
+    }
+
+    #[test]
+    fn test_migrate_rejects_broken_checkpoint() {
+        let json = r#"{
+            "id": "6a0f1b9e-6f6b-4a86-9a43-1c3a2b6c1f00",
+            "name": "broken",
+            "tabs": [{
+                "id": "0c6d3c1e-3d0a-4c53-8f0e-5a1d7a6f2b11",
+                "name": "Tab 1",
+                "is_active": true,
+                "checkpoints": ["{\"nodes\": 1}"]
+            }]
+        }"#;
+        assert!(matches!(
+            doc_from_json(json),
+            Err(MigrationError::InvalidCheckpoint { .. })
+        ));
+    }
+}
@@ -0,0 +1,119 @@
+use async_channel::{Receiver, Sender};
+use bevy::prelude::*;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+use crate::bundle::BundleError;
+use crate::formats::FormatError;
+use crate::migrations::MigrationError;
+use crate::store::StoreError;
+use crate::utils::ReflectableUuid;
+
+/// Errors of actions started by the user, shown as a toast instead of crashing the app.
+#[derive(Debug)]
+pub enum VeloError {
+    ReadFile { path: PathBuf, err: io::Error },
+    WriteFile { path: PathBuf, err: io::Error },
+    Json(serde_json::Error),
+    Import(MigrationError),
+    Store(StoreError),
+    DocNotFound(ReflectableUuid),
+    MissingImages(usize),
+    Fetch(String),
+    Share(String),
+    Clipboard(String),
+    Export(String),
+    ImportTab(FormatError),
+    Bundle(BundleError),
+    Image(String),
+    UnknownColor(String),
+}
+
+impl fmt::Display for VeloError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VeloError::ReadFile { path, err } => {
+                write!(f, "could not read {}: {}", path.display(), err)
+            }
+            VeloError::WriteFile { path, err } => {
+                write!(f, "could not write {}: {}", path.display(), err)
+            }
+            VeloError::Json(err) => write!(f, "could not serialize document: {}", err),
+            VeloError::Import(err) => write!(f, "could not import document: {}", err),
+            VeloError::Store(err) => write!(f, "storage error: {}", err),
+            VeloError::DocNotFound(doc_id) => write!(f, "document {} not found", doc_id.0),
+            VeloError::MissingImages(count) => {
+                write!(f, "{} image(s) of the document are missing", count)
+            }
+            VeloError::Fetch(err) => write!(f, "could not fetch document: {}", err),
+            VeloError::Share(err) => write!(f, "could not share document: {}", err),
+            VeloError::Clipboard(err) => write!(f, "could not access clipboard: {}", err),
+            VeloError::Export(err) => write!(f, "could not export tab: {}", err),
+            VeloError::ImportTab(err) => write!(f, "could not import into tab: {}", err),
+            VeloError::Bundle(err) => write!(f, "could not read or write bundle: {}", err),
+            VeloError::Image(err) => write!(f, "could not decode image: {}", err),
+            VeloError::UnknownColor(key) => {
+                write!(f, "unknown color {}, using the default one", key)
+            }
+        }
+    }
+}
+
+impl std::error::Error for VeloError {}
+
+impl From<serde_json::Error> for VeloError {
+    fn from(err: serde_json::Error) -> Self {
+        VeloError::Json(err)
+    }
+}
+
+impl From<FormatError> for VeloError {
+    fn from(err: FormatError) -> Self {
+        VeloError::ImportTab(err)
+    }
+}
+
+impl From<BundleError> for VeloError {
+    fn from(err: BundleError) -> Self {
+        VeloError::Bundle(err)
+    }
+}
+
+impl From<MigrationError> for VeloError {
+    fn from(err: MigrationError) -> Self {
+        VeloError::Import(err)
+    }
+}
+
+impl From<StoreError> for VeloError {
+    fn from(err: StoreError) -> Self {
+        VeloError::Store(err)
+    }
+}
+
+#[derive(Event, Debug)]
+pub enum Notification {
+    Info(String),
+    Error(VeloError),
+}
+
+impl From<VeloError> for Notification {
+    fn from(err: VeloError) -> Self {
+        Notification::Error(err)
+    }
+}
+
+/// Lets callbacks running outside of bevy, like http requests, send notifications.
+#[derive(Resource, Clone)]
+pub struct NotificationChannel {
+    pub tx: Sender<Notification>,
+    pub rx: Receiver<Notification>,
+}
+
+impl Default for NotificationChannel {
+    fn default() -> Self {
+        let (tx, rx) = async_channel::unbounded();
+        NotificationChannel { tx, rx }
+    }
+}
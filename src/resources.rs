@@ -1,7 +1,9 @@
-use crate::components::Doc;
+use crate::doc_cache::DocCache;
+use crate::formats::outline::OutlineStyle;
 #[cfg(not(target_arch = "wasm32"))]
 use crate::ui_plugin::SearchIndexState;
 use crate::utils::ReflectableUuid;
+use crate::{DEFAULT_AUTOSAVE_DELAY, DEFAULT_HISTORY_DEPTH};
 use bevy::prelude::*;
 use bevy_cosmic_edit::CosmicFont;
 use std::collections::{HashMap, HashSet};
@@ -10,11 +12,17 @@ use std::path::PathBuf;
 #[derive(Resource, Default)]
 pub struct AppState {
     pub current_document: Option<ReflectableUuid>,
-    pub docs: HashMap<ReflectableUuid, Doc>,
+    pub docs: DocCache,
     pub github_token: Option<String>,
     #[cfg(not(target_arch = "wasm32"))]
     pub search_index: Option<SearchIndexState>,
     pub doc_list_ui: HashSet<ReflectableUuid>,
+    /// Base64 encoded PNGs keyed by hash, shared by all loaded documents.
+    pub images: HashMap<String, String>,
+    /// Tags of the stored documents, kept for the doc list filter and tag autocomplete.
+    pub tags: HashMap<ReflectableUuid, Vec<String>>,
+    /// Only documents with all of these tags are listed.
+    pub tag_filter: Vec<String>,
 }
 
 #[derive(Resource, Debug)]
@@ -29,6 +37,121 @@ pub struct SaveTabRequest {
     pub tab_id: ReflectableUuid,
 }
 
+#[derive(Resource, Debug)]
+pub struct ExportTabRequest {
+    pub doc_id: ReflectableUuid,
+    pub tab_id: ReflectableUuid,
+    pub target: ExportTarget,
+    pub format: ExportFormat,
+    pub png: PngExport,
+}
+
+/// PNG options chosen in the export modal, the scale follows the screen.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PngExport {
+    /// Transparent instead of the canvas background.
+    pub transparent: bool,
+    /// Render only the area of this note.
+    pub node: Option<ReflectableUuid>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExportTarget {
+    File(PathBuf),
+    /// Only for text formats.
+    Clipboard,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Svg,
+    Png,
+    Markdown(OutlineStyle),
+    Mermaid,
+    Dot,
+    JsonCanvas,
+    Opml,
+    /// The whole document as an offline viewer page.
+    Html,
+}
+
+impl ExportFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Svg => "svg",
+            ExportFormat::Png => "png",
+            ExportFormat::Markdown(_) => "md",
+            ExportFormat::Mermaid => "mmd",
+            ExportFormat::Dot => "dot",
+            ExportFormat::JsonCanvas => "canvas",
+            ExportFormat::Opml => "opml",
+            ExportFormat::Html => "html",
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ExportFormat::Svg => "SVG",
+            ExportFormat::Png => "PNG",
+            ExportFormat::Markdown(_) => "Markdown outline",
+            ExportFormat::Mermaid => "Mermaid",
+            ExportFormat::Dot => "Graphviz DOT",
+            ExportFormat::JsonCanvas => "JSON Canvas",
+            ExportFormat::Opml => "OPML",
+            ExportFormat::Html => "HTML viewer",
+        }
+    }
+}
+
+#[derive(Resource, Debug)]
+pub struct ImportTabRequest {
+    pub source: ImportSource,
+    pub format: ImportFormat,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImportSource {
+    File(PathBuf),
+    Clipboard,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportFormat {
+    MarkdownOutline,
+    Mermaid,
+    Dot,
+    JsonCanvas,
+    Excalidraw,
+    Opml,
+    Csv,
+}
+
+impl ImportFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImportFormat::MarkdownOutline => "md",
+            ImportFormat::Mermaid => "mmd",
+            ImportFormat::Dot => "dot",
+            ImportFormat::JsonCanvas => "canvas",
+            ImportFormat::Excalidraw => "excalidraw",
+            ImportFormat::Opml => "opml",
+            ImportFormat::Csv => "csv",
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ImportFormat::MarkdownOutline => "Markdown outline",
+            ImportFormat::Mermaid => "Mermaid flowchart",
+            ImportFormat::Dot => "Graphviz DOT",
+            ImportFormat::JsonCanvas => "JSON Canvas",
+            ImportFormat::Excalidraw => "Excalidraw scene",
+            ImportFormat::Opml => "OPML outline",
+            ImportFormat::Csv => "CSV table",
+        }
+    }
+}
+
 #[derive(Resource, Debug)]
 pub struct LoadDocRequest {
     pub doc_id: ReflectableUuid,
@@ -38,8 +161,49 @@ pub struct LoadDocRequest {
 pub struct LoadTabRequest {
     pub doc_id: ReflectableUuid,
     pub tab_id: ReflectableUuid,
-    pub drop_last_checkpoint: bool, // Useful for undo functionality
+    pub step: CheckpointStep,
+}
+
+/// Which checkpoint `load_tab` should restore.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum CheckpointStep {
+    #[default]
+    Current,
+    /// Moves the latest checkpoint to the tab's redo stack.
+    Undo,
+    /// Moves the latest redo checkpoint back to the tab's checkpoints.
+    Redo,
 }
 
 #[derive(Resource, Default)]
 pub struct FontSystemState(pub Option<Handle<CosmicFont>>);
+
+/// How many undo steps every tab keeps, `history_depth` in the native config.
+#[derive(Resource, Debug)]
+pub struct HistoryDepth(pub usize);
+
+impl Default for HistoryDepth {
+    fn default() -> Self {
+        HistoryDepth(DEFAULT_HISTORY_DEPTH)
+    }
+}
+
+/// Tracks edits of the current document so it can be saved once editing stops.
+#[derive(Resource, Debug)]
+pub struct Autosave {
+    /// Seconds without changes before saving, `autosave_delay` in the native config.
+    /// Zero or less disables autosave.
+    pub delay: f64,
+    pub unsaved: bool,
+    pub last_change: f64,
+}
+
+impl Default for Autosave {
+    fn default() -> Self {
+        Autosave {
+            delay: DEFAULT_AUTOSAVE_DELAY,
+            unsaved: false,
+            last_change: 0.,
+        }
+    }
+}
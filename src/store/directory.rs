@@ -0,0 +1,70 @@
+use bevy_pkv::PkvStore;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use super::{DocumentStore, StoreError};
+use crate::components::Doc;
+use crate::utils::{ReflectableUuid, UserPreferences};
+use crate::workspace::Workspace;
+
+/// Documents in a workspace folder. The last opened document and the preferences
+/// belong to this machine rather than to the workspace, so they stay in pkv.
+pub struct DirectoryStore {
+    workspace: Workspace,
+    local: PkvStore,
+}
+
+impl DirectoryStore {
+    pub fn new(root: impl Into<PathBuf>, local: PkvStore) -> Self {
+        DirectoryStore {
+            workspace: Workspace::new(root),
+            local,
+        }
+    }
+}
+
+impl DocumentStore for DirectoryStore {
+    fn doc_names(&self) -> Result<HashMap<ReflectableUuid, String>, StoreError> {
+        Ok(self.workspace.names()?)
+    }
+
+    fn load_doc(&self, doc_id: ReflectableUuid) -> Result<Option<Doc>, StoreError> {
+        Ok(self.workspace.load_doc(doc_id)?)
+    }
+
+    fn save_doc(&mut self, doc: &Doc, images: &HashMap<String, String>) -> Result<(), StoreError> {
+        self.workspace.save_doc(doc, images)?;
+        Ok(())
+    }
+
+    fn delete_doc(&mut self, doc_id: ReflectableUuid) -> Result<(), StoreError> {
+        Ok(self.workspace.remove_doc(doc_id)?)
+    }
+
+    fn tags(&self) -> Result<HashMap<ReflectableUuid, Vec<String>>, StoreError> {
+        Ok(self.workspace.tags()?)
+    }
+
+    fn load_image(&self, hash: &str) -> Option<String> {
+        self.workspace.load_image(hash)
+    }
+
+    fn last_saved(&self) -> Option<ReflectableUuid> {
+        self.local
+            .get("last_saved")
+            .ok()
+            .filter(|doc_id| self.workspace.contains(*doc_id))
+    }
+
+    fn set_last_saved(&mut self, doc_id: ReflectableUuid) -> Result<(), StoreError> {
+        Ok(self.local.set("last_saved", &doc_id)?)
+    }
+
+    fn preferences(&self) -> Option<UserPreferences> {
+        self.local.get("user_preferences").ok()
+    }
+
+    fn set_preferences(&mut self, preferences: &UserPreferences) -> Result<(), StoreError> {
+        Ok(self.local.set("user_preferences", preferences)?)
+    }
+}
@@ -0,0 +1,101 @@
+use std::collections::{HashMap, HashSet};
+
+use super::{DocumentStore, StoreError};
+use crate::components::Doc;
+use crate::migrations::migrate_doc;
+use crate::utils::{doc_image_hashes, ReflectableUuid, UserPreferences};
+
+/// Keeps everything in memory and loses it on exit, `store = "memory"` in the native config.
+/// Lets tests run the ECS systems without touching the pkv location.
+#[derive(Default)]
+pub struct MemoryStore {
+    docs: HashMap<ReflectableUuid, Doc>,
+    images: HashMap<String, String>,
+    last_saved: Option<ReflectableUuid>,
+    preferences: Option<UserPreferences>,
+}
+
+impl MemoryStore {
+    fn remove_unused_images(&mut self, candidates: HashSet<String>) {
+        if candidates.is_empty() {
+            return;
+        }
+        let used: HashSet<String> = self.docs.values().flat_map(doc_image_hashes).collect();
+        self.images
+            .retain(|hash, _| !candidates.contains(hash) || used.contains(hash));
+    }
+}
+
+impl DocumentStore for MemoryStore {
+    fn doc_names(&self) -> Result<HashMap<ReflectableUuid, String>, StoreError> {
+        Ok(self
+            .docs
+            .iter()
+            .map(|(id, doc)| (*id, doc.name.clone()))
+            .collect())
+    }
+
+    fn load_doc(&self, doc_id: ReflectableUuid) -> Result<Option<Doc>, StoreError> {
+        let Some(mut doc) = self.docs.get(&doc_id).cloned() else {
+            return Ok(None);
+        };
+        migrate_doc(&mut doc)?;
+        Ok(Some(doc))
+    }
+
+    fn save_doc(&mut self, doc: &Doc, images: &HashMap<String, String>) -> Result<(), StoreError> {
+        let hashes = doc_image_hashes(doc);
+        for hash in hashes.iter() {
+            if let Some(image) = images.get(hash) {
+                self.images
+                    .entry(hash.clone())
+                    .or_insert_with(|| image.clone());
+            }
+        }
+        if let Some(previous) = self.docs.insert(doc.id, doc.clone()) {
+            let dropped = doc_image_hashes(&previous)
+                .difference(&hashes)
+                .cloned()
+                .collect();
+            self.remove_unused_images(dropped);
+        }
+        Ok(())
+    }
+
+    fn delete_doc(&mut self, doc_id: ReflectableUuid) -> Result<(), StoreError> {
+        if let Some(doc) = self.docs.remove(&doc_id) {
+            self.remove_unused_images(doc_image_hashes(&doc));
+        }
+        Ok(())
+    }
+
+    fn tags(&self) -> Result<HashMap<ReflectableUuid, Vec<String>>, StoreError> {
+        Ok(self
+            .docs
+            .iter()
+            .map(|(id, doc)| (*id, doc.tags.clone()))
+            .collect())
+    }
+
+    fn load_image(&self, hash: &str) -> Option<String> {
+        self.images.get(hash).cloned()
+    }
+
+    fn last_saved(&self) -> Option<ReflectableUuid> {
+        self.last_saved
+    }
+
+    fn set_last_saved(&mut self, doc_id: ReflectableUuid) -> Result<(), StoreError> {
+        self.last_saved = Some(doc_id);
+        Ok(())
+    }
+
+    fn preferences(&self) -> Option<UserPreferences> {
+        self.preferences.clone()
+    }
+
+    fn set_preferences(&mut self, preferences: &UserPreferences) -> Result<(), StoreError> {
+        self.preferences = Some(preferences.clone());
+        Ok(())
+    }
+}
@@ -0,0 +1,168 @@
+use bevy::prelude::*;
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+
+use crate::components::Doc;
+use crate::migrations::MigrationError;
+use crate::utils::{ReflectableUuid, UserPreferences};
+use crate::workspace::WorkspaceError;
+
+mod directory;
+mod memory;
+mod pkv;
+
+pub use directory::DirectoryStore;
+pub use memory::MemoryStore;
+pub use pkv::PkvDocumentStore;
+
+/// Where documents are persisted, every system that reads or writes stored documents goes through it.
+pub trait DocumentStore: Send + Sync {
+    /// Names of all stored documents.
+    fn doc_names(&self) -> Result<HashMap<ReflectableUuid, String>, StoreError>;
+
+    /// Reads and migrates a document, `None` when it is not stored.
+    fn load_doc(&self, doc_id: ReflectableUuid) -> Result<Option<Doc>, StoreError>;
+
+    /// Writes a document together with its name, tags and the images it references.
+    /// Images the previous version referenced are dropped unless another document uses them.
+    fn save_doc(&mut self, doc: &Doc, images: &HashMap<String, String>) -> Result<(), StoreError>;
+
+    /// Removes a document and the images no other document uses.
+    fn delete_doc(&mut self, doc_id: ReflectableUuid) -> Result<(), StoreError>;
+
+    fn tags(&self) -> Result<HashMap<ReflectableUuid, Vec<String>>, StoreError>;
+
+    /// Base64 encoded PNG, the format used by `AppState::images`.
+    fn load_image(&self, hash: &str) -> Option<String>;
+
+    /// The stored images among `hashes`, keyed by hash. Stores that keep all
+    /// images in one value override it to read that value once.
+    fn load_images(&self, hashes: &[String]) -> HashMap<String, String> {
+        hashes
+            .iter()
+            .filter_map(|hash| Some((hash.clone(), self.load_image(hash)?)))
+            .collect()
+    }
+
+    /// Document opened on start.
+    fn last_saved(&self) -> Option<ReflectableUuid>;
+
+    fn set_last_saved(&mut self, doc_id: ReflectableUuid) -> Result<(), StoreError>;
+
+    fn preferences(&self) -> Option<UserPreferences>;
+
+    fn set_preferences(&mut self, preferences: &UserPreferences) -> Result<(), StoreError>;
+}
+
+#[derive(Debug)]
+pub enum StoreError {
+    Pkv(String),
+    Workspace(WorkspaceError),
+    Migration(MigrationError),
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StoreError::Pkv(err) => write!(f, "pkv: {}", err),
+            StoreError::Workspace(err) => write!(f, "{}", err),
+            StoreError::Migration(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for StoreError {}
+
+impl From<WorkspaceError> for StoreError {
+    fn from(err: WorkspaceError) -> Self {
+        StoreError::Workspace(err)
+    }
+}
+
+impl From<MigrationError> for StoreError {
+    fn from(err: MigrationError) -> Self {
+        StoreError::Migration(err)
+    }
+}
+
+impl From<bevy_pkv::GetError> for StoreError {
+    fn from(err: bevy_pkv::GetError) -> Self {
+        StoreError::Pkv(err.to_string())
+    }
+}
+
+impl From<bevy_pkv::SetError> for StoreError {
+    fn from(err: bevy_pkv::SetError) -> Self {
+        StoreError::Pkv(err.to_string())
+    }
+}
+
+/// `store` in the native config.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StoreKind {
+    Pkv,
+    Directory,
+    Memory,
+}
+
+impl StoreKind {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "pkv" => Some(StoreKind::Pkv),
+            "directory" => Some(StoreKind::Directory),
+            "memory" => Some(StoreKind::Memory),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct DocStore(Box<dyn DocumentStore>);
+
+impl DocStore {
+    pub fn new(store: impl DocumentStore + 'static) -> Self {
+        DocStore(Box::new(store))
+    }
+
+    /// Picks the store configured in `~/.velo.toml`, pkv when nothing is configured.
+    pub fn from_config(organization: &str, application: &str) -> Self {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(config) = crate::utils::read_config_file() {
+            let kind = config.store.unwrap_or(if config.workspace.is_some() {
+                StoreKind::Directory
+            } else {
+                StoreKind::Pkv
+            });
+            match (kind, config.workspace) {
+                (StoreKind::Pkv, _) => {}
+                (StoreKind::Directory, Some(path)) => {
+                    let local = bevy_pkv::PkvStore::new(organization, application);
+                    return DocStore::new(DirectoryStore::new(path, local));
+                }
+                (StoreKind::Directory, None) => {
+                    error!("The directory store needs a workspace path, using pkv");
+                }
+                (StoreKind::Memory, _) => return DocStore::new(MemoryStore::default()),
+            }
+        }
+        DocStore::new(PkvDocumentStore::new(bevy_pkv::PkvStore::new(
+            organization,
+            application,
+        )))
+    }
+}
+
+impl Deref for DocStore {
+    type Target = dyn DocumentStore;
+
+    fn deref(&self) -> &Self::Target {
+        self.0.as_ref()
+    }
+}
+
+impl DerefMut for DocStore {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.0.as_mut()
+    }
+}
@@ -0,0 +1,247 @@
+use bevy_pkv::{GetError, PkvStore};
+use serde::de::DeserializeOwned;
+use std::collections::{HashMap, HashSet};
+
+use super::{DocumentStore, StoreError};
+use crate::components::Doc;
+use crate::migrations::migrate_doc;
+use crate::tags::dedup_tags;
+use crate::utils::{doc_image_hashes, ReflectableUuid, UserPreferences};
+
+/// Documents in the browser's local storage or in a key value database next to the app,
+/// under the `docs`, `tags`, `names`, `images` and `image_refs` keys.
+pub struct PkvDocumentStore {
+    pkv: PkvStore,
+}
+
+impl PkvDocumentStore {
+    pub fn new(pkv: PkvStore) -> Self {
+        PkvDocumentStore { pkv }
+    }
+
+    /// A missing key is an empty store.
+    fn get_or_default<T: DeserializeOwned + Default>(&self, key: &str) -> Result<T, StoreError> {
+        match self.pkv.get(key) {
+            Ok(value) => Ok(value),
+            Err(GetError::NotFound) => Ok(T::default()),
+            // nothing was written to a fresh database yet, so its table does not exist
+            #[cfg(not(target_arch = "wasm32"))]
+            Err(GetError::ReDbTableError(_)) => Ok(T::default()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Image hashes every stored document references, so that unused images can be found without reading `docs`.
+    /// Stores written before the index existed get it built from `docs`.
+    fn image_refs(
+        &self,
+        docs: &HashMap<ReflectableUuid, Doc>,
+    ) -> Result<HashMap<ReflectableUuid, HashSet<String>>, StoreError> {
+        let refs: Option<HashMap<ReflectableUuid, HashSet<String>>> =
+            self.get_or_default("image_refs")?;
+        Ok(refs.unwrap_or_else(|| {
+            docs.iter()
+                .map(|(doc_id, doc)| (*doc_id, doc_image_hashes(doc)))
+                .collect()
+        }))
+    }
+
+    /// Stores the index and drops the images among `candidates` it does not mention.
+    fn set_image_refs(
+        &mut self,
+        refs: &HashMap<ReflectableUuid, HashSet<String>>,
+        candidates: HashSet<String>,
+    ) -> Result<(), StoreError> {
+        self.pkv.set("image_refs", refs)?;
+        if candidates.is_empty() {
+            return Ok(());
+        }
+        let used: HashSet<&String> = refs.values().flatten().collect();
+        let mut images: HashMap<String, String> = self.get_or_default("images")?;
+        let count = images.len();
+        images.retain(|hash, _| !candidates.contains(hash) || used.contains(hash));
+        if images.len() != count {
+            self.pkv.set("images", &images)?;
+        }
+        Ok(())
+    }
+}
+
+impl DocumentStore for PkvDocumentStore {
+    fn doc_names(&self) -> Result<HashMap<ReflectableUuid, String>, StoreError> {
+        self.get_or_default("names")
+    }
+
+    fn load_doc(&self, doc_id: ReflectableUuid) -> Result<Option<Doc>, StoreError> {
+        let mut docs: HashMap<ReflectableUuid, Doc> = self.get_or_default("docs")?;
+        let Some(mut doc) = docs.remove(&doc_id) else {
+            return Ok(None);
+        };
+        migrate_doc(&mut doc)?;
+        Ok(Some(doc))
+    }
+
+    fn save_doc(&mut self, doc: &Doc, images: &HashMap<String, String>) -> Result<(), StoreError> {
+        let mut docs: HashMap<ReflectableUuid, Doc> = self.get_or_default("docs")?;
+        let mut refs = self.image_refs(&docs)?;
+        let hashes = doc_image_hashes(doc);
+        let previous = refs.insert(doc.id, hashes.clone()).unwrap_or_default();
+        docs.insert(doc.id, doc.clone());
+        self.pkv.set("docs", &docs)?;
+        let mut tags: HashMap<ReflectableUuid, Vec<String>> = self.get_or_default("tags")?;
+        tags.insert(doc.id, dedup_tags(&doc.tags));
+        self.pkv.set("tags", &tags)?;
+        let mut names: HashMap<ReflectableUuid, String> = self.get_or_default("names")?;
+        names.insert(doc.id, doc.name.clone());
+        self.pkv.set("names", &names)?;
+        let mut stored_images: HashMap<String, String> = self.get_or_default("images")?;
+        let mut changed = false;
+        for hash in hashes.iter() {
+            if stored_images.contains_key(hash) {
+                continue;
+            }
+            if let Some(image) = images.get(hash) {
+                stored_images.insert(hash.clone(), image.clone());
+                changed = true;
+            }
+        }
+        if changed {
+            self.pkv.set("images", &stored_images)?;
+        }
+        let dropped = previous.difference(&hashes).cloned().collect();
+        self.set_image_refs(&refs, dropped)
+    }
+
+    fn delete_doc(&mut self, doc_id: ReflectableUuid) -> Result<(), StoreError> {
+        let mut docs: HashMap<ReflectableUuid, Doc> = self.get_or_default("docs")?;
+        let mut refs = self.image_refs(&docs)?;
+        let dropped = refs.remove(&doc_id).unwrap_or_default();
+        if docs.remove(&doc_id).is_some() {
+            self.pkv.set("docs", &docs)?;
+        }
+        self.set_image_refs(&refs, dropped)?;
+        let mut tags: HashMap<ReflectableUuid, Vec<String>> = self.get_or_default("tags")?;
+        if tags.remove(&doc_id).is_some() {
+            self.pkv.set("tags", &tags)?;
+        }
+        let mut names: HashMap<ReflectableUuid, String> = self.get_or_default("names")?;
+        if names.remove(&doc_id).is_some() {
+            self.pkv.set("names", &names)?;
+        }
+        Ok(())
+    }
+
+    /// Older versions appended the tags on every save, so entries may hold duplicates.
+    fn tags(&self) -> Result<HashMap<ReflectableUuid, Vec<String>>, StoreError> {
+        let tags: HashMap<ReflectableUuid, Vec<String>> = self.get_or_default("tags")?;
+        Ok(tags
+            .into_iter()
+            .map(|(doc_id, tags)| (doc_id, dedup_tags(&tags)))
+            .collect())
+    }
+
+    fn load_image(&self, hash: &str) -> Option<String> {
+        let mut images: HashMap<String, String> = self.pkv.get("images").ok()?;
+        images.remove(hash)
+    }
+
+    fn load_images(&self, hashes: &[String]) -> HashMap<String, String> {
+        let Ok(mut images) = self.pkv.get::<HashMap<String, String>>("images") else {
+            return HashMap::new();
+        };
+        hashes
+            .iter()
+            .filter_map(|hash| Some((hash.clone(), images.remove(hash)?)))
+            .collect()
+    }
+
+    fn last_saved(&self) -> Option<ReflectableUuid> {
+        self.pkv.get("last_saved").ok()
+    }
+
+    fn set_last_saved(&mut self, doc_id: ReflectableUuid) -> Result<(), StoreError> {
+        Ok(self.pkv.set("last_saved", &doc_id)?)
+    }
+
+    fn preferences(&self) -> Option<UserPreferences> {
+        self.pkv.get("user_preferences").ok()
+    }
+
+    fn set_preferences(&mut self, preferences: &UserPreferences) -> Result<(), StoreError> {
+        Ok(self.pkv.set("user_preferences", preferences)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::{Tab, TabSnapshot};
+    use crate::history::TabHistory;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_save_load_and_delete() {
+        let dir = tempdir().unwrap();
+        let mut store = PkvDocumentStore::new(PkvStore::new_in_dir(dir.path()));
+        let doc = Doc {
+            id: ReflectableUuid::generate(),
+            name: "notes".to_string(),
+            tags: vec!["work".to_string()],
+            schema_version: crate::migrations::CURRENT_SCHEMA_VERSION,
+            ..Default::default()
+        };
+        assert!(store.doc_names().unwrap().is_empty());
+
+        store.save_doc(&doc, &HashMap::new()).unwrap();
+        assert_eq!(store.load_doc(doc.id).unwrap().unwrap().name, "notes");
+        assert_eq!(store.doc_names().unwrap()[&doc.id], "notes");
+        assert_eq!(store.tags().unwrap()[&doc.id], doc.tags);
+
+        store.delete_doc(doc.id).unwrap();
+        assert!(store.load_doc(doc.id).unwrap().is_none());
+        assert!(store.tags().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_unused_images_are_dropped() {
+        let dir = tempdir().unwrap();
+        let mut store = PkvDocumentStore::new(PkvStore::new_in_dir(dir.path()));
+        let with_image = |hash: &str| {
+            let mut snapshot = TabSnapshot::default();
+            snapshot
+                .images
+                .insert(uuid::Uuid::new_v4(), hash.to_string());
+            Doc {
+                id: ReflectableUuid::generate(),
+                schema_version: crate::migrations::CURRENT_SCHEMA_VERSION,
+                tabs: vec![Tab {
+                    history: TabHistory::new(snapshot, 0.),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }
+        };
+        let images = HashMap::from([
+            ("own".to_string(), "a".to_string()),
+            ("shared".to_string(), "b".to_string()),
+        ]);
+        let mut doc = with_image("own");
+        doc.tabs.push(with_image("shared").tabs.remove(0));
+        let other = with_image("shared");
+        store.save_doc(&doc, &images).unwrap();
+        store.save_doc(&other, &images).unwrap();
+
+        doc.tabs.truncate(1);
+        doc.tabs[0].history = TabHistory::default();
+        store.save_doc(&doc, &images).unwrap();
+        assert_eq!(store.load_image("own"), None);
+        assert_eq!(store.load_image("shared"), Some("b".to_string()));
+        assert_eq!(
+            store.load_images(&["own".to_string(), "shared".to_string()]),
+            HashMap::from([("shared".to_string(), "b".to_string())])
+        );
+
+        store.delete_doc(other.id).unwrap();
+        assert_eq!(store.load_image("shared"), None);
+    }
+}
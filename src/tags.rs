@@ -0,0 +1,139 @@
+use std::collections::BTreeSet;
+
+use crate::resources::AppState;
+use crate::utils::ReflectableUuid;
+
+/// Trimmed tag, `None` when nothing is left.
+pub fn normalize_tag(tag: &str) -> Option<String> {
+    let tag = tag.trim().trim_matches(',').trim();
+    if tag.is_empty() {
+        None
+    } else {
+        Some(tag.to_string())
+    }
+}
+
+/// Tags are compared ignoring case, the first spelling wins.
+pub fn dedup_tags(tags: &[String]) -> Vec<String> {
+    let mut result: Vec<String> = vec![];
+    for tag in tags.iter().filter_map(|tag| normalize_tag(tag)) {
+        if !contains_tag(&result, &tag) {
+            result.push(tag);
+        }
+    }
+    result
+}
+
+pub fn contains_tag(tags: &[String], tag: &str) -> bool {
+    tags.iter().any(|t| t.eq_ignore_ascii_case(tag))
+}
+
+/// Returns whether `tags` changed.
+pub fn add_tag(tags: &mut Vec<String>, tag: &str) -> bool {
+    match normalize_tag(tag) {
+        Some(tag) if !contains_tag(tags, &tag) => {
+            tags.push(tag);
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Shortest known tag starting with `prefix` that is not in `exclude`.
+pub fn complete_tag<'a>(
+    prefix: &str,
+    known: impl IntoIterator<Item = &'a String>,
+    exclude: &[String],
+) -> Option<String> {
+    let prefix = prefix.trim().to_lowercase();
+    if prefix.is_empty() {
+        return None;
+    }
+    known
+        .into_iter()
+        .filter(|tag| tag.len() > prefix.len() && tag.to_lowercase().starts_with(&prefix))
+        .filter(|tag| !contains_tag(exclude, tag))
+        .min_by(|a, b| a.len().cmp(&b.len()).then(a.cmp(b)))
+        .cloned()
+}
+
+/// Comma separated tags typed into the doc list filter.
+pub fn parse_tag_filter(text: &str) -> Vec<String> {
+    dedup_tags(&text.split(',').map(str::to_string).collect::<Vec<_>>())
+}
+
+/// A document matches when it has every tag of the filter.
+pub fn matches_tag_filter(tags: &[String], filter: &[String]) -> bool {
+    filter.iter().all(|tag| contains_tag(tags, tag))
+}
+
+/// Tags of a loaded document, or the stored ones when it is not in memory.
+pub fn doc_tags(app_state: &AppState, doc_id: ReflectableUuid) -> &[String] {
+    if let Some(doc) = app_state.docs.get(&doc_id) {
+        return &doc.tags;
+    }
+    if let Some(doc) = app_state.docs.evicted().find(|doc| doc.id == doc_id) {
+        return &doc.tags;
+    }
+    app_state
+        .tags
+        .get(&doc_id)
+        .map(|tags| tags.as_slice())
+        .unwrap_or_default()
+}
+
+/// Every tag in use, for autocomplete.
+pub fn known_tags(app_state: &AppState) -> BTreeSet<String> {
+    app_state
+        .tags
+        .values()
+        .flatten()
+        .chain(app_state.docs.values().flat_map(|doc| doc.tags.iter()))
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(tags: &[&str]) -> Vec<String> {
+        tags.iter().map(|tag| tag.to_string()).collect()
+    }
+
+    #[test]
+    fn test_dedup_tags() {
+        assert_eq!(
+            dedup_tags(&tags(&["work", " Work ", "", "home", "work,"])),
+            tags(&["work", "home"])
+        );
+        let mut doc_tags = tags(&["work"]);
+        assert!(!add_tag(&mut doc_tags, "WORK"));
+        assert!(add_tag(&mut doc_tags, " ideas "));
+        assert_eq!(doc_tags, tags(&["work", "ideas"]));
+    }
+
+    #[test]
+    fn test_complete_tag() {
+        let known = tags(&["workshop", "work", "writing", "home"]);
+        assert_eq!(complete_tag("wo", &known, &[]), Some("work".to_string()));
+        assert_eq!(
+            complete_tag("Wo", &known, &tags(&["work"])),
+            Some("workshop".to_string())
+        );
+        assert_eq!(complete_tag("work", &known, &tags(&["workshop"])), None);
+        assert_eq!(complete_tag("", &known, &[]), None);
+    }
+
+    #[test]
+    fn test_tag_filter() {
+        let filter = parse_tag_filter("Work, ideas,");
+        assert_eq!(filter, tags(&["Work", "ideas"]));
+        assert!(matches_tag_filter(
+            &tags(&["ideas", "work", "home"]),
+            &filter
+        ));
+        assert!(!matches_tag_filter(&tags(&["work"]), &filter));
+        assert!(matches_tag_filter(&tags(&[]), &[]));
+    }
+}
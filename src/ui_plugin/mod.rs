@@ -3,7 +3,10 @@ use bevy::prelude::*;
 
 use serde::{Deserialize, Serialize};
 
-use crate::resources::AppState;
+#[cfg(target_arch = "wasm32")]
+use crate::notifications::VeloError;
+use crate::notifications::{Notification, NotificationChannel};
+use crate::resources::{AppState, Autosave, HistoryDepth};
 
 use crate::canvas::arrow::components::{ArrowConnect, ArrowType};
 use crate::canvas::arrow::events::{CreateArrow, RedrawArrow};
@@ -84,6 +87,29 @@ use drawing::*;
 mod active_editor_changed;
 use active_editor_changed::*;
 
+#[path = "systems/autosave.rs"]
+mod autosave;
+use autosave::*;
+
+#[path = "systems/recovery.rs"]
+mod recovery;
+use recovery::*;
+
+#[path = "systems/tags.rs"]
+mod tags;
+use tags::*;
+
+#[path = "systems/export.rs"]
+mod export;
+use export::*;
+#[path = "systems/import.rs"]
+mod import;
+use import::*;
+
+#[path = "systems/notifications.rs"]
+mod notifications;
+use notifications::*;
+
 pub struct UiPlugin;
 
 #[derive(Event, Default)]
@@ -92,12 +118,27 @@ pub struct AddRect<T> {
     pub image: Option<Handle<Image>>,
 }
 
+#[derive(Event)]
+pub struct AddDrawing<T> {
+    pub drawing: DrawingJsonNode<T>,
+}
+
 #[derive(Event)]
 pub struct SaveStore {
     pub doc_id: ReflectableUuid,
     pub path: Option<PathBuf>, // Save current document to file
 }
 
+/// A document was written to the store.
+#[derive(Event)]
+pub struct Saved {
+    pub doc_id: ReflectableUuid,
+}
+
+/// Records the active tab as a new undo checkpoint.
+#[derive(Event, Default)]
+pub struct AddCheckpoint;
+
 #[derive(Debug, PartialEq, Eq)]
 pub enum NodeInteractionType {
     Hover,
@@ -131,20 +172,20 @@ pub enum NodeType {
     Circle,
 }
 
-#[derive(Serialize, Deserialize, Clone, Default)]
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq)]
 pub enum TextPos {
     #[default]
     Center,
     TopLeft,
 }
 
-#[derive(Serialize, Deserialize, Default)]
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq)]
 pub struct JsonNodeText {
     pub text: String,
     pub pos: TextPos,
 }
 
-#[derive(Serialize, Deserialize, Default)]
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq)]
 pub struct JsonNode<T> {
     pub id: Uuid,
     pub node_type: NodeType,
@@ -158,7 +199,7 @@ pub struct JsonNode<T> {
     pub visible: bool,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct DrawingJsonNode<T> {
     pub x: f32,
     pub y: f32,
@@ -169,8 +210,9 @@ pub struct DrawingJsonNode<T> {
     pub width: f32,
 }
 
-pub const MAX_CHECKPOINTS: i32 = 7;
-pub const MAX_SAVED_DOCS_IN_MEMORY: i32 = 7;
+pub const DEFAULT_HISTORY_DEPTH: usize = 200;
+pub const DEFAULT_AUTOSAVE_DELAY: f64 = 3.;
+pub const MAX_SAVED_DOCS_IN_MEMORY: usize = 7;
 
 #[derive(Resource, Default)]
 pub struct UiState {
@@ -195,13 +237,20 @@ impl Plugin for UiPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<UiState>();
         app.init_resource::<AppState>();
+        app.init_resource::<HistoryDepth>();
+        app.init_resource::<Autosave>();
+        app.init_resource::<NotificationChannel>();
 
         app.add_event::<AddRect<(String, Color)>>();
+        app.add_event::<AddDrawing<(String, Color)>>();
         app.add_event::<CreateArrow>();
         app.add_event::<RedrawArrow>();
         app.add_event::<SaveStore>();
+        app.add_event::<Saved>();
+        app.add_event::<AddCheckpoint>();
         app.add_event::<UpdateDeleteDocBtn>();
         app.add_event::<NodeInteraction>();
+        app.add_event::<Notification>();
 
         #[cfg(not(target_arch = "wasm32"))]
         app.add_systems(
@@ -211,6 +260,12 @@ impl Plugin for UiPlugin {
         #[cfg(target_arch = "wasm32")]
         app.add_systems(Startup, load_from_url.before(init_layout));
         app.add_systems(Startup, init_layout);
+        app.add_systems(Startup, open_journal);
+        app.add_systems(
+            Update,
+            offer_recovery.run_if(resource_exists::<crate::journal::JournalRecovery>()),
+        );
+        app.add_systems(Last, close_journal);
 
         app.add_systems(
             Update,
@@ -239,6 +294,30 @@ impl Plugin for UiPlugin {
                 .chain()
                 .distributive_run_if(should_save_tab),
         );
+        app.add_systems(Update, add_checkpoint.before(save_tab));
+        app.add_systems(
+            Update,
+            (export_tab, remove_export_tab_request)
+                .chain()
+                .distributive_run_if(should_export_tab)
+                .after(save_tab),
+        );
+        app.add_systems(
+            Update,
+            // the nodes are spawned this frame, so their arrows can connect in the next one
+            (import_into_tab, remove_import_tab_request)
+                .chain()
+                .distributive_run_if(should_import_tab)
+                .before(create_new_node),
+        );
+        // drawings go above the nodes spawned in the same frame
+        app.add_systems(Update, create_new_drawing.after(create_new_node));
+        app.add_systems(
+            Update,
+            (detect_changes, autosave, update_save_indicator)
+                .chain()
+                .after(save_doc),
+        );
 
         app.add_systems(
             Update,
@@ -249,7 +328,12 @@ impl Plugin for UiPlugin {
 
         app.add_systems(
             Update,
-            (load_tab, remove_load_tab_request)
+            // undo saves pending changes before the checkpoint is dropped
+            (
+                journal_history_step.after(save_tab),
+                load_tab,
+                remove_load_tab_request,
+            )
                 .chain()
                 .distributive_run_if(should_load_tab),
         );
@@ -280,6 +364,22 @@ impl Plugin for UiPlugin {
         #[cfg(not(target_arch = "wasm32"))]
         app.add_systems(Update, (search_box_click, search_box_text_changed));
 
+        app.add_systems(
+            Update,
+            (forward_notifications, show_notifications, expire_toasts).chain(),
+        );
+
+        app.add_systems(
+            Update,
+            (
+                tag_box_click,
+                tag_input_changed,
+                remove_tag_click,
+                tag_filter_changed,
+                update_tag_chips,
+            ),
+        );
+
         app.add_systems(
             Update,
             (
@@ -297,6 +397,7 @@ impl Plugin for UiPlugin {
                 #[cfg(not(target_arch = "wasm32"))]
                 update_particles_effect,
                 save_to_store.after(save_tab),
+                flush_evicted_docs.after(save_doc).before(save_to_store),
                 canvas_click,
                 active_editor_changed,
                 interactive_node.before(canvas_click),
@@ -306,6 +407,16 @@ impl Plugin for UiPlugin {
                 update_drawing_position,
             ),
         );
+        #[cfg(not(target_arch = "wasm32"))]
+        app.add_systems(
+            Update,
+            (
+                export_tab_handler,
+                png_export_toggle_handler,
+                copy_tab_outline_handler,
+                import_tab_handler,
+            ),
+        );
         app.add_systems(
             Update,
             (drawing_two_points, enable_two_points_draw_mode).chain(),
@@ -332,7 +443,7 @@ impl Plugin for UiPlugin {
 }
 
 #[cfg(target_arch = "wasm32")]
-fn load_from_url(mut commands: Commands) {
+fn load_from_url(mut commands: Commands, notifications: Res<NotificationChannel>) {
     let (tx, rx) = async_channel::bounded(1);
     commands.insert_resource(CommChannels { tx: tx.clone(), rx });
     let href = web_sys::window().unwrap().location().href().unwrap();
@@ -346,11 +457,20 @@ fn load_from_url(mut commands: Commands) {
         if links.len() == 1 {
             let url = links.first().unwrap().as_str().to_owned();
             let cc = tx.clone();
+            let notifications = notifications.tx.clone();
             let task = pool.spawn(async move {
                 let request = ehttp::Request::get(url);
-                ehttp::fetch(request, move |result| {
-                    let json_string = result.unwrap().text().unwrap();
-                    cc.try_send(json_string).unwrap();
+                ehttp::fetch(request, move |result| match result.map(|r| r.text()) {
+                    Ok(Some(json_string)) => {
+                        let _ = cc.try_send(json_string);
+                    }
+                    Ok(None) => {
+                        let err = VeloError::Fetch("response is not text".to_string());
+                        let _ = notifications.try_send(err.into());
+                    }
+                    Err(err) => {
+                        let _ = notifications.try_send(VeloError::Fetch(err).into());
+                    }
                 });
             });
             task.detach();
@@ -359,11 +479,24 @@ fn load_from_url(mut commands: Commands) {
 }
 
 #[cfg(not(target_arch = "wasm32"))]
-fn read_native_config(mut app_state: ResMut<AppState>) {
+fn read_native_config(
+    mut app_state: ResMut<AppState>,
+    mut history_depth: ResMut<HistoryDepth>,
+    mut autosave: ResMut<Autosave>,
+) {
     use crate::utils::read_config_file;
 
     let config = read_config_file().unwrap_or_default();
     if let Some(github_token) = &config.github_access_token {
         app_state.github_token = Some(github_token.clone());
     }
+    if let Some(depth) = config.history_depth {
+        history_depth.0 = depth;
+    }
+    if let Some(max_docs) = config.max_docs_in_memory {
+        app_state.docs.set_capacity(max_docs);
+    }
+    if let Some(delay) = config.autosave_delay {
+        autosave.delay = delay;
+    }
 }
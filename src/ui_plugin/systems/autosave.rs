@@ -0,0 +1,142 @@
+use bevy::ecs::query::ReadOnlyWorldQuery;
+use bevy::prelude::*;
+
+use super::ui_helpers::{Drawing, RawText, SaveIndicator, VeloNode};
+use super::{AddCheckpoint, Saved};
+use crate::canvas::arrow::components::ArrowMeta;
+use crate::resources::{AppState, Autosave, CheckpointStep, LoadTabRequest, SaveDocRequest};
+
+/// Newly spawned components come from loading a tab, edits that spawn or despawn
+/// entities send `AddCheckpoint` instead.
+fn edited<T: Component, F: ReadOnlyWorldQuery>(query: &Query<Ref<T>, F>) -> bool {
+    query
+        .iter()
+        .any(|component| component.is_changed() && !component.is_added())
+}
+
+pub fn detect_changes(
+    nodes: Query<Ref<Transform>, With<VeloNode>>,
+    texts: Query<Ref<RawText>>,
+    arrows: Query<Ref<ArrowMeta>>,
+    drawings: Query<Ref<Drawing<(String, Color)>>>,
+    mut checkpoints: EventReader<AddCheckpoint>,
+    load_request: Option<Res<LoadTabRequest>>,
+    mut autosave: ResMut<Autosave>,
+    time: Res<Time>,
+) {
+    let checkpoint = !checkpoints.is_empty();
+    checkpoints.clear();
+    let history_step = load_request.is_some_and(|request| request.step != CheckpointStep::Current);
+    if checkpoint
+        || history_step
+        || edited(&nodes)
+        || edited(&texts)
+        || edited(&arrows)
+        || edited(&drawings)
+    {
+        autosave.unsaved = true;
+        autosave.last_change = time.elapsed_seconds_f64();
+    }
+}
+
+pub fn autosave(
+    mut commands: Commands,
+    mut saved: EventReader<Saved>,
+    mut autosave: ResMut<Autosave>,
+    app_state: Res<AppState>,
+    save_request: Option<Res<SaveDocRequest>>,
+    time: Res<Time>,
+) {
+    let current_document = app_state.current_document;
+    if saved
+        .iter()
+        .any(|saved| Some(saved.doc_id) == current_document)
+    {
+        autosave.unsaved = false;
+    }
+    if !autosave.unsaved || autosave.delay <= 0. || save_request.is_some() {
+        return;
+    }
+    if time.elapsed_seconds_f64() - autosave.last_change < autosave.delay {
+        return;
+    }
+    let Some(doc_id) = app_state.current_document else {
+        return;
+    };
+    // save_tab only adds a checkpoint when the tab differs from the latest one
+    commands.insert_resource(SaveDocRequest { doc_id, path: None });
+}
+
+pub fn update_save_indicator(
+    autosave: Res<Autosave>,
+    mut indicator: Query<&mut Text, With<SaveIndicator>>,
+) {
+    if !autosave.is_changed() {
+        return;
+    }
+    let status = if autosave.unsaved { "unsaved" } else { "saved" };
+    for mut text in indicator.iter_mut() {
+        if text.sections[0].value != status {
+            text.sections[0].value = status.to_string();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::ReflectableUuid;
+
+    fn app() -> App {
+        let mut app = App::new();
+        app.init_resource::<Time>()
+            .init_resource::<Autosave>()
+            .init_resource::<AppState>()
+            .add_event::<AddCheckpoint>()
+            .add_event::<Saved>()
+            .add_systems(Update, (detect_changes, autosave).chain());
+        app
+    }
+
+    #[test]
+    fn test_moving_node_marks_unsaved() {
+        let mut app = app();
+        let node = app
+            .world
+            .spawn((Transform::default(), VeloNode::default()))
+            .id();
+        app.update();
+        assert!(!app.world.resource::<Autosave>().unsaved);
+
+        app.world.get_mut::<Transform>(node).unwrap().translation.x = 10.;
+        app.update();
+        assert!(app.world.resource::<Autosave>().unsaved);
+    }
+
+    #[test]
+    fn test_autosave_requests_save_after_delay() {
+        let mut app = app();
+        let doc_id = ReflectableUuid::generate();
+        app.world.resource_mut::<AppState>().current_document = Some(doc_id);
+        app.world.resource_mut::<Autosave>().delay = 1.;
+        app.world.send_event(AddCheckpoint);
+        app.update();
+        assert!(app.world.get_resource::<SaveDocRequest>().is_none());
+
+        app.world.resource_mut::<Autosave>().last_change = -1.;
+        app.update();
+        assert_eq!(app.world.resource::<SaveDocRequest>().doc_id, doc_id);
+
+        // a failed write keeps the document unsaved and is retried
+        app.world.remove_resource::<SaveDocRequest>();
+        app.update();
+        assert!(app.world.resource::<Autosave>().unsaved);
+        assert_eq!(app.world.resource::<SaveDocRequest>().doc_id, doc_id);
+
+        app.world.remove_resource::<SaveDocRequest>();
+        app.world.send_event(Saved { doc_id });
+        app.update();
+        assert!(!app.world.resource::<Autosave>().unsaved);
+        assert!(app.world.get_resource::<SaveDocRequest>().is_none());
+    }
+}
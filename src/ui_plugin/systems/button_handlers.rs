@@ -5,16 +5,16 @@ use std::{collections::VecDeque, time::Duration};
 use bevy::sprite::collide_aabb::collide;
 use bevy::{prelude::*, window::PrimaryWindow};
 
+use crate::store::DocStore;
 use bevy_cosmic_edit::{CosmicEdit, CosmicEditHistory, CosmicFont};
-use bevy_pkv::PkvStore;
 use bevy_prototype_lyon::prelude::{Fill, Stroke};
 use cosmic_text::{Cursor, Edit};
 use serde::Serialize;
-use serde_json::{json, Value};
+use serde_json::Value;
 use uuid::Uuid;
 
 use crate::themes::Theme;
-use crate::{AddRect, JsonNode, JsonNodeText, NodeType, UiState};
+use crate::{AddCheckpoint, AddRect, JsonNode, JsonNodeText, NodeType, UiState};
 
 use super::ui_helpers::{
     spawn_modal, ButtonAction, ChangeColor, ChangeTheme, DeleteDoc, DocListItemButton, DrawPencil,
@@ -24,10 +24,13 @@ use super::ui_helpers::{
 use super::{ExportToFile, ImportFromFile, ImportFromUrl, MainPanel, ShareDoc};
 use crate::canvas::arrow::components::{ArrowMeta, ArrowMode};
 use crate::components::{Doc, MainCamera, Tab};
+use crate::migrations::CURRENT_SCHEMA_VERSION;
+use crate::notifications::{Notification, NotificationChannel, VeloError};
 use crate::resources::{AppState, FontSystemState, LoadDocRequest, SaveDocRequest};
 use crate::utils::{
-    bevy_color_to_cosmic, get_timestamp, load_doc_to_memory, ReflectableUuid, UserPreferences,
-    DARK_THEME_ICON_CODE, LIGHT_THEME_ICON_CODE,
+    bevy_color_to_cosmic, export_doc, get_timestamp, load_doc_to_memory, read_stored_doc,
+    stored_doc_names, take_doc_images, ReflectableUuid, UserPreferences, DARK_THEME_ICON_CODE,
+    LIGHT_THEME_ICON_CODE,
 };
 
 #[path = "../../macros.rs"]
@@ -60,6 +63,7 @@ pub fn rec_button_handlers(
         ),
     >,
     theme: Res<Theme>,
+    mut checkpoint_events: EventWriter<AddCheckpoint>,
 ) {
     let mut camera_transform = camera_proj_query.single_mut();
     let x = camera_transform.translation.x;
@@ -81,6 +85,7 @@ pub fn rec_button_handlers(
                                 pos: crate::TextPos::Center,
                             },
                             bg_color: pair_struct!(theme.node_bg),
+                            visible: true,
                             ..default()
                         },
                         image: None,
@@ -100,6 +105,7 @@ pub fn rec_button_handlers(
                                 pos: crate::TextPos::Center,
                             },
                             bg_color: pair_struct!(theme.node_bg),
+                            visible: true,
                             ..default()
                         },
                         image: None,
@@ -119,12 +125,18 @@ pub fn rec_button_handlers(
                                 pos: crate::TextPos::Center,
                             },
                             bg_color: pair_struct!(theme.paper_node_bg),
+                            visible: true,
                             ..default()
                         },
                         image: None,
                     });
                 }
                 super::ui_helpers::ButtonTypes::Del => {
+                    if ui_state.entity_to_draw_selected.is_some()
+                        || ui_state.entity_to_edit.is_some()
+                    {
+                        checkpoint_events.send_default();
+                    }
                     if let Some(id) = ui_state.entity_to_draw_selected {
                         ui_state.entity_to_draw_selected = None;
                         for (entity, drawing) in &mut drawings.iter_mut() {
@@ -266,6 +278,7 @@ pub fn rec_button_handlers(
                                 pos: crate::TextPos::Center,
                             },
                             bg_color: pair_struct!(theme.color_none),
+                            visible: true,
                             ..default()
                         },
                         image: None,
@@ -426,22 +439,12 @@ pub fn new_doc_handler(
                 let doc_id = ReflectableUuid::generate();
                 let name = "Untitled".to_string();
                 let tab_id = ReflectableUuid::generate();
-                let mut checkpoints = VecDeque::new();
-                checkpoints.push_back(
-                    json!({
-                        "nodes": [],
-                        "arrows": [],
-                        "images": {},
-                        "drawings": []
-                    })
-                    .to_string(),
-                );
                 let tabs = vec![Tab {
                     id: tab_id,
                     name: "Tab 1".to_string(),
-                    checkpoints,
                     is_active: true,
                     z_index: 1.,
+                    ..Default::default()
                 }];
                 app_state.docs.insert(
                     doc_id,
@@ -450,6 +453,8 @@ pub fn new_doc_handler(
                         name: name.clone(),
                         tabs,
                         tags: vec![],
+                        schema_version: CURRENT_SCHEMA_VERSION,
+                        images: HashMap::new(),
                     },
                 );
                 commands.insert_resource(SaveDocRequest {
@@ -523,7 +528,7 @@ pub fn delete_doc_handler(
     mut app_state: ResMut<AppState>,
     main_panel_query: Query<Entity, With<MainPanel>>,
     windows: Query<&Window, With<PrimaryWindow>>,
-    pkv: Res<PkvStore>,
+    store: Res<DocStore>,
     mut cosmic_fonts: ResMut<Assets<CosmicFont>>,
     font_system_state: ResMut<FontSystemState>,
     theme: Res<Theme>,
@@ -533,13 +538,24 @@ pub fn delete_doc_handler(
         match *interaction {
             Interaction::Pressed => {
                 if app_state.docs.len() == 1 {
-                    if let Ok(docs) = pkv.get::<HashMap<ReflectableUuid, Doc>>("docs") {
-                        if docs.len() > 1 {
-                            for (id, doc) in docs.iter() {
+                    if let Some(names) = stored_doc_names(&store) {
+                        if names.len() > 1 {
+                            for id in names.keys() {
                                 if app_state.docs.len() != 1 {
                                     break;
                                 }
-                                app_state.docs.insert(*id, doc.clone());
+                                if app_state.docs.contains_key(id) {
+                                    continue;
+                                }
+                                let mut doc = match read_stored_doc(*id, &store) {
+                                    Ok(doc) => doc,
+                                    Err(err) => {
+                                        error!("{}", err);
+                                        continue;
+                                    }
+                                };
+                                take_doc_images(&mut doc, &mut app_state);
+                                app_state.docs.insert_stored(*id, doc);
                             }
                         } else {
                             // do not allow deletion if there is less than two docs
@@ -626,11 +642,15 @@ pub fn export_to_file(
 }
 
 #[cfg(target_arch = "wasm32")]
-pub fn set_window_property(mut app_state: ResMut<AppState>, mut pkv: ResMut<PkvStore>) {
+pub fn set_window_property(mut app_state: ResMut<AppState>, store: Res<DocStore>) {
     if let Some(doc_id) = app_state.current_document {
-        load_doc_to_memory(doc_id, &mut app_state, &mut pkv);
-        let current_doc = app_state.docs.get(&doc_id).unwrap().clone();
-        let value = serde_json::to_string_pretty(&current_doc).unwrap();
+        let _ = load_doc_to_memory(doc_id, &mut app_state, &store);
+        let Ok(current_doc) = export_doc(doc_id, &app_state) else {
+            return;
+        };
+        let Ok(value) = serde_json::to_string_pretty(&current_doc) else {
+            return;
+        };
         let window = wasm_bindgen::JsValue::from(web_sys::window().unwrap());
         let velo_var = wasm_bindgen::JsValue::from("velo");
         let state = wasm_bindgen::JsValue::from(value);
@@ -653,59 +673,38 @@ struct GistCreateRequest {
 pub fn shared_doc_handler(
     mut app_state: ResMut<AppState>,
     mut query: Query<&Interaction, (Changed<Interaction>, With<ShareDoc>)>,
-    mut pkv: ResMut<PkvStore>,
+    store: Res<DocStore>,
+    channel: Res<NotificationChannel>,
+    mut notifications: EventWriter<Notification>,
 ) {
     for interaction in &mut query.iter_mut() {
         match *interaction {
             Interaction::Pressed => {
                 if let Some(doc_id) = app_state.current_document {
-                    load_doc_to_memory(doc_id, &mut app_state, &mut pkv);
-                    let current_doc = app_state.docs.get(&doc_id).unwrap().clone();
-                    let contents = serde_json::to_string_pretty(&current_doc).unwrap();
-                    let mut files = std::collections::HashMap::new();
-                    let filename = "velo.json";
-                    let file = GistFile {
-                        content: contents.to_string(),
+                    if let Err(err) = load_doc_to_memory(doc_id, &mut app_state, &store) {
+                        notifications.send(err.into());
+                    }
+                    let Some(github_token) = app_state.github_token.clone() else {
+                        notifications.send(
+                            VeloError::Share("github_access_token is not configured".to_string())
+                                .into(),
+                        );
+                        continue;
                     };
-                    files.insert(filename.to_string(), file);
-
-                    let request = GistCreateRequest {
-                        description: "Velo Document".to_string(),
-                        public: true,
-                        files,
+                    let request = match gist_request(doc_id, &app_state, &github_token) {
+                        Ok(request) => request,
+                        Err(err) => {
+                            notifications.send(err.into());
+                            continue;
+                        }
                     };
-
-                    let mut request = ehttp::Request::post(
-                        "https://api.github.com/gists",
-                        serde_json::to_string_pretty(&request).unwrap(),
-                    );
-                    request.headers.insert(
-                        "Accept".to_string(),
-                        "application/vnd.github.v3+json".to_string(),
-                    );
-                    request.headers.insert(
-                        "Authorization".to_string(),
-                        format!("token {}", app_state.github_token.as_ref().unwrap()),
-                    );
-                    #[cfg(not(target_arch = "wasm32"))]
-                    let mut clipboard = arboard::Clipboard::new().unwrap();
+                    let tx = channel.tx.clone();
                     ehttp::fetch(request, move |result| {
-                        let response = result.unwrap();
-                        if response.ok {
-                            let res_json: Value =
-                                serde_json::from_str(response.text().unwrap().as_str()).unwrap();
-                            let files: Value = res_json["files"].clone();
-                            let velo = files["velo.json"].clone();
-                            #[cfg(not(target_arch = "wasm32"))]
-                            clipboard
-                                .set_text(format!(
-                                    "https://staffengineer.github.io/velo?document={}",
-                                    velo["raw_url"].to_string().replace('\"', "")
-                                ))
-                                .unwrap();
-                        } else {
-                            error!("Error sharing document: {}", response.status_text);
-                        }
+                        let notification = match share_url(result) {
+                            Ok(url) => copy_share_url(url),
+                            Err(err) => err.into(),
+                        };
+                        let _ = tx.try_send(notification);
                     });
                 }
             }
@@ -715,6 +714,71 @@ pub fn shared_doc_handler(
     }
 }
 
+fn gist_request(
+    doc_id: ReflectableUuid,
+    app_state: &AppState,
+    github_token: &str,
+) -> Result<ehttp::Request, VeloError> {
+    let current_doc = export_doc(doc_id, app_state)?;
+    let contents = serde_json::to_string_pretty(&current_doc)?;
+    let mut files = std::collections::HashMap::new();
+    let filename = "velo.json";
+    let file = GistFile { content: contents };
+    files.insert(filename.to_string(), file);
+
+    let request = GistCreateRequest {
+        description: "Velo Document".to_string(),
+        public: true,
+        files,
+    };
+
+    let mut request = ehttp::Request::post(
+        "https://api.github.com/gists",
+        serde_json::to_string_pretty(&request)?,
+    );
+    request.headers.insert(
+        "Accept".to_string(),
+        "application/vnd.github.v3+json".to_string(),
+    );
+    request.headers.insert(
+        "Authorization".to_string(),
+        format!("token {}", github_token),
+    );
+    Ok(request)
+}
+
+fn share_url(result: Result<ehttp::Response, String>) -> Result<String, VeloError> {
+    let response = result.map_err(VeloError::Share)?;
+    if !response.ok {
+        return Err(VeloError::Share(response.status_text));
+    }
+    let text = response
+        .text()
+        .ok_or_else(|| VeloError::Share("response is not text".to_string()))?;
+    let res_json: Value = serde_json::from_str(&text)?;
+    let raw_url = res_json["files"]["velo.json"]["raw_url"]
+        .as_str()
+        .ok_or_else(|| VeloError::Share("response has no raw_url".to_string()))?;
+    Ok(format!(
+        "https://staffengineer.github.io/velo?document={}",
+        raw_url
+    ))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn copy_share_url(url: String) -> Notification {
+    let copied = arboard::Clipboard::new().and_then(|mut clipboard| clipboard.set_text(url));
+    match copied {
+        Ok(()) => Notification::Info("Share link copied to clipboard".to_string()),
+        Err(err) => VeloError::Clipboard(err.to_string()).into(),
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn copy_share_url(url: String) -> Notification {
+    Notification::Info(format!("Shared as {}", url))
+}
+
 pub fn import_from_file(
     mut commands: Commands,
     mut query: Query<&Interaction, (Changed<Interaction>, With<ImportFromFile>)>,
@@ -878,7 +942,7 @@ pub fn enable_two_points_draw_mode(
 }
 
 pub fn change_theme(
-    mut pkv: ResMut<PkvStore>,
+    mut store: ResMut<DocStore>,
     mut change_theme_button: Query<&Interaction, (Changed<Interaction>, With<ChangeTheme>)>,
     mut change_theme_label: Query<&mut Text, (With<ChangeTheme>, Without<Tooltip>)>,
     mut tooltip_label: Query<&mut Text, (With<Tooltip>, Without<ChangeTheme>)>,
@@ -899,12 +963,9 @@ pub fn change_theme(
                             }
                         }
                         text.sections[0].value = LIGHT_THEME_ICON_CODE.to_string();
-                        let _ = pkv.set(
-                            "user_preferences",
-                            &UserPreferences {
-                                theme_name: Some("dark".to_string()),
-                            },
-                        );
+                        let _ = store.set_preferences(&UserPreferences {
+                            theme_name: Some("dark".to_string()),
+                        });
                     }
                     if icon_code == LIGHT_THEME_ICON_CODE {
                         for mut tooltip in &mut tooltip_label.iter_mut() {
@@ -917,12 +978,9 @@ pub fn change_theme(
                             }
                         }
                         text.sections[0].value = DARK_THEME_ICON_CODE.to_string();
-                        let _ = pkv.set(
-                            "user_preferences",
-                            &UserPreferences {
-                                theme_name: Some("light".to_string()),
-                            },
-                        );
+                        let _ = store.set_preferences(&UserPreferences {
+                            theme_name: Some("light".to_string()),
+                        });
                     }
                 }
             }
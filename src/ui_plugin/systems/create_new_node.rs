@@ -8,7 +8,7 @@ use crate::{
     utils::ReflectableUuid,
 };
 
-use super::{ui_helpers::spawn_sprite_node, AddRect, NodeMeta, UiState};
+use super::{ui_helpers::spawn_sprite_node, AddCheckpoint, AddRect, NodeMeta, UiState};
 
 pub fn create_new_node(
     mut commands: Commands,
@@ -22,6 +22,7 @@ pub fn create_new_node(
     mut z_index_local: Local<f32>,
     mut materials: ResMut<Assets<CustomShadowMaterial>>,
     mut meshes: ResMut<Assets<Mesh>>,
+    mut checkpoint_events: EventWriter<AddCheckpoint>,
 ) {
     let window = windows.single_mut();
     for event in events.iter() {
@@ -56,8 +57,9 @@ pub fn create_new_node(
                 position: (event.node.x, event.node.y, tab.z_index),
                 text_pos: event.node.text.pos.clone(),
                 is_active: true,
-                visible: true,
+                visible: event.node.visible,
             },
         );
+        checkpoint_events.send_default();
     }
 }
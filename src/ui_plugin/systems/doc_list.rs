@@ -10,11 +10,13 @@ use crate::{resources::FontSystemState, themes::Theme, ui_plugin::ui_helpers::Do
 
 use crate::resources::{AppState, LoadDocRequest, SaveDocRequest};
 
-use std::collections::{HashMap, HashSet};
+use std::collections::HashSet;
 
-use bevy_pkv::PkvStore;
+use crate::store::DocStore;
+use crate::tags::{doc_tags, matches_tag_filter};
 
-use crate::{ui_plugin::ui_helpers::add_list_item, utils::ReflectableUuid};
+use crate::ui_plugin::ui_helpers::add_list_item;
+use crate::utils::{stored_doc_names, ReflectableUuid};
 
 use super::{
     ui_helpers::{DeleteDoc, DocList, DocListItemContainer},
@@ -95,7 +97,7 @@ pub fn doc_list_ui_changed(
     mut last_doc_list: Local<HashSet<ReflectableUuid>>,
     mut doc_list_query: Query<Entity, With<DocList>>,
     asset_server: Res<AssetServer>,
-    pkv: Res<PkvStore>,
+    store: Res<DocStore>,
     mut query_container: Query<Entity, With<DocListItemContainer>>,
     mut event_writer: EventWriter<UpdateDeleteDocBtn>,
     theme: Res<Theme>,
@@ -105,17 +107,25 @@ pub fn doc_list_ui_changed(
 ) {
     let primary_window = windows.single();
     let scale_factor = primary_window.scale_factor() as f32;
-    if app_state.is_changed() && app_state.doc_list_ui != *last_doc_list {
+    if !app_state.is_changed() {
+        return;
+    }
+    let visible_docs: HashSet<ReflectableUuid> = app_state
+        .doc_list_ui
+        .iter()
+        .filter(|doc_id| matches_tag_filter(doc_tags(&app_state, **doc_id), &app_state.tag_filter))
+        .copied()
+        .collect();
+    if visible_docs != *last_doc_list {
         // Think about re-using UI elements instead of destroying and re-creating them
         for entity in query_container.iter_mut() {
             commands.entity(entity).despawn_recursive();
         }
         let doc_list = doc_list_query.single_mut();
-        let mut doc_tuples: Vec<(String, ReflectableUuid)> = app_state
-            .doc_list_ui
+        let mut doc_tuples: Vec<(String, ReflectableUuid)> = visible_docs
             .iter()
             .map(|doc_id| {
-                let doc_name = get_doc_name(*doc_id, &pkv, &app_state);
+                let doc_name = get_doc_name(*doc_id, &store, &app_state);
                 (doc_name, *doc_id)
             })
             .collect();
@@ -135,19 +145,19 @@ pub fn doc_list_ui_changed(
             commands.entity(doc_list).add_child(doc_list_item);
         }
         event_writer.send(UpdateDeleteDocBtn);
-        *last_doc_list = app_state.doc_list_ui.clone();
+        *last_doc_list = visible_docs;
     }
 }
 
 pub fn get_doc_name(
     doc_id: ReflectableUuid,
-    pkv: &Res<PkvStore>,
+    store: &Res<DocStore>,
     app_state: &Res<AppState>,
 ) -> String {
     if let Some(doc) = app_state.docs.get(&doc_id) {
         return doc.name.clone();
     }
-    if let Ok(names) = pkv.get::<HashMap<ReflectableUuid, String>>("names") {
+    if let Some(names) = stored_doc_names(store) {
         if let Some(name) = names.get(&doc_id) {
             return name.clone();
         }
@@ -11,8 +11,8 @@ use crate::{
 };
 
 use super::{
-    ui_helpers::{Drawing, InteractiveNode, MainPanel, TwoPointsDrawType},
-    NodeInteraction, NodeInteractionType, UiState,
+    ui_helpers::{drawing_path, Drawing, InteractiveNode, MainPanel, TwoPointsDrawType},
+    AddCheckpoint, AddDrawing, NodeInteraction, NodeInteractionType, UiState,
 };
 
 #[path = "../../macros.rs"]
@@ -50,6 +50,7 @@ pub fn set_focus_drawing(
     mut node_interaction_events: EventReader<NodeInteraction>,
     mut ui_state: ResMut<UiState>,
     drawing_container_q: Query<&Drawing<(String, Color)>, With<Drawing<(String, Color)>>>,
+    mut checkpoint_events: EventWriter<AddCheckpoint>,
 ) {
     for event in node_interaction_events.iter() {
         if let Ok(drawing) = drawing_container_q.get(event.entity) {
@@ -68,8 +69,11 @@ pub fn set_focus_drawing(
                 ui_state.entity_to_draw_hold = Some(drawing.id);
             }
         }
-        if event.node_interaction_type == NodeInteractionType::LeftMouseRelease {
+        if event.node_interaction_type == NodeInteractionType::LeftMouseRelease
+            && ui_state.entity_to_draw_hold.is_some()
+        {
             ui_state.entity_to_draw_hold = None;
+            checkpoint_events.send_default();
         }
     }
 }
@@ -91,6 +95,7 @@ pub fn drawing_two_points(
         With<Drawing<(String, Color)>>,
     >,
     interaction_query: Query<&Interaction, (Changed<Interaction>, With<MainPanel>)>,
+    mut checkpoint_events: EventWriter<AddCheckpoint>,
 ) {
     if *previous_draw_mode != ui_state.drawing_two_points_mode.clone()
         || ui_state.entity_to_draw_selected.is_some()
@@ -259,6 +264,7 @@ pub fn drawing_two_points(
             }
 
             if end.is_some() {
+                checkpoint_events.send_default();
                 *drawing_entity = None;
                 *start = None;
                 *end = None;
@@ -282,6 +288,7 @@ pub fn drawing(
     >,
     mut app_state: ResMut<AppState>,
     mut z_index_local: Local<f32>,
+    mut checkpoint_events: EventWriter<AddCheckpoint>,
 ) {
     if ui_state.entity_to_draw_hold.is_some() || ui_state.entity_to_draw_selected.is_some() {
         *holding_state = None;
@@ -316,7 +323,9 @@ pub fn drawing(
         if buttons.just_released(MouseButton::Left) {
             *holding_state = None;
             primary_window.cursor.icon = CursorIcon::Default;
-            ui_state.entity_to_draw = None;
+            if ui_state.entity_to_draw.take().is_some() {
+                checkpoint_events.send_default();
+            }
         }
 
         if let Some(holding_time) = *holding_state {
@@ -331,15 +340,7 @@ pub fn drawing(
                                         continue;
                                     }
                                     drawing_line.points.push(pos);
-                                    let mut path_builder = PathBuilder::new();
-                                    let mut points_iter = drawing_line.points.iter();
-                                    let start = points_iter.next().unwrap();
-                                    path_builder.move_to(*start);
-                                    path_builder.line_to(*start);
-                                    for point in points_iter {
-                                        path_builder.line_to(*point);
-                                    }
-                                    *path = path_builder.build();
+                                    *path = drawing_path(&drawing_line.points);
                                 }
                             }
                         } else {
@@ -374,6 +375,42 @@ pub fn drawing(
     }
 }
 
+pub fn create_new_drawing(
+    mut commands: Commands,
+    mut events: EventReader<AddDrawing<(String, Color)>>,
+    mut app_state: ResMut<AppState>,
+    mut z_index_local: Local<f32>,
+    mut checkpoint_events: EventWriter<AddCheckpoint>,
+) {
+    for event in events.iter() {
+        let Some(tab) = app_state
+            .current_document
+            .and_then(|doc_id| app_state.docs.get_mut(&doc_id))
+            .and_then(|doc| doc.tabs.iter_mut().find(|tab| tab.is_active))
+        else {
+            continue;
+        };
+        *z_index_local += 0.01 % f32::MAX;
+        tab.z_index += *z_index_local;
+        let drawing = &event.drawing;
+        commands.spawn((
+            ShapeBundle {
+                path: drawing_path(&drawing.points),
+                transform: Transform::from_xyz(drawing.x, drawing.y, tab.z_index),
+                ..Default::default()
+            },
+            Stroke::new(drawing.drawing_color.1, drawing.width),
+            Drawing {
+                id: drawing.id,
+                points: drawing.points.clone(),
+                drawing_color: drawing.drawing_color.clone(),
+            },
+            InteractiveNode,
+        ));
+        checkpoint_events.send_default();
+    }
+}
+
 pub fn update_drawing_position(
     mut cursor_moved_events: EventReader<CursorMoved>,
     camera_q: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
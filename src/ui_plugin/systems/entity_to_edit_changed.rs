@@ -106,7 +106,10 @@ pub fn entity_to_edit_changed(
                             current_edit: 0,
                         };
                         let text = get_cosmic_text(cosmic_edit.editor.buffer());
-                        raw_text.last_text = text.clone();
+                        // assigning marks the text changed, which marks the document unsaved
+                        if raw_text.last_text != text {
+                            raw_text.last_text = text.clone();
+                        }
                         let markdown_theme = BevyMarkdownTheme {
                             code_theme: theme.code_theme.clone(),
                             code_default_lang: theme.code_default_lang.clone(),
@@ -173,7 +176,10 @@ pub fn entity_to_edit_changed(
                             current_edit: 0,
                         };
                         let text = get_cosmic_text(cosmic_edit.editor.buffer());
-                        raw_text.last_text = text.clone();
+                        // assigning marks the text changed, which marks the document unsaved
+                        if raw_text.last_text != text {
+                            raw_text.last_text = text.clone();
+                        }
                         let markdown_theme = BevyMarkdownTheme {
                             code_theme: theme.code_theme.clone(),
                             code_default_lang: theme.code_default_lang.clone(),
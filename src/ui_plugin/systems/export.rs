@@ -0,0 +1,299 @@
+use bevy::{prelude::*, window::PrimaryWindow};
+use bevy_cosmic_edit::CosmicFont;
+
+#[cfg(not(target_arch = "wasm32"))]
+use super::ui_helpers::{
+    spawn_modal, CopyTabOutline, ExportTab, ModalTop, PngExportOption, PngExportOptions,
+    PngExportToggle,
+};
+#[cfg(not(target_arch = "wasm32"))]
+use super::{MainPanel, ModalAction};
+use crate::formats::dot::tab_to_dot;
+use crate::formats::html::doc_to_html;
+use crate::formats::json_canvas::tab_to_canvas;
+use crate::formats::mermaid::tab_to_mermaid;
+use crate::formats::node_export_bounds;
+use crate::formats::opml::tab_to_opml;
+use crate::formats::outline::{tab_to_outline, OutlineStyle};
+use crate::formats::png::{export_font_system, tab_to_png, PngOptions};
+use crate::formats::svg::tab_to_svg;
+use crate::notifications::{Notification, VeloError};
+use crate::resources::{
+    AppState, ExportFormat, ExportTabRequest, ExportTarget, FontSystemState, PngExport,
+    SaveTabRequest,
+};
+use crate::themes::Theme;
+use crate::utils::export_doc;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::utils::ReflectableUuid;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::UiState;
+
+pub fn should_export_tab(request: Option<Res<ExportTabRequest>>) -> bool {
+    request.is_some()
+}
+
+pub fn remove_export_tab_request(world: &mut World) {
+    world.remove_resource::<ExportTabRequest>().unwrap();
+}
+
+/// Opens the export modal from the menu buttons, command+shift+E exports to SVG.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn export_tab_handler(
+    mut commands: Commands,
+    query: Query<(&Interaction, &ExportTab), Changed<Interaction>>,
+    input: Res<Input<KeyCode>>,
+    mut ui_state: ResMut<UiState>,
+    main_panel_query: Query<Entity, With<MainPanel>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    mut cosmic_fonts: ResMut<Assets<CosmicFont>>,
+    font_system_state: Res<FontSystemState>,
+    theme: Res<Theme>,
+) {
+    #[cfg(target_os = "macos")]
+    let command = input.any_pressed([KeyCode::SuperLeft, KeyCode::SuperRight]);
+    #[cfg(not(target_os = "macos"))]
+    let command = input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    let shift = input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    let shortcut =
+        (command && shift && input.just_pressed(KeyCode::E) && ui_state.modal_id.is_none())
+            .then_some(ExportFormat::Svg);
+    let clicked = query
+        .iter()
+        .find(|(interaction, _)| **interaction == Interaction::Pressed)
+        .map(|(_, export_tab)| export_tab.format);
+    let Some(format) = clicked.or(shortcut) else {
+        return;
+    };
+    let id = ReflectableUuid::generate();
+    let selection = ui_state.entity_to_edit;
+    *ui_state = UiState::default();
+    commands.insert_resource(bevy_cosmic_edit::ActiveEditor { entity: None });
+    ui_state.modal_id = Some(id);
+    let entity = spawn_modal(
+        &mut commands,
+        &theme,
+        &mut cosmic_fonts,
+        font_system_state.0.clone().unwrap(),
+        windows.single(),
+        id,
+        ModalAction::ExportTab(format),
+    );
+    if format == ExportFormat::Png {
+        commands.entity(entity).insert(PngExportOptions {
+            selection,
+            ..default()
+        });
+    }
+    commands.entity(main_panel_query.single()).add_child(entity);
+}
+
+/// Switches the PNG options of the export modal, highlighting the ones that are on.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn png_export_toggle_handler(
+    mut toggles: Query<(&Interaction, &PngExportToggle, &mut BorderColor), Changed<Interaction>>,
+    mut modals: Query<(&ModalTop, &mut PngExportOptions)>,
+    theme: Res<Theme>,
+) {
+    for (interaction, toggle, mut border_color) in toggles.iter_mut() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        for (modal, mut options) in modals.iter_mut() {
+            if modal.id != toggle.id {
+                continue;
+            }
+            let on = match toggle.option {
+                PngExportOption::Transparent => {
+                    options.export.transparent = !options.export.transparent;
+                    options.export.transparent
+                }
+                // without a selected note the whole tab is exported
+                PngExportOption::Selection => {
+                    options.export.node = match options.export.node {
+                        Some(_) => None,
+                        None => options.selection,
+                    };
+                    options.export.node.is_some()
+                }
+            };
+            *border_color = if on {
+                theme.selected_node_border
+            } else {
+                theme.btn_border
+            }
+            .into();
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn copy_tab_outline_handler(
+    mut commands: Commands,
+    query: Query<&Interaction, (Changed<Interaction>, With<CopyTabOutline>)>,
+    app_state: Res<AppState>,
+) {
+    if query
+        .iter()
+        .any(|interaction| *interaction == Interaction::Pressed)
+    {
+        request_tab_export(
+            &mut commands,
+            &app_state,
+            ExportTarget::Clipboard,
+            ExportFormat::Markdown(OutlineStyle::List),
+            PngExport::default(),
+        );
+    }
+}
+
+/// Saves the active tab first so that the export sees its latest state.
+pub fn request_tab_export(
+    commands: &mut Commands,
+    app_state: &AppState,
+    target: ExportTarget,
+    format: ExportFormat,
+    png: PngExport,
+) {
+    let Some(doc_id) = app_state.current_document else {
+        return;
+    };
+    let Some(tab) = app_state
+        .docs
+        .get(&doc_id)
+        .and_then(|doc| doc.tabs.iter().find(|tab| tab.is_active))
+    else {
+        return;
+    };
+    commands.insert_resource(SaveTabRequest {
+        doc_id,
+        tab_id: tab.id,
+    });
+    commands.insert_resource(ExportTabRequest {
+        doc_id,
+        tab_id: tab.id,
+        target,
+        format,
+        png,
+    });
+}
+
+pub fn export_tab(
+    request: Res<ExportTabRequest>,
+    app_state: Res<AppState>,
+    theme: Res<Theme>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    mut cosmic_fonts: ResMut<Assets<CosmicFont>>,
+    font_system_state: Res<FontSystemState>,
+    mut notifications: EventWriter<Notification>,
+) {
+    let Some(tab) = app_state
+        .docs
+        .get(&request.doc_id)
+        .and_then(|doc| doc.tabs.iter().find(|tab| tab.id == request.tab_id))
+    else {
+        notifications.send(VeloError::DocNotFound(request.doc_id).into());
+        return;
+    };
+    let contents = match request.format {
+        ExportFormat::Svg => tab_to_svg(tab.history.head(), &app_state.images, &theme).into_bytes(),
+        ExportFormat::Png => {
+            let mut bundled_fonts = None;
+            let font_system = match font_system_state
+                .0
+                .as_ref()
+                .and_then(|handle| cosmic_fonts.get_mut(handle))
+            {
+                Some(font) => &mut font.0,
+                None => bundled_fonts.insert(export_font_system()),
+            };
+            // render at the resolution of the screen so the image is as sharp as the canvas
+            let options = PngOptions {
+                scale: windows
+                    .get_single()
+                    .map_or(1., |window| window.scale_factor() as f32),
+                background: request.png.transparent.then_some(Color::NONE),
+                region: request
+                    .png
+                    .node
+                    .and_then(|id| node_export_bounds(tab.history.head(), id.0)),
+            };
+            match tab_to_png(
+                tab.history.head(),
+                &app_state.images,
+                &theme,
+                font_system,
+                options,
+            ) {
+                Ok(png) => png,
+                Err(err) => {
+                    notifications.send(VeloError::Export(err.to_string()).into());
+                    return;
+                }
+            }
+        }
+        ExportFormat::Markdown(style) => tab_to_outline(tab.history.head(), style).into_bytes(),
+        ExportFormat::Mermaid => tab_to_mermaid(tab.history.head()).into_bytes(),
+        ExportFormat::Dot => tab_to_dot(tab.history.head(), &theme).into_bytes(),
+        ExportFormat::JsonCanvas => tab_to_canvas(tab.history.head(), &theme).into_bytes(),
+        ExportFormat::Opml => tab_to_opml(tab.history.head(), &tab.name).into_bytes(),
+        ExportFormat::Html => {
+            let html = export_doc(request.doc_id, &app_state).and_then(|doc| {
+                doc_to_html(&doc, &theme).map_err(|err| VeloError::Export(err.to_string()))
+            });
+            match html {
+                Ok(html) => html.into_bytes(),
+                Err(err) => {
+                    notifications.send(err.into());
+                    return;
+                }
+            }
+        }
+    };
+    match &request.target {
+        ExportTarget::File(path) => match std::fs::write(path, contents) {
+            Ok(()) => {
+                // the viewer holds every tab of the document
+                let name = match request.format {
+                    ExportFormat::Html => app_state.docs.get(&request.doc_id).map(|doc| &doc.name),
+                    _ => None,
+                };
+                notifications.send(Notification::Info(format!(
+                    "Exported {} to {}",
+                    name.unwrap_or(&tab.name),
+                    path.display()
+                )))
+            }
+            Err(err) => notifications.send(
+                VeloError::WriteFile {
+                    path: path.clone(),
+                    err,
+                }
+                .into(),
+            ),
+        },
+        ExportTarget::Clipboard => {
+            notifications.send(copy_to_clipboard(&tab.name, contents));
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn copy_to_clipboard(tab_name: &str, contents: Vec<u8>) -> Notification {
+    let copied = String::from_utf8(contents)
+        .map_err(|err| err.to_string())
+        .and_then(|text| {
+            arboard::Clipboard::new()
+                .and_then(|mut clipboard| clipboard.set_text(text))
+                .map_err(|err| err.to_string())
+        });
+    match copied {
+        Ok(()) => Notification::Info(format!("Copied {} to clipboard", tab_name)),
+        Err(err) => VeloError::Clipboard(err).into(),
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn copy_to_clipboard(_tab_name: &str, _contents: Vec<u8>) -> Notification {
+    VeloError::Clipboard("not supported in the browser".to_string()).into()
+}
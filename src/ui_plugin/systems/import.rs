@@ -0,0 +1,201 @@
+use bevy::prelude::*;
+#[cfg(not(target_arch = "wasm32"))]
+use bevy::window::PrimaryWindow;
+#[cfg(not(target_arch = "wasm32"))]
+use bevy_cosmic_edit::CosmicFont;
+
+#[cfg(not(target_arch = "wasm32"))]
+use super::ui_helpers::{spawn_modal, ImportTab, PasteTab};
+use super::{AddDrawing, AddRect, CreateArrow};
+#[cfg(not(target_arch = "wasm32"))]
+use super::{MainPanel, ModalAction};
+use crate::components::MainCamera;
+use crate::formats::csv::csv_to_tab;
+use crate::formats::dot::dot_to_tab;
+use crate::formats::excalidraw::excalidraw_to_tab;
+use crate::formats::json_canvas::canvas_to_tab;
+use crate::formats::mermaid::mermaid_to_tab;
+use crate::formats::opml::opml_to_tab;
+use crate::formats::outline::outline_to_tab;
+use crate::formats::{snapshot_bounds, FormatError, ThemeColors};
+use crate::notifications::{Notification, VeloError};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::resources::FontSystemState;
+use crate::resources::{ImportFormat, ImportSource, ImportTabRequest};
+use crate::themes::Theme;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::utils::ReflectableUuid;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::UiState;
+use crate::{DrawingJsonNode, JsonNode, JsonNodeText};
+
+pub fn should_import_tab(request: Option<Res<ImportTabRequest>>) -> bool {
+    request.is_some()
+}
+
+pub fn remove_import_tab_request(world: &mut World) {
+    world.remove_resource::<ImportTabRequest>().unwrap();
+}
+
+/// Opens the import modal from the menu, or imports the clipboard right away.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn import_tab_handler(
+    mut commands: Commands,
+    query: Query<(&Interaction, &ImportTab), Changed<Interaction>>,
+    paste_query: Query<(&Interaction, &PasteTab), Changed<Interaction>>,
+    mut ui_state: ResMut<UiState>,
+    main_panel_query: Query<Entity, With<MainPanel>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    mut cosmic_fonts: ResMut<Assets<CosmicFont>>,
+    font_system_state: Res<FontSystemState>,
+    theme: Res<Theme>,
+) {
+    if let Some((_, paste_tab)) = paste_query
+        .iter()
+        .find(|(interaction, _)| **interaction == Interaction::Pressed)
+    {
+        commands.insert_resource(ImportTabRequest {
+            source: ImportSource::Clipboard,
+            format: paste_tab.format,
+        });
+        return;
+    }
+    let Some(format) = query
+        .iter()
+        .find(|(interaction, _)| **interaction == Interaction::Pressed)
+        .map(|(_, import_tab)| import_tab.format)
+    else {
+        return;
+    };
+    let id = ReflectableUuid::generate();
+    *ui_state = UiState::default();
+    commands.insert_resource(bevy_cosmic_edit::ActiveEditor { entity: None });
+    ui_state.modal_id = Some(id);
+    let entity = spawn_modal(
+        &mut commands,
+        &theme,
+        &mut cosmic_fonts,
+        font_system_state.0.clone().unwrap(),
+        windows.single(),
+        id,
+        ModalAction::ImportTab(format),
+    );
+    commands.entity(main_panel_query.single()).add_child(entity);
+}
+
+/// Adds the imported nodes and arrows to the active tab, centered on the view.
+pub fn import_into_tab(
+    request: Res<ImportTabRequest>,
+    theme: Res<Theme>,
+    camera_query: Query<&Transform, With<MainCamera>>,
+    mut add_rect: EventWriter<AddRect<(String, Color)>>,
+    mut create_arrow: EventWriter<CreateArrow>,
+    mut add_drawing: EventWriter<AddDrawing<(String, Color)>>,
+    mut notifications: EventWriter<Notification>,
+) {
+    let text = match read_source(&request.source) {
+        Ok(text) => text,
+        Err(err) => {
+            notifications.send(err.into());
+            return;
+        }
+    };
+    let snapshot = match request.format {
+        ImportFormat::MarkdownOutline => Ok(outline_to_tab(&text, &theme)),
+        ImportFormat::Mermaid => mermaid_to_tab(&text, &theme),
+        ImportFormat::Dot => dot_to_tab(&text, &theme),
+        ImportFormat::JsonCanvas => canvas_to_tab(&text, &theme),
+        ImportFormat::Excalidraw => excalidraw_to_tab(&text, &theme),
+        ImportFormat::Opml => opml_to_tab(&text, &theme),
+        ImportFormat::Csv => csv_to_tab(&text, &theme),
+    };
+    let (snapshot, bounds) = match snapshot.and_then(|snapshot| {
+        let bounds = snapshot_bounds(&snapshot).ok_or(FormatError::Empty)?;
+        Ok((snapshot, bounds))
+    }) {
+        Ok(imported) => imported,
+        Err(err) => {
+            notifications.send(VeloError::from(err).into());
+            return;
+        }
+    };
+    let center = camera_query
+        .get_single()
+        .map_or(Vec2::ZERO, |transform| transform.translation.truncate());
+    let offset = center - (bounds.min + bounds.max) / 2.;
+
+    let colors = ThemeColors::new(&theme);
+    for node in &snapshot.nodes {
+        let color = colors.get(&node.bg_color).unwrap_or(theme.node_bg);
+        add_rect.send(AddRect {
+            node: JsonNode {
+                id: node.id,
+                node_type: node.node_type.clone(),
+                x: node.x + offset.x,
+                y: node.y + offset.y,
+                z: node.z,
+                width: node.width,
+                height: node.height,
+                text: JsonNodeText {
+                    text: node.text.text.clone(),
+                    pos: node.text.pos.clone(),
+                },
+                bg_color: (node.bg_color.clone(), color),
+                visible: node.visible,
+            },
+            image: None,
+        });
+    }
+    for arrow in &snapshot.arrows {
+        create_arrow.send(CreateArrow {
+            visible: arrow.visible,
+            arrow_type: arrow.arrow_type,
+            start: arrow.start,
+            end: arrow.end,
+        });
+    }
+    for drawing in &snapshot.drawings {
+        let color = colors
+            .get(&drawing.drawing_color)
+            .unwrap_or(theme.drawing_pencil_btn);
+        add_drawing.send(AddDrawing {
+            drawing: DrawingJsonNode {
+                x: drawing.x + offset.x,
+                y: drawing.y + offset.y,
+                z: drawing.z,
+                id: drawing.id,
+                points: drawing.points.clone(),
+                drawing_color: (drawing.drawing_color.clone(), color),
+                width: drawing.width,
+            },
+        });
+    }
+    let message = match snapshot.drawings.len() {
+        0 => format!("Imported {} notes", snapshot.nodes.len()),
+        drawings => format!(
+            "Imported {} notes and {} drawings",
+            snapshot.nodes.len(),
+            drawings
+        ),
+    };
+    notifications.send(Notification::Info(message));
+}
+
+fn read_source(source: &ImportSource) -> Result<String, VeloError> {
+    match source {
+        ImportSource::File(path) => {
+            std::fs::read_to_string(path).map_err(|err| VeloError::ReadFile {
+                path: path.clone(),
+                err,
+            })
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        ImportSource::Clipboard => arboard::Clipboard::new()
+            .and_then(|mut clipboard| clipboard.get_text())
+            .map_err(|err| VeloError::Clipboard(err.to_string())),
+        #[cfg(target_arch = "wasm32")]
+        ImportSource::Clipboard => Err(VeloError::Clipboard(
+            "not supported in the browser".to_string(),
+        )),
+    }
+}
@@ -0,0 +1,83 @@
+use bevy::prelude::*;
+
+use crate::{
+    themes::Theme,
+    ui_plugin::ui_helpers::{DocHeader, TagChipList, TagSuggestion},
+};
+
+/// Strip above the canvas with the tags of the current document and a box to add new ones.
+pub fn add_doc_header(commands: &mut Commands, theme: &Res<Theme>, tag_input: Entity) -> Entity {
+    let header = commands
+        .spawn((
+            NodeBundle {
+                border_color: theme.btn_border.into(),
+                background_color: theme.bottom_panel_bg.into(),
+                style: Style {
+                    border: UiRect::all(Val::Px(1.0)),
+                    position_type: PositionType::Absolute,
+                    left: Val::Percent(0.),
+                    right: Val::Percent(0.),
+                    top: Val::Percent(0.),
+                    width: Val::Percent(100.),
+                    height: Val::Percent(4.),
+                    padding: UiRect::horizontal(Val::Px(10.)),
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Start,
+                    overflow: Overflow::clip(),
+                    ..default()
+                },
+                ..default()
+            },
+            DocHeader,
+        ))
+        .id();
+    let label = commands
+        .spawn(TextBundle::from_section(
+            "Tags:",
+            TextStyle {
+                font_size: theme.font_size,
+                color: theme.font,
+                ..default()
+            },
+        ))
+        .id();
+    let chips = commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    height: Val::Percent(100.),
+                    align_items: AlignItems::Center,
+                    margin: UiRect::horizontal(Val::Px(5.)),
+                    ..default()
+                },
+                ..default()
+            },
+            TagChipList,
+        ))
+        .id();
+    let suggestion = commands
+        .spawn((
+            TextBundle {
+                text: Text::from_section(
+                    "",
+                    TextStyle {
+                        font_size: theme.font_size,
+                        color: theme.font.with_a(0.5),
+                        ..default()
+                    },
+                ),
+                style: Style {
+                    margin: UiRect::left(Val::Px(5.)),
+                    ..default()
+                },
+                ..default()
+            },
+            TagSuggestion,
+        ))
+        .id();
+    commands.entity(header).add_child(label);
+    commands.entity(header).add_child(chips);
+    commands.entity(header).add_child(tag_input);
+    commands.entity(header).add_child(suggestion);
+    header
+}
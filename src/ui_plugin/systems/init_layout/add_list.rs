@@ -1,5 +1,6 @@
-use std::collections::{HashMap, VecDeque};
+use std::collections::HashMap;
 
+use crate::store::DocStore;
 use bevy::{
     a11y::{
         accesskit::{NodeBuilder, Role},
@@ -7,12 +8,12 @@ use bevy::{
     },
     prelude::*,
 };
-use bevy_pkv::PkvStore;
 
 use super::ui_helpers::ScrollingList;
+use crate::migrations::CURRENT_SCHEMA_VERSION;
 use crate::resources::{AppState, LoadDocRequest};
 use crate::ui_plugin::ui_helpers::DocList;
-use crate::utils::ReflectableUuid;
+use crate::utils::{stored_doc_names, ReflectableUuid};
 use crate::{
     components::{Doc, Tab},
     themes::Theme,
@@ -22,13 +23,8 @@ pub fn add_list(
     commands: &mut Commands,
     theme: &Res<Theme>,
     app_state: &mut ResMut<AppState>,
-    pkv: &mut ResMut<PkvStore>,
+    store: &DocStore,
 ) -> Entity {
-    if let Ok(last_saved) = pkv.get::<ReflectableUuid>("last_saved") {
-        app_state.current_document = Some(last_saved);
-        commands.insert_resource(LoadDocRequest { doc_id: last_saved });
-    }
-
     let top = commands
         .spawn(NodeBundle {
             style: Style {
@@ -58,18 +54,31 @@ pub fn add_list(
         ))
         .id();
 
-    if let Ok(names) = pkv.get::<HashMap<ReflectableUuid, String>>("names") {
+    if let Some(names) = stored_doc_names(store) {
+        // the last saved document may have been removed from a workspace folder by hand
+        let doc_id = store
+            .last_saved()
+            .filter(|id| names.contains_key(id))
+            .or_else(|| names.keys().next().cloned());
+        if let Some(doc_id) = doc_id {
+            app_state.current_document = Some(doc_id);
+            commands.insert_resource(LoadDocRequest { doc_id });
+        }
         let keys: Vec<_> = names.keys().collect();
         app_state.doc_list_ui.extend(keys);
+        match store.tags() {
+            Ok(tags) => app_state.tags = tags,
+            Err(err) => error!("Failed to read tags: {}", err),
+        }
     } else {
         let tab_id = ReflectableUuid::generate();
         let tab_name: String = "Tab 1".to_string();
         let tabs = vec![Tab {
             id: tab_id,
             name: tab_name,
-            checkpoints: VecDeque::new(),
             z_index: 1.,
             is_active: true,
+            ..Default::default()
         }];
         let doc_id = ReflectableUuid::generate();
         app_state.docs.insert(
@@ -79,6 +88,8 @@ pub fn add_list(
                 name: "Untitled".to_string(),
                 tabs,
                 tags: vec![],
+                schema_version: CURRENT_SCHEMA_VERSION,
+                images: HashMap::new(),
             },
         );
         app_state.current_document = Some(doc_id);
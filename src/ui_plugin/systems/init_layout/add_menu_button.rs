@@ -19,6 +19,24 @@ pub fn add_menu_button(
         "Save Document" => "\u{e161}",
         "Export To File" => "\u{e2c6}",
         "Import From File" => "\u{e255}",
+        "Export Tab To SVG" => "\u{e3f4}",
+        "Export Tab To PNG" => "\u{e410}",
+        "Export Tab To Markdown Outline" => "\u{e873}",
+        "Copy Tab Outline To Clipboard" => "\u{e14d}",
+        "Import Markdown Outline" => "\u{e890}",
+        "Paste Markdown Outline From Clipboard" => "\u{e14f}",
+        "Export Tab To Mermaid" => "\u{e335}",
+        "Import Mermaid Flowchart" => "\u{e0b6}",
+        "Export Tab To DOT" => "\u{e6dd}",
+        "Import Graphviz DOT" => "\u{e168}",
+        "Export Tab To JSON Canvas" => "\u{e871}",
+        "Import JSON Canvas" => "\u{e8f1}",
+        "Import Excalidraw Scene" => "\u{e3c9}",
+        "Export Tab To OPML" => "\u{e241}",
+        "Import OPML Outline" => "\u{e8ef}",
+        "Import Notes From CSV" => "\u{e85d}",
+        "Paste CSV Notes From Clipboard" => "\u{e14f}",
+        "Export Document To HTML" => "\u{e051}",
         "Import From URL" => "\u{e902}",
         "Save Document to window.velo object" => "\u{e866}",
         "Share Document (copy URL to clipboard)" => "\u{e80d}",
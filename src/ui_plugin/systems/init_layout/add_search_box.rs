@@ -28,11 +28,11 @@ pub fn add_search_box(
             border_color: theme.btn_border.into(),
             background_color: theme.search_box_bg.into(),
             style: Style {
-                width: Val::Percent(80.),
-                height: Val::Percent(8.),
+                width: Val::Percent(60.),
+                height: Val::Percent(100.),
                 border: UiRect::all(Val::Px(1.)),
                 flex_direction: FlexDirection::Column,
-                margin: UiRect::all(Val::Px(5.)),
+                margin: UiRect::right(Val::Px(5.)),
                 ..default()
             },
             ..default()
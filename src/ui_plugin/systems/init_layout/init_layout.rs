@@ -4,18 +4,23 @@ use bevy::prelude::*;
 use bevy::window::PrimaryWindow;
 use bevy_cosmic_edit::{create_cosmic_font_system, CosmicFont, CosmicFontConfig};
 
-use bevy_pkv::PkvStore;
+use crate::store::DocStore;
 
 use super::ui_helpers::{
     self, AddTab, BottomPanel, ButtonAction, ChangeTheme, DrawPencil, LeftPanel, LeftPanelControls,
-    LeftPanelExplorer, MainPanel, Menu, NewDoc, ParticlesEffect, Root, SaveDoc, TextPosMode,
-    TwoPointsDraw,
+    LeftPanelExplorer, MainPanel, Menu, NewDoc, ParticlesEffect, Root, SaveDoc, SaveIndicator,
+    TagFilter, TagInput, TextPosMode, ToastContainer, TooltipPosition, TwoPointsDraw,
+};
+use super::{
+    CommChannels, CopyTabOutline, ExportTab, ExportToFile, ImportFromFile, ImportFromUrl,
+    ImportTab, PasteTab, ShareDoc,
 };
-use super::{CommChannels, ExportToFile, ImportFromFile, ImportFromUrl, ShareDoc};
 use crate::canvas::arrow::components::{ArrowMode, ArrowType};
+use crate::formats::outline::OutlineStyle;
 use crate::resources::{AppState, FontSystemState};
+use crate::resources::{ExportFormat, ImportFormat};
 use crate::themes::Theme;
-use crate::utils::get_theme_key;
+use crate::utils::{get_theme_key, velo_font_bytes};
 use crate::TextPos;
 
 #[path = "../../../macros.rs"]
@@ -74,26 +79,28 @@ use add_search_box::*;
 mod add_visibility;
 use add_visibility::*;
 
+#[path = "add_tag_box.rs"]
+mod add_tag_box;
+use add_tag_box::*;
+
+#[path = "add_doc_header.rs"]
+mod add_doc_header;
+use add_doc_header::*;
+
 // Think about splitting this function to wasm and native
 pub fn init_layout(
     mut commands: Commands,
     mut app_state: ResMut<AppState>,
     asset_server: Res<AssetServer>,
-    mut pkv: ResMut<PkvStore>,
+    store: Res<DocStore>,
     mut cosmic_fonts: ResMut<Assets<CosmicFont>>,
     windows: Query<&Window, With<PrimaryWindow>>,
     mut fonts: ResMut<Assets<Font>>,
     theme: Res<Theme>,
 ) {
     // font setup
-    let font_bytes_regular = include_bytes!("../../../../assets/fonts/VictorMono-Regular.ttf");
-    let font_bytes_bold = include_bytes!("../../../../assets/fonts/VictorMono-Bold.ttf");
-    let font_bytes_italic = include_bytes!("../../../../assets/fonts/VictorMono-Italic.ttf");
-    let font_bytes_bold_italic =
-        include_bytes!("../../../../assets/fonts/VictorMono-BoldItalic.ttf");
-    let font_bytes_medium = include_bytes!("../../../../assets/fonts/VictorMono-Medium.ttf");
-    let font_bytes_semibold = include_bytes!("../../../../assets/fonts/VictorMono-SemiBold.ttf");
-    let font = Font::try_from_bytes(font_bytes_regular.to_vec()).unwrap();
+    let font_bytes = velo_font_bytes();
+    let font = Font::try_from_bytes(font_bytes[0].to_vec()).unwrap();
     let text_style = TextStyle {
         font: TextStyle::default().font,
         font_size: 14.0,
@@ -103,14 +110,7 @@ pub fn init_layout(
     let cosmic_font_config = CosmicFontConfig {
         fonts_dir_path: None,
         load_system_fonts: true,
-        font_bytes: Some(vec![
-            font_bytes_regular,
-            font_bytes_italic,
-            font_bytes_bold,
-            font_bytes_bold_italic,
-            font_bytes_medium,
-            font_bytes_semibold,
-        ]),
+        font_bytes: Some(font_bytes),
     };
     let font_system = create_cosmic_font_system(cosmic_font_config);
     let cosmic_font_handle = cosmic_fonts.add(CosmicFont(font_system));
@@ -155,8 +155,33 @@ pub fn init_layout(
         AddTab,
     );
     commands.entity(bottom_panel).add_child(add_tab);
+    let save_indicator = commands
+        .spawn((
+            TextBundle {
+                text: Text::from_section(
+                    "saved",
+                    TextStyle {
+                        font_size: theme.font_size,
+                        color: theme.font,
+                        ..default()
+                    },
+                ),
+                style: Style {
+                    margin: UiRect {
+                        left: Val::Auto,
+                        right: Val::Px(10.),
+                        ..default()
+                    },
+                    ..default()
+                },
+                ..default()
+            },
+            SaveIndicator,
+        ))
+        .id();
+    commands.entity(bottom_panel).add_child(save_indicator);
 
-    let docs = add_list(&mut commands, &theme, &mut app_state, &mut pkv);
+    let docs = add_list(&mut commands, &theme, &mut app_state, &store);
 
     let root_ui = commands
         .spawn((
@@ -230,6 +255,184 @@ pub fn init_layout(
         ImportFromFile,
     );
     #[cfg(not(target_arch = "wasm32"))]
+    let export_svg = add_menu_button(
+        &mut commands,
+        &theme,
+        "Export Tab To SVG".to_string(),
+        &icon_font,
+        ExportTab {
+            format: ExportFormat::Svg,
+        },
+    );
+    #[cfg(not(target_arch = "wasm32"))]
+    let export_png = add_menu_button(
+        &mut commands,
+        &theme,
+        "Export Tab To PNG".to_string(),
+        &icon_font,
+        ExportTab {
+            format: ExportFormat::Png,
+        },
+    );
+    #[cfg(not(target_arch = "wasm32"))]
+    let export_outline = add_menu_button(
+        &mut commands,
+        &theme,
+        "Export Tab To Markdown Outline".to_string(),
+        &icon_font,
+        ExportTab {
+            format: ExportFormat::Markdown(OutlineStyle::Headings),
+        },
+    );
+    #[cfg(not(target_arch = "wasm32"))]
+    let copy_outline = add_menu_button(
+        &mut commands,
+        &theme,
+        "Copy Tab Outline To Clipboard".to_string(),
+        &icon_font,
+        CopyTabOutline,
+    );
+    #[cfg(not(target_arch = "wasm32"))]
+    let import_outline = add_menu_button(
+        &mut commands,
+        &theme,
+        "Import Markdown Outline".to_string(),
+        &icon_font,
+        ImportTab {
+            format: ImportFormat::MarkdownOutline,
+        },
+    );
+    #[cfg(not(target_arch = "wasm32"))]
+    let paste_outline = add_menu_button(
+        &mut commands,
+        &theme,
+        "Paste Markdown Outline From Clipboard".to_string(),
+        &icon_font,
+        PasteTab {
+            format: ImportFormat::MarkdownOutline,
+        },
+    );
+    #[cfg(not(target_arch = "wasm32"))]
+    let export_mermaid = add_menu_button(
+        &mut commands,
+        &theme,
+        "Export Tab To Mermaid".to_string(),
+        &icon_font,
+        ExportTab {
+            format: ExportFormat::Mermaid,
+        },
+    );
+    #[cfg(not(target_arch = "wasm32"))]
+    let import_mermaid = add_menu_button(
+        &mut commands,
+        &theme,
+        "Import Mermaid Flowchart".to_string(),
+        &icon_font,
+        ImportTab {
+            format: ImportFormat::Mermaid,
+        },
+    );
+    #[cfg(not(target_arch = "wasm32"))]
+    let export_dot = add_menu_button(
+        &mut commands,
+        &theme,
+        "Export Tab To DOT".to_string(),
+        &icon_font,
+        ExportTab {
+            format: ExportFormat::Dot,
+        },
+    );
+    #[cfg(not(target_arch = "wasm32"))]
+    let import_dot = add_menu_button(
+        &mut commands,
+        &theme,
+        "Import Graphviz DOT".to_string(),
+        &icon_font,
+        ImportTab {
+            format: ImportFormat::Dot,
+        },
+    );
+    #[cfg(not(target_arch = "wasm32"))]
+    let export_canvas = add_menu_button(
+        &mut commands,
+        &theme,
+        "Export Tab To JSON Canvas".to_string(),
+        &icon_font,
+        ExportTab {
+            format: ExportFormat::JsonCanvas,
+        },
+    );
+    #[cfg(not(target_arch = "wasm32"))]
+    let import_canvas = add_menu_button(
+        &mut commands,
+        &theme,
+        "Import JSON Canvas".to_string(),
+        &icon_font,
+        ImportTab {
+            format: ImportFormat::JsonCanvas,
+        },
+    );
+    #[cfg(not(target_arch = "wasm32"))]
+    let import_excalidraw = add_menu_button(
+        &mut commands,
+        &theme,
+        "Import Excalidraw Scene".to_string(),
+        &icon_font,
+        ImportTab {
+            format: ImportFormat::Excalidraw,
+        },
+    );
+    #[cfg(not(target_arch = "wasm32"))]
+    let export_opml = add_menu_button(
+        &mut commands,
+        &theme,
+        "Export Tab To OPML".to_string(),
+        &icon_font,
+        ExportTab {
+            format: ExportFormat::Opml,
+        },
+    );
+    #[cfg(not(target_arch = "wasm32"))]
+    let import_opml = add_menu_button(
+        &mut commands,
+        &theme,
+        "Import OPML Outline".to_string(),
+        &icon_font,
+        ImportTab {
+            format: ImportFormat::Opml,
+        },
+    );
+    #[cfg(not(target_arch = "wasm32"))]
+    let import_csv = add_menu_button(
+        &mut commands,
+        &theme,
+        "Import Notes From CSV".to_string(),
+        &icon_font,
+        ImportTab {
+            format: ImportFormat::Csv,
+        },
+    );
+    #[cfg(not(target_arch = "wasm32"))]
+    let paste_csv = add_menu_button(
+        &mut commands,
+        &theme,
+        "Paste CSV Notes From Clipboard".to_string(),
+        &icon_font,
+        PasteTab {
+            format: ImportFormat::Csv,
+        },
+    );
+    #[cfg(not(target_arch = "wasm32"))]
+    let export_html = add_menu_button(
+        &mut commands,
+        &theme,
+        "Export Document To HTML".to_string(),
+        &icon_font,
+        ExportTab {
+            format: ExportFormat::Html,
+        },
+    );
+    #[cfg(not(target_arch = "wasm32"))]
     let import_url = add_menu_button(
         &mut commands,
         &theme,
@@ -253,6 +456,42 @@ pub fn init_layout(
     commands.entity(menu).add_child(import_file);
     #[cfg(not(target_arch = "wasm32"))]
     commands.entity(menu).add_child(import_url);
+    #[cfg(not(target_arch = "wasm32"))]
+    commands.entity(menu).add_child(export_svg);
+    #[cfg(not(target_arch = "wasm32"))]
+    commands.entity(menu).add_child(export_png);
+    #[cfg(not(target_arch = "wasm32"))]
+    commands.entity(menu).add_child(export_outline);
+    #[cfg(not(target_arch = "wasm32"))]
+    commands.entity(menu).add_child(copy_outline);
+    #[cfg(not(target_arch = "wasm32"))]
+    commands.entity(menu).add_child(import_outline);
+    #[cfg(not(target_arch = "wasm32"))]
+    commands.entity(menu).add_child(paste_outline);
+    #[cfg(not(target_arch = "wasm32"))]
+    commands.entity(menu).add_child(export_mermaid);
+    #[cfg(not(target_arch = "wasm32"))]
+    commands.entity(menu).add_child(import_mermaid);
+    #[cfg(not(target_arch = "wasm32"))]
+    commands.entity(menu).add_child(export_dot);
+    #[cfg(not(target_arch = "wasm32"))]
+    commands.entity(menu).add_child(import_dot);
+    #[cfg(not(target_arch = "wasm32"))]
+    commands.entity(menu).add_child(export_canvas);
+    #[cfg(not(target_arch = "wasm32"))]
+    commands.entity(menu).add_child(import_canvas);
+    #[cfg(not(target_arch = "wasm32"))]
+    commands.entity(menu).add_child(import_excalidraw);
+    #[cfg(not(target_arch = "wasm32"))]
+    commands.entity(menu).add_child(export_opml);
+    #[cfg(not(target_arch = "wasm32"))]
+    commands.entity(menu).add_child(import_opml);
+    #[cfg(not(target_arch = "wasm32"))]
+    commands.entity(menu).add_child(import_csv);
+    #[cfg(not(target_arch = "wasm32"))]
+    commands.entity(menu).add_child(paste_csv);
+    #[cfg(not(target_arch = "wasm32"))]
+    commands.entity(menu).add_child(export_html);
     if app_state.github_token.is_some() {
         let share_doc = add_menu_button(
             &mut commands,
@@ -265,7 +504,7 @@ pub fn init_layout(
     }
     #[cfg(target_arch = "wasm32")]
     commands.entity(menu).add_child(set_window_prop);
-    let theme_key = get_theme_key(&pkv);
+    let theme_key = get_theme_key(&store);
     let theme_msg = if theme_key == "light" {
         "Enable dark theme (restart is required for now)".to_string()
     } else {
@@ -337,8 +576,46 @@ pub fn init_layout(
         ))
         .id();
 
+    let (tag_input, tag_input_text) = add_tag_box(
+        &mut commands,
+        &theme,
+        &mut cosmic_fonts,
+        cosmic_font_handle.clone(),
+        primary_window.scale_factor() as f32,
+        Style {
+            width: Val::Percent(15.),
+            height: Val::Percent(80.),
+            ..default()
+        },
+        "Add a tag, Tab completes it",
+        TooltipPosition::Bottom,
+    );
+    commands.entity(tag_input_text).insert(TagInput);
+    let doc_header = add_doc_header(&mut commands, &theme, tag_input);
+
+    let toasts = commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    right: Val::Px(10.),
+                    top: Val::Percent(6.),
+                    max_width: Val::Percent(40.),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::End,
+                    ..default()
+                },
+                z_index: ZIndex::Global(10),
+                ..default()
+            },
+            ToastContainer,
+        ))
+        .id();
+
     commands.entity(right_panel).add_child(main_panel);
+    commands.entity(right_panel).add_child(doc_header);
     commands.entity(right_panel).add_child(bottom_panel);
+    commands.entity(right_panel).add_child(toasts);
 
     let left_panel_controls = commands
         .spawn((
@@ -357,14 +634,40 @@ pub fn init_layout(
             LeftPanelControls,
         ))
         .id();
+    let filters = commands
+        .spawn(NodeBundle {
+            style: Style {
+                width: Val::Percent(80.),
+                height: Val::Percent(8.),
+                margin: UiRect::all(Val::Px(5.)),
+                ..default()
+            },
+            ..default()
+        })
+        .id();
     #[cfg(not(target_arch = "wasm32"))]
     let search_box = add_search_box(
+        &mut commands,
+        &theme,
+        &mut cosmic_fonts,
+        cosmic_font_handle.clone(),
+        primary_window.scale_factor() as f32,
+    );
+    let (tag_filter, tag_filter_text) = add_tag_box(
         &mut commands,
         &theme,
         &mut cosmic_fonts,
         cosmic_font_handle,
         primary_window.scale_factor() as f32,
+        Style {
+            flex_grow: 1.,
+            height: Val::Percent(100.),
+            ..default()
+        },
+        "Filter documents by tags, separated by commas",
+        TooltipPosition::Top,
     );
+    commands.entity(tag_filter_text).insert(TagFilter);
     let left_panel_explorer = commands
         .spawn((
             NodeBundle {
@@ -382,7 +685,9 @@ pub fn init_layout(
         ))
         .id();
     #[cfg(not(target_arch = "wasm32"))]
-    commands.entity(left_panel_explorer).add_child(search_box);
+    commands.entity(filters).add_child(search_box);
+    commands.entity(filters).add_child(tag_filter);
+    commands.entity(left_panel_explorer).add_child(filters);
     commands.entity(left_panel_explorer).add_child(docs);
 
     commands.entity(left_panel).add_child(left_panel_controls);
@@ -8,7 +8,7 @@ use bevy::{
 use bevy_cosmic_edit::{
     get_cosmic_text, get_text_spans, ActiveEditor, CosmicEdit, CosmicEditHistory, EditHistoryItem,
 };
-use bevy_prototype_lyon::prelude::{PathBuilder, ShapeBundle, Stroke};
+use bevy_prototype_lyon::prelude::{ShapeBundle, Stroke};
 use cosmic_text::Edit;
 #[cfg(not(target_arch = "wasm32"))]
 use image::*;
@@ -18,13 +18,14 @@ use uuid::Uuid;
 
 use crate::{
     components::MainCamera,
-    resources::{LoadTabRequest, SaveTabRequest},
+    journal::{Journal, JournalOp},
+    resources::{CheckpointStep, LoadTabRequest, SaveTabRequest},
     themes::Theme,
     utils::{bevy_color_to_cosmic, ReflectableUuid},
-    AddRect, JsonNode, JsonNodeText, NodeType, UiState,
+    AddCheckpoint, AddRect, JsonNode, JsonNodeText, NodeType, UiState,
 };
 
-use super::ui_helpers::{Drawing, EditableText, InteractiveNode, VeloNode};
+use super::ui_helpers::{drawing_path, Drawing, EditableText, InteractiveNode, VeloNode};
 use crate::resources::{AppState, SaveDocRequest};
 
 #[path = "../../macros.rs"]
@@ -51,6 +52,9 @@ pub fn keyboard_input_system(
         With<Drawing<(String, Color)>>,
     >,
     velo_node_query: Query<(Entity, &VeloNode)>,
+    mut checkpoint_events: EventWriter<AddCheckpoint>,
+    active_editor: Res<ActiveEditor>,
+    mut journal: Option<ResMut<Journal>>,
 ) {
     let camera_transform = camera_proj_query.single_mut();
     let x = camera_transform.translation.x;
@@ -62,10 +66,12 @@ pub fn keyboard_input_system(
     #[cfg(not(target_os = "macos"))]
     let command = input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
     let shift = input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    // Ctrl+Z and friends belong to the text editor while one is active
+    let canvas_shortcuts = active_editor.entity.is_none();
     if command && input.just_pressed(KeyCode::C) {
-        if ui_state.entity_to_draw_selected.is_some() {
+        if let Some(entity_to_draw_selected) = ui_state.entity_to_draw_selected {
             for (drawing, gt) in &mut drawing_q.iter_mut() {
-                if drawing.id == ui_state.entity_to_draw_selected.unwrap() {
+                if drawing.id == entity_to_draw_selected {
                     *copied_drawing = Some((drawing.clone(), gt.affine().translation.z));
                 }
             }
@@ -77,15 +83,7 @@ pub fn keyboard_input_system(
         insert_from_clipboard(&mut images, &mut events, x, y, scale_factor, &theme);
 
         if let Some((copied_drawing, z_index)) = copied_drawing.clone() {
-            let mut path_builder = PathBuilder::new();
-            let mut points_iter = copied_drawing.points.iter();
-            let start = points_iter.next().unwrap();
-            path_builder.move_to(*start);
-            path_builder.line_to(*start);
-            for point in points_iter {
-                path_builder.line_to(*point);
-            }
-            let path = path_builder.build();
+            let path = drawing_path(&copied_drawing.points);
             commands.spawn((
                 ShapeBundle {
                     path,
@@ -115,13 +113,34 @@ pub fn keyboard_input_system(
                 });
             }
         }
-    } else if command && input.just_pressed(KeyCode::L) {
+    } else if command
+        && canvas_shortcuts
+        && (input.just_pressed(KeyCode::Y) || shift && input.just_pressed(KeyCode::Z))
+    {
+        if let Some(current_doc) = app_state.docs.get(&app_state.current_document.unwrap()) {
+            if let Some(active_tab) = current_doc.tabs.iter().find(|t| t.is_active) {
+                commands.insert_resource(LoadTabRequest {
+                    doc_id: app_state.current_document.unwrap(),
+                    tab_id: active_tab.id,
+                    step: CheckpointStep::Redo,
+                });
+            }
+        }
+    } else if command
+        && canvas_shortcuts
+        && (input.just_pressed(KeyCode::L) || input.just_pressed(KeyCode::Z))
+    {
         if let Some(current_doc) = app_state.docs.get(&app_state.current_document.unwrap()) {
             if let Some(active_tab) = current_doc.tabs.iter().find(|t| t.is_active) {
+                // capture edits made since the last checkpoint, so they can be redone
+                commands.insert_resource(SaveTabRequest {
+                    doc_id: app_state.current_document.unwrap(),
+                    tab_id: active_tab.id,
+                });
                 commands.insert_resource(LoadTabRequest {
                     doc_id: app_state.current_document.unwrap(),
                     tab_id: active_tab.id,
-                    drop_last_checkpoint: true,
+                    step: CheckpointStep::Undo,
                 });
             }
         }
@@ -139,6 +158,7 @@ pub fn keyboard_input_system(
                     pos: crate::TextPos::Center,
                 },
                 bg_color: pair_struct!(theme.paper_node_bg),
+                visible: true,
                 ..Default::default()
             },
             image: None,
@@ -158,6 +178,7 @@ pub fn keyboard_input_system(
                     pos: crate::TextPos::Center,
                 },
                 bg_color: pair_struct!(theme.node_bg),
+                visible: true,
                 ..default()
             },
             image: None,
@@ -177,6 +198,7 @@ pub fn keyboard_input_system(
                     pos: crate::TextPos::Center,
                 },
                 bg_color: pair_struct!(theme.node_bg),
+                visible: true,
                 ..default()
             },
             image: None,
@@ -184,11 +206,17 @@ pub fn keyboard_input_system(
         input.release_all()
     } else if input.just_pressed(KeyCode::Delete) {
         if let Some(id) = ui_state.entity_to_edit {
+            let mut deleted = false;
             for (entity, node) in velo_node_query.iter() {
                 if node.id == id {
                     commands.entity(entity).despawn_recursive();
+                    deleted = true;
                 }
             }
+            // one undo step for the whole deletion
+            if deleted {
+                checkpoint_events.send_default();
+            }
         }
         input.release_all()
     } else {
@@ -224,14 +252,28 @@ pub fn keyboard_input_system(
                     *ui_state = UiState::default();
                 }
                 if let Some(doc_id) = ui_state.doc_to_edit {
+                    let name = get_cosmic_text(cosmic_edit.editor.buffer());
                     let doc = app_state.docs.get_mut(&doc_id).unwrap();
-                    doc.name = get_cosmic_text(cosmic_edit.editor.buffer())
+                    if doc.name != name {
+                        doc.name = name.clone();
+                        app_state.docs.mark_dirty(&doc_id);
+                        if let Some(journal) = &mut journal {
+                            journal.append(doc_id, JournalOp::RenameDoc { name });
+                        }
+                    }
                 }
                 if let Some(tab_id) = ui_state.tab_to_edit {
                     if let Some(doc_id) = app_state.current_document {
+                        let name = get_cosmic_text(cosmic_edit.editor.buffer());
                         let doc = app_state.docs.get_mut(&doc_id).unwrap();
                         if let Some(tab) = doc.tabs.iter_mut().find(|x| x.id == tab_id) {
-                            tab.name = get_cosmic_text(cosmic_edit.editor.buffer())
+                            if tab.name != name {
+                                tab.name = name.clone();
+                                app_state.docs.mark_dirty(&doc_id);
+                                if let Some(journal) = &mut journal {
+                                    journal.append(doc_id, JournalOp::RenameTab { tab_id, name });
+                                }
+                            }
                         }
                     }
                 }
@@ -1,31 +1,29 @@
-use base64::{engine::general_purpose, Engine};
-use bevy::{
-    prelude::*,
-    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
-    window::PrimaryWindow,
-};
+use bevy::{prelude::*, window::PrimaryWindow};
 use bevy_cosmic_edit::CosmicFont;
-use bevy_prototype_lyon::prelude::{PathBuilder, ShapeBundle, Stroke};
+use bevy_prototype_lyon::prelude::{ShapeBundle, Stroke};
 
 use super::{
     ui_helpers::{
-        add_tab, spawn_sprite_node, BottomPanel, Drawing, InteractiveNode, NodeMeta, TabContainer,
-        VeloNode,
+        add_tab, drawing_path, spawn_sprite_node, BottomPanel, Drawing, InteractiveNode, NodeMeta,
+        TabContainer, VeloNode,
     },
-    DeleteDoc, DeleteTab, DrawingJsonNode,
+    DeleteDoc, DeleteTab,
+};
+use crate::notifications::{Notification, VeloError};
+use crate::{
+    canvas::arrow::events::CreateArrow,
+    utils::{decode_image, load_doc_to_memory},
 };
-use crate::{canvas::arrow::events::CreateArrow, utils::load_doc_to_memory};
 use crate::{
     canvas::{arrow::components::ArrowMeta, shadows::CustomShadowMaterial},
-    resources::{FontSystemState, LoadTabRequest},
+    resources::{CheckpointStep, FontSystemState, LoadTabRequest},
     themes::Theme,
 };
 
 use crate::resources::{AppState, LoadDocRequest};
+use crate::store::DocStore;
 use crate::utils::ReflectableUuid;
-use crate::{JsonNode, UiState};
-use bevy_pkv::PkvStore;
-use image::{load_from_memory_with_format, ImageFormat};
+use crate::UiState;
 use serde_json::{Map, Value};
 
 pub fn should_load_doc(request: Option<Res<LoadDocRequest>>) -> bool {
@@ -49,7 +47,7 @@ pub fn load_doc(
     mut app_state: ResMut<AppState>,
     mut commands: Commands,
     mut bottom_panel: Query<Entity, With<BottomPanel>>,
-    mut pkv: ResMut<PkvStore>,
+    store: Res<DocStore>,
     asset_server: Res<AssetServer>,
     mut tabs_query: Query<Entity, With<TabContainer>>,
     mut delete_doc: Query<(&mut Visibility, &DeleteDoc), With<DeleteDoc>>,
@@ -57,6 +55,7 @@ pub fn load_doc(
     mut cosmic_fonts: ResMut<Assets<CosmicFont>>,
     font_system_state: ResMut<FontSystemState>,
     windows: Query<&Window, With<PrimaryWindow>>,
+    mut notifications: EventWriter<Notification>,
 ) {
     let primary_window = windows.single();
     let scale_factor = primary_window.scale_factor() as f32;
@@ -69,13 +68,18 @@ pub fn load_doc(
             *visibility = Visibility::Hidden;
         }
     }
-    load_doc_to_memory(doc_id, &mut app_state, &mut pkv);
+    if let Err(err) = load_doc_to_memory(doc_id, &mut app_state, &store) {
+        notifications.send(err.into());
+    }
+    let Some(doc) = app_state.docs.get(&doc_id) else {
+        return;
+    };
 
     let mut tabs = vec![];
     for entity in tabs_query.iter_mut() {
         commands.entity(entity).despawn_recursive();
     }
-    for tab in app_state.docs.get_mut(&doc_id).unwrap().tabs.iter() {
+    for tab in doc.tabs.iter() {
         let tab_view: Entity = add_tab(
             &mut commands,
             &mut cosmic_fonts,
@@ -92,7 +96,7 @@ pub fn load_doc(
             commands.insert_resource(LoadTabRequest {
                 doc_id,
                 tab_id: tab.id,
-                drop_last_checkpoint: false,
+                step: CheckpointStep::Current,
             });
         }
     }
@@ -108,7 +112,7 @@ pub fn load_tab(
     mut ui_state: ResMut<UiState>,
     mut commands: Commands,
     mut res_images: ResMut<Assets<Image>>,
-    mut create_arrow: EventWriter<CreateArrow>,
+    (mut create_arrow, mut notifications): (EventWriter<CreateArrow>, EventWriter<Notification>),
     mut delete_tab: Query<(&mut Visibility, &DeleteTab), (With<DeleteTab>, Without<ArrowMeta>)>,
     mut cosmic_fonts: ResMut<Assets<CosmicFont>>,
     font_system_state: ResMut<FontSystemState>,
@@ -145,53 +149,40 @@ pub fn load_tab(
             *visibility = Visibility::Hidden;
         }
     }
-    for tab in app_state.docs.get_mut(&doc_id).unwrap().tabs.iter_mut() {
+    let app_state = &mut *app_state;
+    let Some(doc) = app_state.docs.get_mut(&doc_id) else {
+        notifications.send(VeloError::DocNotFound(doc_id).into());
+        return;
+    };
+    let mut changed = false;
+    for tab in doc.tabs.iter_mut() {
         if tab.id == request.tab_id {
-            if tab.checkpoints.is_empty() {
-                break;
-            }
-
-            let json = if request.drop_last_checkpoint && tab.checkpoints.len() > 1 {
-                tab.checkpoints.pop_back().unwrap()
-            } else {
-                tab.checkpoints.back().unwrap().clone()
+            changed = match request.step {
+                CheckpointStep::Current => false,
+                CheckpointStep::Undo => tab.history.undo(),
+                CheckpointStep::Redo => tab.history.redo(),
             };
-            let mut json: Value = serde_json::from_str(&json).unwrap();
-            let images = json["images"].as_object().unwrap();
-            let nodes = json["nodes"].as_array().unwrap();
-            for node in nodes.iter() {
-                let json_node: JsonNode<String> = serde_json::from_value(node.clone()).unwrap();
-                let image: Option<Handle<Image>> = match images.get(&json_node.id.to_string()) {
-                    Some(image) => {
-                        let image_bytes = general_purpose::STANDARD
-                            .decode(image.as_str().unwrap().as_bytes())
-                            .unwrap();
-                        let img =
-                            load_from_memory_with_format(&image_bytes, ImageFormat::Png).unwrap();
-                        let size: Extent3d = Extent3d {
-                            width: img.width(),
-                            height: img.height(),
-                            ..Default::default()
-                        };
-                        let image = Image::new(
-                            size,
-                            TextureDimension::D2,
-                            img.into_bytes(),
-                            TextureFormat::Rgba8UnormSrgb,
-                        );
-                        let image_handle = res_images.add(image);
-                        Some(image_handle)
+            let snapshot = tab.history.head().clone();
+            for json_node in snapshot.nodes {
+                // a broken image or color drops only itself, not the whole tab
+                let image = match snapshot
+                    .images
+                    .get(&json_node.id)
+                    .and_then(|hash| app_state.images.get(hash))
+                    .map(|image| decode_image(image))
+                {
+                    Some(Ok(image)) => Some(res_images.add(image)),
+                    Some(Err(err)) => {
+                        notifications.send(err.into());
+                        None
                     }
                     None => None,
                 };
-                let theme_color = local_theme
-                    .as_ref()
-                    .unwrap()
-                    .get(json_node.bg_color.as_str())
-                    .unwrap();
-                let pair_bg_color = (
+                let pair_bg_color = theme_color(
+                    local_theme.as_ref().unwrap(),
                     json_node.bg_color,
-                    serde_json::from_value(theme_color.clone()).unwrap(),
+                    ("node_bg", theme.node_bg),
+                    &mut notifications,
                 );
                 let _ = spawn_sprite_node(
                     &mut commands,
@@ -216,9 +207,7 @@ pub fn load_tab(
                 );
             }
 
-            let arrows = json["arrows"].as_array_mut().unwrap();
-            for arrow in arrows.iter() {
-                let arrow_meta: ArrowMeta = serde_json::from_value(arrow.clone()).unwrap();
+            for arrow_meta in snapshot.arrows {
                 create_arrow.send(CreateArrow {
                     visible: arrow_meta.visible,
                     start: arrow_meta.start,
@@ -226,27 +215,13 @@ pub fn load_tab(
                     arrow_type: arrow_meta.arrow_type,
                 });
             }
-            let drawings = json["drawings"].as_array_mut().unwrap();
-            for drawing in drawings.iter() {
-                let drawing_json_node: DrawingJsonNode<String> =
-                    serde_json::from_value(drawing.clone()).unwrap();
-                let mut path_builder = PathBuilder::new();
-                let mut points_iter = drawing_json_node.points.iter();
-                let start = points_iter.next().unwrap();
-                path_builder.move_to(*start);
-                path_builder.line_to(*start);
-                for point in points_iter {
-                    path_builder.line_to(*point);
-                }
-                let path = path_builder.build();
-                let theme_color = local_theme
-                    .as_ref()
-                    .unwrap()
-                    .get(drawing_json_node.drawing_color.as_str())
-                    .unwrap();
-                let pair_color = (
+            for drawing_json_node in snapshot.drawings {
+                let path = drawing_path(&drawing_json_node.points);
+                let pair_color = theme_color(
+                    local_theme.as_ref().unwrap(),
                     drawing_json_node.drawing_color,
-                    serde_json::from_value(theme_color.clone()).unwrap(),
+                    ("drawing_pencil_btn", theme.drawing_pencil_btn),
+                    &mut notifications,
                 );
                 commands.spawn((
                     ShapeBundle {
@@ -270,4 +245,26 @@ pub fn load_tab(
             break;
         }
     }
+    if changed {
+        app_state.docs.mark_dirty(&doc_id);
+    }
+}
+
+/// The theme color named `key`, or `default` when the theme has no such color.
+fn theme_color(
+    theme: &Map<String, Value>,
+    key: String,
+    default: (&str, Color),
+    notifications: &mut EventWriter<Notification>,
+) -> (String, Color) {
+    match theme
+        .get(key.as_str())
+        .and_then(|value| serde_json::from_value(value.clone()).ok())
+    {
+        Some(color) => (key, color),
+        None => {
+            notifications.send(VeloError::UnknownColor(key).into());
+            (default.0.to_string(), default.1)
+        }
+    }
 }
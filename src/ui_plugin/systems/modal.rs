@@ -1,19 +1,29 @@
-use std::collections::HashMap;
 use std::fs::canonicalize;
 use std::path::PathBuf;
 
+use crate::store::DocStore;
+use async_channel::Sender;
 use bevy::prelude::*;
 use bevy::tasks::IoTaskPool;
 use bevy_cosmic_edit::{get_cosmic_text, ActiveEditor, CosmicEdit};
-use bevy_pkv::PkvStore;
 use cosmic_text::Edit;
 use linkify::{LinkFinder, LinkKind};
 
-use super::ui_helpers::{ModalCancel, ModalConfirm, ModalTop};
-use super::{CommChannels, EditableText, ModalAction, TabContainer};
+use super::ui_helpers::{ModalCancel, ModalConfirm, ModalTop, PngExportOptions};
+use super::{
+    discard_journal, recover_journal, request_tab_export, CommChannels, EditableText, ModalAction,
+    TabContainer,
+};
+use crate::bundle::{bundle_to_json, has_bundle_extension, is_bundle};
 use crate::components::Doc;
-use crate::resources::{AppState, LoadDocRequest, LoadTabRequest, SaveDocRequest};
-use crate::utils::ReflectableUuid;
+use crate::journal::{Journal, JournalOp, JournalRecovery};
+use crate::migrations::doc_from_json;
+use crate::notifications::{Notification, NotificationChannel, VeloError};
+use crate::resources::{
+    AppState, CheckpointStep, ExportTarget, HistoryDepth, ImportSource, ImportTabRequest,
+    LoadDocRequest, LoadTabRequest, SaveDocRequest,
+};
+use crate::utils::{remove_stored_doc, stored_doc_names, take_doc_images, ReflectableUuid};
 use crate::UiState;
 
 pub fn cancel_modal(
@@ -24,11 +34,18 @@ pub fn cancel_modal(
     >,
     mut state: ResMut<UiState>,
     query: Query<(Entity, &ModalTop), With<ModalTop>>,
+    mut journal: Option<ResMut<Journal>>,
+    recovery: Option<Res<JournalRecovery>>,
 ) {
     for (interaction, path_modal_cancel) in interaction_query.iter_mut() {
         if let Interaction::Pressed = interaction {
             for (entity, path_modal_top) in query.iter() {
                 if path_modal_cancel.id == path_modal_top.id {
+                    if path_modal_top.action == ModalAction::RecoverJournal {
+                        if let (Some(journal), Some(recovery)) = (&mut journal, &recovery) {
+                            discard_journal(&mut commands, journal, recovery);
+                        }
+                    }
                     commands.entity(entity).despawn_recursive();
                     state.modal_id = None;
                 }
@@ -40,14 +57,20 @@ pub fn cancel_modal(
 fn delete_doc(
     app_state: &mut ResMut<AppState>,
     commands: &mut Commands,
-    pkv: &mut ResMut<PkvStore>,
+    store: &mut ResMut<DocStore>,
+    notifications: &mut EventWriter<Notification>,
 ) {
     let current_document = app_state.current_document.unwrap();
     let id_to_remove = current_document;
     app_state.docs.remove(&current_document);
-    remove_from_storage(pkv, id_to_remove, app_state.current_document.unwrap());
-    app_state.current_document = app_state.docs.keys().next().cloned();
+    if let Err(err) = remove_from_storage(store, id_to_remove, app_state.current_document.unwrap())
+    {
+        notifications.send(err.into());
+    }
+    let most_recent = app_state.docs.keys().next_back().cloned();
+    app_state.current_document = most_recent;
     app_state.doc_list_ui.remove(&id_to_remove);
+    app_state.tags.remove(&id_to_remove);
     commands.insert_resource(LoadDocRequest {
         doc_id: app_state.current_document.unwrap(),
     });
@@ -69,6 +92,7 @@ fn delete_tab(
     app_state: &mut ResMut<AppState>,
     commands: &mut Commands,
     query_container: &mut Query<(Entity, &TabContainer), With<TabContainer>>,
+    journal: &mut Option<ResMut<Journal>>,
 ) {
     let current_document = app_state.current_document.unwrap();
     let tab_id = app_state
@@ -94,7 +118,7 @@ fn delete_tab(
     }
     let index = app_state
         .docs
-        .get_mut(&current_document)
+        .get(&current_document)
         .unwrap()
         .tabs
         .iter()
@@ -114,10 +138,15 @@ fn delete_tab(
         .last_mut()
         .unwrap();
     last_tab.is_active = true;
+    let last_tab_id = last_tab.id;
+    app_state.docs.mark_dirty(&current_document);
+    if let Some(journal) = journal {
+        journal.append(current_document, JournalOp::DeleteTab { tab_id });
+    }
     commands.insert_resource(LoadTabRequest {
         doc_id: current_document,
-        tab_id: last_tab.id,
-        drop_last_checkpoint: false,
+        tab_id: last_tab_id,
+        step: CheckpointStep::Current,
     });
 }
 
@@ -125,21 +154,29 @@ pub fn load_doc_handler(
     mut commands: Commands,
     mut app_state: ResMut<AppState>,
     comm_channels: Res<CommChannels>,
-    pkv: Res<PkvStore>,
+    store: Res<DocStore>,
+    mut notifications: EventWriter<Notification>,
 ) {
-    if comm_channels.rx.is_empty() {
+    let Ok(r) = comm_channels.rx.try_recv() else {
         return;
-    }
-    let r = comm_channels
-        .rx
-        .try_recv()
-        .expect("Failed to receive document string");
-    let import_document: Doc = serde_json::from_str(&r).expect("Failed to deserialize document");
-    if let Ok(docs) = pkv.get::<HashMap<ReflectableUuid, Doc>>("docs") {
-        if docs.contains_key(&import_document.id) {
+    };
+    let mut import_document: Doc = match doc_from_json(&r) {
+        Ok(doc) => doc,
+        Err(err) => {
+            notifications.send(VeloError::Import(err).into());
+            return;
+        }
+    };
+    if let Some(names) = stored_doc_names(&store) {
+        if names.contains_key(&import_document.id) {
+            notifications.send(Notification::Info(format!(
+                "{} is already imported",
+                import_document.name
+            )));
             return;
         }
     }
+    take_doc_images(&mut import_document, &mut app_state);
     app_state.current_document = Some(import_document.id);
     app_state.doc_list_ui.insert(import_document.id);
     app_state
@@ -158,16 +195,21 @@ pub fn confirm_modal(
     >,
     mut app_state: ResMut<AppState>,
     mut ui_state: ResMut<UiState>,
-    query_top: Query<(Entity, &ModalTop), With<ModalTop>>,
+    query_top: Query<(Entity, &ModalTop, Option<&PngExportOptions>), With<ModalTop>>,
     mut tab_query_container: Query<(Entity, &TabContainer), With<TabContainer>>,
-    mut pkv: ResMut<PkvStore>,
+    mut store: ResMut<DocStore>,
     input: Res<Input<KeyCode>>,
     mut query_path: Query<(&CosmicEdit, &EditableText), With<EditableText>>,
     comm_channels: Res<CommChannels>,
+    mut journal: Option<ResMut<Journal>>,
+    recovery: Option<Res<JournalRecovery>>,
+    history_depth: Res<HistoryDepth>,
+    notification_channel: Res<NotificationChannel>,
+    mut notifications: EventWriter<Notification>,
 ) {
     for (interaction, path_modal_confirm) in interaction_query.iter_mut() {
         if let Interaction::Pressed = interaction {
-            for (entity, path_modal_top) in query_top.iter() {
+            for (entity, path_modal_top, png_options) in query_top.iter() {
                 if path_modal_confirm.id == path_modal_top.id {
                     for (editor, editable_text) in query_path.iter_mut() {
                         let text = get_cosmic_text(editor.editor.buffer());
@@ -181,34 +223,35 @@ pub fn confirm_modal(
                                     break;
                                 }
                                 ModalAction::LoadFromFile => {
-                                    if let Ok(path) = canonicalize(PathBuf::from(text.trim())) {
-                                        let json = std::fs::read_to_string(path)
-                                            .expect("Error reading document from file");
-                                        let cc = comm_channels.tx.clone();
-                                        cc.try_send(json).unwrap()
+                                    if let Err(err) = load_from_file(text.trim(), &comm_channels) {
+                                        notifications.send(err.into());
                                     }
                                 }
                                 ModalAction::LoadFromUrl => {
-                                    let pool = IoTaskPool::get();
-                                    let url = text.trim();
-                                    let mut finder = LinkFinder::new();
-                                    finder.kinds(&[LinkKind::Url]);
-                                    let links: Vec<_> = finder.links(url).collect();
-                                    if links.len() == 1 {
-                                        let url = links.first().unwrap().as_str().to_owned();
-                                        let cc = comm_channels.tx.clone();
-                                        let task = pool.spawn(async move {
-                                            let request = ehttp::Request::get(url);
-                                            ehttp::fetch(request, move |result| {
-                                                let json_string = result.unwrap().text().unwrap();
-                                                cc.try_send(json_string).unwrap();
-                                            });
-                                        });
-                                        task.detach();
-                                    }
+                                    load_from_url(
+                                        text.trim(),
+                                        &comm_channels,
+                                        &notification_channel,
+                                    );
+                                }
+                                ModalAction::ImportTab(format) => {
+                                    commands.insert_resource(ImportTabRequest {
+                                        source: ImportSource::File(PathBuf::from(text.trim())),
+                                        format,
+                                    });
+                                }
+                                ModalAction::ExportTab(format) => {
+                                    request_tab_export(
+                                        &mut commands,
+                                        &app_state,
+                                        ExportTarget::File(PathBuf::from(text.trim())),
+                                        format,
+                                        png_options.map_or_else(default, |options| options.export),
+                                    );
                                 }
                                 ModalAction::DeleteDocument => {}
                                 ModalAction::DeleteTab => {}
+                                ModalAction::RecoverJournal => {}
                             }
                         }
                     }
@@ -216,11 +259,36 @@ pub fn confirm_modal(
                         ModalAction::SaveToFile => {}
                         ModalAction::LoadFromFile => {}
                         ModalAction::LoadFromUrl => {}
+                        ModalAction::ExportTab(_) => {}
+                        ModalAction::ImportTab(_) => {}
                         ModalAction::DeleteDocument => {
-                            delete_doc(&mut app_state, &mut commands, &mut pkv);
+                            delete_doc(
+                                &mut app_state,
+                                &mut commands,
+                                &mut store,
+                                &mut notifications,
+                            );
                         }
                         ModalAction::DeleteTab => {
-                            delete_tab(&mut app_state, &mut commands, &mut tab_query_container);
+                            delete_tab(
+                                &mut app_state,
+                                &mut commands,
+                                &mut tab_query_container,
+                                &mut journal,
+                            );
+                        }
+                        ModalAction::RecoverJournal => {
+                            if let (Some(journal), Some(recovery)) = (&mut journal, &recovery) {
+                                recover_journal(
+                                    &mut app_state,
+                                    &mut commands,
+                                    &mut store,
+                                    journal,
+                                    recovery,
+                                    history_depth.0,
+                                    &mut notifications,
+                                );
+                            }
                         }
                     }
                 }
@@ -231,7 +299,7 @@ pub fn confirm_modal(
         }
     }
     if input.just_pressed(KeyCode::Return) {
-        for (entity, path_modal_top) in query_top.iter() {
+        for (entity, path_modal_top, png_options) in query_top.iter() {
             if Some(path_modal_top.id) == ui_state.modal_id {
                 for (editor, editable_text) in query_path.iter_mut() {
                     let text = get_cosmic_text(editor.editor.buffer());
@@ -245,34 +313,31 @@ pub fn confirm_modal(
                                 break;
                             }
                             ModalAction::LoadFromFile => {
-                                if let Ok(path) = canonicalize(PathBuf::from(text.trim())) {
-                                    let json = std::fs::read_to_string(path)
-                                        .expect("Error reading document from file");
-                                    let cc = comm_channels.tx.clone();
-                                    cc.try_send(json).unwrap()
+                                if let Err(err) = load_from_file(text.trim(), &comm_channels) {
+                                    notifications.send(err.into());
                                 }
                             }
                             ModalAction::LoadFromUrl => {
-                                let pool = IoTaskPool::get();
-                                let url = text.trim();
-                                let mut finder = LinkFinder::new();
-                                finder.kinds(&[LinkKind::Url]);
-                                let links: Vec<_> = finder.links(url).collect();
-                                if links.len() == 1 {
-                                    let url = links.first().unwrap().as_str().to_owned();
-                                    let cc = comm_channels.tx.clone();
-                                    let task = pool.spawn(async move {
-                                        let request = ehttp::Request::get(url);
-                                        ehttp::fetch(request, move |result| {
-                                            let json_string = result.unwrap().text().unwrap();
-                                            cc.try_send(json_string).unwrap();
-                                        });
-                                    });
-                                    task.detach();
-                                }
+                                load_from_url(text.trim(), &comm_channels, &notification_channel);
+                            }
+                            ModalAction::ImportTab(format) => {
+                                commands.insert_resource(ImportTabRequest {
+                                    source: ImportSource::File(PathBuf::from(text.trim())),
+                                    format,
+                                });
+                            }
+                            ModalAction::ExportTab(format) => {
+                                request_tab_export(
+                                    &mut commands,
+                                    &app_state,
+                                    ExportTarget::File(PathBuf::from(text.trim())),
+                                    format,
+                                    png_options.map_or_else(default, |options| options.export),
+                                );
                             }
                             ModalAction::DeleteDocument => {}
                             ModalAction::DeleteTab => {}
+                            ModalAction::RecoverJournal => {}
                         }
                     }
                 }
@@ -280,11 +345,34 @@ pub fn confirm_modal(
                     ModalAction::SaveToFile => {}
                     ModalAction::LoadFromFile => {}
                     ModalAction::LoadFromUrl => {}
+                    ModalAction::ExportTab(_) => {}
+                    ModalAction::ImportTab(_) => {}
                     ModalAction::DeleteDocument => {
-                        delete_doc(&mut app_state, &mut commands, &mut pkv);
+                        delete_doc(
+                            &mut app_state,
+                            &mut commands,
+                            &mut store,
+                            &mut notifications,
+                        );
                     }
-                    ModalAction::DeleteTab => {
-                        delete_tab(&mut app_state, &mut commands, &mut tab_query_container)
+                    ModalAction::DeleteTab => delete_tab(
+                        &mut app_state,
+                        &mut commands,
+                        &mut tab_query_container,
+                        &mut journal,
+                    ),
+                    ModalAction::RecoverJournal => {
+                        if let (Some(journal), Some(recovery)) = (&mut journal, &recovery) {
+                            recover_journal(
+                                &mut app_state,
+                                &mut commands,
+                                &mut store,
+                                journal,
+                                recovery,
+                                history_depth.0,
+                                &mut notifications,
+                            );
+                        }
                     }
                 }
             }
@@ -296,28 +384,73 @@ pub fn confirm_modal(
 }
 
 fn remove_from_storage(
-    pkv: &mut ResMut<PkvStore>,
+    store: &mut ResMut<DocStore>,
     id_to_remove: ReflectableUuid,
     new_id: ReflectableUuid,
-) {
-    if let Ok(mut docs) = pkv.get::<HashMap<ReflectableUuid, Doc>>("docs") {
-        if docs.remove(&id_to_remove).is_some() {
-            pkv.set("docs", &docs).unwrap();
+) -> Result<(), VeloError> {
+    remove_stored_doc(store, id_to_remove)?;
+    if store.last_saved() == Some(id_to_remove) {
+        if let Err(err) = store.set_last_saved(new_id) {
+            error!("Failed to update last saved document: {}", err);
         }
     }
-    if let Ok(mut tags) = pkv.get::<HashMap<ReflectableUuid, Vec<String>>>("tags") {
-        if tags.remove(&id_to_remove).is_some() {
-            pkv.set("tags", &tags).unwrap();
-        }
-    }
-    if let Ok(mut names) = pkv.get::<HashMap<ReflectableUuid, String>>("names") {
-        if names.remove(&id_to_remove).is_some() {
-            pkv.set("names", &names).unwrap();
-        }
-    }
-    if let Ok(last_saved) = pkv.get::<ReflectableUuid>("last_saved") {
-        if last_saved == id_to_remove {
-            pkv.set("last_saved", &new_id).unwrap();
-        }
+    Ok(())
+}
+
+/// Sends the document at `path` to `load_doc_handler`.
+fn load_from_file(path: &str, comm_channels: &CommChannels) -> Result<(), VeloError> {
+    let path = PathBuf::from(path);
+    let contents =
+        canonicalize(&path)
+            .and_then(std::fs::read)
+            .map_err(|err| VeloError::ReadFile {
+                path: path.clone(),
+                err,
+            })?;
+    let json = match is_bundle(&contents) || has_bundle_extension(&path) {
+        true => bundle_to_json(&contents)?,
+        false => String::from_utf8(contents).map_err(|err| VeloError::ReadFile {
+            path,
+            err: std::io::Error::new(std::io::ErrorKind::InvalidData, err),
+        })?,
+    };
+    send_doc_json(&comm_channels.tx, json)
+}
+
+/// Fetches the document at `url` in the background, failures are reported through `notifications`.
+fn load_from_url(url: &str, comm_channels: &CommChannels, notifications: &NotificationChannel) {
+    let mut finder = LinkFinder::new();
+    finder.kinds(&[LinkKind::Url]);
+    let links: Vec<_> = finder.links(url).collect();
+    if links.len() != 1 {
+        let _ = notifications
+            .tx
+            .try_send(VeloError::Fetch(format!("{} is not a valid url", url)).into());
+        return;
     }
+    let url = links.first().unwrap().as_str().to_owned();
+    let cc = comm_channels.tx.clone();
+    let tx = notifications.tx.clone();
+    let task = IoTaskPool::get().spawn(async move {
+        let request = ehttp::Request::get(url);
+        ehttp::fetch(request, move |result| {
+            let sent = result
+                .map_err(VeloError::Fetch)
+                .and_then(|response| {
+                    response
+                        .text()
+                        .ok_or_else(|| VeloError::Fetch("response is not text".to_string()))
+                })
+                .and_then(|json| send_doc_json(&cc, json));
+            if let Err(err) = sent {
+                let _ = tx.try_send(err.into());
+            }
+        });
+    });
+    task.detach();
+}
+
+fn send_doc_json(tx: &Sender<String>, json: String) -> Result<(), VeloError> {
+    tx.try_send(json)
+        .map_err(|_| VeloError::Fetch("another document is still being loaded".to_string()))
 }
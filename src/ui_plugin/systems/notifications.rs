@@ -0,0 +1,77 @@
+use bevy::prelude::*;
+
+use super::ui_helpers::{Toast, ToastContainer};
+use crate::notifications::{Notification, NotificationChannel};
+use crate::themes::Theme;
+
+const TOAST_SECONDS: f64 = 5.;
+
+pub fn forward_notifications(
+    channel: Res<NotificationChannel>,
+    mut notifications: EventWriter<Notification>,
+) {
+    while let Ok(notification) = channel.rx.try_recv() {
+        notifications.send(notification);
+    }
+}
+
+pub fn show_notifications(
+    mut commands: Commands,
+    mut notifications: EventReader<Notification>,
+    container_query: Query<Entity, With<ToastContainer>>,
+    theme: Res<Theme>,
+    time: Res<Time>,
+) {
+    let Ok(container) = container_query.get_single() else {
+        return;
+    };
+    for notification in notifications.iter() {
+        let (message, border_color) = match notification {
+            Notification::Info(message) => {
+                info!("{}", message);
+                (message.clone(), theme.btn_border)
+            }
+            Notification::Error(err) => {
+                error!("{}", err);
+                (err.to_string(), theme.del_button)
+            }
+        };
+        let toast = commands
+            .spawn((
+                NodeBundle {
+                    background_color: theme.modal_bg.into(),
+                    border_color: border_color.into(),
+                    style: Style {
+                        border: UiRect::all(Val::Px(2.)),
+                        padding: UiRect::all(Val::Px(8.)),
+                        margin: UiRect::bottom(Val::Px(5.)),
+                        ..default()
+                    },
+                    ..default()
+                },
+                Toast {
+                    expires_at: time.elapsed_seconds_f64() + TOAST_SECONDS,
+                },
+            ))
+            .with_children(|toast| {
+                toast.spawn(TextBundle::from_section(
+                    message,
+                    TextStyle {
+                        font_size: theme.font_size,
+                        color: theme.font,
+                        ..default()
+                    },
+                ));
+            })
+            .id();
+        commands.entity(container).add_child(toast);
+    }
+}
+
+pub fn expire_toasts(mut commands: Commands, toasts: Query<(Entity, &Toast)>, time: Res<Time>) {
+    for (entity, toast) in toasts.iter() {
+        if time.elapsed_seconds_f64() >= toast.expires_at {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
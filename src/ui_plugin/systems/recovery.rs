@@ -0,0 +1,143 @@
+use std::path::PathBuf;
+
+use crate::store::DocStore;
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use bevy_cosmic_edit::CosmicFont;
+
+use super::ui_helpers::{spawn_modal, MainPanel};
+use super::ModalAction;
+use crate::journal::{replay, Journal, JournalOp, JournalRecovery};
+use crate::notifications::Notification;
+use crate::resources::{AppState, CheckpointStep, FontSystemState, LoadDocRequest, LoadTabRequest};
+use crate::themes::Theme;
+use crate::utils::{forget_unused_images, load_doc_to_memory, store_doc, ReflectableUuid};
+use crate::UiState;
+
+pub fn open_journal(mut commands: Commands) {
+    let Ok(home_dir) = std::env::var("HOME") else {
+        return;
+    };
+    let path = PathBuf::from(home_dir).join(".velo.journals");
+    match Journal::open(&path) {
+        Ok((journal, pending)) => {
+            commands.insert_resource(journal);
+            if !pending.is_empty() {
+                warn!("Velo did not shut down properly, found unsaved changes");
+                commands.insert_resource(JournalRecovery {
+                    entries: pending,
+                    offered: false,
+                });
+            }
+        }
+        Err(err) => error!("Failed to open journal: {}", err),
+    }
+}
+
+pub fn close_journal(world: &mut World) {
+    if world.resource::<Events<AppExit>>().is_empty() {
+        return;
+    }
+    if let Some(journal) = world.remove_resource::<Journal>() {
+        journal.remove();
+    }
+}
+
+/// Undo and redo are journaled before `load_tab` moves the checkpoint,
+/// replaying them on an unchanged history has the same effect.
+pub fn journal_history_step(request: Res<LoadTabRequest>, journal: Option<ResMut<Journal>>) {
+    let Some(mut journal) = journal else {
+        return;
+    };
+    let tab_id = request.tab_id;
+    match request.step {
+        CheckpointStep::Current => {}
+        CheckpointStep::Undo => journal.append(request.doc_id, JournalOp::Undo { tab_id }),
+        CheckpointStep::Redo => journal.append(request.doc_id, JournalOp::Redo { tab_id }),
+    }
+}
+
+pub fn offer_recovery(
+    mut commands: Commands,
+    mut recovery: ResMut<JournalRecovery>,
+    mut ui_state: ResMut<UiState>,
+    main_panel_query: Query<Entity, With<MainPanel>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    mut cosmic_fonts: ResMut<Assets<CosmicFont>>,
+    font_system_state: ResMut<FontSystemState>,
+    theme: Res<Theme>,
+) {
+    if recovery.offered || ui_state.modal_id.is_some() {
+        return;
+    }
+    let (Ok(main_panel), Some(font_system)) =
+        (main_panel_query.get_single(), font_system_state.0.clone())
+    else {
+        return;
+    };
+    recovery.offered = true;
+    let id = ReflectableUuid::generate();
+    ui_state.modal_id = Some(id);
+    let entity = spawn_modal(
+        &mut commands,
+        &theme,
+        &mut cosmic_fonts,
+        font_system,
+        windows.single(),
+        id,
+        ModalAction::RecoverJournal,
+    );
+    commands.entity(main_panel).add_child(entity);
+}
+
+/// Replays the journal into the affected documents and saves them.
+pub fn recover_journal(
+    app_state: &mut ResMut<AppState>,
+    commands: &mut Commands,
+    store: &mut ResMut<DocStore>,
+    journal: &mut Journal,
+    recovery: &JournalRecovery,
+    history_depth: usize,
+    notifications: &mut EventWriter<Notification>,
+) {
+    let doc_ids = recovery.doc_ids();
+    for doc_id in doc_ids.iter() {
+        if let Err(err) = load_doc_to_memory(*doc_id, app_state, store) {
+            notifications.send(err.into());
+        }
+        let app_state = &mut **app_state;
+        let Some(doc) = app_state.docs.get_mut(doc_id) else {
+            warn!("Journal refers to missing document {}", doc_id.0);
+            continue;
+        };
+        let applied = replay(doc, &recovery.entries, &mut app_state.images, history_depth);
+        app_state.docs.mark_dirty(doc_id);
+        info!("Recovered {} changes of document {}", applied, doc_id.0);
+        let doc = app_state.docs.get(doc_id).unwrap();
+        if let Err(err) = store_doc(store, app_state, doc) {
+            notifications.send(err.into());
+            continue;
+        }
+        let tags = doc.tags.clone();
+        app_state.tags.insert(*doc_id, tags);
+        app_state.docs.mark_clean(doc_id);
+        journal.append(*doc_id, JournalOp::Saved);
+    }
+    forget_unused_images(app_state);
+    if let Some(current_document) = app_state.current_document {
+        if doc_ids.contains(&current_document) {
+            commands.insert_resource(LoadDocRequest {
+                doc_id: current_document,
+            });
+        }
+    }
+    commands.remove_resource::<JournalRecovery>();
+}
+
+pub fn discard_journal(commands: &mut Commands, journal: &mut Journal, recovery: &JournalRecovery) {
+    for doc_id in recovery.doc_ids() {
+        journal.append(doc_id, JournalOp::Saved);
+    }
+    commands.remove_resource::<JournalRecovery>();
+}
@@ -1,6 +1,6 @@
 use super::{
     ui_helpers::{ResizeMarker, VeloShape},
-    NodeInteraction, NodeType, RawText, RedrawArrow, VeloNode,
+    AddCheckpoint, NodeInteraction, NodeType, RawText, RedrawArrow, VeloNode,
 };
 use crate::{
     canvas::{arrow::components::ArrowConnect, shadows::systems::Shadow},
@@ -54,12 +54,14 @@ pub fn resize_entity_start(
 pub fn resize_entity_end(
     mut ui_state: ResMut<UiState>,
     mut node_interaction_events: EventReader<NodeInteraction>,
+    mut checkpoint_events: EventWriter<AddCheckpoint>,
 ) {
     for event in node_interaction_events.iter() {
         if event.node_interaction_type == super::NodeInteractionType::LeftMouseRelease
             && ui_state.entity_to_resize.is_some()
         {
             ui_state.entity_to_resize = None;
+            checkpoint_events.send_default();
         }
     }
 }
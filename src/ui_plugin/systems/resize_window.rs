@@ -4,7 +4,7 @@ use cosmic_text::Edit;
 
 use crate::resources::AppState;
 
-use crate::resources::LoadTabRequest;
+use crate::resources::{CheckpointStep, LoadTabRequest};
 
 use super::ui_helpers::DocListItemButton;
 use super::ui_helpers::TabButton;
@@ -24,7 +24,7 @@ pub fn resize_notificator(
                 commands.insert_resource(LoadTabRequest {
                     doc_id: current_doc.id,
                     tab_id: active_tab.id,
-                    drop_last_checkpoint: false,
+                    step: CheckpointStep::Current,
                 });
             }
         }
@@ -1,22 +1,28 @@
 use base64::{engine::general_purpose, Engine};
-use bevy::prelude::*;
+use bevy::{asset::HandleId, prelude::*};
 
+use crate::store::DocStore;
 use bevy_cosmic_edit::CosmicEdit;
-use bevy_pkv::PkvStore;
 use bevy_prototype_lyon::prelude::Stroke;
 use image::*;
 
-use serde_json::json;
-use std::{collections::HashMap, io::Cursor};
+use std::{collections::HashMap, io::Cursor, path::Path};
 
 use super::ui_helpers::{Drawing, VeloNode, VeloShape};
-use super::{DrawingJsonNode, RawText, SaveStore};
+use super::{AddCheckpoint, DrawingJsonNode, RawText, SaveStore, Saved};
+use crate::bundle::{doc_to_bundle, has_bundle_extension};
 use crate::canvas::arrow::components::ArrowMeta;
-use crate::components::Doc;
+use crate::components::TabSnapshot;
+use crate::history::SnapshotDelta;
+use crate::journal::{Journal, JournalOp};
+use crate::notifications::{Notification, VeloError};
 use crate::resources::SaveDocRequest;
-use crate::resources::{AppState, SaveTabRequest};
-use crate::utils::{load_doc_to_memory, ReflectableUuid};
-use crate::{JsonNode, JsonNodeText, MAX_CHECKPOINTS};
+use crate::resources::{AppState, HistoryDepth, SaveTabRequest};
+use crate::utils::{
+    export_doc, forget_unused_images, get_timestamp, image_hash, load_doc_to_memory, store_doc,
+    ReflectableUuid,
+};
+use crate::{JsonNode, JsonNodeText};
 
 pub fn should_save_doc(request: Option<Res<SaveDocRequest>>) -> bool {
     request.is_some()
@@ -34,18 +40,45 @@ pub fn remove_save_tab_request(world: &mut World) {
     world.remove_resource::<SaveTabRequest>().unwrap();
 }
 
+pub fn add_checkpoint(
+    mut commands: Commands,
+    app_state: Res<AppState>,
+    mut events: EventReader<AddCheckpoint>,
+) {
+    if events.is_empty() {
+        return;
+    }
+    events.clear();
+    let Some(doc_id) = app_state.current_document else {
+        return;
+    };
+    if let Some(current_doc) = app_state.docs.get(&doc_id) {
+        if let Some(active_tab) = current_doc.tabs.iter().find(|t| t.is_active) {
+            commands.insert_resource(SaveTabRequest {
+                doc_id,
+                tab_id: active_tab.id,
+            });
+        }
+    }
+}
+
 pub fn save_doc(
     request: Res<SaveDocRequest>,
     mut app_state: ResMut<AppState>,
-    mut pkv: ResMut<PkvStore>,
+    store: Res<DocStore>,
     mut commands: Commands,
     mut events: EventWriter<SaveStore>,
+    mut notifications: EventWriter<Notification>,
 ) {
     let doc_id = request.doc_id;
 
-    load_doc_to_memory(doc_id, &mut app_state, &mut pkv);
-
-    for tab in app_state.docs.get_mut(&doc_id).unwrap().tabs.iter() {
+    if let Err(err) = load_doc_to_memory(doc_id, &mut app_state, &store) {
+        notifications.send(err.into());
+    }
+    let Some(doc) = app_state.docs.get(&doc_id) else {
+        return;
+    };
+    for tab in doc.tabs.iter() {
         if tab.is_active {
             commands.insert_resource(SaveTabRequest {
                 doc_id,
@@ -61,50 +94,44 @@ pub fn save_doc(
 }
 
 pub fn save_to_store(
-    mut pkv: ResMut<PkvStore>,
+    mut store: ResMut<DocStore>,
     mut app_state: ResMut<AppState>,
     mut events: EventReader<SaveStore>,
+    mut journal: Option<ResMut<Journal>>,
+    mut saved: EventWriter<Saved>,
+    mut notifications: EventWriter<Notification>,
 ) {
     for event in events.iter() {
         let doc_id = event.doc_id;
-        if let Ok(mut docs) = pkv.get::<HashMap<ReflectableUuid, Doc>>("docs") {
-            docs.insert(doc_id, app_state.docs.get(&doc_id).unwrap().clone());
-            pkv.set("docs", &docs).unwrap();
-        } else {
-            let mut docs = HashMap::new();
-            docs.insert(doc_id, app_state.docs.get(&doc_id).unwrap().clone());
-            pkv.set("docs", &docs).unwrap();
+        let Some(doc) = app_state.docs.get(&doc_id) else {
+            notifications.send(VeloError::DocNotFound(doc_id).into());
+            continue;
+        };
+        // the document stays dirty, so it is written again when it is evicted or saved next time
+        if let Err(err) = store_doc(&mut store, &app_state, doc) {
+            notifications.send(err.into());
+            continue;
         }
-        if let Ok(mut tags) = pkv.get::<HashMap<ReflectableUuid, Vec<String>>>("tags") {
-            let doc = app_state.docs.get(&doc_id).unwrap();
-            if let Some(tags) = tags.get_mut(&doc_id) {
-                tags.append(&mut doc.tags.clone());
-            } else {
-                tags.insert(doc.id, doc.tags.clone());
-            }
-            pkv.set("tags", &tags).unwrap();
-        } else {
-            let doc = app_state.docs.get(&doc_id).unwrap();
-            let mut tags = HashMap::new();
-            tags.insert(doc.id, doc.tags.clone());
-            pkv.set("tags", &tags).unwrap();
+        saved.send(Saved { doc_id });
+        let tags = doc.tags.clone();
+        app_state.tags.insert(doc_id, tags);
+        app_state.docs.mark_clean(&doc_id);
+        if let Some(journal) = &mut journal {
+            journal.append(doc_id, JournalOp::Saved);
         }
-        if let Ok(mut names) = pkv.get::<HashMap<ReflectableUuid, String>>("names") {
-            let doc = app_state.docs.get(&doc_id).unwrap();
-            names.insert(doc.id, doc.name.clone());
-            pkv.set("names", &names).unwrap();
-        } else {
-            let doc = app_state.docs.get(&doc_id).unwrap();
-            let mut names = HashMap::new();
-            names.insert(doc.id, doc.name.clone());
-            pkv.set("names", &names).unwrap();
+        if let Err(err) = store.set_last_saved(doc_id) {
+            error!("Failed to update last saved document: {}", err);
         }
-        pkv.set("last_saved", &doc_id).unwrap();
+        forget_unused_images(&mut app_state);
 
         if let Some(path) = event.path.clone() {
-            let current_doc = app_state.docs.get(&doc_id).unwrap().clone();
-            std::fs::write(path, serde_json::to_string_pretty(&current_doc).unwrap())
-                .expect("Error saving current document to file")
+            match write_doc_to_file(doc_id, &app_state, &path) {
+                Ok(()) => notifications.send(Notification::Info(format!(
+                    "Saved document to {}",
+                    path.display()
+                ))),
+                Err(err) => notifications.send(err.into()),
+            }
         }
         #[cfg(not(target_arch = "wasm32"))]
         {
@@ -125,6 +152,46 @@ pub fn save_to_store(
     }
 }
 
+fn write_doc_to_file(
+    doc_id: ReflectableUuid,
+    app_state: &AppState,
+    path: &Path,
+) -> Result<(), VeloError> {
+    let current_doc = export_doc(doc_id, app_state)?;
+    let contents = match has_bundle_extension(path) {
+        true => doc_to_bundle(&current_doc)?,
+        false => serde_json::to_string_pretty(&current_doc)?.into_bytes(),
+    };
+    std::fs::write(path, contents).map_err(|err| VeloError::WriteFile {
+        path: path.to_path_buf(),
+        err,
+    })
+}
+
+/// Writes documents with unsaved changes that were evicted from memory.
+pub fn flush_evicted_docs(
+    mut store: ResMut<DocStore>,
+    mut app_state: ResMut<AppState>,
+    mut journal: Option<ResMut<Journal>>,
+    mut notifications: EventWriter<Notification>,
+) {
+    let evicted = app_state.docs.take_evicted();
+    if evicted.is_empty() {
+        return;
+    }
+    for doc in evicted.iter() {
+        if let Err(err) = store_doc(&mut store, &app_state, doc) {
+            notifications.send(err.into());
+            continue;
+        }
+        app_state.tags.insert(doc.id, doc.tags.clone());
+        if let Some(journal) = &mut journal {
+            journal.append(doc.id, JournalOp::Saved);
+        }
+    }
+    forget_unused_images(&mut app_state);
+}
+
 pub fn save_tab(
     images: Res<Assets<Image>>,
     arrows: Query<(&ArrowMeta, &Visibility), With<ArrowMeta>>,
@@ -137,32 +204,44 @@ pub fn save_tab(
         (&Transform, &Drawing<(String, Color)>, &Stroke),
         With<Drawing<(String, Color)>>,
     >,
+    mut image_hashes: Local<HashMap<HandleId, String>>,
+    history_depth: Res<HistoryDepth>,
+    mut journal: Option<ResMut<Journal>>,
+    mut notifications: EventWriter<Notification>,
 ) {
     #[cfg(not(target_arch = "wasm32"))]
     if let Some(index) = &mut app_state.search_index {
         index.tabs_to_delete.insert(request.tab_id.0);
     }
-    let mut json = json!({
-        "images": {},
-        "nodes": [],
-        "arrows": [],
-        "drawings": []
-    });
-    let json_images = json["images"].as_object_mut().unwrap();
+    let mut snapshot = TabSnapshot::default();
     for (raw_text, cosmic_edit, _) in raw_text_query.iter() {
         if let Some(handle) = cosmic_edit.bg_image.clone() {
-            let image = images.get(&handle).unwrap();
+            // images never change after they are added, so each one is encoded only once
+            if let Some(hash) = image_hashes.get(&handle.id()) {
+                if app_state.images.contains_key(hash) {
+                    snapshot.images.insert(raw_text.id.0, hash.clone());
+                    continue;
+                }
+            }
+            let Some(image) = images.get(&handle) else {
+                notifications.send(VeloError::Image("image of a note is not loaded".into()).into());
+                continue;
+            };
             if let Ok(img) = image.clone().try_into_dynamic() {
                 let mut image_data: Vec<u8> = Vec::new();
                 img.write_to(&mut Cursor::new(&mut image_data), ImageOutputFormat::Png)
                     .unwrap();
-                let res_base64 = general_purpose::STANDARD.encode(image_data);
-                json_images.insert(raw_text.id.0.to_string(), json!(res_base64));
+                let hash = image_hash(&image_data);
+                app_state
+                    .images
+                    .entry(hash.clone())
+                    .or_insert_with(|| general_purpose::STANDARD.encode(image_data));
+                image_hashes.insert(handle.id(), hash.clone());
+                snapshot.images.insert(raw_text.id.0, hash);
             }
         }
     }
 
-    let json_nodes = json["nodes"].as_array_mut().unwrap();
     for (raw_text, cosmic_edit, parent) in raw_text_query.iter() {
         let (border_parent, border) = border_query.get(parent.get()).unwrap();
         let (top_transform, top_visibility) = velo_node_query.get(border_parent.get()).unwrap();
@@ -171,7 +250,7 @@ pub fn save_tab(
         let z = top_transform.translation.z;
         let (width, height) = (cosmic_edit.width, cosmic_edit.height);
         let visible = top_visibility == Visibility::Visible;
-        json_nodes.push(json!(JsonNode {
+        snapshot.nodes.push(JsonNode {
             visible,
             node_type: border.node_type.clone(),
             id: raw_text.id.0,
@@ -183,9 +262,9 @@ pub fn save_tab(
             bg_color: border.pair_color.0.clone(),
             text: JsonNodeText {
                 text: raw_text.last_text.clone(),
-                pos: cosmic_edit.text_pos.clone().into()
+                pos: cosmic_edit.text_pos.clone().into(),
             },
-        }));
+        });
         #[cfg(not(target_arch = "wasm32"))]
         if let Some(index) = &mut app_state.search_index {
             index.node_updates.insert(
@@ -199,54 +278,88 @@ pub fn save_tab(
         }
     }
 
-    let json_arrows = json["arrows"].as_array_mut().unwrap();
     for (arrow_meta, visibility) in arrows.iter() {
         let mut meta = *arrow_meta;
         meta.visible = visibility == Visibility::Visible;
-        json_arrows.push(json!(meta));
+        snapshot.arrows.push(meta);
     }
 
-    let json_drawing = json["drawings"].as_array_mut().unwrap();
     for (transform, drawing, stroke) in drawing_query.iter() {
-        json_drawing.push(json!(DrawingJsonNode {
+        snapshot.drawings.push(DrawingJsonNode {
             x: transform.translation.x,
             y: transform.translation.y,
             z: transform.translation.z,
             width: stroke.options.line_width,
             id: drawing.id,
             points: drawing.points.clone(),
-            drawing_color: drawing.drawing_color.0.clone()
-        }));
+            drawing_color: drawing.drawing_color.0.clone(),
+        });
     }
 
+    // keep query order out of the snapshot so that unchanged tabs compare equal
+    snapshot.nodes.sort_by_key(|node| node.id);
+    snapshot
+        .arrows
+        .sort_by_key(|arrow| (arrow.start.id.0, arrow.end.id.0));
+    snapshot.drawings.sort_by_key(|drawing| drawing.id.0);
+
     let doc_id = request.doc_id;
 
-    for tab in &mut app_state.docs.get_mut(&doc_id).unwrap().tabs {
+    let app_state = &mut *app_state;
+    let mut changed = false;
+    let Some(doc) = app_state.docs.get_mut(&doc_id) else {
+        notifications.send(VeloError::DocNotFound(doc_id).into());
+        return;
+    };
+    for tab in &mut doc.tabs {
         if request.tab_id == tab.id {
-            if (tab.checkpoints.len() as i32) > MAX_CHECKPOINTS {
-                tab.checkpoints.pop_front();
+            let previous = journal.is_some().then(|| tab.history.head().clone());
+            let timestamp = get_timestamp();
+            if !tab.history.push(snapshot, timestamp, history_depth.0) {
+                break;
             }
-            if let Some(last) = tab.checkpoints.back() {
-                if last == &json.to_string() {
-                    break;
-                }
+            changed = true;
+            if let (Some(journal), Some(previous)) = (&mut journal, previous) {
+                let delta = SnapshotDelta::between(&previous, tab.history.head());
+                let images = delta
+                    .images
+                    .values()
+                    .filter_map(|hash| Some((hash.clone(), app_state.images.get(hash)?.clone())))
+                    .collect();
+                journal.append(
+                    doc_id,
+                    JournalOp::Checkpoint {
+                        tab_id: tab.id,
+                        timestamp,
+                        delta: Box::new(delta),
+                        images,
+                    },
+                );
             }
-            tab.checkpoints.push_back(json.to_string());
             break;
         }
     }
+    if changed {
+        app_state.docs.mark_dirty(&doc_id);
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::components::Doc;
+    use crate::history::TabHistory;
+    use crate::store::{DirectoryStore, DocumentStore, MemoryStore, PkvDocumentStore};
+    use crate::utils::ReflectableUuid;
+    use bevy_pkv::PkvStore;
     use tempfile::tempdir;
 
     #[test]
-    /// No PKV with tags
+    /// No stored tags
     fn test_save_doc1() {
         // Setup
         let mut app = App::new();
+        app.add_event::<Notification>();
         app.add_systems(Update, (save_doc, save_to_store.after(save_doc)));
         let temp_dir = tempdir().unwrap();
         let temp_file_path = temp_dir.path().join("test_doc.json");
@@ -259,12 +372,14 @@ mod tests {
                 id: doc_id,
                 name: "test_doc".to_string(),
                 tags: vec!["test_tag".to_string()],
+                schema_version: crate::migrations::CURRENT_SCHEMA_VERSION,
+                images: HashMap::new(),
                 tabs: vec![crate::components::Tab {
                     id: tab_id,
                     is_active: true,
                     name: "Test tab".to_string(),
-                    checkpoints: std::collections::VecDeque::new(),
                     z_index: 1.,
+                    ..Default::default()
                 }],
             },
         );
@@ -274,26 +389,26 @@ mod tests {
         };
         app.insert_resource(request);
         app.add_event::<SaveStore>();
-        PkvStore::new("test", "test").clear().unwrap();
-        app.insert_resource(PkvStore::new("test", "test"));
+        app.add_event::<Saved>();
+        app.insert_resource(DocStore::new(MemoryStore::default()));
         app.insert_resource(app_state);
 
         // Run systems
         app.update();
 
         // Assertions
-        let pkv = app.world.resource::<PkvStore>();
-        let saved_docs: HashMap<ReflectableUuid, Doc> = pkv.get("docs").unwrap();
-        assert_eq!(saved_docs.get(&doc_id).unwrap().name, "test_doc");
-        assert!(saved_docs.get(&doc_id).unwrap().tabs[0].is_active);
-        let saved_tags: HashMap<ReflectableUuid, Vec<String>> = pkv.get("tags").unwrap();
+        let store = app.world.resource::<DocStore>();
+        let saved_doc = store.load_doc(doc_id).unwrap().unwrap();
+        assert_eq!(saved_doc.name, "test_doc");
+        assert!(saved_doc.tabs[0].is_active);
+        let saved_tags = store.tags().unwrap();
         assert_eq!(
             saved_tags.get(&doc_id).unwrap(),
             &vec!["test_tag".to_string()]
         );
-        let saved_names: HashMap<ReflectableUuid, String> = pkv.get("names").unwrap();
+        let saved_names = store.doc_names().unwrap();
         assert_eq!(saved_names.get(&doc_id).unwrap(), "test_doc");
-        assert_eq!(pkv.get::<ReflectableUuid>("last_saved").unwrap(), doc_id);
+        assert_eq!(store.last_saved(), Some(doc_id));
         let file_contents = std::fs::read_to_string(temp_file_path).unwrap();
         let saved_doc: Doc = serde_json::from_str(&file_contents).unwrap();
         assert_eq!(saved_doc.name, "test_doc");
@@ -305,6 +420,7 @@ mod tests {
     fn test_save_doc2() {
         // Setup
         let mut app = App::new();
+        app.add_event::<Notification>();
         app.add_systems(Update, (save_doc, save_to_store.after(save_doc)));
         let temp_dir = tempdir().unwrap();
         let temp_file_path = temp_dir.path().join("test_doc.json");
@@ -317,12 +433,14 @@ mod tests {
                 id: doc_id,
                 name: "test_doc".to_string(),
                 tags: vec!["test_tag_1".to_string()],
+                schema_version: crate::migrations::CURRENT_SCHEMA_VERSION,
+                images: HashMap::new(),
                 tabs: vec![crate::components::Tab {
                     id: tab_id,
                     is_active: true,
                     z_index: 1.,
                     name: "Test tab".to_string(),
-                    checkpoints: std::collections::VecDeque::new(),
+                    ..Default::default()
                 }],
             },
         );
@@ -331,31 +449,31 @@ mod tests {
             path: Some(temp_file_path.clone()),
         };
         app.insert_resource(request);
-        PkvStore::new("test", "test1").clear().unwrap();
-        let mut pkv = PkvStore::new("test", "test1");
+        let mut pkv = PkvStore::new_in_dir(temp_dir.path().join("pkv"));
         let mut tags = HashMap::new();
         tags.insert(ReflectableUuid::generate(), vec!["test_tag_2".to_string()]);
         pkv.set("tags", &tags).unwrap();
         app.add_event::<SaveStore>();
-        app.insert_resource(pkv);
+        app.add_event::<Saved>();
+        app.insert_resource(DocStore::new(PkvDocumentStore::new(pkv)));
         app.insert_resource(app_state);
 
         // Run systems
         app.update();
 
         // Assertions
-        let pkv = app.world.resource::<PkvStore>();
-        let saved_docs: HashMap<ReflectableUuid, Doc> = pkv.get("docs").unwrap();
-        assert_eq!(saved_docs.get(&doc_id).unwrap().name, "test_doc");
-        assert!(saved_docs.get(&doc_id).unwrap().tabs[0].is_active);
-        let saved_tags: HashMap<ReflectableUuid, Vec<String>> = pkv.get("tags").unwrap();
+        let store = app.world.resource::<DocStore>();
+        let saved_doc = store.load_doc(doc_id).unwrap().unwrap();
+        assert_eq!(saved_doc.name, "test_doc");
+        assert!(saved_doc.tabs[0].is_active);
+        let saved_tags = store.tags().unwrap();
         assert_eq!(
             saved_tags.get(&doc_id).unwrap(),
             &vec!["test_tag_1".to_string()]
         );
-        let saved_names: HashMap<ReflectableUuid, String> = pkv.get("names").unwrap();
+        let saved_names = store.doc_names().unwrap();
         assert_eq!(saved_names.get(&doc_id).unwrap(), "test_doc");
-        assert_eq!(pkv.get::<ReflectableUuid>("last_saved").unwrap(), doc_id);
+        assert_eq!(store.last_saved(), Some(doc_id));
         let file_contents = std::fs::read_to_string(temp_file_path).unwrap();
         let saved_doc: Doc = serde_json::from_str(&file_contents).unwrap();
         assert_eq!(saved_doc.name, "test_doc");
@@ -363,29 +481,31 @@ mod tests {
     }
 
     #[test]
-    /// the PKV store already has tags for the document being saved.
+    /// the PKV store already has tags for the document being saved, they are replaced.
     fn test_save_doc3() {
         // Setup
         let mut app = App::new();
+        app.add_event::<Notification>();
         app.add_systems(Update, (save_doc, save_to_store.after(save_doc)));
         let temp_dir = tempdir().unwrap();
         let temp_file_path = temp_dir.path().join("test_doc.json");
         let doc_id = ReflectableUuid::generate();
         let tab_id = ReflectableUuid::generate();
         let mut app_state = AppState::default();
-        let existing_tags = vec!["test_tag_2".to_string(), "test_tag_1".to_string()];
         app_state.docs.insert(
             doc_id,
             Doc {
                 id: doc_id,
                 name: "test_doc".to_string(),
                 tags: vec!["test_tag_1".to_string()],
+                schema_version: crate::migrations::CURRENT_SCHEMA_VERSION,
+                images: HashMap::new(),
                 tabs: vec![crate::components::Tab {
                     id: tab_id,
                     is_active: true,
                     z_index: 1.,
                     name: "Test tab".to_string(),
-                    checkpoints: std::collections::VecDeque::new(),
+                    ..Default::default()
                 }],
             },
         );
@@ -394,13 +514,13 @@ mod tests {
             path: Some(temp_file_path.clone()),
         };
         app.insert_resource(request);
-        PkvStore::new("test", "test3").clear().unwrap();
-        let mut pkv = PkvStore::new("test", "test3");
+        let mut pkv = PkvStore::new_in_dir(temp_dir.path().join("pkv"));
         let mut tags = HashMap::new();
         tags.insert(doc_id, vec!["test_tag_2".to_string()]);
         pkv.set("tags", &tags).unwrap();
         app.add_event::<SaveStore>();
-        app.insert_resource(pkv);
+        app.add_event::<Saved>();
+        app.insert_resource(DocStore::new(PkvDocumentStore::new(pkv)));
         app.insert_resource(app_state);
 
         // Run systems
@@ -408,23 +528,347 @@ mod tests {
 
         // Assertions
         // Check that the document was saved to the PKV store
-        let pkv = app.world.resource::<PkvStore>();
-        let saved_docs: HashMap<ReflectableUuid, Doc> = pkv.get("docs").unwrap();
-        assert_eq!(saved_docs.get(&doc_id).unwrap().name, "test_doc");
-        assert!(saved_docs.get(&doc_id).unwrap().tabs[0].is_active);
+        let store = app.world.resource::<DocStore>();
+        let saved_doc = store.load_doc(doc_id).unwrap().unwrap();
+        assert_eq!(saved_doc.name, "test_doc");
+        assert!(saved_doc.tabs[0].is_active);
         // Check that the tags were saved to the PKV store
-        let saved_tags: HashMap<ReflectableUuid, Vec<String>> = pkv.get("tags").unwrap();
-        let expected_tags = existing_tags;
+        let saved_tags = store.tags().unwrap();
+        let expected_tags = vec!["test_tag_1".to_string()];
         assert_eq!(saved_tags.get(&doc_id).unwrap(), &expected_tags);
         // Check that the name was saved to the PKV store
-        let saved_names: HashMap<ReflectableUuid, String> = pkv.get("names").unwrap();
+        let saved_names = store.doc_names().unwrap();
         assert_eq!(saved_names.get(&doc_id).unwrap(), "test_doc");
         // Check that the last_saved field was updated in the PKV store
-        assert_eq!(pkv.get::<ReflectableUuid>("last_saved").unwrap(), doc_id);
+        assert_eq!(store.last_saved(), Some(doc_id));
         // Check that the file was saved to the correct path
         let file_contents = std::fs::read_to_string(temp_file_path).unwrap();
         let saved_doc: Doc = serde_json::from_str(&file_contents).unwrap();
         assert_eq!(saved_doc.name, "test_doc");
         assert!(saved_doc.tabs[0].is_active);
     }
+
+    fn app_with_tab(tab: crate::components::Tab) -> (App, ReflectableUuid) {
+        let mut app = App::new();
+        app.add_systems(Update, save_tab);
+        let doc_id = ReflectableUuid::generate();
+        let tab_id = tab.id;
+        let mut app_state = AppState::default();
+        app_state.docs.insert(
+            doc_id,
+            Doc {
+                id: doc_id,
+                tabs: vec![tab],
+                ..Default::default()
+            },
+        );
+        app.insert_resource(app_state);
+        app.add_plugins((MinimalPlugins, AssetPlugin::default()))
+            .add_asset::<Image>();
+        app.insert_resource(SaveTabRequest { doc_id, tab_id });
+        (app, doc_id)
+    }
+
+    fn tab_with_history(history: TabHistory) -> crate::components::Tab {
+        crate::components::Tab {
+            id: ReflectableUuid::generate(),
+            is_active: true,
+            z_index: 1.,
+            name: "Test tab".to_string(),
+            history,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_save_tab_writes_journal() {
+        let history = TabHistory::new(
+            TabSnapshot {
+                nodes: vec![JsonNode::default()],
+                ..Default::default()
+            },
+            0.,
+        );
+        let tab = tab_with_history(history);
+        let tab_id = tab.id;
+        let (mut app, doc_id) = app_with_tab(tab);
+        app.insert_resource(HistoryDepth(10));
+        app.add_event::<Notification>();
+        let temp_dir = tempdir().unwrap();
+        let journal_dir = temp_dir.path().join("journals");
+        let (journal, _) = Journal::open(&journal_dir).unwrap();
+        app.insert_resource(journal);
+
+        app.update();
+
+        app.world.remove_resource::<Journal>();
+        let (_, pending) = Journal::open(&journal_dir).unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].doc_id, doc_id);
+        let JournalOp::Checkpoint {
+            tab_id: id, delta, ..
+        } = &pending[0].op
+        else {
+            panic!("expected a checkpoint");
+        };
+        assert_eq!(*id, tab_id);
+        assert_eq!(delta.removed_nodes.len(), 1);
+    }
+
+    #[test]
+    fn test_save_tab_new_checkpoint_clears_redo() {
+        let node_snapshot = TabSnapshot {
+            nodes: vec![JsonNode::default()],
+            ..Default::default()
+        };
+        let moved_snapshot = TabSnapshot {
+            nodes: vec![JsonNode {
+                x: 5.,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let mut history = TabHistory::new(TabSnapshot::default(), 0.);
+        history.push(node_snapshot.clone(), 1., 10);
+        history.push(moved_snapshot, 2., 10);
+        history.undo();
+        let (mut app, doc_id) = app_with_tab(tab_with_history(history));
+        app.insert_resource(HistoryDepth(10));
+        app.add_event::<Notification>();
+
+        app.update();
+
+        let mut app_state = app.world.resource_mut::<AppState>();
+        let tab = &mut app_state.docs.get_mut(&doc_id).unwrap().tabs[0];
+        assert_eq!(tab.history.head(), &TabSnapshot::default());
+        assert!(!tab.history.redo());
+        assert!(tab.history.undo());
+        assert_eq!(tab.history.head(), &node_snapshot);
+    }
+
+    #[test]
+    fn test_save_tab_unchanged_keeps_redo() {
+        let node_snapshot = TabSnapshot {
+            nodes: vec![JsonNode::default()],
+            ..Default::default()
+        };
+        let mut history = TabHistory::new(TabSnapshot::default(), 0.);
+        history.push(node_snapshot.clone(), 1., 10);
+        history.undo();
+        let (mut app, doc_id) = app_with_tab(tab_with_history(history));
+        app.insert_resource(HistoryDepth(10));
+        app.add_event::<Notification>();
+
+        app.update();
+
+        let mut app_state = app.world.resource_mut::<AppState>();
+        let tab = &mut app_state.docs.get_mut(&doc_id).unwrap().tabs[0];
+        assert!(tab.history.redo());
+        assert_eq!(tab.history.head(), &node_snapshot);
+    }
+
+    #[test]
+    fn test_save_tab_respects_history_depth() {
+        let node_snapshot = TabSnapshot {
+            nodes: vec![JsonNode::default()],
+            ..Default::default()
+        };
+        let mut history = TabHistory::new(TabSnapshot::default(), 0.);
+        history.push(node_snapshot, 1., 10);
+        let (mut app, doc_id) = app_with_tab(tab_with_history(history));
+        app.insert_resource(HistoryDepth(1));
+        app.add_event::<Notification>();
+
+        app.update();
+
+        let mut app_state = app.world.resource_mut::<AppState>();
+        let tab = &mut app_state.docs.get_mut(&doc_id).unwrap().tabs[0];
+        assert!(tab.history.undo());
+        assert!(!tab.history.undo());
+    }
+
+    #[test]
+    fn test_save_to_store_collects_unused_images() {
+        let mut app = App::new();
+        app.add_event::<Notification>();
+        app.add_systems(Update, save_to_store);
+        let temp_dir = tempdir().unwrap();
+        let temp_file_path = temp_dir.path().join("test_doc.json");
+        let doc_id = ReflectableUuid::generate();
+        let node_id = uuid::Uuid::new_v4();
+        let mut snapshot = TabSnapshot::default();
+        snapshot.images.insert(node_id, "used".to_string());
+        let mut app_state = AppState::default();
+        app_state.docs.insert(
+            doc_id,
+            Doc {
+                id: doc_id,
+                tabs: vec![tab_with_history(TabHistory::new(snapshot, 0.))],
+                ..Default::default()
+            },
+        );
+        app_state
+            .images
+            .insert("used".to_string(), "used_png".to_string());
+        app_state
+            .images
+            .insert("unused".to_string(), "unused_png".to_string());
+        let mut store = MemoryStore::default();
+        let mut stale_snapshot = TabSnapshot::default();
+        stale_snapshot.images.insert(node_id, "stale".to_string());
+        let stale_doc = Doc {
+            id: ReflectableUuid::generate(),
+            tabs: vec![tab_with_history(TabHistory::new(stale_snapshot, 0.))],
+            ..Default::default()
+        };
+        let stale_images = HashMap::from([("stale".to_string(), "stale_png".to_string())]);
+        store.save_doc(&stale_doc, &stale_images).unwrap();
+        store.delete_doc(stale_doc.id).unwrap();
+        app.insert_resource(DocStore::new(store));
+        app.insert_resource(app_state);
+        app.add_event::<SaveStore>();
+        app.add_event::<Saved>();
+        app.world.send_event(SaveStore {
+            doc_id,
+            path: Some(temp_file_path.clone()),
+        });
+
+        app.update();
+
+        let store = app.world.resource::<DocStore>();
+        assert_eq!(store.load_image("used").unwrap(), "used_png");
+        assert!(store.load_image("stale").is_none());
+        assert!(store.load_doc(doc_id).unwrap().unwrap().images.is_empty());
+        let app_state = app.world.resource::<AppState>();
+        assert_eq!(app_state.images.len(), 1);
+        let file_contents = std::fs::read_to_string(temp_file_path).unwrap();
+        let exported_doc: Doc = serde_json::from_str(&file_contents).unwrap();
+        assert_eq!(
+            exported_doc.images,
+            HashMap::from([("used".to_string(), "used_png".to_string())])
+        );
+    }
+
+    #[test]
+    fn test_flush_evicted_docs() {
+        let mut app = App::new();
+        app.add_event::<Notification>();
+        app.add_systems(Update, flush_evicted_docs);
+        let evicted_id = ReflectableUuid::generate();
+        let node_id = uuid::Uuid::new_v4();
+        let mut snapshot = TabSnapshot::default();
+        snapshot.images.insert(node_id, "evicted".to_string());
+        let mut app_state = AppState::default();
+        app_state.docs.set_capacity(1);
+        app_state.docs.insert(
+            evicted_id,
+            Doc {
+                id: evicted_id,
+                name: "evicted".to_string(),
+                tabs: vec![tab_with_history(TabHistory::new(snapshot, 0.))],
+                ..Default::default()
+            },
+        );
+        app_state
+            .images
+            .insert("evicted".to_string(), "evicted_png".to_string());
+        let doc_id = ReflectableUuid::generate();
+        app_state.docs.insert_stored(
+            doc_id,
+            Doc {
+                id: doc_id,
+                ..Default::default()
+            },
+        );
+        app.insert_resource(DocStore::new(MemoryStore::default()));
+        app.insert_resource(app_state);
+
+        app.update();
+
+        let store = app.world.resource::<DocStore>();
+        assert_eq!(store.doc_names().unwrap().len(), 1);
+        assert_eq!(store.load_doc(evicted_id).unwrap().unwrap().name, "evicted");
+        assert_eq!(store.load_image("evicted").unwrap(), "evicted_png");
+        let app_state = app.world.resource::<AppState>();
+        assert_eq!(app_state.docs.evicted().count(), 0);
+        assert!(app_state.images.is_empty());
+    }
+
+    #[test]
+    fn test_save_to_store_reports_bad_path() {
+        let mut app = App::new();
+        app.add_event::<Notification>();
+        app.add_systems(Update, save_to_store);
+        let dir = tempdir().unwrap();
+        let doc_id = ReflectableUuid::generate();
+        let mut app_state = AppState::default();
+        app_state.docs.insert(
+            doc_id,
+            Doc {
+                id: doc_id,
+                tabs: vec![tab_with_history(TabHistory::default())],
+                ..Default::default()
+            },
+        );
+        app.insert_resource(DocStore::new(MemoryStore::default()));
+        app.insert_resource(app_state);
+        app.add_event::<SaveStore>();
+        app.add_event::<Saved>();
+        let path = dir.path().join("missing").join("doc.json");
+        app.world.send_event(SaveStore {
+            doc_id,
+            path: Some(path.clone()),
+        });
+
+        app.update();
+
+        let events = app.world.resource::<Events<Notification>>();
+        let mut reader = events.get_reader();
+        let notifications: Vec<_> = reader.iter(events).collect();
+        assert_eq!(notifications.len(), 1);
+        match notifications[0] {
+            Notification::Error(VeloError::WriteFile { path: err_path, .. }) => {
+                assert_eq!(*err_path, path)
+            }
+            notification => panic!("unexpected notification {:?}", notification),
+        }
+        // the document is still saved to the store
+        let store = app.world.resource::<DocStore>();
+        assert!(store.load_doc(doc_id).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_save_to_store_writes_workspace() {
+        let mut app = App::new();
+        app.add_event::<Notification>();
+        app.add_systems(Update, save_to_store);
+        let workspace_dir = tempdir().unwrap();
+        let local_dir = tempdir().unwrap();
+        let doc_id = ReflectableUuid::generate();
+        let mut app_state = AppState::default();
+        app_state.docs.insert(
+            doc_id,
+            Doc {
+                id: doc_id,
+                name: "workspace doc".to_string(),
+                tabs: vec![tab_with_history(TabHistory::default())],
+                ..Default::default()
+            },
+        );
+        app.insert_resource(DocStore::new(DirectoryStore::new(
+            workspace_dir.path(),
+            PkvStore::new_in_dir(local_dir.path()),
+        )));
+        app.insert_resource(app_state);
+        app.add_event::<SaveStore>();
+        app.add_event::<Saved>();
+        app.world.send_event(SaveStore { doc_id, path: None });
+
+        app.update();
+
+        let workspace = crate::workspace::Workspace::new(workspace_dir.path());
+        let doc = workspace.load_doc(doc_id).unwrap().unwrap();
+        assert_eq!(doc.name, "workspace doc");
+        assert_eq!(doc.tabs.len(), 1);
+    }
 }
@@ -1,10 +1,10 @@
 use bevy::prelude::*;
 use bevy::window::PrimaryWindow;
 
+use crate::store::DocStore;
 use bevy_cosmic_edit::get_cosmic_text;
 use bevy_cosmic_edit::ActiveEditor;
 use bevy_cosmic_edit::CosmicEdit;
-use bevy_pkv::PkvStore;
 use bevy_prototype_lyon::prelude::Stroke;
 use cosmic_text::Edit;
 use std::collections::HashMap;
@@ -23,7 +23,7 @@ use uuid::Uuid;
 
 use crate::resources::AppState;
 use crate::themes::Theme;
-use crate::utils::ReflectableUuid;
+use crate::utils::{stored_doc_names, ReflectableUuid};
 use crate::APP_NAME;
 use crate::ORG_NAME;
 
@@ -87,7 +87,7 @@ pub fn search_box_text_changed(
     mut velo_border: Query<(&mut Stroke, &VeloShape), With<VeloShape>>,
     mut previous_search_text: Local<String>,
     mut app_state: ResMut<AppState>,
-    pkv: Res<PkvStore>,
+    store: Res<DocStore>,
     theme: Res<Theme>,
 ) {
     let str = get_cosmic_text(text_query.single().editor.buffer());
@@ -116,7 +116,7 @@ pub fn search_box_text_changed(
                     Err(e) => info!("Error searching index {:?}", e),
                 }
             }
-        } else if let Ok(names) = pkv.get::<HashMap<ReflectableUuid, String>>("names") {
+        } else if let Some(names) = stored_doc_names(&store) {
             highlight_search_match_nodes(&HashSet::new(), &mut velo_border, &theme);
             let keys_in_storage: Vec<_> = names.keys().collect();
             let keys_in_memory: Vec<_> = app_state.docs.keys().cloned().collect();
@@ -173,15 +173,15 @@ pub fn update_search_index(
         document.add_text(index.schema().get_field("full_text").unwrap(), str);
         document.add_text(
             index.schema().get_field("doc_id").unwrap(),
-            &node_search_location.doc_id.to_string(),
+            node_search_location.doc_id.to_string(),
         );
         document.add_text(
             index.schema().get_field("tab_id").unwrap(),
-            &node_search_location.tab_id.to_string(),
+            node_search_location.tab_id.to_string(),
         );
         document.add_text(
             index.schema().get_field("node_id").unwrap(),
-            &node_search_location.node_id.to_string(),
+            node_search_location.node_id.to_string(),
         );
 
         index_writer.add_document(document)?;
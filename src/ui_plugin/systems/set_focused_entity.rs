@@ -1,12 +1,13 @@
 use bevy::{prelude::*, window::PrimaryWindow};
 
-use super::{ui_helpers::RawText, NodeInteraction, UiState};
+use super::{ui_helpers::RawText, AddCheckpoint, NodeInteraction, UiState};
 
 pub fn set_focused_entity(
     mut windows: Query<&mut Window, With<PrimaryWindow>>,
     mut node_interaction_events: EventReader<NodeInteraction>,
     mut ui_state: ResMut<UiState>,
     velo: Query<&RawText, With<RawText>>,
+    mut checkpoint_events: EventWriter<AddCheckpoint>,
 ) {
     let mut primary_window = windows.single_mut();
 
@@ -40,8 +41,11 @@ pub fn set_focused_entity(
                 crate::ui_plugin::NodeInteractionType::LeftMouseRelease => {}
             }
         }
-        if event.node_interaction_type == crate::ui_plugin::NodeInteractionType::LeftMouseRelease {
+        if event.node_interaction_type == crate::ui_plugin::NodeInteractionType::LeftMouseRelease
+            && ui_state.hold_entity.is_some()
+        {
             ui_state.hold_entity = None;
+            checkpoint_events.send_default();
         }
     }
 }
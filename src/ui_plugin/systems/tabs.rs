@@ -1,4 +1,4 @@
-use std::{collections::VecDeque, time::Duration};
+use std::time::Duration;
 
 use bevy::prelude::*;
 
@@ -9,7 +9,10 @@ use cosmic_text::{Cursor, Edit};
 use super::ui_helpers::{spawn_modal, AddTab, DeleteTab, TabButton};
 use super::MainPanel;
 use crate::components::Tab;
-use crate::resources::{AppState, FontSystemState, LoadDocRequest, LoadTabRequest, SaveTabRequest};
+use crate::journal::{Journal, JournalOp};
+use crate::resources::{
+    AppState, CheckpointStep, FontSystemState, LoadDocRequest, LoadTabRequest, SaveTabRequest,
+};
 use crate::themes::Theme;
 use crate::utils::{bevy_color_to_cosmic, get_timestamp, ReflectableUuid};
 use crate::UiState;
@@ -52,11 +55,12 @@ pub fn select_tab_handler(
                 {
                     tab.is_active = tab.id == selected_tab.id;
                 }
+                state.docs.mark_dirty(&current_document);
 
                 commands.insert_resource(LoadTabRequest {
                     doc_id: current_document,
                     tab_id: selected_tab.id,
-                    drop_last_checkpoint: false,
+                    step: CheckpointStep::Current,
                 });
             }
             Interaction::Hovered => {}
@@ -69,6 +73,7 @@ pub fn add_tab_handler(
     mut commands: Commands,
     mut interaction_query: Query<&Interaction, (Changed<Interaction>, With<AddTab>)>,
     mut app_state: ResMut<AppState>,
+    mut journal: Option<ResMut<Journal>>,
 ) {
     for interaction in &mut interaction_query {
         match *interaction {
@@ -85,14 +90,18 @@ pub fn add_tab_handler(
                     }
                     tab.is_active = false;
                 }
-                let tabs_len = tabs.len();
+                let name = "Tab ".to_string() + &(tabs.len() + 1).to_string();
                 tabs.push(Tab {
                     id: tab_id,
-                    name: "Tab ".to_string() + &(tabs_len + 1).to_string(),
-                    checkpoints: VecDeque::new(),
+                    name: name.clone(),
                     is_active: true,
                     z_index: 1.,
+                    ..Default::default()
                 });
+                app_state.docs.mark_dirty(&current_document);
+                if let Some(journal) = &mut journal {
+                    journal.append(current_document, JournalOp::AddTab { tab_id, name });
+                }
                 commands.insert_resource(LoadDocRequest {
                     doc_id: app_state.current_document.unwrap(),
                 });
@@ -110,7 +119,7 @@ pub fn rename_tab_handler(
         (Changed<Interaction>, With<TabButton>),
     >,
     mut ui_state: ResMut<UiState>,
-    mut app_state: ResMut<AppState>,
+    app_state: Res<AppState>,
     mut double_click: Local<(Duration, Option<ReflectableUuid>)>,
     theme: Res<Theme>,
 ) {
@@ -137,7 +146,7 @@ pub fn rename_tab_handler(
                     let current_document = app_state.current_document.unwrap();
                     let tab = app_state
                         .docs
-                        .get_mut(&current_document)
+                        .get(&current_document)
                         .unwrap()
                         .tabs
                         .iter()
@@ -158,7 +167,7 @@ pub fn rename_tab_handler(
 pub fn delete_tab_handler(
     mut commands: Commands,
     mut interaction_query: Query<&Interaction, (Changed<Interaction>, With<DeleteTab>)>,
-    mut app_state: ResMut<AppState>,
+    app_state: Res<AppState>,
     mut ui_state: ResMut<UiState>,
     main_panel_query: Query<Entity, With<MainPanel>>,
     windows: Query<&Window, With<PrimaryWindow>>,
@@ -174,12 +183,7 @@ pub fn delete_tab_handler(
                 *ui_state = UiState::default();
                 commands.insert_resource(bevy_cosmic_edit::ActiveEditor { entity: None });
                 let current_document = app_state.current_document.unwrap();
-                let tabs_len = app_state
-                    .docs
-                    .get_mut(&current_document)
-                    .unwrap()
-                    .tabs
-                    .len();
+                let tabs_len = app_state.docs.get(&current_document).unwrap().tabs.len();
                 if tabs_len < 2 {
                     return;
                 }
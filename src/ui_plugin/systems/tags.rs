@@ -0,0 +1,190 @@
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use bevy_cosmic_edit::{
+    cosmic_edit_set_text, get_cosmic_text, ActiveEditor, CosmicEdit, CosmicFont, CosmicText,
+};
+use cosmic_text::{Action, Edit};
+
+use super::ui_helpers::{
+    add_tag_chip, RemoveTag, TagBox, TagChipList, TagFilter, TagInput, TagSuggestion,
+};
+use super::UiState;
+use crate::journal::{Journal, JournalOp};
+use crate::resources::{AppState, Autosave};
+use crate::tags::{add_tag, complete_tag, known_tags, parse_tag_filter};
+use crate::themes::Theme;
+use crate::utils::ReflectableUuid;
+
+pub fn tag_box_click(
+    mut commands: Commands,
+    mut interaction_query: Query<(&Interaction, Entity), (Changed<Interaction>, With<TagBox>)>,
+    mut state: ResMut<UiState>,
+    mut windows: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    let mut primary_window = windows.single_mut();
+    for (interaction, entity) in &mut interaction_query {
+        match *interaction {
+            Interaction::Pressed => {
+                primary_window.cursor.icon = CursorIcon::Text;
+                *state = UiState::default();
+                commands.insert_resource(ActiveEditor {
+                    entity: Some(entity),
+                });
+            }
+            Interaction::Hovered => {
+                primary_window.cursor.icon = CursorIcon::Hand;
+            }
+            Interaction::None => {
+                primary_window.cursor.icon = CursorIcon::Default;
+            }
+        }
+    }
+}
+
+fn set_tag_box_text(
+    cosmic_edit: &mut CosmicEdit,
+    cosmic_fonts: &mut Assets<CosmicFont>,
+    text: &str,
+) {
+    let font = cosmic_fonts
+        .get_mut(&cosmic_edit.font_system.clone())
+        .unwrap();
+    cosmic_edit_set_text(
+        CosmicText::OneStyle(text.to_string()),
+        cosmic_edit.attrs.clone(),
+        &mut cosmic_edit.editor,
+        &mut font.0,
+    );
+    cosmic_edit.editor.action(&mut font.0, Action::BufferEnd);
+    cosmic_edit.editor.buffer_mut().set_redraw(true);
+}
+
+/// Return adds the typed tag to the current document, Tab completes it from the tags in use.
+pub fn tag_input_changed(
+    mut input_query: Query<&mut CosmicEdit, With<TagInput>>,
+    mut suggestion_query: Query<&mut Text, With<TagSuggestion>>,
+    mut cosmic_fonts: ResMut<Assets<CosmicFont>>,
+    mut app_state: ResMut<AppState>,
+    mut autosave: ResMut<Autosave>,
+    mut journal: Option<ResMut<Journal>>,
+    mut previous_text: Local<String>,
+    time: Res<Time>,
+) {
+    let Ok(mut cosmic_edit) = input_query.get_single_mut() else {
+        return;
+    };
+    let mut text = get_cosmic_text(cosmic_edit.editor.buffer());
+    if text == *previous_text {
+        return;
+    }
+    if text.contains('\n') {
+        let tag = text.replace('\n', "");
+        if let Some(id) = app_state.current_document {
+            let added = app_state
+                .docs
+                .get_mut(&id)
+                .is_some_and(|doc| add_tag(&mut doc.tags, &tag));
+            if added {
+                app_state.docs.mark_dirty(&id);
+                journal_tags(&mut journal, &app_state, id);
+                autosave.unsaved = true;
+                autosave.last_change = time.elapsed_seconds_f64();
+            }
+        }
+        text = String::new();
+        set_tag_box_text(&mut cosmic_edit, &mut cosmic_fonts, &text);
+    } else if text.contains('\t') {
+        let prefix = text.replace('\t', "");
+        text = current_doc_tags(&app_state)
+            .and_then(|tags| complete_tag(&prefix, &known_tags(&app_state), &tags))
+            .unwrap_or(prefix);
+        set_tag_box_text(&mut cosmic_edit, &mut cosmic_fonts, &text);
+    }
+    let suggestion = current_doc_tags(&app_state)
+        .and_then(|tags| complete_tag(&text, &known_tags(&app_state), &tags))
+        .unwrap_or_default();
+    for mut suggestion_text in suggestion_query.iter_mut() {
+        suggestion_text.sections[0].value = suggestion.clone();
+    }
+    *previous_text = text;
+}
+
+fn current_doc_tags(app_state: &AppState) -> Option<Vec<String>> {
+    let doc_id = app_state.current_document?;
+    app_state.docs.get(&doc_id).map(|doc| doc.tags.clone())
+}
+
+/// Re-creates the chips in the document header when the current document or its tags change.
+pub fn update_tag_chips(
+    mut commands: Commands,
+    app_state: Res<AppState>,
+    chip_list_query: Query<Entity, With<TagChipList>>,
+    theme: Res<Theme>,
+    mut last_tags: Local<Option<(ReflectableUuid, Vec<String>)>>,
+) {
+    if !app_state.is_changed() {
+        return;
+    }
+    let tags = app_state
+        .current_document
+        .and_then(|doc_id| current_doc_tags(&app_state).map(|tags| (doc_id, tags)));
+    if tags == *last_tags {
+        return;
+    }
+    for chip_list in chip_list_query.iter() {
+        commands.entity(chip_list).despawn_descendants();
+        if let Some((_, tags)) = &tags {
+            for tag in tags {
+                let chip = add_tag_chip(&mut commands, &theme, tag);
+                commands.entity(chip_list).add_child(chip);
+            }
+        }
+    }
+    *last_tags = tags;
+}
+
+pub fn remove_tag_click(
+    interaction_query: Query<(&Interaction, &RemoveTag), Changed<Interaction>>,
+    mut app_state: ResMut<AppState>,
+    mut autosave: ResMut<Autosave>,
+    mut journal: Option<ResMut<Journal>>,
+    time: Res<Time>,
+) {
+    for (interaction, remove_tag) in interaction_query.iter() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        if let Some(id) = app_state.current_document {
+            let Some(doc) = app_state.docs.get_mut(&id) else {
+                continue;
+            };
+            doc.tags.retain(|tag| tag != &remove_tag.tag);
+            app_state.docs.mark_dirty(&id);
+            journal_tags(&mut journal, &app_state, id);
+            autosave.unsaved = true;
+            autosave.last_change = time.elapsed_seconds_f64();
+        }
+    }
+}
+
+fn journal_tags(journal: &mut Option<ResMut<Journal>>, app_state: &AppState, id: ReflectableUuid) {
+    if let (Some(journal), Some(doc)) = (journal, app_state.docs.get(&id)) {
+        let tags = doc.tags.clone();
+        journal.append(id, JournalOp::SetTags { tags });
+    }
+}
+
+pub fn tag_filter_changed(
+    filter_query: Query<&CosmicEdit, With<TagFilter>>,
+    mut app_state: ResMut<AppState>,
+    mut previous_text: Local<String>,
+) {
+    let Ok(cosmic_edit) = filter_query.get_single() else {
+        return;
+    };
+    let text = get_cosmic_text(cosmic_edit.editor.buffer());
+    if text != *previous_text {
+        app_state.tag_filter = parse_tag_filter(&text.replace('\n', ","));
+        *previous_text = text;
+    }
+}
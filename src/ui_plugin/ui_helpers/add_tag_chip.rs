@@ -0,0 +1,38 @@
+use bevy::prelude::*;
+
+use crate::themes::Theme;
+
+use super::RemoveTag;
+
+/// Tag of the current document, clicking it removes the tag.
+pub fn add_tag_chip(commands: &mut Commands, theme: &Res<Theme>, tag: &str) -> Entity {
+    commands
+        .spawn((
+            ButtonBundle {
+                border_color: theme.btn_border.into(),
+                background_color: theme.doc_list_bg.into(),
+                style: Style {
+                    border: UiRect::all(Val::Px(1.)),
+                    padding: UiRect::horizontal(Val::Px(5.)),
+                    margin: UiRect::right(Val::Px(5.)),
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                ..default()
+            },
+            RemoveTag {
+                tag: tag.to_string(),
+            },
+        ))
+        .with_children(|chip| {
+            chip.spawn(TextBundle::from_section(
+                format!("{} \u{00d7}", tag),
+                TextStyle {
+                    font_size: theme.font_size,
+                    color: theme.font,
+                    ..default()
+                },
+            ));
+        })
+        .id()
+}
@@ -2,6 +2,7 @@ use crate::{ui_plugin::NodeType, utils::ReflectableUuid};
 use bevy::prelude::*;
 use bevy_markdown::TextSpanMetadata;
 
+use crate::resources::{ExportFormat, ImportFormat, PngExport};
 use crate::TextPos;
 
 #[derive(Component)]
@@ -55,6 +56,25 @@ pub struct SaveDoc;
 #[derive(Component, Clone)]
 pub struct ExportToFile;
 
+#[derive(Component, Clone)]
+pub struct ExportTab {
+    pub format: ExportFormat,
+}
+
+#[derive(Component, Clone)]
+pub struct CopyTabOutline;
+
+#[derive(Component, Clone)]
+pub struct ImportTab {
+    pub format: ImportFormat,
+}
+
+#[derive(Component, Clone)]
+pub struct PasteTab {
+    pub format: ImportFormat,
+}
+
+#[cfg(target_arch = "wasm32")]
 #[derive(Component, Clone)]
 pub struct SetWindowProperty;
 
@@ -94,6 +114,29 @@ pub struct SearchText {
     pub id: ReflectableUuid,
 }
 
+/// Single line text box for tags, either `TagInput` or `TagFilter`.
+#[derive(Component)]
+pub struct TagBox;
+
+/// Adds a tag to the current document on Return.
+#[derive(Component)]
+pub struct TagInput;
+
+/// Comma separated tags the doc list is filtered by.
+#[derive(Component)]
+pub struct TagFilter;
+
+#[derive(Component)]
+pub struct TagSuggestion;
+
+#[derive(Component)]
+pub struct TagChipList;
+
+#[derive(Component)]
+pub struct RemoveTag {
+    pub tag: String,
+}
+
 #[derive(Component, Default)]
 pub struct ScrollingList {
     pub position: f32,
@@ -101,6 +144,7 @@ pub struct ScrollingList {
 
 #[derive(Component)]
 pub struct DocListItemContainer {
+    #[allow(dead_code)]
     pub id: ReflectableUuid,
 }
 
@@ -125,6 +169,21 @@ pub struct MainPanel;
 #[derive(Component)]
 pub struct BottomPanel;
 
+#[derive(Component)]
+pub struct DocHeader;
+
+#[derive(Component)]
+pub struct ToastContainer;
+
+#[derive(Component)]
+pub struct Toast {
+    /// `Time::elapsed_seconds_f64` after which the toast is removed.
+    pub expires_at: f64,
+}
+
+#[derive(Component)]
+pub struct SaveIndicator;
+
 #[derive(Component)]
 pub struct LeftPanel;
 
@@ -178,6 +237,7 @@ pub struct RawText {
 
 #[derive(Component, Default)]
 pub struct BevyMarkdownView {
+    #[allow(dead_code)]
     pub id: ReflectableUuid,
     pub span_metadata: Vec<TextSpanMetadata>,
 }
@@ -204,6 +264,9 @@ pub enum ModalAction {
     LoadFromUrl,
     DeleteDocument,
     DeleteTab,
+    RecoverJournal,
+    ExportTab(ExportFormat),
+    ImportTab(ImportFormat),
 }
 
 impl std::fmt::Display for ModalAction {
@@ -211,9 +274,45 @@ impl std::fmt::Display for ModalAction {
         match self {
             ModalAction::DeleteDocument => write!(f, "delete document"),
             ModalAction::DeleteTab => write!(f, "delete tab"),
+            ModalAction::RecoverJournal => write!(f, "recover unsaved changes"),
             ModalAction::LoadFromFile => write!(f, "Load from file:"),
             ModalAction::LoadFromUrl => write!(f, "Load from URL:"),
             ModalAction::SaveToFile => write!(f, "Save to file:"),
+            ModalAction::ExportTab(format) => {
+                write!(f, "Export tab to {}:", format.name())
+            }
+            ModalAction::ImportTab(format) => {
+                write!(f, "Import {} from file:", format.name())
+            }
+        }
+    }
+}
+
+/// PNG options of an export modal, kept on its `ModalTop` until it is confirmed.
+#[derive(Component, Default)]
+pub struct PngExportOptions {
+    /// The note that was selected when the modal was opened.
+    pub selection: Option<ReflectableUuid>,
+    pub export: PngExport,
+}
+
+#[derive(Component)]
+pub struct PngExportToggle {
+    pub id: ReflectableUuid,
+    pub option: PngExportOption,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum PngExportOption {
+    Transparent,
+    Selection,
+}
+
+impl PngExportOption {
+    pub fn label(&self) -> &'static str {
+        match self {
+            PngExportOption::Transparent => "Transparent",
+            PngExportOption::Selection => "Selected note",
         }
     }
 }
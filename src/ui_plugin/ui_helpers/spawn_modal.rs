@@ -8,9 +8,10 @@ use cosmic_text::AttrsOwned;
 
 use super::{
     add_rectangle_txt, EditableText, GenericButton, ModalAction, ModalCancel, ModalConfirm,
-    ModalTop,
+    ModalTop, PngExportOption, PngExportToggle,
 };
 use crate::{
+    resources::ExportFormat,
     themes::Theme,
     ui_plugin::TextPos,
     utils::{bevy_color_to_cosmic, ReflectableUuid},
@@ -31,6 +32,8 @@ pub fn spawn_modal(
         ModalAction::SaveToFile => "./velo.json".to_string(),
         ModalAction::LoadFromFile => "./velo.json".to_string(),
         ModalAction::LoadFromUrl => "https://gist..".to_string(),
+        ModalAction::ExportTab(format) => format!("./velo.{}", format.extension()),
+        ModalAction::ImportTab(format) => format!("./velo.{}", format.extension()),
         _ => "".to_string(),
     };
     let top = commands
@@ -140,7 +143,11 @@ pub fn spawn_modal(
     commands.entity(modal_static).add_child(cancel_button);
 
     let modal_dynamic = match modal_action {
-        ModalAction::SaveToFile | ModalAction::LoadFromFile | ModalAction::LoadFromUrl => {
+        ModalAction::SaveToFile
+        | ModalAction::LoadFromFile
+        | ModalAction::LoadFromUrl
+        | ModalAction::ExportTab(_)
+        | ModalAction::ImportTab(_) => {
             let top = commands
                 .spawn(NodeBundle {
                     style: Style {
@@ -214,7 +221,7 @@ pub fn spawn_modal(
             commands.entity(top).add_child(button);
             top
         }
-        ModalAction::DeleteDocument | ModalAction::DeleteTab => {
+        ModalAction::DeleteDocument | ModalAction::DeleteTab | ModalAction::RecoverJournal => {
             let top = commands
                 .spawn(NodeBundle {
                     style: Style {
@@ -270,7 +277,56 @@ pub fn spawn_modal(
         },))
         .id();
     commands.entity(modal).add_child(modal_dynamic);
+    if modal_action == ModalAction::ExportTab(ExportFormat::Png) {
+        let options = spawn_png_export_toggles(commands, theme, id);
+        commands.entity(modal).add_child(options);
+    }
     commands.entity(modal).add_child(modal_static);
     commands.entity(top).add_child(modal);
     top
 }
+
+/// Row of toggles for the options of a PNG export, see `PngExportOptions`.
+fn spawn_png_export_toggles(
+    commands: &mut Commands,
+    theme: &Res<Theme>,
+    id: ReflectableUuid,
+) -> Entity {
+    let row = commands
+        .spawn(NodeBundle {
+            style: Style {
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::SpaceAround,
+                width: Val::Percent(100.),
+                height: Val::Percent(20.),
+                ..default()
+            },
+            ..default()
+        })
+        .id();
+    for option in [PngExportOption::Transparent, PngExportOption::Selection] {
+        let toggle = commands
+            .spawn((
+                ButtonBundle {
+                    border_color: theme.btn_border.into(),
+                    background_color: theme.ok_cancel_bg.into(),
+                    style: Style {
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        border: UiRect::all(Val::Px(1.)),
+                        padding: UiRect::all(Val::Px(5.)),
+                        ..default()
+                    },
+                    ..default()
+                },
+                GenericButton,
+                PngExportToggle { id, option },
+            ))
+            .with_children(|builder| {
+                builder.spawn(add_rectangle_txt(theme, option.label().to_string()));
+            })
+            .id();
+        commands.entity(row).add_child(toggle);
+    }
+    row
+}
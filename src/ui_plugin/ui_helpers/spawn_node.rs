@@ -16,6 +16,7 @@ use crate::TextPos;
 
 use super::{BevyMarkdownView, InteractiveNode, RawText, ResizeMarker, VeloNode, VeloShape};
 use crate::canvas::arrow::components::{ArrowConnect, ArrowConnectPos};
+use crate::canvas::arrow::utils::arrow_connector_offset;
 use crate::utils::{bevy_color_to_cosmic, ReflectableUuid};
 
 #[derive(Clone)]
@@ -32,6 +33,47 @@ pub struct NodeMeta {
     pub visible: bool,
 }
 
+/// Outline of a node centered at the origin, also used by the exporters.
+pub fn node_shape_path(node_type: &NodeType, width: f32, height: f32) -> Path {
+    let points = [
+        Vec2::new(-width / 2., -height / 2.),
+        Vec2::new(-width / 2., height / 2.),
+        Vec2::new(width / 2., height / 2.),
+        Vec2::new(width / 2., -height / 2.),
+    ];
+
+    match node_type {
+        NodeType::Rect => bevy_prototype_lyon::prelude::GeometryBuilder::build_as(
+            &bevy_prototype_lyon::shapes::RoundedPolygon {
+                points: points.into_iter().collect(),
+                closed: true,
+                radius: 10.,
+            },
+        ),
+        NodeType::Paper => bevy_prototype_lyon::prelude::GeometryBuilder::build_as(
+            &bevy_prototype_lyon::shapes::Polygon {
+                points: points.into_iter().collect(),
+                closed: true,
+            },
+        ),
+        NodeType::Circle => bevy_prototype_lyon::prelude::GeometryBuilder::build_as(
+            &bevy_prototype_lyon::shapes::Circle {
+                radius: width / 2.,
+                center: Vec2::new(0., 0.),
+            },
+        ),
+    }
+}
+
+pub fn markdown_theme(theme: &Theme) -> BevyMarkdownTheme {
+    BevyMarkdownTheme {
+        code_theme: theme.code_theme.clone(),
+        code_default_lang: theme.code_default_lang.clone(),
+        link: bevy_color_to_cosmic(theme.link),
+        inline_code: bevy_color_to_cosmic(theme.inline_code),
+    }
+}
+
 pub fn spawn_sprite_node(
     commands: &mut Commands,
     materials: &mut ResMut<Assets<CustomShadowMaterial>>,
@@ -70,34 +112,7 @@ pub fn spawn_sprite_node(
         ))
         .id();
 
-    let points = [
-        Vec2::new(-width / 2., -height / 2.),
-        Vec2::new(-width / 2., height / 2.),
-        Vec2::new(width / 2., height / 2.),
-        Vec2::new(width / 2., -height / 2.),
-    ];
-
-    let path: Path = match item_meta.node_type {
-        NodeType::Rect => bevy_prototype_lyon::prelude::GeometryBuilder::build_as(
-            &bevy_prototype_lyon::shapes::RoundedPolygon {
-                points: points.into_iter().collect(),
-                closed: true,
-                radius: 10.,
-            },
-        ),
-        NodeType::Paper => bevy_prototype_lyon::prelude::GeometryBuilder::build_as(
-            &bevy_prototype_lyon::shapes::Polygon {
-                points: points.into_iter().collect(),
-                closed: true,
-            },
-        ),
-        NodeType::Circle => bevy_prototype_lyon::prelude::GeometryBuilder::build_as(
-            &bevy_prototype_lyon::shapes::Circle {
-                radius: width / 2.,
-                center: Vec2::new(0., 0.),
-            },
-        ),
-    };
+    let path = node_shape_path(&item_meta.node_type, width, height);
     let has_border = item_meta.node_type != NodeType::Paper;
     let is_transparent = item_meta.pair_bg_color.clone().1 == Color::NONE;
     let shape = commands
@@ -133,16 +148,10 @@ pub fn spawn_sprite_node(
     let (text, span_metadata) = match item_meta.is_active {
         true => (CosmicText::OneStyle(item_meta.text.clone()), vec![]),
         false => {
-            let markdown_theme = BevyMarkdownTheme {
-                code_theme: theme.code_theme.clone(),
-                code_default_lang: theme.code_default_lang.clone(),
-                link: bevy_color_to_cosmic(theme.link),
-                inline_code: bevy_color_to_cosmic(theme.inline_code),
-            };
             let markdown_lines = generate_markdown_lines(BevyMarkdown {
                 text: item_meta.text.clone(),
                 attrs: AttrsOwned::new(attrs),
-                markdown_theme,
+                markdown_theme: markdown_theme(theme),
             })
             .expect("should handle markdown convertion");
             (
@@ -310,12 +319,7 @@ fn spawn_arrow_marker(
     height: f32,
     pos: ArrowConnectPos,
 ) -> Entity {
-    let (x, y) = match pos {
-        ArrowConnectPos::Left => (-width / 2., 0.),
-        ArrowConnectPos::Bottom => (0., -height / 2.),
-        ArrowConnectPos::Top => (0., height / 2.),
-        ArrowConnectPos::Right => (width / 2., 0.),
-    };
+    let Vec2 { x, y } = arrow_connector_offset(width, height, pos);
     let arrow_marker_container = commands
         .spawn(SpriteBundle {
             sprite: Sprite {
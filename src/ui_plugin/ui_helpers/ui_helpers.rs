@@ -1,4 +1,5 @@
 use bevy::{prelude::*, text::BreakLineOn};
+use bevy_prototype_lyon::prelude::{Path, PathBuilder};
 
 use crate::themes::Theme;
 #[path = "components.rs"]
@@ -16,6 +17,23 @@ pub use add_tab::*;
 #[path = "add_list_item.rs"]
 mod add_list_item;
 pub use add_list_item::*;
+#[path = "add_tag_chip.rs"]
+mod add_tag_chip;
+pub use add_tag_chip::*;
+
+/// Polyline of a drawing, also used by the exporters.
+pub fn drawing_path(points: &[Vec2]) -> Path {
+    let mut path_builder = PathBuilder::new();
+    let mut points_iter = points.iter();
+    if let Some(start) = points_iter.next() {
+        path_builder.move_to(*start);
+        path_builder.line_to(*start);
+        for point in points_iter {
+            path_builder.line_to(*point);
+        }
+    }
+    path_builder.build()
+}
 
 pub fn add_rectangle_txt(theme: &Res<Theme>, text: String) -> TextBundle {
     let text_style = TextStyle {
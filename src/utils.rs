@@ -1,24 +1,29 @@
-use bevy::prelude::*;
+use base64::{engine::general_purpose, Engine};
+use bevy::{
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+};
 
 use bevy_cosmic_edit::CosmicTextPos;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 
+use crate::notifications::VeloError;
 use crate::resources::AppState;
+use crate::store::{DocStore, StoreKind};
 use crate::ui_plugin::TextPos;
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::{fs, path::PathBuf};
 use uuid::Uuid;
 
-use bevy_pkv::PkvStore;
-
-use crate::{components::Doc, ui_plugin::MAX_SAVED_DOCS_IN_MEMORY};
+use crate::components::Doc;
 
 #[derive(Clone, Reflect, Default, Debug, Copy, Eq, PartialEq, Hash, Serialize, Deserialize)]
 #[reflect_value]
 pub struct ReflectableUuid(pub Uuid);
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct UserPreferences {
     pub theme_name: Option<String>,
 }
@@ -45,30 +50,148 @@ pub fn get_timestamp() -> f64 {
 
 pub fn load_doc_to_memory(
     doc_id: ReflectableUuid,
-    app_state: &mut ResMut<AppState>,
-    pkv: &mut ResMut<PkvStore>,
-) {
+    app_state: &mut AppState,
+    store: &DocStore,
+) -> Result<(), VeloError> {
     if app_state.docs.contains_key(&doc_id) {
-        return;
+        app_state.docs.touch(&doc_id);
+    } else if !app_state.docs.reclaim(&doc_id) {
+        let mut doc = read_stored_doc(doc_id, store)?;
+        take_doc_images(&mut doc, app_state);
+        app_state.docs.insert_stored(doc_id, doc);
     }
-    if let Ok(docs) = pkv.get::<HashMap<ReflectableUuid, Doc>>("docs") {
-        if docs.contains_key(&doc_id) {
-            let keys = app_state.docs.keys().cloned().collect::<Vec<_>>();
-            while (app_state.docs.len() as i32) >= MAX_SAVED_DOCS_IN_MEMORY {
-                app_state.docs.remove(&keys[0]);
-            }
-            app_state
-                .docs
-                .insert(doc_id, docs.get(&doc_id).unwrap().clone());
-        } else {
-            error!("Document not found in pkv");
+    load_doc_images(doc_id, app_state, store)
+}
+
+/// Reads and migrates a stored document.
+pub fn read_stored_doc(doc_id: ReflectableUuid, store: &DocStore) -> Result<Doc, VeloError> {
+    store
+        .load_doc(doc_id)?
+        .ok_or(VeloError::DocNotFound(doc_id))
+}
+
+/// Names of all stored documents, `None` when nothing was saved yet.
+pub fn stored_doc_names(store: &DocStore) -> Option<HashMap<ReflectableUuid, String>> {
+    match store.doc_names() {
+        Ok(names) if !names.is_empty() => Some(names),
+        Ok(_) => None,
+        Err(err) => {
+            error!("Failed to list documents: {}", err);
+            None
         }
     }
 }
 
+pub fn remove_stored_doc(store: &mut DocStore, doc_id: ReflectableUuid) -> Result<(), VeloError> {
+    Ok(store.delete_doc(doc_id)?)
+}
+
+/// Content address of an image, hex encoded sha256 of its PNG bytes.
+pub fn image_hash(png: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(png))
+}
+
+/// Decodes a stored image, a base64 encoded PNG, into an RGBA texture.
+pub fn decode_image(data: &str) -> Result<Image, VeloError> {
+    let bytes = general_purpose::STANDARD
+        .decode(data.as_bytes())
+        .map_err(|err| VeloError::Image(err.to_string()))?;
+    let image = image::load_from_memory_with_format(&bytes, image::ImageFormat::Png)
+        .map_err(|err| VeloError::Image(err.to_string()))?
+        .to_rgba8();
+    let size = Extent3d {
+        width: image.width(),
+        height: image.height(),
+        ..Default::default()
+    };
+    Ok(Image::new(
+        size,
+        TextureDimension::D2,
+        image.into_raw(),
+        TextureFormat::Rgba8UnormSrgb,
+    ))
+}
+
+/// Hashes of all images referenced by the checkpoints of `doc`.
+pub fn doc_image_hashes(doc: &Doc) -> HashSet<String> {
+    doc.tabs
+        .iter()
+        .flat_map(|tab| tab.history.image_hashes().cloned())
+        .collect()
+}
+
+/// Moves images embedded in an imported or migrated document to the shared store.
+pub fn take_doc_images(doc: &mut Doc, app_state: &mut AppState) {
+    app_state.images.extend(doc.images.drain());
+}
+
+/// Makes sure every image referenced by a loaded document is in memory.
+fn load_doc_images(
+    doc_id: ReflectableUuid,
+    app_state: &mut AppState,
+    store: &DocStore,
+) -> Result<(), VeloError> {
+    let Some(doc) = app_state.docs.get(&doc_id) else {
+        return Ok(());
+    };
+    let missing: Vec<String> = doc_image_hashes(doc)
+        .into_iter()
+        .filter(|hash| !app_state.images.contains_key(hash))
+        .collect();
+    let found = store.load_images(&missing);
+    let not_found = missing.len() - found.len();
+    app_state.images.extend(found);
+    if not_found > 0 {
+        return Err(VeloError::MissingImages(not_found));
+    }
+    Ok(())
+}
+
+/// Copy of a loaded document with its images embedded, so it can be opened elsewhere.
+pub fn export_doc(doc_id: ReflectableUuid, app_state: &AppState) -> Result<Doc, VeloError> {
+    let mut doc = app_state
+        .docs
+        .get(&doc_id)
+        .ok_or(VeloError::DocNotFound(doc_id))?
+        .clone();
+    for tab in doc.tabs.iter_mut() {
+        tab.history.clear_redo();
+    }
+    for hash in doc_image_hashes(&doc) {
+        if let Some(image) = app_state.images.get(&hash) {
+            doc.images.insert(hash, image.clone());
+        }
+    }
+    Ok(doc)
+}
+
+/// Writes a document with its tags, name and images to the store.
+pub fn store_doc(store: &mut DocStore, app_state: &AppState, doc: &Doc) -> Result<(), VeloError> {
+    Ok(store.save_doc(doc, &app_state.images)?)
+}
+
+/// Drops in-memory images no loaded or evicted document references,
+/// the store drops its own copies when documents stop using them.
+pub fn forget_unused_images(app_state: &mut AppState) {
+    let loaded_hashes: HashSet<String> = app_state
+        .docs
+        .values()
+        .chain(app_state.docs.evicted())
+        .flat_map(doc_image_hashes)
+        .collect();
+    app_state
+        .images
+        .retain(|hash, _| loaded_hashes.contains(hash));
+}
+
 #[derive(Debug, Default)]
 pub struct Config {
     pub github_access_token: Option<String>,
+    pub history_depth: Option<usize>,
+    pub max_docs_in_memory: Option<usize>,
+    pub autosave_delay: Option<f64>,
+    pub workspace: Option<PathBuf>,
+    pub store: Option<StoreKind>,
 }
 
 #[cfg(not(target_arch = "wasm32"))]
@@ -83,6 +206,33 @@ pub fn read_config_file() -> Option<Config> {
             config.github_access_token = Some(token_str.to_owned());
         }
     }
+    if let Some(depth) = config_value.get("history_depth") {
+        if let Some(depth) = depth.as_integer() {
+            config.history_depth = Some(depth.max(1) as usize);
+        }
+    }
+    if let Some(max_docs) = config_value.get("max_docs_in_memory") {
+        if let Some(max_docs) = max_docs.as_integer() {
+            config.max_docs_in_memory = Some(max_docs.max(1) as usize);
+        }
+    }
+    if let Some(workspace) = config_value.get("workspace").and_then(|w| w.as_str()) {
+        config.workspace = Some(match workspace.strip_prefix("~/") {
+            Some(relative) => PathBuf::from(&home_dir).join(relative),
+            None => PathBuf::from(workspace),
+        });
+    }
+    if let Some(store) = config_value.get("store").and_then(|s| s.as_str()) {
+        config.store = StoreKind::parse(store);
+        if config.store.is_none() {
+            warn!("Unknown store {}, expected pkv, directory or memory", store);
+        }
+    }
+    if let Some(delay) = config_value.get("autosave_delay") {
+        if let Some(delay) = delay.as_float().or(delay.as_integer().map(|d| d as f64)) {
+            config.autosave_delay = Some(delay);
+        }
+    }
     Some(config)
 }
 
@@ -104,6 +254,18 @@ impl From<CosmicTextPos> for TextPos {
     }
 }
 
+/// Victor Mono in the regular, italic, bold, bold italic, medium and semibold styles.
+pub fn velo_font_bytes() -> Vec<&'static [u8]> {
+    vec![
+        include_bytes!("../assets/fonts/VictorMono-Regular.ttf"),
+        include_bytes!("../assets/fonts/VictorMono-Italic.ttf"),
+        include_bytes!("../assets/fonts/VictorMono-Bold.ttf"),
+        include_bytes!("../assets/fonts/VictorMono-BoldItalic.ttf"),
+        include_bytes!("../assets/fonts/VictorMono-Medium.ttf"),
+        include_bytes!("../assets/fonts/VictorMono-SemiBold.ttf"),
+    ]
+}
+
 pub fn bevy_color_to_cosmic(color: bevy::prelude::Color) -> cosmic_text::Color {
     cosmic_text::Color::rgba(
         (color.r() * 255.) as u8,
@@ -113,16 +275,11 @@ pub fn bevy_color_to_cosmic(color: bevy::prelude::Color) -> cosmic_text::Color {
     )
 }
 
-pub fn get_theme_key(pkv: &PkvStore) -> String {
-    if let Ok(user_preferences) = pkv.get::<UserPreferences>("user_preferences") {
-        if let Some(theme_name) = user_preferences.theme_name {
-            theme_name
-        } else {
-            "light".to_string()
-        }
-    } else {
-        "light".to_string()
-    }
+pub fn get_theme_key(store: &DocStore) -> String {
+    store
+        .preferences()
+        .and_then(|user_preferences| user_preferences.theme_name)
+        .unwrap_or_else(|| "light".to_string())
 }
 
 pub static DARK_THEME_ICON_CODE: &str = "\u{e51c}";
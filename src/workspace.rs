@@ -0,0 +1,387 @@
+use base64::{engine::general_purpose, Engine};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::components::Doc;
+use crate::migrations::{migrate_doc, MigrationError};
+use crate::utils::{doc_image_hashes, ReflectableUuid};
+
+/// Documents stored as plain files in a folder, `workspace` in the native config.
+///
+/// ```text
+/// docs/<doc id>/doc.json          name, tags, image hashes and tabs without their history
+/// docs/<doc id>/tabs/<tab id>.json
+/// images/<hash>.png
+/// ```
+///
+/// Files are pretty printed with sorted keys and only rewritten when their content changes,
+/// so the folder diffs well under version control.
+#[derive(Debug, Clone)]
+pub struct Workspace {
+    root: PathBuf,
+}
+
+#[derive(Debug)]
+pub enum WorkspaceError {
+    Io(io::Error),
+    Json(serde_json::Error),
+    Migration(MigrationError),
+}
+
+impl fmt::Display for WorkspaceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WorkspaceError::Io(err) => write!(f, "{}", err),
+            WorkspaceError::Json(err) => write!(f, "invalid workspace file: {}", err),
+            WorkspaceError::Migration(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for WorkspaceError {}
+
+impl From<io::Error> for WorkspaceError {
+    fn from(err: io::Error) -> Self {
+        WorkspaceError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for WorkspaceError {
+    fn from(err: serde_json::Error) -> Self {
+        WorkspaceError::Json(err)
+    }
+}
+
+impl From<MigrationError> for WorkspaceError {
+    fn from(err: MigrationError) -> Self {
+        WorkspaceError::Migration(err)
+    }
+}
+
+impl Workspace {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Workspace { root: root.into() }
+    }
+
+    fn docs_dir(&self) -> PathBuf {
+        self.root.join("docs")
+    }
+
+    fn doc_dir(&self, doc_id: ReflectableUuid) -> PathBuf {
+        self.docs_dir().join(doc_id.0.to_string())
+    }
+
+    fn image_path(&self, hash: &str) -> PathBuf {
+        self.root.join("images").join(format!("{}.png", hash))
+    }
+
+    pub fn doc_ids(&self) -> Result<Vec<ReflectableUuid>, WorkspaceError> {
+        let mut ids = vec![];
+        let entries = match fs::read_dir(self.docs_dir()) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(ids),
+            Err(err) => return Err(err.into()),
+        };
+        for entry in entries {
+            let entry = entry?;
+            if let Some(id) = entry
+                .file_name()
+                .to_str()
+                .and_then(|name| uuid::Uuid::parse_str(name).ok())
+            {
+                if entry.path().join("doc.json").exists() {
+                    ids.push(ReflectableUuid(id));
+                }
+            }
+        }
+        ids.sort_by_key(|id| id.0);
+        Ok(ids)
+    }
+
+    pub fn contains(&self, doc_id: ReflectableUuid) -> bool {
+        self.doc_dir(doc_id).join("doc.json").exists()
+    }
+
+    fn read_meta(&self, doc_id: ReflectableUuid) -> Result<Value, WorkspaceError> {
+        let content = fs::read_to_string(self.doc_dir(doc_id).join("doc.json"))?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    pub fn names(&self) -> Result<HashMap<ReflectableUuid, String>, WorkspaceError> {
+        let mut names = HashMap::new();
+        for doc_id in self.doc_ids()? {
+            let meta = self.read_meta(doc_id)?;
+            let name = meta["name"].as_str().unwrap_or_default().to_string();
+            names.insert(doc_id, name);
+        }
+        Ok(names)
+    }
+
+    pub fn tags(&self) -> Result<HashMap<ReflectableUuid, Vec<String>>, WorkspaceError> {
+        let mut tags = HashMap::new();
+        for doc_id in self.doc_ids()? {
+            let meta = self.read_meta(doc_id)?;
+            let doc_tags = serde_json::from_value(meta["tags"].clone()).unwrap_or_default();
+            tags.insert(doc_id, doc_tags);
+        }
+        Ok(tags)
+    }
+
+    /// Images a stored document references, as listed in its `doc.json`.
+    fn doc_image_refs(&self, doc_id: ReflectableUuid) -> Result<HashSet<String>, WorkspaceError> {
+        if !self.contains(doc_id) {
+            return Ok(HashSet::new());
+        }
+        let meta = self.read_meta(doc_id)?;
+        match meta.get("image_hashes") {
+            Some(hashes) => Ok(serde_json::from_value(hashes.clone())?),
+            // written before the list existed
+            None => Ok(self
+                .load_doc(doc_id)?
+                .map(|doc| doc_image_hashes(&doc))
+                .unwrap_or_default()),
+        }
+    }
+
+    pub fn load_doc(&self, doc_id: ReflectableUuid) -> Result<Option<Doc>, WorkspaceError> {
+        if !self.contains(doc_id) {
+            return Ok(None);
+        }
+        let mut meta = self.read_meta(doc_id)?;
+        if let Some(tabs) = meta["tabs"].as_array_mut() {
+            for tab in tabs.iter_mut() {
+                let Some(tab_id) = tab["id"].as_str() else {
+                    continue;
+                };
+                let path = self
+                    .doc_dir(doc_id)
+                    .join("tabs")
+                    .join(format!("{}.json", tab_id));
+                if let Ok(content) = fs::read_to_string(path) {
+                    tab["history"] = serde_json::from_str(&content)?;
+                }
+            }
+        }
+        let mut doc: Doc = serde_json::from_value(meta)?;
+        migrate_doc(&mut doc)?;
+        Ok(Some(doc))
+    }
+
+    /// Writes the document and the images it references, returns how many files changed.
+    pub fn save_doc(
+        &self,
+        doc: &Doc,
+        images: &HashMap<String, String>,
+    ) -> Result<usize, WorkspaceError> {
+        let doc_dir = self.doc_dir(doc.id);
+        let previous_hashes = self.doc_image_refs(doc.id)?;
+        let hashes = doc_image_hashes(doc);
+        let mut meta = serde_json::to_value(doc)?;
+        let meta_object = meta.as_object_mut().unwrap();
+        meta_object.remove("images");
+        let mut sorted_hashes = hashes.iter().collect::<Vec<_>>();
+        sorted_hashes.sort();
+        meta_object.insert(
+            "image_hashes".to_string(),
+            serde_json::to_value(sorted_hashes)?,
+        );
+        let mut written = 0;
+        let mut tab_files = HashSet::new();
+        if let Some(tabs) = meta["tabs"].as_array_mut() {
+            for tab in tabs.iter_mut() {
+                let tab = tab.as_object_mut().unwrap();
+                let file_name = format!("{}.json", tab["id"].as_str().unwrap());
+                if let Some(history) = tab.remove("history") {
+                    if write_if_changed(&doc_dir.join("tabs").join(&file_name), &history)? {
+                        written += 1;
+                    }
+                }
+                tab_files.insert(file_name);
+            }
+        }
+        if write_if_changed(&doc_dir.join("doc.json"), &meta)? {
+            written += 1;
+        }
+        if let Ok(entries) = fs::read_dir(doc_dir.join("tabs")) {
+            for entry in entries {
+                let entry = entry?;
+                if !tab_files.contains(entry.file_name().to_str().unwrap_or_default()) {
+                    fs::remove_file(entry.path())?;
+                    written += 1;
+                }
+            }
+        }
+        for hash in hashes.iter() {
+            let path = self.image_path(hash);
+            if path.exists() {
+                continue;
+            }
+            if let Some(image) = images.get(hash) {
+                let Ok(png) = general_purpose::STANDARD.decode(image.as_bytes()) else {
+                    continue;
+                };
+                fs::create_dir_all(path.parent().unwrap())?;
+                fs::write(path, png)?;
+                written += 1;
+            }
+        }
+        let dropped = previous_hashes.difference(&hashes).cloned().collect();
+        written += self.remove_unused_images(dropped)?;
+        Ok(written)
+    }
+
+    pub fn remove_doc(&self, doc_id: ReflectableUuid) -> Result<(), WorkspaceError> {
+        let hashes = self.doc_image_refs(doc_id)?;
+        match fs::remove_dir_all(self.doc_dir(doc_id)) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err.into()),
+            _ => {}
+        }
+        self.remove_unused_images(hashes)?;
+        Ok(())
+    }
+
+    /// Base64 encoded PNG, the format used by `AppState::images`.
+    pub fn load_image(&self, hash: &str) -> Option<String> {
+        let png = fs::read(self.image_path(hash)).ok()?;
+        Some(general_purpose::STANDARD.encode(png))
+    }
+
+    /// Hashes of the images referenced by any stored document, read from their `doc.json`.
+    fn image_hashes(&self) -> Result<HashSet<String>, WorkspaceError> {
+        let mut hashes = HashSet::new();
+        for doc_id in self.doc_ids()? {
+            hashes.extend(self.doc_image_refs(doc_id)?);
+        }
+        Ok(hashes)
+    }
+
+    /// Deletes the image files among `candidates` no stored document references, returns how many.
+    fn remove_unused_images(&self, candidates: HashSet<String>) -> Result<usize, WorkspaceError> {
+        if candidates.is_empty() {
+            return Ok(0);
+        }
+        let used = self.image_hashes()?;
+        let mut removed = 0;
+        for hash in candidates.difference(&used) {
+            match fs::remove_file(self.image_path(hash)) {
+                Ok(()) => removed += 1,
+                Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+                Err(err) => return Err(err.into()),
+            }
+        }
+        Ok(removed)
+    }
+}
+
+/// Keys come out sorted because `serde_json` maps are ordered.
+fn write_if_changed(path: &Path, value: &Value) -> io::Result<bool> {
+    let mut content = serde_json::to_string_pretty(value)?;
+    content.push('\n');
+    if fs::read_to_string(path).is_ok_and(|old| old == content) {
+        return Ok(false);
+    }
+    fs::create_dir_all(path.parent().unwrap())?;
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, content)?;
+    fs::rename(tmp, path)?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::{Tab, TabSnapshot};
+    use crate::history::TabHistory;
+    use crate::migrations::CURRENT_SCHEMA_VERSION;
+    use tempfile::tempdir;
+
+    fn doc_with_image(hash: &str) -> Doc {
+        let mut snapshot = TabSnapshot::default();
+        snapshot
+            .images
+            .insert(uuid::Uuid::new_v4(), hash.to_string());
+        let id = ReflectableUuid::generate();
+        Doc {
+            id,
+            name: "notes".to_string(),
+            tags: vec!["work".to_string()],
+            schema_version: CURRENT_SCHEMA_VERSION,
+            tabs: vec![Tab {
+                id: ReflectableUuid::generate(),
+                name: "Tab 1".to_string(),
+                is_active: true,
+                z_index: 1.,
+                history: TabHistory::new(snapshot, 0.),
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_save_and_load_doc() {
+        let dir = tempdir().unwrap();
+        let workspace = Workspace::new(dir.path());
+        let png = general_purpose::STANDARD.encode(b"png");
+        let doc = doc_with_image("hash");
+        let images = HashMap::from([("hash".to_string(), png.clone())]);
+
+        assert_eq!(workspace.save_doc(&doc, &images).unwrap(), 3);
+        assert_eq!(workspace.save_doc(&doc, &images).unwrap(), 0);
+
+        let loaded = workspace.load_doc(doc.id).unwrap().unwrap();
+        assert_eq!(loaded.name, "notes");
+        assert_eq!(loaded.tags, doc.tags);
+        assert_eq!(loaded.tabs[0].history, doc.tabs[0].history);
+        assert_eq!(workspace.load_image("hash"), Some(png));
+        assert_eq!(workspace.names().unwrap()[&doc.id], "notes");
+        let meta = fs::read_to_string(
+            dir.path()
+                .join("docs")
+                .join(doc.id.0.to_string())
+                .join("doc.json"),
+        )
+        .unwrap();
+        assert!(!meta.contains("history"));
+    }
+
+    #[test]
+    fn test_removed_tabs_and_images_are_deleted() {
+        let dir = tempdir().unwrap();
+        let workspace = Workspace::new(dir.path());
+        let images = HashMap::from([
+            ("hash".to_string(), String::new()),
+            ("shared".to_string(), String::new()),
+        ]);
+        let mut doc = doc_with_image("hash");
+        workspace.save_doc(&doc, &images).unwrap();
+
+        let other = doc_with_image("shared");
+        workspace.save_doc(&other, &images).unwrap();
+        doc.tabs.push(other.tabs[0].clone());
+        workspace.save_doc(&doc, &images).unwrap();
+
+        doc.tabs[0].id = ReflectableUuid::generate();
+        doc.tabs[0].history = TabHistory::default();
+        // new tab, doc.json, removed tab and removed image
+        assert_eq!(workspace.save_doc(&doc, &images).unwrap(), 4);
+        let tabs_dir = dir
+            .path()
+            .join("docs")
+            .join(doc.id.0.to_string())
+            .join("tabs");
+        assert_eq!(fs::read_dir(tabs_dir).unwrap().count(), 2);
+        assert_eq!(workspace.load_image("hash"), None);
+
+        workspace.remove_doc(doc.id).unwrap();
+        assert_eq!(workspace.doc_ids().unwrap(), vec![other.id]);
+        assert_eq!(workspace.load_image("shared"), Some(String::new()));
+        workspace.remove_doc(other.id).unwrap();
+        assert!(workspace.doc_ids().unwrap().is_empty());
+        assert_eq!(workspace.load_image("shared"), None);
+    }
+}